@@ -194,6 +194,16 @@
               partitionType = "count";
             }
           );
+
+          # Run the end-to-end uploads against the stub API (in-memory storage)
+          nunu-cli-e2e = craneLib.cargoNextest (
+            commonArgs
+            // {
+              inherit cargoArtifacts;
+              cargoExtraArgs = "--locked --features e2e";
+              cargoNextestExtraArgs = "--test e2e";
+            }
+          );
         };
 
         packages =
@@ -1,14 +1,78 @@
-use crate::config::Config;
+use crate::api::dns::CachingResolver;
+use crate::api::errors::api_error;
+use crate::api::proxy;
+use crate::api::schema::parse_response;
+use crate::api::tls::TlsSettings;
+use crate::config::{Config, StorageHttpVersion};
 use crate::error::{Error, Result};
-use crate::{ci_metadata::CiMetadata, metadata::VcsMetadata};
-use log::{debug, info};
+use crate::t;
+use crate::{
+    artifact::ArtifactInfo,
+    budget::SizeBudgetCheck,
+    ci_metadata::CiMetadata,
+    encryption::{DataKey, EncryptionInfo},
+    metadata::VcsMetadata,
+    receipts::{ReceiptKey, UploadReceipt},
+    retention::BuildSummary,
+    size_diff::PreviousBuild,
+    symbols::SymbolsInfo,
+    upload::shard::Shard,
+};
+use bytes::Bytes;
+use log::{debug, info, warn};
 use reqwest::Client as HttpClient;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How long a fallback API URL gets to answer its health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Path of the health check of a fallback API URL unless the config sets another
+const DEFAULT_HEALTH_PATH: &str = "/health";
+
+/// Part bodies are sent in slices of this size, so progress is seen while a part uploads
+const PART_BODY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Idle connections kept open per host, enough for the parallel part uploads of several files
+const POOL_MAX_IDLE_PER_HOST: usize = 64;
+
+/// How long an idle pooled connection is kept before it is closed
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// TCP keepalive interval, so idle pooled connections survive NAT and load balancer timeouts
+const TCP_KEEPALIVE: Duration = Duration::from_secs(30);
 
 #[derive(Clone)]
 pub struct Client {
     config: Config,
     http: HttpClient,
+    /// Client for storage uploads, negotiating HTTP/2 over TLS unless HTTP/1.1 is forced
+    storage: HttpClient,
+    /// HTTP/2 prior-knowledge client for cleartext storage URLs, present when HTTP/2 is forced
+    storage_h2c: Option<HttpClient>,
+    /// HTTP/3-only client for storage uploads, present when `--http3` is requested
+    #[cfg(feature = "http3")]
+    storage_h3: Option<Http3Storage>,
+}
+
+/// HTTP clients built from a config's transport options, shared through
+/// [`crate::config::SharedHttp`] so every [`Client`] reuses the same connection pools
+#[derive(Clone, Debug)]
+pub(crate) struct HttpClients {
+    http: HttpClient,
+    storage: HttpClient,
+    storage_h2c: Option<HttpClient>,
+    #[cfg(feature = "http3")]
+    storage_h3: Option<Http3Storage>,
+}
+
+/// HTTP/3 client plus a sticky flag that disables it after the first transport failure
+#[cfg(feature = "http3")]
+#[derive(Clone, Debug)]
+struct Http3Storage {
+    http: HttpClient,
+    usable: std::sync::Arc<std::sync::atomic::AtomicBool>,
 }
 
 /// Build platform enum matching the backend schema
@@ -23,10 +87,49 @@ pub enum BuildPlatform {
     IosSimulator,
     Xbox,
     Playstation,
+    Switch,
+    Webgl,
+    SteamDeck,
+}
+
+/// CPU architecture of a build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Arch {
+    X64,
+    Arm64,
+    /// Multi-architecture (e.g. macOS universal) binary
+    Universal,
+}
+
+impl Arch {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Arch::X64 => "x64",
+            Arch::Arm64 => "arm64",
+            Arch::Universal => "universal",
+        }
+    }
+}
+
+impl std::str::FromStr for Arch {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "x64" | "x86_64" | "amd64" => Ok(Arch::X64),
+            "arm64" | "aarch64" => Ok(Arch::Arm64),
+            "universal" => Ok(Arch::Universal),
+            _ => Err(Error::ConfigError(format!(
+                "Invalid architecture: '{s}'. Valid architectures are: x64, arm64, universal"
+            ))),
+        }
+    }
 }
 
 /// Deletion policy enum for auto-delete functionality
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DeletionPolicy {
     LeastRecent,
@@ -69,6 +172,9 @@ impl BuildPlatform {
             BuildPlatform::IosSimulator => "ios-simulator",
             BuildPlatform::Xbox => "xbox",
             BuildPlatform::Playstation => "playstation",
+            BuildPlatform::Switch => "switch",
+            BuildPlatform::Webgl => "webgl",
+            BuildPlatform::SteamDeck => "steam-deck",
         }
     }
 }
@@ -86,14 +192,26 @@ impl std::str::FromStr for BuildPlatform {
             "ios-simulator" => Ok(BuildPlatform::IosSimulator),
             "xbox" => Ok(BuildPlatform::Xbox),
             "playstation" => Ok(BuildPlatform::Playstation),
+            "switch" => Ok(BuildPlatform::Switch),
+            "webgl" => Ok(BuildPlatform::Webgl),
+            "steam-deck" => Ok(BuildPlatform::SteamDeck),
+            "steamdeck" => {
+                crate::deprecations::check_value("platform", s)?;
+                Ok(BuildPlatform::SteamDeck)
+            }
             _ => Err(Error::ConfigError(format!(
-                "Invalid platform: '{s}'. Valid platforms are: windows, macos, linux, android, ios-native, ios-simulator, xbox, playstation"
+                "Invalid platform: '{s}'. Valid platforms are: windows, macos, linux, android, ios-native, ios-simulator, xbox, playstation, switch, webgl, steam-deck"
             ))),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Build metadata attached to an upload
+///
+/// Metadata problems never block the upload: each part is serialized on its own, a part
+/// that fails is left out, and control characters (e.g. NUL bytes from git output) are
+/// removed from strings, each noted in `collection_warnings`.
+#[derive(Deserialize, Debug, Clone)]
 pub struct BuildDetails {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vcs: Option<VcsMetadata>,
@@ -101,6 +219,128 @@ pub struct BuildDetails {
     pub ci: Option<CiMetadata>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub upload: Option<UploadInfo>,
+    /// Free-form key/value metadata (`--meta`, config and manifest `metadata`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, String>,
+    /// Details read from the uploaded file (signing, versions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<ArtifactInfo>,
+    /// Artifact size measured against its platform's `--size-budget`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_budget: Option<SizeBudgetCheck>,
+    /// Metadata that could not be gathered (e.g. no git binary in a sandboxed runner)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collection_warnings: Vec<String>,
+}
+
+impl Serialize for BuildDetails {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut warnings = self.collection_warnings.clone();
+        let mut fields = Vec::new();
+        let mut add = |name: &'static str, value: serde_json::Result<serde_json::Value>| match value
+        {
+            Ok(mut value) => {
+                if strip_control_characters(&mut value) {
+                    warnings.push(format!("control characters removed from {name} metadata"));
+                }
+                fields.push((name, value));
+            }
+            Err(e) => {
+                warn!("Leaving out {name} metadata that could not be serialized: {e}");
+                warnings.push(format!("{name} metadata left out: {e}"));
+            }
+        };
+        if let Some(vcs) = &self.vcs {
+            add("vcs", serde_json::to_value(vcs));
+        }
+        if let Some(ci) = &self.ci {
+            add("ci", serde_json::to_value(ci));
+        }
+        if let Some(upload) = &self.upload {
+            add("upload", serde_json::to_value(upload));
+        }
+        if !self.custom.is_empty() {
+            add("custom", serde_json::to_value(&self.custom));
+        }
+        if let Some(artifact) = &self.artifact {
+            add("artifact", serde_json::to_value(artifact));
+        }
+        if let Some(size_budget) = &self.size_budget {
+            add("size_budget", serde_json::to_value(size_budget));
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+        for (name, value) in &fields {
+            map.serialize_entry(name, value)?;
+        }
+        if !warnings.is_empty() {
+            map.serialize_entry("collection_warnings", &warnings)?;
+        }
+        map.end()
+    }
+}
+
+/// Remove control characters other than tabs and line breaks from the strings in `value`,
+/// returning whether there were any
+fn strip_control_characters(value: &mut serde_json::Value) -> bool {
+    let unwanted = |c: char| c.is_control() && !matches!(c, '\n' | '\r' | '\t');
+    match value {
+        serde_json::Value::String(text) if text.contains(unwanted) => {
+            text.retain(|c| !unwanted(c));
+            true
+        }
+        serde_json::Value::Array(items) => items
+            .iter_mut()
+            .fold(false, |found, item| strip_control_characters(item) | found),
+        serde_json::Value::Object(map) => map
+            .values_mut()
+            .fold(false, |found, item| strip_control_characters(item) | found),
+        _ => false,
+    }
+}
+
+impl BuildDetails {
+    /// Returns a copy with `extra` merged into the custom metadata, overriding existing keys
+    #[must_use]
+    pub fn with_custom(&self, extra: &BTreeMap<String, String>) -> Self {
+        let mut details = self.clone();
+        details
+            .custom
+            .extend(extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+        details
+    }
+
+    /// Returns these details with the artifact information of a specific file
+    #[must_use]
+    pub fn with_artifact(mut self, artifact: Option<ArtifactInfo>) -> Self {
+        self.artifact = artifact;
+        self
+    }
+
+    /// Returns these details with the size budget check of a specific file
+    #[must_use]
+    pub fn with_size_budget(mut self, size_budget: Option<SizeBudgetCheck>) -> Self {
+        self.size_budget = size_budget;
+        self
+    }
+}
+
+/// Upload defaults set centrally for a project by its admins, applied to the options an
+/// upload leaves unset
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectSettings {
+    #[serde(default)]
+    pub auto_delete: Option<bool>,
+    /// `least_recent` or `oldest`
+    #[serde(default)]
+    pub deletion_policy: Option<String>,
+    #[serde(default)]
+    pub default_tags: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -112,7 +352,7 @@ pub struct UploadInfo {
     pub uploader: Option<String>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct UploadRequest {
     pub name: String,
@@ -132,6 +372,71 @@ pub struct UploadRequest {
     pub details: Option<BuildDetails>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
+    /// Preferred storage region for deployments with regional buckets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_notes: Option<String>,
+    /// Kind of auxiliary artifact (`symbols`, `shader-cache`, `content-pack`), or for
+    /// builds a hint where the platform alone is ambiguous (e.g. `aab`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    /// Set when the file is debug symbols for existing builds rather than a build
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbols: Option<SymbolsInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<Arch>,
+    /// Set when the parts are encrypted with a data key wrapped by the project key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionInfo>,
+    /// Set when several machines upload the parts; shards with the same key join one upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+}
+
+impl UploadRequest {
+    /// Request with the fields of the deprecated positional upload methods, leaving the rest unset
+    #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
+    fn legacy(
+        name: &str,
+        filename: &str,
+        size: u64,
+        platform: &str,
+        multipart: bool,
+        description: Option<String>,
+        upload_timeout: Option<u32>,
+        auto_delete: bool,
+        deletion_policy: Option<String>,
+        details: Option<BuildDetails>,
+        tags: Option<Vec<String>>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            description,
+            file_name: filename.to_string(),
+            file_size: size,
+            platform: platform.to_string(),
+            multipart,
+            auto_delete: Some(auto_delete),
+            deletion_policy,
+            upload_timeout,
+            details,
+            tags,
+            storage_region: None,
+            build_version: None,
+            build_number: None,
+            release_notes: None,
+            artifact_type: None,
+            symbols: None,
+            arch: None,
+            encryption: None,
+            shard: None,
+        }
+    }
 }
 
 /// Response from the server for a single-part upload request
@@ -139,8 +444,12 @@ pub struct UploadRequest {
 #[serde(rename_all = "snake_case")]
 pub struct SinglePartUploadResponse {
     pub build_id: String,
+    #[serde(alias = "presigned_url")]
     pub upload_url: String,
     pub object_key: String,
+    /// Region of the bucket the upload URL targets, if reported by the server
+    #[serde(default)]
+    pub storage_region: Option<String>,
 }
 
 /// Response from the server for a multipart upload request
@@ -152,22 +461,22 @@ pub struct MultipartUploadResponse {
     pub object_key: String,
     pub total_parts: usize,
     pub part_size: usize,
-}
-
-/// Request to get upload URLs for specific parts (now GET with query params)
-#[derive(Serialize)]
-#[serde(rename_all = "snake_case")]
-pub struct GetUploadUrlsParams {
-    pub upload_id: String,
-    pub object_key: String,
-    #[serde(rename = "part_numbers[]")]
-    pub part_numbers: Vec<u64>,
+    /// Region of the bucket the part URLs target, if reported by the server
+    #[serde(default)]
+    pub storage_region: Option<String>,
+    /// Whether the server can copy parts stored by earlier uploads into this one
+    #[serde(default)]
+    pub part_reuse: bool,
 }
 
 /// Response with upload URLs for parts
+///
+/// Older API revisions called the list `presigned_urls` and each entry's URL
+/// `presigned_url`; both spellings are accepted.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct GetUploadUrlsResponse {
+    #[serde(alias = "presigned_urls")]
     pub upload_urls: Vec<UploadUrlPart>,
 }
 
@@ -175,17 +484,44 @@ pub struct GetUploadUrlsResponse {
 #[serde(rename_all = "snake_case")]
 pub struct UploadUrlPart {
     pub part_number: u64,
+    #[serde(alias = "presigned_url", alias = "upload_url")]
     pub url: String,
 }
 
 /// Uploaded part metadata
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct UploadedPart {
     pub part_number: u64,
     pub etag: String,
 }
 
+/// A part to copy from the object of an earlier upload
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct PartReuse {
+    pub part_number: u64,
+    pub source_object_key: String,
+    pub source_part_number: u64,
+}
+
+/// Request to copy parts of earlier uploads into a multipart upload
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ReusePartsRequest {
+    pub upload_id: String,
+    pub object_key: String,
+    pub parts: Vec<PartReuse>,
+}
+
+/// The parts the server copied; parts it could not copy are left out
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ReusePartsResponse {
+    #[serde(default)]
+    pub parts: Vec<UploadedPart>,
+}
+
 /// Request to complete multipart upload
 #[derive(Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -196,45 +532,386 @@ pub struct CompleteMultipartUploadRequest {
     pub parts: Vec<UploadedPart>,
 }
 
+/// Request reporting the parts one shard of a sharded upload uploaded
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CompleteShardRequest {
+    pub build_id: String,
+    pub upload_id: String,
+    pub object_key: String,
+    /// 1-based index of the shard
+    pub shard: u32,
+    pub parts: Vec<UploadedPart>,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "snake_case")]
 pub struct CompleteRequest {
     pub build_id: String,
 }
 
+/// Response from the server after completing an upload
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct CompleteResponse {
+    /// Signed receipt of the completed upload (absent on servers without receipt signing)
+    #[serde(default)]
+    pub receipt: Option<UploadReceipt>,
+}
+
+/// Response after a shard reported its parts
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct CompleteShardResponse {
+    /// Whether this was the last shard and the upload is now complete
+    #[serde(default)]
+    pub completed: bool,
+    /// Indexes of the shards that have not reported yet
+    #[serde(default)]
+    pub pending_shards: Vec<u32>,
+    /// Receipt, present once the upload is complete
+    #[serde(flatten)]
+    pub complete: CompleteResponse,
+}
+
+/// Server-side processing state of an upload being completed
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadStatus {
+    /// Processing step, e.g. `assembling` or `scanning`
+    pub state: String,
+    #[serde(default)]
+    pub percent: Option<u8>,
+    /// Human-readable description of the step
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// An uploaded file as stored by the server
+#[derive(Debug, Clone, Deserialize)]
+pub struct StoredObject {
+    pub size: u64,
+    /// SHA-256 of the stored object as lowercase hex, if the server has computed it
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// One page of a project's builds
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct BuildListResponse {
+    pub builds: Vec<BuildSummary>,
+    /// Cursor of the next page, absent on the last page
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+/// Public keys the server signs upload receipts with
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ReceiptKeysResponse {
+    pub keys: Vec<ReceiptKey>,
+}
+
+/// Response from the server after deleting a build
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct DeleteBuildResponse {
+    pub build_id: String,
+    /// Whether the build was permanently deleted instead of moved to the trash
+    #[serde(default)]
+    pub purged: bool,
+    /// Until when the build can still be restored (RFC 3339 timestamp)
+    #[serde(default)]
+    pub restorable_until: Option<String>,
+}
+
+/// Periodic upload progress report shown on the dashboard while a build is uploading
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct ProgressReport {
+    pub build_id: String,
+    pub uploaded_bytes: u64,
+    pub total_bytes: u64,
+    pub percent: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_seconds: Option<u64>,
+}
+
+/// Small text file attached to every build uploaded in one batch (e.g. a checksum manifest)
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct BuildSetAttachment {
+    pub build_ids: Vec<String>,
+    pub file_name: String,
+    pub content_type: String,
+    pub content: String,
+}
+
 impl Client {
+    /// Creates a new API client
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be constructed from the transport settings, e.g.
+    /// when a CA bundle or client certificate is unreadable. Use [`Client::try_new`] to
+    /// handle this as an error.
     #[must_use]
+    #[allow(clippy::expect_used)]
     pub fn new(config: Config) -> Self {
-        // Check for proxy configuration
-        if let Ok(proxy) = std::env::var("HTTPS_PROXY").or_else(|_| std::env::var("https_proxy")) {
-            info!("Using proxy: {}", Self::redact_proxy_url(&proxy));
+        Self::try_new(config).expect("failed to build the HTTP client")
+    }
+
+    /// Creates a new API client, failing if the transport settings are unusable
+    ///
+    /// The HTTP clients are built on first use and shared by all clients created from clones
+    /// of the same config, so connections and TLS sessions are reused across files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be constructed from the transport settings.
+    pub fn try_new(config: Config) -> Result<Self> {
+        let clients = if let Some(clients) = config.http.clients.get() {
+            clients.clone()
+        } else {
+            let clients = Self::http_clients(&config)?;
+            // A concurrent first use may have won the race; everyone uses its clients
+            config.http.clients.get_or_init(|| clients).clone()
+        };
+
+        Ok(Self {
+            config,
+            http: clients.http,
+            storage: clients.storage,
+            storage_h2c: clients.storage_h2c,
+            #[cfg(feature = "http3")]
+            storage_h3: clients.storage_h3,
+        })
+    }
+
+    /// Build the HTTP clients for the transport options of `config`
+    fn http_clients(config: &Config) -> Result<HttpClients> {
+        // Check for proxy configuration (explicit proxies are logged as they are set up)
+        if config.transport.proxy.is_set() {
+            debug!("Using explicit proxy settings instead of the proxy environment variables");
+        } else if let Ok(proxy) =
+            std::env::var("HTTPS_PROXY").or_else(|_| std::env::var("https_proxy"))
+        {
+            info!("Using proxy: {}", proxy::redact(&proxy));
         } else if let Ok(proxy) =
             std::env::var("HTTP_PROXY").or_else(|_| std::env::var("http_proxy"))
         {
-            info!("Using proxy: {}", Self::redact_proxy_url(&proxy));
+            info!("Using proxy: {}", proxy::redact(&proxy));
         } else {
             debug!("No proxy configured (direct connection)");
         }
 
-        Self {
-            http: HttpClient::new(), // reqwest automatically uses proxy
-            config,
+        // reqwest uses the proxy environment variables unless explicit proxies are set.
+        // API requests stay on HTTP/1.1; only storage uploads benefit from multiplexing.
+        let http = Self::http_builder(config)?.http1_only().build()?;
+        // Over TLS, HTTP/2 is negotiated through ALPN so storage without it (or a proxy
+        // that strips it) still gets HTTP/1.1. Prior knowledge is only used for cleartext
+        // storage URLs when HTTP/2 is forced, as there is no handshake to negotiate in.
+        let (storage, storage_h2c) = match config.transport.storage_http {
+            StorageHttpVersion::Auto => (Self::http_builder(config)?.build()?, None),
+            StorageHttpVersion::Http1 => {
+                debug!("Using HTTP/1.1 for storage uploads");
+                (Self::http_builder(config)?.http1_only().build()?, None)
+            }
+            StorageHttpVersion::Http2 => {
+                debug!("Using HTTP/2 for storage uploads");
+                let h2c = Self::http_builder(config)?
+                    .http2_prior_knowledge()
+                    .build()?;
+                (Self::http_builder(config)?.build()?, Some(h2c))
+            }
+        };
+
+        #[cfg(feature = "http3")]
+        let storage_h3 = if config.transport.http3 {
+            match Self::http_builder(config)?.http3_prior_knowledge().build() {
+                Ok(http) => {
+                    debug!("HTTP/3 enabled for storage uploads");
+                    Some(Http3Storage {
+                        http,
+                        usable: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true)),
+                    })
+                }
+                Err(e) => {
+                    warn!("Failed to initialize HTTP/3 client, using HTTP/1.1/2: {e}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(HttpClients {
+            http,
+            storage,
+            storage_h2c,
+            #[cfg(feature = "http3")]
+            storage_h3,
+        })
+    }
+
+    /// HTTP client builder with the DNS and proxy settings from the transport options applied
+    fn http_builder(config: &Config) -> Result<reqwest::ClientBuilder> {
+        let transport = &config.transport;
+        let mut builder = HttpClient::builder()
+            .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            // Size HTTP/2 flow control windows to the connection, so parts multiplexed over
+            // one connection are not throttled by the default 64 KiB window
+            .http2_adaptive_window(true);
+
+        if let Some(proxy) = transport
+            .proxy
+            .build(config.api_urls(), |name| std::env::var(name).ok())?
+        {
+            builder = builder.proxy(proxy);
+        }
+
+        let tls = TlsSettings::new(transport)?;
+        for certificate in tls.roots {
+            builder = builder.add_root_certificate(certificate);
+        }
+        if let Some(identity) = tls.identity {
+            builder = builder.identity(identity);
+        }
+        builder = builder.danger_accept_invalid_certs(tls.insecure);
+
+        if let Some(timeout) = transport.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        if let Some(ttl) = transport.dns_cache_ttl {
+            debug!("Caching DNS resolutions for {}s", ttl.as_secs());
+            builder = builder.dns_resolver(std::sync::Arc::new(CachingResolver::new(ttl)));
+        }
+
+        for (host, addr) in &transport.resolve {
+            debug!("Resolving {host} to {}", addr.ip());
+            builder = builder.resolve(host, *addr);
         }
+
+        Ok(builder)
     }
 
-    /// Redact sensitive information from proxy URLs
-    fn redact_proxy_url(url: &str) -> String {
-        if let Ok(mut parsed) = url::Url::parse(url) {
-            if parsed.username() != "" || parsed.password().is_some() {
-                let _ = parsed.set_username("***");
-                let _ = parsed.set_password(Some("***"));
-                parsed.to_string()
-            } else {
-                // No credentials - safe to show
-                url.to_string()
+    /// Send a PUT to the storage `url`, preferring HTTP/3 when enabled
+    ///
+    /// `build` is called once per attempt so the request body can be recreated
+    /// if the HTTP/3 attempt fails and we fall back to HTTP/1.1/2.
+    async fn send_storage_put<F>(&self, url: &str, build: F) -> reqwest::Result<reqwest::Response>
+    where
+        F: Fn(&HttpClient) -> reqwest::RequestBuilder,
+    {
+        #[cfg(feature = "http3")]
+        if let Some(h3) = &self.storage_h3
+            && h3.usable.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            match build(&h3.http)
+                .version(reqwest::Version::HTTP_3)
+                .send()
+                .await
+            {
+                Ok(response) => return Ok(response),
+                Err(e) if e.is_connect() || e.is_request() || e.is_timeout() => {
+                    warn!("HTTP/3 upload failed, falling back to HTTP/1.1/2: {e}");
+                    h3.usable.store(false, std::sync::atomic::Ordering::Relaxed);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        match &self.storage_h2c {
+            Some(h2c) if url.starts_with("http://") => build(h2c).send().await,
+            _ => build(&self.storage).send().await,
+        }
+    }
+
+    /// Send a request to the API, failing over to a fallback API URL if `url` is unreachable
+    ///
+    /// `build` is called with the URL to request, once per attempt. Only connection
+    /// failures trigger a failover, so a request the server may have received is never
+    /// sent twice. Each attempt may take up to the transport's `request_timeout`.
+    async fn send_api<F>(&self, url: &str, build: F) -> reqwest::Result<reqwest::Response>
+    where
+        F: Fn(&HttpClient, &str) -> reqwest::RequestBuilder,
+    {
+        let build = |url: &str| {
+            let request = build(&self.http, url);
+            match self.config.transport.request_timeout {
+                Some(timeout) => request.timeout(timeout),
+                None => request,
+            }
+        };
+        match build(url).send().await {
+            Err(e) if e.is_connect() => {
+                let Some((failed, path)) = self.config.split_api_url(url) else {
+                    return Err(e);
+                };
+                let Some(api_url) = self.fail_over(failed).await else {
+                    return Err(e);
+                };
+                build(&format!("{api_url}{path}")).send().await
+            }
+            result => result,
+        }
+    }
+
+    /// Switch away from the API URL at index `failed` to the first healthy one
+    ///
+    /// The selection is shared by every client created from the same config, so it sticks
+    /// for the rest of the run. Returns `None` if no other API URL is healthy.
+    async fn fail_over(&self, failed: usize) -> Option<String> {
+        let active = self.config.active_api_index();
+        if active != failed {
+            // Another request already failed over
+            return self.config.api_urls().nth(active).map(str::to_string);
+        }
+
+        for (index, api_url) in self.config.api_urls().enumerate() {
+            if index == failed || !self.is_healthy(api_url).await {
+                continue;
+            }
+            let active = self.config.switch_api_url(failed, index);
+            let active_url = self.config.api_urls().nth(active)?;
+            warn!(
+                "API at {} is unreachable, using {active_url} for the rest of the run",
+                self.config.api_urls().nth(failed).unwrap_or_default()
+            );
+            return Some(active_url.to_string());
+        }
+
+        warn!("API is unreachable and no fallback API URL is healthy");
+        None
+    }
+
+    /// Whether the API at `api_url` answers its health check
+    async fn is_healthy(&self, api_url: &str) -> bool {
+        let health_path = self
+            .config
+            .failover
+            .health_path
+            .as_deref()
+            .unwrap_or(DEFAULT_HEALTH_PATH);
+        let result = self
+            .http
+            .get(format!("{api_url}{health_path}"))
+            .timeout(HEALTH_CHECK_TIMEOUT)
+            .send()
+            .await;
+        match result {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                debug!("Health check of {api_url} failed: {}", response.status());
+                false
+            }
+            Err(e) => {
+                debug!("Health check of {api_url} failed: {e}");
+                false
             }
-        } else {
-            "<proxy configured>".to_string()
         }
     }
 
@@ -243,6 +920,10 @@ impl Client {
     /// # Errors
     ///
     /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    #[deprecated(
+        since = "0.1.20",
+        note = "use `request_upload_url_with` and an `UploadRequest`"
+    )]
     #[allow(clippy::too_many_arguments)]
     pub async fn request_upload_url(
         &self,
@@ -257,39 +938,48 @@ impl Client {
         details: Option<BuildDetails>,
         tags: Option<Vec<String>>,
     ) -> Result<SinglePartUploadResponse> {
-        let url = format!("{}/upload", self.config.base_upload_url());
-        debug!("Requesting upload URL from: {url}");
-
-        let request = UploadRequest {
-            name: name.to_string(),
+        self.request_upload_url_with(&UploadRequest::legacy(
+            name,
+            filename,
+            size,
+            platform,
+            false,
             description,
-            file_name: filename.to_string(),
-            file_size: size,
-            platform: platform.to_string(),
-            multipart: false,
             upload_timeout,
-            auto_delete: Some(auto_delete),
+            auto_delete,
             deletion_policy,
             details,
             tags,
-        };
-
-        debug!("Upload request: {request:?}");
+        ))
+        .await
+    }
 
-        let response = self
-            .http
-            .post(&url)
-            .header("x-api-key", self.config.token.clone())
-            .json(&request)
-            .send()
+    /// Request a upload URL for the single-part upload described by `request`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn request_upload_url_with(
+        &self,
+        request: &UploadRequest,
+    ) -> Result<SinglePartUploadResponse> {
+        let url = format!("{}/upload", self.config.base_upload_url());
+        debug!("Requesting upload URL from: {url}");
+
+        debug!("Upload request: {request:?}");
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(request)
+            })
             .await?;
 
         info!("Received response with status: {response:?}");
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::ApiError(format!("Status {status}: {body}")));
+            return Err(error_response("Upload request", response).await);
         }
 
         // Get the response body as text first to log it
@@ -297,10 +987,7 @@ impl Client {
         debug!("Response body: {body}");
 
         // Try to parse it
-        let upload_response: SinglePartUploadResponse =
-            serde_json::from_str(&body).map_err(|e| {
-                Error::ApiError(format!("Failed to parse response: {e}. Body was: {body}"))
-            })?;
+        let upload_response: SinglePartUploadResponse = parse_response("upload", &body)?;
 
         debug!(
             "Received upload URL for build: {} (object: {})",
@@ -319,31 +1006,20 @@ impl Client {
         info!("Uploading {} bytes to URL", data.len());
         debug!("Upload URL: {url}");
 
+        let data = Bytes::from(data);
         let response = self
-            .http
-            .put(url)
-            .header("Content-Type", "application/octet-stream")
-            .header("Content-Length", data.len().to_string())
-            .body(data)
-            .send()
+            .send_storage_put(url, |http| {
+                http.put(url)
+                    .header("Content-Type", "application/octet-stream")
+                    .header("Content-Length", data.len().to_string())
+                    .body(data.clone())
+            })
             .await
             .map_err(|e| {
                 if e.is_connect() {
-                    Error::UploadError(format!(
-                        "Cannot connect to storage. Possible causes:\n\
-                     - Firewall blocking *.r2.cloudflarestorage.com\n\
-                     - Network proxy required (set HTTPS_PROXY environment variable)\n\
-                     - DNS resolution failure\n\
-                     Error details: {e}"
-                    ))
+                    Error::UploadError(t!("error.storage_connect", error = e))
                 } else if e.is_request() {
-                    Error::UploadError(format!(
-                        "Request failed. This may indicate:\n\
-                     - Network interruption during upload\n\
-                     - Proxy interfering with the request\n\
-                     - SSL/TLS issue\n\
-                     Error details: {e}"
-                    ))
+                    Error::UploadError(t!("error.storage_request", error = e))
                 } else {
                     Error::UploadError(format!("HTTP error: {e}"))
                 }
@@ -370,16 +1046,22 @@ impl Client {
                     .and_then(|s| s.split("</Message>").next())
                     .unwrap_or(&body);
 
-                return Err(Error::UploadError(format!(
-                    "Storage error: {error_code} - {error_message}\n\
+                return Err(Error::StorageStatus {
+                    status,
+                    message: format!(
+                        "Storage error: {error_code} - {error_message}\n\
                  \n\
                  To diagnose, test the upload URL directly:\n\
                  echo 'test' > test.txt\n\
                  curl -X PUT -H 'Content-Type: application/octet-stream' --data-binary @test.txt -v '<url>'"
-                )));
+                    ),
+                });
             }
 
-            return Err(Error::UploadError(format!("Status {status}: {body}")));
+            return Err(Error::StorageStatus {
+                status,
+                message: format!("Status {status}: {body}"),
+            });
         }
 
         info!("Upload successful");
@@ -394,7 +1076,7 @@ impl Client {
     pub async fn upload_to_url_with_progress<F>(
         &self,
         url: &str,
-        data: Vec<u8>,
+        data: impl Into<Bytes>,
         progress_callback: F,
     ) -> Result<()>
     where
@@ -405,55 +1087,56 @@ impl Client {
         use std::sync::Arc;
         use std::sync::atomic::{AtomicU64, Ordering};
 
+        let data: Bytes = data.into();
         info!("Uploading {} bytes to URL", data.len());
         debug!("Upload URL: {url}");
 
         let total_size = data.len() as u64;
 
-        let cursor = Cursor::new(data);
-        let reader = tokio::io::BufReader::new(cursor);
-        let stream = tokio_util::io::ReaderStream::new(reader);
-
         // Use Arc<AtomicU64> so both closures can access the counter
         let uploaded = Arc::new(AtomicU64::new(0));
-        let uploaded_clone = uploaded.clone();
-
-        let stream_with_progress = stream.map(move |chunk| {
-            if let Ok(ref bytes) = chunk {
-                let new_uploaded = uploaded_clone.fetch_add(bytes.len() as u64, Ordering::Relaxed)
-                    + bytes.len() as u64;
-                progress_callback(new_uploaded);
-            }
-            chunk
-        });
+        let progress_callback = Arc::new(progress_callback);
+
+        // The body is rebuilt for each attempt, restarting progress from zero
+        let make_body = || {
+            uploaded.store(0, Ordering::Relaxed);
+            let uploaded_clone = uploaded.clone();
+            let progress_callback = progress_callback.clone();
+
+            let cursor = Cursor::new(data.clone());
+            let reader = tokio::io::BufReader::new(cursor);
+            let stream = tokio_util::io::ReaderStream::new(reader);
+
+            let stream_with_progress = stream.map(move |chunk| {
+                if let Ok(ref bytes) = chunk {
+                    let new_uploaded = uploaded_clone
+                        .fetch_add(bytes.len() as u64, Ordering::Relaxed)
+                        + bytes.len() as u64;
+                    progress_callback(new_uploaded);
+                }
+                chunk
+            });
 
-        let body = reqwest::Body::wrap_stream(stream_with_progress);
+            reqwest::Body::wrap_stream(stream_with_progress)
+        };
 
         let response = self
-            .http
-            .put(url)
-            .header("Content-Type", "application/octet-stream")
-            .header("Content-Length", total_size.to_string())
-            .body(body)
-            .send()
+            .send_storage_put(url, |http| {
+                http.put(url)
+                    .header("Content-Type", "application/octet-stream")
+                    .header("Content-Length", total_size.to_string())
+                    .body(make_body())
+            })
             .await
             .map_err(|e| {
                 let bytes_uploaded = uploaded.load(Ordering::Relaxed);
                 if e.is_connect() {
-                    Error::UploadError(format!(
-                        "Cannot connect to storage. Possible causes:\n\
-                     - Firewall blocking *.r2.cloudflarestorage.com\n\
-                     - Network proxy required (set HTTPS_PROXY environment variable)\n\
-                     - DNS resolution failure\n\
-                     Error details: {e}"
-                    ))
+                    Error::UploadError(t!("error.storage_connect", error = e))
                 } else if e.is_request() {
-                    Error::UploadError(format!(
-                        "Request failed after uploading {bytes_uploaded} bytes. This may indicate:\n\
-                     - Network interruption during upload\n\
-                     - Proxy interfering with the request\n\
-                     - SSL/TLS issue\n\
-                     Error details: {e}"
+                    Error::UploadError(t!(
+                        "error.storage_request_after",
+                        bytes = bytes_uploaded,
+                        error = e
                     ))
                 } else {
                     Error::UploadError(format!("HTTP error: {e}"))
@@ -480,16 +1163,22 @@ impl Client {
                     .and_then(|s| s.split("</Message>").next())
                     .unwrap_or(&body);
 
-                return Err(Error::UploadError(format!(
-                    "Storage error: {error_code} - {error_message}\n\
+                return Err(Error::StorageStatus {
+                    status,
+                    message: format!(
+                        "Storage error: {error_code} - {error_message}\n\
                  \n\
                  To diagnose, test the upload URL directly:\n\
                  echo 'test' > test.txt\n\
                  curl -X PUT -H 'Content-Type: application/octet-stream' --data-binary @test.txt -v '<presigned-url>'"
-                )));
+                    ),
+                });
             }
 
-            return Err(Error::UploadError(format!("Status {status}: {body}")));
+            return Err(Error::StorageStatus {
+                status,
+                message: format!("Status {status}: {body}"),
+            });
         }
 
         info!("Upload successful");
@@ -501,7 +1190,7 @@ impl Client {
     /// # Errors
     ///
     /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
-    pub async fn complete_upload(&self, build_id: &str) -> Result<()> {
+    pub async fn complete_upload(&self, build_id: &str) -> Result<CompleteResponse> {
         let url = format!("{}/upload/complete", self.config.base_upload_url());
         debug!("Completing upload for build: {build_id}");
 
@@ -510,22 +1199,73 @@ impl Client {
         };
 
         let response = self
-            .http
-            .post(&url)
-            .header("x-api-key", self.config.token.clone())
-            .json(&request)
-            .send()
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(&request)
+            })
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::ApiError(format!(
-                "Complete failed - Status {status}: {body}"
-            )));
+            return Err(error_response("Complete", response).await);
         }
 
         info!("Upload completed successfully");
+        parse_complete_response(response).await
+    }
+
+    /// Report upload progress for a build so it can be shown on the dashboard
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn report_progress(&self, report: &ProgressReport) -> Result<()> {
+        let url = format!("{}/upload/progress", self.config.base_upload_url());
+        debug!(
+            "Reporting progress for build {}: {}%",
+            report.build_id, report.percent
+        );
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(report)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Progress report", response).await);
+        }
+
+        Ok(())
+    }
+
+    /// Attach a file to a set of builds uploaded together
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn upload_build_set_attachment(&self, attachment: &BuildSetAttachment) -> Result<()> {
+        let url = format!("{}/attachments", self.config.base_upload_url());
+        debug!(
+            "Attaching {} to {} build(s)",
+            attachment.file_name,
+            attachment.build_ids.len()
+        );
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(attachment)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Attachment upload", response).await);
+        }
+
         Ok(())
     }
 
@@ -534,6 +1274,10 @@ impl Client {
     /// # Errors
     ///
     /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    #[deprecated(
+        since = "0.1.20",
+        note = "use `initiate_multipart_upload_with` and an `UploadRequest`"
+    )]
     #[allow(clippy::too_many_arguments)]
     pub async fn initiate_multipart_upload(
         &self,
@@ -548,46 +1292,52 @@ impl Client {
         details: Option<BuildDetails>,
         tags: Option<Vec<String>>,
     ) -> Result<MultipartUploadResponse> {
-        let url = format!("{}/upload", self.config.base_upload_url());
-        debug!("Initiating multipart upload at: {url}");
-
-        let request = UploadRequest {
-            name: name.to_string(),
+        self.initiate_multipart_upload_with(&UploadRequest::legacy(
+            name,
+            filename,
+            size,
+            platform,
+            true,
             description,
-            file_name: filename.to_string(),
-            file_size: size,
-            platform: platform.to_string(),
-            multipart: true,
-            auto_delete: Some(auto_delete),
-            deletion_policy,
             upload_timeout,
+            auto_delete,
+            deletion_policy,
             details,
             tags,
-        };
+        ))
+        .await
+    }
+
+    /// Initiate the multipart upload described by `request`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn initiate_multipart_upload_with(
+        &self,
+        request: &UploadRequest,
+    ) -> Result<MultipartUploadResponse> {
+        let url = format!("{}/upload", self.config.base_upload_url());
+        debug!("Initiating multipart upload at: {url}");
 
         debug!("Upload request: {request:?}");
 
         let response = self
-            .http
-            .post(&url)
-            .header("x-api-key", self.config.token.clone())
-            .json(&request)
-            .send()
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(request)
+            })
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::ApiError(format!("Status {status}: {body}")));
+            return Err(error_response("Multipart initiate", response).await);
         }
 
         let body = response.text().await?;
         debug!("Initiate response body: {body}");
 
-        let upload_response: MultipartUploadResponse =
-            serde_json::from_str(&body).map_err(|e| {
-                Error::ApiError(format!("Failed to parse response: {e}. Body was: {body}"))
-            })?;
+        let upload_response: MultipartUploadResponse = parse_response("multipart/initiate", &body)?;
 
         debug!(
             "Initiated multipart upload - build_id: {}, upload_id: {}, total_parts: {}",
@@ -627,15 +1377,16 @@ impl Client {
             ("part_numbers", &part_numbers_str),
         ];
 
-        let response = self.http.get(&url).query(&query_params).send().await?;
+        let response = self
+            .send_api(&url, |http, url| http.get(url).query(&query_params))
+            .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::ApiError(format!("Status {status}: {body}")));
+            return Err(error_response("Part URL request", response).await);
         }
 
-        let urls_response: GetUploadUrlsResponse = response.json().await?;
+        let body = response.text().await?;
+        let urls_response: GetUploadUrlsResponse = parse_response("multipart/urls", &body)?;
         debug!("Received {} upload URLs", urls_response.upload_urls.len());
 
         Ok(urls_response)
@@ -646,19 +1397,58 @@ impl Client {
     /// # Errors
     ///
     /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
-    pub async fn upload_part(&self, url: &str, data: Vec<u8>) -> Result<String> {
+    pub async fn upload_part(&self, url: &str, data: Bytes) -> Result<String> {
+        self.upload_part_with_progress(url, data, |_| {}).await
+    }
+
+    /// Upload a part like [`Client::upload_part`], calling `progress_callback` with the
+    /// bytes of the part sent so far as the body is sent
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn upload_part_with_progress<F>(
+        &self,
+        url: &str,
+        data: Bytes,
+        progress_callback: F,
+    ) -> Result<String>
+    where
+        F: Fn(u64) + Send + Sync + 'static,
+    {
+        use futures::StreamExt;
+
+        let progress_callback = std::sync::Arc::new(progress_callback);
+        let make_body = || {
+            let progress_callback = progress_callback.clone();
+            let chunks: Vec<Bytes> = (0..data.len())
+                .step_by(PART_BODY_CHUNK_SIZE)
+                .map(|start| data.slice(start..(start + PART_BODY_CHUNK_SIZE).min(data.len())))
+                .collect();
+            let mut sent = 0;
+            reqwest::Body::wrap_stream(futures::stream::iter(chunks).map(move |chunk| {
+                sent += chunk.len() as u64;
+                progress_callback(sent);
+                Ok::<_, std::io::Error>(chunk)
+            }))
+        };
+
         let response = self
-            .http
-            .put(url)
-            .header("Content-Type", "application/octet-stream")
-            .body(data)
-            .send()
+            .send_storage_put(url, |http| {
+                http.put(url)
+                    .header("Content-Type", "application/octet-stream")
+                    .header("Content-Length", data.len().to_string())
+                    .body(make_body())
+            })
             .await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(Error::UploadError(format!("Status {status}: {body}")));
+            return Err(Error::StorageStatus {
+                status,
+                message: format!("Status {status}: {body}"),
+            });
         }
 
         // Extract ETag from response headers
@@ -672,6 +1462,46 @@ impl Client {
         Ok(etag)
     }
 
+    /// Copy parts stored by earlier uploads into a multipart upload, returning the parts
+    /// the server copied
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code or the response cannot be parsed.
+    pub async fn reuse_parts(
+        &self,
+        upload_id: &str,
+        object_key: &str,
+        parts: Vec<PartReuse>,
+    ) -> Result<Vec<UploadedPart>> {
+        let url = format!("{}/upload/parts/reuse", self.config.base_upload_url());
+        debug!("Requesting reuse of {} parts at: {url}", parts.len());
+
+        let request = ReusePartsRequest {
+            upload_id: upload_id.to_string(),
+            object_key: object_key.to_string(),
+            parts,
+        };
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(&request)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Part reuse", response).await);
+        }
+
+        let body = response.text().await?;
+        let reused: ReusePartsResponse = parse_response("multipart/reuse", &body)?;
+        debug!("Server reused {} parts", reused.parts.len());
+        Ok(reused.parts)
+    }
+
     /// Complete a multipart upload
     ///
     /// # Errors
@@ -683,7 +1513,7 @@ impl Client {
         upload_id: &str,
         object_key: &str,
         parts: Vec<UploadedPart>,
-    ) -> Result<()> {
+    ) -> Result<CompleteResponse> {
         let url = format!("{}/upload/complete", self.config.base_upload_url());
         debug!("Completing multipart upload for build: {build_id}");
 
@@ -695,23 +1525,62 @@ impl Client {
         };
 
         let response = self
-            .http
-            .post(&url)
-            .header("x-api-key", self.config.token.clone())
-            .json(&request)
-            .send()
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(&request)
+            })
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::ApiError(format!(
-                "Complete multipart failed - Status {status}: {body}"
-            )));
+            return Err(error_response("Complete multipart", response).await);
         }
 
         info!("Multipart upload completed successfully");
-        Ok(())
+        parse_complete_response(response).await
+    }
+
+    /// Report the parts of one shard of a sharded multipart upload
+    ///
+    /// The server completes the upload once every shard has reported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code or the response cannot be parsed.
+    pub async fn complete_shard(
+        &self,
+        build_id: &str,
+        upload_id: &str,
+        object_key: &str,
+        shard: u32,
+        parts: Vec<UploadedPart>,
+    ) -> Result<CompleteShardResponse> {
+        let url = format!("{}/upload/shards/complete", self.config.base_upload_url());
+        debug!("Completing shard {shard} of build {build_id}");
+
+        let request = CompleteShardRequest {
+            build_id: build_id.to_string(),
+            upload_id: upload_id.to_string(),
+            object_key: object_key.to_string(),
+            shard,
+            parts,
+        };
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(&request)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Complete shard", response).await);
+        }
+
+        let body = response.text().await?;
+        parse_response("shard complete", &body)
     }
 
     /// Abort an upload
@@ -739,22 +1608,634 @@ impl Client {
         }
 
         let response = self
-            .http
-            .delete(&url)
-            .header("x-api-key", self.config.token.clone())
-            .query(&query_params)
-            .send()
+            .send_api(&url, |http, url| {
+                http.delete(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .query(&query_params)
+            })
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::ApiError(format!(
-                "Abort upload failed - Status {status}: {body}"
-            )));
+            return Err(error_response("Abort upload", response).await);
         }
 
         info!("Upload aborted successfully");
         Ok(())
     }
+
+    /// Delete a build
+    ///
+    /// Deleted builds are moved to the trash and can be restored until the server's
+    /// soft-delete window expires, unless `purge` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn delete_build(&self, build_id: &str, purge: bool) -> Result<DeleteBuildResponse> {
+        let url = format!("{}/{build_id}", self.config.base_upload_url());
+        debug!("Deleting build {build_id} (purge: {purge})");
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.delete(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .query(&[("purge", purge)])
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Delete build", response).await);
+        }
+
+        let body = response.text().await?;
+        debug!("Delete response body: {body}");
+
+        // Older servers reply without a body
+        if body.trim().is_empty() {
+            return Ok(DeleteBuildResponse {
+                build_id: build_id.to_string(),
+                purged: purge,
+                restorable_until: None,
+            });
+        }
+
+        parse_response("delete", &body)
+    }
+
+    /// Restore a deleted build from the trash
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn restore_build(&self, build_id: &str) -> Result<()> {
+        let url = format!("{}/{build_id}/restore", self.config.base_upload_url());
+        debug!("Restoring build {build_id}");
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Restore build", response).await);
+        }
+
+        info!("Build restored successfully");
+        Ok(())
+    }
+
+    /// Find the build uploaded before `build_id` on the same platform (and branch, if given)
+    ///
+    /// Returns `Ok(None)` if there is no earlier build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code other than 404 or the response cannot be parsed.
+    pub async fn previous_build(
+        &self,
+        build_id: &str,
+        platform: &str,
+        branch: Option<&str>,
+    ) -> Result<Option<PreviousBuild>> {
+        let url = format!("{}/{build_id}/previous", self.config.base_upload_url());
+        debug!("Looking up the build before {build_id} (platform: {platform})");
+
+        let mut query_params = vec![("platform", platform)];
+        if let Some(branch) = branch {
+            query_params.push(("branch", branch));
+        }
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.get(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .query(&query_params)
+            })
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(error_response("Previous build lookup", response).await);
+        }
+
+        let body = response.text().await?;
+        parse_response("previous build", &body).map(Some)
+    }
+
+    /// Fetch the processing state of an upload being completed
+    ///
+    /// Returns `Ok(None)` if the server does not report it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code other than 404 or the response cannot be parsed.
+    pub async fn upload_status(&self, build_id: &str) -> Result<Option<UploadStatus>> {
+        let url = format!("{}/{build_id}/upload/status", self.config.base_upload_url());
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.get(url).header("x-api-key", self.config.token.clone())
+            })
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(error_response("Upload status", response).await);
+        }
+
+        let body = response.text().await?;
+        parse_response("upload status", &body).map(Some)
+    }
+
+    /// Fetch the size and SHA-256 of the object stored for `build_id`
+    ///
+    /// Returns `Ok(None)` if the server does not report it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code other than 404 or the response cannot be parsed.
+    pub async fn stored_object(&self, build_id: &str) -> Result<Option<StoredObject>> {
+        let url = format!("{}/{build_id}/upload/object", self.config.base_upload_url());
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.get(url).header("x-api-key", self.config.token.clone())
+            })
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(error_response("Stored object", response).await);
+        }
+
+        let body = response.text().await?;
+        parse_response("stored object", &body).map(Some)
+    }
+
+    /// Merge fields into the details of an uploaded build
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails or if the server returns a non-success status code.
+    pub async fn update_build_details(
+        &self,
+        build_id: &str,
+        details: &serde_json::Value,
+    ) -> Result<()> {
+        let url = format!("{}/{build_id}/details", self.config.base_upload_url());
+        debug!("Updating details of build {build_id}");
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.patch(url)
+                    .header("x-api-key", self.config.token.clone())
+                    .json(details)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Build details update", response).await);
+        }
+
+        Ok(())
+    }
+
+    /// Generate a data key for encrypting one upload, wrapped by the project's KMS key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code (e.g. the project has no encryption key) or the response cannot be parsed.
+    pub async fn create_data_key(&self) -> Result<DataKey> {
+        let url = format!("{}/encryption/data-keys", self.config.base_upload_url());
+        debug!("Requesting an upload data key");
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.post(url)
+                    .header("x-api-key", self.config.token.clone())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Data key request", response).await);
+        }
+
+        let body = response.text().await?;
+        parse_response("data key", &body)
+    }
+
+    /// List every build of the project, following pagination
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an HTTP request fails, the server returns a non-success status
+    /// code or a response cannot be parsed.
+    pub async fn list_builds(&self) -> Result<Vec<BuildSummary>> {
+        let url = self.config.base_upload_url();
+        let mut builds = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            debug!("Listing builds (cursor: {cursor:?})");
+            let response = self
+                .send_api(&url, |http, url| {
+                    let request = http
+                        .get(url)
+                        .header("x-api-key", self.config.token.clone())
+                        .query(&[("limit", "100")]);
+                    match &cursor {
+                        Some(cursor) => request.query(&[("cursor", cursor)]),
+                        None => request,
+                    }
+                })
+                .await?;
+
+            if !response.status().is_success() {
+                return Err(error_response("Build list", response).await);
+            }
+
+            let body = response.text().await?;
+            let page: BuildListResponse = parse_response("build list", &body)?;
+            builds.extend(page.builds);
+            match page.next_cursor {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => return Ok(builds),
+            }
+        }
+    }
+
+    /// Fetch the project's upload defaults
+    ///
+    /// Returns `Ok(None)` if the server does not provide them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code other than 404 or the response cannot be parsed.
+    pub async fn get_project_settings(&self) -> Result<Option<ProjectSettings>> {
+        let url = format!("{}/settings", self.config.project_url());
+        debug!("Fetching project settings");
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.get(url).header("x-api-key", self.config.token.clone())
+            })
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(error_response("Project settings", response).await);
+        }
+
+        let body = response.text().await?;
+        parse_response("project settings", &body).map(Some)
+    }
+
+    /// Fetch the public keys the server signs upload receipts with
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request fails, the server returns a non-success status
+    /// code or the response cannot be parsed.
+    pub async fn receipt_keys(&self) -> Result<Vec<ReceiptKey>> {
+        let url = format!("{}/receipts/keys", self.config.base_upload_url());
+        debug!("Fetching receipt signing keys");
+
+        let response = self
+            .send_api(&url, |http, url| {
+                http.get(url).header("x-api-key", self.config.token.clone())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_response("Receipt keys request", response).await);
+        }
+
+        let body = response.text().await?;
+        let keys: ReceiptKeysResponse = parse_response("receipt keys", &body)?;
+        Ok(keys.keys)
+    }
+}
+
+/// Blocking HTTP client with the proxy, TLS and connection settings of `transport`, for the
+/// requests made outside the async runtime: reading the API token from a secret store
+///
+/// `extra_roots` are trusted in addition to the transport's CA bundle, and each request may
+/// take up to `timeout`.
+///
+/// # Errors
+///
+/// Returns an error if the proxy, CA bundle or client certificate is invalid
+#[cfg(any(feature = "vault", feature = "aws-secrets-manager"))]
+pub(crate) fn blocking_http_client(
+    transport: &crate::config::TransportOptions,
+    timeout: Duration,
+    extra_roots: Vec<reqwest::Certificate>,
+) -> Result<reqwest::blocking::Client> {
+    let mut builder = reqwest::blocking::Client::builder().timeout(timeout);
+    if let Some(proxy) = transport
+        .proxy
+        .build(std::iter::empty(), |name| std::env::var(name).ok())?
+    {
+        builder = builder.proxy(proxy);
+    }
+    let tls = TlsSettings::new(transport)?;
+    for certificate in tls.roots.into_iter().chain(extra_roots) {
+        builder = builder.add_root_certificate(certificate);
+    }
+    if let Some(identity) = tls.identity {
+        builder = builder.identity(identity);
+    }
+    builder = builder.danger_accept_invalid_certs(tls.insecure);
+    if let Some(timeout) = transport.connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    for (host, addr) in &transport.resolve {
+        builder = builder.resolve(host, *addr);
+    }
+    Ok(builder.build()?)
+}
+
+/// Turn a non-success API response into an error, parsing the server's error envelope
+async fn error_response(operation: &str, response: reqwest::Response) -> Error {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    api_error(operation, status, &body)
+}
+
+/// Parse the body of a successful complete request
+///
+/// Older servers reply without a body, which yields a response without a receipt.
+async fn parse_complete_response(response: reqwest::Response) -> Result<CompleteResponse> {
+    let body = response.text().await?;
+    debug!("Complete response body: {body}");
+    if body.trim().is_empty() {
+        return Ok(CompleteResponse::default());
+    }
+    parse_response("complete", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_platform_names() {
+        for name in ["switch", "webgl", "steam-deck"] {
+            let platform: BuildPlatform = name.parse().unwrap();
+            assert_eq!(platform.as_str(), name);
+            assert_eq!(
+                serde_json::to_string(&platform).unwrap(),
+                format!("\"{name}\"")
+            );
+            let upper: BuildPlatform = name.to_uppercase().parse().unwrap();
+            assert_eq!(upper.as_str(), name);
+        }
+        assert!(matches!(
+            "webgl".parse::<BuildPlatform>().unwrap(),
+            BuildPlatform::Webgl
+        ));
+        // The deprecated spelling still parses
+        assert!(matches!(
+            "SteamDeck".parse::<BuildPlatform>().unwrap(),
+            BuildPlatform::SteamDeck
+        ));
+        let err = "wii".parse::<BuildPlatform>().unwrap_err();
+        assert!(err.to_string().contains("switch, webgl, steam-deck"));
+    }
+
+    #[test]
+    fn test_part_urls_accept_both_field_names() {
+        let current: GetUploadUrlsResponse = parse_response(
+            "multipart/urls",
+            r#"{"upload_urls": [{"part_number": 2, "url": "https://s3/2"}]}"#,
+        )
+        .unwrap();
+        let legacy: GetUploadUrlsResponse = parse_response(
+            "multipart/urls",
+            r#"{"presigned_urls": [{"part_number": 2, "presigned_url": "https://s3/2"}]}"#,
+        )
+        .unwrap();
+        for response in [current, legacy] {
+            assert_eq!(response.upload_urls.len(), 1);
+            assert_eq!(response.upload_urls[0].part_number, 2);
+            assert_eq!(response.upload_urls[0].url, "https://s3/2");
+        }
+
+        let single: SinglePartUploadResponse = parse_response(
+            "upload",
+            r#"{"build_id": "b1", "presigned_url": "https://s3/b1", "object_key": "k"}"#,
+        )
+        .unwrap();
+        assert_eq!(single.upload_url, "https://s3/b1");
+    }
+
+    #[test]
+    fn test_build_details_strip_control_characters() {
+        let details = BuildDetails {
+            vcs: None,
+            ci: None,
+            upload: None,
+            custom: BTreeMap::from([
+                ("branch".to_string(), "main\u{0}\u{1b}[0m".to_string()),
+                ("notes".to_string(), "line 1\nline 2\t\u{fffd}".to_string()),
+            ]),
+            artifact: None,
+            size_budget: None,
+            collection_warnings: vec!["git is not installed".to_string()],
+        };
+        assert_eq!(
+            serde_json::to_value(&details).unwrap(),
+            serde_json::json!({
+                "custom": {"branch": "main[0m", "notes": "line 1\nline 2\t\u{fffd}"},
+                "collection_warnings": [
+                    "git is not installed",
+                    "control characters removed from custom metadata"
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_legacy_upload_request() {
+        let request = UploadRequest::legacy(
+            "Nightly",
+            "game.zip",
+            42,
+            "windows",
+            true,
+            None,
+            Some(60),
+            false,
+            None,
+            None,
+            Some(vec!["qa".to_string()]),
+        );
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "name": "Nightly",
+                "file_name": "game.zip",
+                "file_size": 42,
+                "platform": "windows",
+                "multipart": true,
+                "auto_delete": false,
+                "upload_timeout": 60,
+                "tags": ["qa"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_clients_share_http_pool() {
+        let config = Config::new(
+            "t".to_string(),
+            "p".to_string(),
+            "https://a/api".to_string(),
+        )
+        .unwrap();
+        let clone = config.clone();
+        assert!(clone.http.clients.get().is_none());
+
+        Client::try_new(config.clone()).unwrap();
+        assert!(clone.http.clients.get().is_some());
+        let _ = Client::new(clone);
+
+        let rebuilt = config.with_transport(crate::config::TransportOptions::default());
+        assert!(rebuilt.http.clients.get().is_none());
+    }
+
+    /// Serve HTTP/1.1 on a local port, answering each request (`METHOD /path?query`) with
+    /// the status and body `respond` returns and recording the requests
+    async fn serve(
+        respond: fn(&str) -> (u16, &'static str),
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let paths = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = paths.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let line = request.lines().next().unwrap_or_default();
+                let line = line
+                    .rsplit_once(' ')
+                    .map_or(line, |(line, _)| line)
+                    .to_string();
+                let (status, body) = respond(&line);
+                seen.lock().unwrap().push(line);
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (base, paths)
+    }
+
+    #[tokio::test]
+    async fn test_send_api_fails_over_to_healthy_url() {
+        let (base, paths) = serve(|request| match request {
+            "GET /b/status" => (200, "ok"),
+            "GET /b/nexus/projects/p/settings" => (200, r#"{"default_tags": ["team"]}"#),
+            _ => (503, ""),
+        })
+        .await;
+        // A port nothing listens on, so connecting to the primary API URL fails
+        let unreachable = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let config = Config::new(
+            "t".to_string(),
+            "p".to_string(),
+            format!("http://{unreachable}"),
+        )
+        .unwrap()
+        .with_fallback_urls(vec![format!("{base}/a"), format!("{base}/b/")])
+        .with_health_path(Some("status".to_string()));
+        let client = Client::try_new(config.clone()).unwrap();
+
+        // The unhealthy fallback is skipped and the request sent to the healthy one
+        let settings = client.get_project_settings().await.unwrap().unwrap();
+        assert_eq!(settings.default_tags, ["team"]);
+        assert_eq!(config.active_api_index(), 2);
+        assert_eq!(
+            *paths.lock().unwrap(),
+            [
+                "GET /a/status",
+                "GET /b/status",
+                "GET /b/nexus/projects/p/settings"
+            ]
+        );
+
+        // Later requests go to the fallback directly
+        client.get_project_settings().await.unwrap();
+        assert_eq!(paths.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_restore_build() {
+        let (base, requests) = serve(|request| match request {
+            "DELETE /api/nexus/projects/p/builds/b1?purge=false" => (
+                200,
+                r#"{"build_id": "b1", "restorable_until": "2026-11-01T00:00:00Z"}"#,
+            ),
+            "DELETE /api/nexus/projects/p/builds/b2?purge=true"
+            | "POST /api/nexus/projects/p/builds/b1/restore" => (200, ""),
+            _ => (404, r#"{"error": "Build not found"}"#),
+        })
+        .await;
+        let config = Config::new("t".to_string(), "p".to_string(), format!("{base}/api")).unwrap();
+        let client = Client::try_new(config).unwrap();
+
+        let deleted = client.delete_build("b1", false).await.unwrap();
+        assert_eq!(deleted.build_id, "b1");
+        assert!(!deleted.purged);
+        assert_eq!(
+            deleted.restorable_until.as_deref(),
+            Some("2026-11-01T00:00:00Z")
+        );
+
+        // Older servers reply without a body
+        let purged = client.delete_build("b2", true).await.unwrap();
+        assert_eq!(purged.build_id, "b2");
+        assert!(purged.purged);
+        assert_eq!(purged.restorable_until, None);
+
+        client.restore_build("b1").await.unwrap();
+        let err = client.restore_build("b3").await.unwrap_err();
+        assert!(err.to_string().contains("Build not found"), "{err}");
+        assert!(client.delete_build("b3", false).await.is_err());
+
+        assert_eq!(requests.lock().unwrap().len(), 5);
+    }
 }
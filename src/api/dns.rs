@@ -0,0 +1,217 @@
+use crate::error::{Error, Result};
+use log::debug;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Most host names kept in the cache; an upload only talks to a handful of hosts
+const MAX_CACHED_HOSTS: usize = 64;
+
+/// A resolution and how long the resolver that made it allowed it to be reused
+#[derive(Debug)]
+struct CachedResolution {
+    resolved_at: Instant,
+    ttl: Duration,
+    addrs: Vec<SocketAddr>,
+}
+
+impl CachedResolution {
+    /// Whether the resolution may still be used by a resolver with the given `ttl`
+    fn is_fresh(&self, ttl: Duration, now: Instant) -> bool {
+        now.saturating_duration_since(self.resolved_at) < self.ttl.min(ttl)
+    }
+}
+
+/// Resolutions keyed by host name
+#[derive(Debug, Default)]
+struct DnsCache {
+    entries: HashMap<String, CachedResolution>,
+}
+
+impl DnsCache {
+    fn get(&self, host: &str, ttl: Duration, now: Instant) -> Option<Vec<SocketAddr>> {
+        self.entries
+            .get(host)
+            .filter(|entry| entry.is_fresh(ttl, now))
+            .map(|entry| entry.addrs.clone())
+    }
+
+    /// Store a resolution, dropping expired entries and the oldest ones beyond the size limit
+    fn insert(&mut self, host: String, ttl: Duration, addrs: Vec<SocketAddr>, now: Instant) {
+        self.entries.insert(
+            host,
+            CachedResolution {
+                resolved_at: now,
+                ttl,
+                addrs,
+            },
+        );
+        self.entries
+            .retain(|_, entry| entry.is_fresh(entry.ttl, now));
+        while self.entries.len() > MAX_CACHED_HOSTS {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.resolved_at)
+                .map(|(host, _)| host.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// Resolutions shared by every client in the process
+static DNS_CACHE: LazyLock<Mutex<DnsCache>> = LazyLock::new(|| Mutex::new(DnsCache::default()));
+
+/// DNS resolver that caches lookups for a fixed TTL
+///
+/// Multipart uploads issue hundreds of requests to the same storage host; caching
+/// avoids re-resolving it for every part on DNS servers that throttle lookups. The cache
+/// is shared by all resolvers, but each only reuses resolutions younger than its own TTL.
+#[derive(Debug, Clone)]
+pub struct CachingResolver {
+    ttl: Duration,
+}
+
+impl CachingResolver {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl }
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        let ttl = self.ttl;
+
+        if let Ok(cache) = DNS_CACHE.lock()
+            && let Some(addrs) = cache.get(&host, ttl, Instant::now())
+        {
+            debug!("Using cached DNS resolution for {host}");
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            return Box::pin(std::future::ready(Ok(addrs)));
+        }
+
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            debug!("Resolved {host} to {} address(es)", addrs.len());
+
+            if let Ok(mut cache) = DNS_CACHE.lock() {
+                cache.insert(host, ttl, addrs.clone(), Instant::now());
+            }
+
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Parse a `--resolve` override in `host:ip` form (curl's `host:port:ip` is also accepted)
+///
+/// The port in the request URL always takes precedence, so a port given here is
+/// only used as a fallback.
+///
+/// # Errors
+///
+/// Returns an error if the value has no host or the address is not a valid IP
+pub fn parse_resolve_override(value: &str) -> Result<(String, SocketAddr)> {
+    let invalid = || {
+        Error::ConfigError(format!(
+            "Invalid resolve override '{value}'. Expected HOST:IP (e.g. storage.example.com:203.0.113.7)"
+        ))
+    };
+
+    let (host, rest) = value.split_once(':').ok_or_else(invalid)?;
+    if host.is_empty() {
+        return Err(invalid());
+    }
+
+    let (port, addr) = match rest.split_once(':') {
+        Some((port, addr)) if !addr.is_empty() && port.parse::<u16>().is_ok() => {
+            (port.parse::<u16>().unwrap_or(0), addr)
+        }
+        _ => (0, rest),
+    };
+
+    let ip: IpAddr = addr
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| invalid())?;
+
+    Ok((host.to_ascii_lowercase(), SocketAddr::new(ip, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolve_override() {
+        let (host, addr) = parse_resolve_override("Storage.example.com:203.0.113.7").unwrap();
+        assert_eq!(host, "storage.example.com");
+        assert_eq!(addr, "203.0.113.7:0".parse().unwrap());
+
+        let (_, addr) = parse_resolve_override("example.com:443:203.0.113.7").unwrap();
+        assert_eq!(addr, "203.0.113.7:443".parse().unwrap());
+
+        let (_, addr) = parse_resolve_override("example.com:[::1]").unwrap();
+        assert_eq!(addr.ip(), "::1".parse::<IpAddr>().unwrap());
+
+        assert!(parse_resolve_override("example.com").is_err());
+        assert!(parse_resolve_override(":203.0.113.7").is_err());
+        assert!(parse_resolve_override("example.com:not-an-ip").is_err());
+    }
+
+    #[test]
+    fn test_dns_cache_ttl_and_size() {
+        let addrs = vec!["203.0.113.7:0".parse().unwrap()];
+        let start = Instant::now();
+        let mut cache = DnsCache::default();
+        cache.insert(
+            "a.example.com".to_string(),
+            Duration::from_mins(1),
+            addrs.clone(),
+            start,
+        );
+
+        let later = start + Duration::from_secs(30);
+        assert!(
+            cache
+                .get("a.example.com", Duration::from_mins(1), later)
+                .is_some()
+        );
+        // A resolver with a shorter TTL does not reuse the longer-lived entry
+        assert!(
+            cache
+                .get("a.example.com", Duration::from_secs(10), later)
+                .is_none()
+        );
+        // Nor does a longer TTL extend the entry past the TTL it was stored with
+        let expired = start + Duration::from_secs(90);
+        assert!(
+            cache
+                .get("a.example.com", Duration::from_mins(5), expired)
+                .is_none()
+        );
+
+        // Expired entries are evicted on write, and the oldest beyond the limit
+        for i in 0..=MAX_CACHED_HOSTS {
+            cache.insert(
+                format!("host{i}"),
+                Duration::from_mins(1),
+                addrs.clone(),
+                expired + Duration::from_millis(i as u64),
+            );
+        }
+        assert_eq!(cache.entries.len(), MAX_CACHED_HOSTS);
+        assert!(!cache.entries.contains_key("a.example.com"));
+        assert!(!cache.entries.contains_key("host0"));
+    }
+}
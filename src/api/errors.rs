@@ -0,0 +1,134 @@
+//! Structured API error responses turned into actionable messages
+//!
+//! The backend reports failures as `{"error": {"code", "message", "docs_url"}}` (older
+//! endpoints send the fields at the top level). Known codes get a hint on how to fix the
+//! problem; anything that isn't an error envelope is shown as the raw body.
+
+use crate::error::Error;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::fmt::Write as _;
+
+/// Error details sent by the API
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(default)]
+    pub docs_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Envelope {
+    Nested { error: ApiErrorBody },
+    Flat(ApiErrorBody),
+}
+
+/// Parse an error response body, if it is an error envelope
+#[must_use]
+pub fn parse_error_body(body: &str) -> Option<ApiErrorBody> {
+    match serde_json::from_str(body).ok()? {
+        Envelope::Nested { error } | Envelope::Flat(error) => Some(error),
+    }
+}
+
+/// What to do about an error, by error code or, failing that, HTTP status
+#[must_use]
+pub fn hint(code: Option<&str>, status: StatusCode) -> Option<&'static str> {
+    let by_code = code.and_then(|code| {
+        Some(match code {
+            "invalid_api_key" | "api_key_expired" | "unauthorized" => {
+                "Check the API token (--token, NUNU_API_TOKEN or `api_token` in the config file) and that it has not expired"
+            }
+            "forbidden" | "insufficient_permissions" => {
+                "The API token has no access to this project; use a token with upload permission for it"
+            }
+            "project_not_found" => {
+                "Check the project ID (--project-id, NUNU_PROJECT_ID or `project_id` in the config file); aliases are resolved from the config file's `projects`"
+            }
+            "build_not_found" => {
+                "Check the build ID; deleted builds can be restored with `nunu-cli builds restore` until the trash window expires"
+            }
+            "storage_limit_exceeded" | "quota_exceeded" => {
+                "Delete old builds, or pass --auto-delete to remove them automatically when storage runs out"
+            }
+            "file_too_large" => "The file exceeds this project's maximum build size",
+            "invalid_platform" => {
+                "Pass --platform with one of: windows, macos, linux, android, ios-native, ios-simulator, xbox, playstation, switch, webgl, steam-deck"
+            }
+            "upload_expired" => {
+                "The upload URLs expired before the upload finished; retry, or raise --upload-timeout"
+            }
+            "rate_limited" => "Too many requests; wait a moment and try again",
+            "unsupported_client" | "cli_outdated" => {
+                "This nunu-cli version is no longer supported by the server; upgrade nunu-cli"
+            }
+            _ => return None,
+        })
+    });
+    by_code.or(match status {
+        StatusCode::UNAUTHORIZED => {
+            Some("Check the API token (--token, NUNU_API_TOKEN or `api_token` in the config file)")
+        }
+        StatusCode::TOO_MANY_REQUESTS => Some("Too many requests; wait a moment and try again"),
+        _ => None,
+    })
+}
+
+/// Build the error for a failed API request, including the server's message, a hint and
+/// the documentation link when available
+#[must_use]
+pub fn api_error(operation: &str, status: StatusCode, body: &str) -> Error {
+    let Some(error) = parse_error_body(body) else {
+        let mut message = format!("{operation} failed - Status {status}: {body}");
+        if let Some(hint) = hint(None, status) {
+            let _ = write!(message, "\n  Hint: {hint}");
+        }
+        return Error::ApiStatus {
+            status,
+            code: None,
+            message,
+        };
+    };
+
+    let mut message = format!(
+        "{operation} failed - Status {status}: {} [{}]",
+        error.message, error.code
+    );
+    if let Some(hint) = hint(Some(&error.code), status) {
+        let _ = write!(message, "\n  Hint: {hint}");
+    }
+    if let Some(docs_url) = &error.docs_url {
+        let _ = write!(message, "\n  Docs: {docs_url}");
+    }
+    Error::ApiStatus {
+        status,
+        code: Some(error.code),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error() {
+        let body = r#"{"error": {"code": "storage_limit_exceeded", "message": "Project storage is full", "docs_url": "https://docs.nunu.ai/storage"}}"#;
+        assert_eq!(
+            api_error("Upload request", StatusCode::FORBIDDEN, body).to_string(),
+            "API request failed: Upload request failed - Status 403 Forbidden: Project storage is full [storage_limit_exceeded]\n  \
+             Hint: Delete old builds, or pass --auto-delete to remove them automatically when storage runs out\n  \
+             Docs: https://docs.nunu.ai/storage"
+        );
+
+        let flat = parse_error_body(r#"{"code": "build_not_found", "message": "No such build"}"#);
+        assert_eq!(flat.map(|e| e.code).as_deref(), Some("build_not_found"));
+
+        assert_eq!(
+            api_error("Delete build", StatusCode::BAD_GATEWAY, "<html>").to_string(),
+            "API request failed: Delete build failed - Status 502 Bad Gateway: <html>"
+        );
+    }
+}
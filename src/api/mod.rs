@@ -1,2 +1,7 @@
 pub mod client;
-pub use client::{BuildPlatform, Client, DeletionPolicy};
+pub mod dns;
+pub mod errors;
+pub mod proxy;
+pub mod schema;
+pub mod tls;
+pub use client::{Arch, BuildPlatform, Client, DeletionPolicy};
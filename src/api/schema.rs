@@ -0,0 +1,157 @@
+use crate::error::{Error, Result};
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+
+/// Maximum number of body characters included in parse error messages
+const MAX_BODY_PREVIEW: usize = 500;
+
+/// Maximum number of renamed fields remapped for a single response
+const MAX_RENAMED_FIELDS: usize = 8;
+
+/// Schema drift notices already logged, so each is reported once per process
+static REPORTED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Leniently parse an API response body
+///
+/// Unknown fields are logged (once per process) instead of being silently dropped.
+/// Required fields the server sent under a different spelling (e.g. `uploadUrl` for
+/// `upload_url`) are remapped with a warning. If a required field is missing entirely
+/// the error hints that the CLI may be outdated for this server.
+///
+/// # Errors
+///
+/// Returns an error if the body is not JSON or does not match the expected schema
+pub fn parse_response<T: DeserializeOwned>(endpoint: &str, body: &str) -> Result<T> {
+    let mut value: Value = serde_json::from_str(body).map_err(|e| {
+        Error::ApiError(format!(
+            "{endpoint}: response is not valid JSON ({e}). Body was: {}",
+            preview(body)
+        ))
+    })?;
+
+    for _ in 0..=MAX_RENAMED_FIELDS {
+        let mut unknown = Vec::new();
+        let error = match serde_ignored::deserialize(value.clone(), |path| {
+            unknown.push(path.to_string());
+        }) {
+            Ok(parsed) => {
+                for field in unknown {
+                    report_once(&format!("{endpoint}:{field}"), || {
+                        info!(
+                            "{endpoint}: ignoring unknown response field '{field}' (a newer nunu-cli may use it)"
+                        );
+                    });
+                }
+                return Ok(parsed);
+            }
+            Err(e) => e,
+        };
+
+        let Some(field) = missing_field(&error) else {
+            return Err(Error::ApiError(format!(
+                "{endpoint}: unexpected response format ({error}). Body was: {}",
+                preview(body)
+            )));
+        };
+
+        let Some(renamed) = find_renamed(&value, &field) else {
+            return Err(Error::ApiError(format!(
+                "{endpoint}: response is missing required field '{field}'. This nunu-cli version ({}) may be outdated for this server; try upgrading",
+                env!("CARGO_PKG_VERSION")
+            )));
+        };
+
+        report_once(&format!("{endpoint}:{renamed}->{field}"), || {
+            warn!(
+                "{endpoint}: server sent '{renamed}' instead of '{field}'; accepting it, but nunu-cli may be outdated for this server"
+            );
+        });
+        if let Value::Object(map) = &mut value
+            && let Some(v) = map.remove(&renamed)
+        {
+            map.insert(field, v);
+        }
+    }
+
+    Err(Error::ApiError(format!(
+        "{endpoint}: too many renamed fields in response. Body was: {}",
+        preview(body)
+    )))
+}
+
+/// Extract the field name from serde's "missing field `name`" error
+fn missing_field(error: &serde_json::Error) -> Option<String> {
+    let message = error.to_string();
+    let rest = message.strip_prefix("missing field `")?;
+    rest.split_once('`').map(|(field, _)| field.to_string())
+}
+
+/// Find a top-level key that differs from `field` only in case or separators
+fn find_renamed(value: &Value, field: &str) -> Option<String> {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .collect::<String>()
+            .to_ascii_lowercase()
+    };
+    let wanted = normalize(field);
+
+    value
+        .as_object()?
+        .keys()
+        .find(|key| key.as_str() != field && normalize(key) == wanted)
+        .cloned()
+}
+
+fn report_once(key: &str, report: impl FnOnce()) {
+    if let Ok(mut reported) = REPORTED.lock()
+        && reported.insert(key.to_string())
+    {
+        report();
+    }
+}
+
+fn preview(body: &str) -> String {
+    match body.char_indices().nth(MAX_BODY_PREVIEW) {
+        Some((end, _)) => format!("{}… ({} bytes total)", &body[..end], body.len()),
+        None => body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    struct Response {
+        build_id: String,
+        upload_url: String,
+    }
+
+    #[test]
+    fn test_parse_response_tolerates_drift() {
+        let parsed: Response = parse_response(
+            "test",
+            r#"{"build_id": "b1", "uploadUrl": "https://x", "region": "eu"}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.build_id, "b1");
+        assert_eq!(parsed.upload_url, "https://x");
+
+        let err = parse_response::<Response>("test", r#"{"build_id": "b1"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("missing required field 'upload_url'"));
+        assert!(err.contains("outdated"));
+
+        let long_body = "x".repeat(2000);
+        let err = parse_response::<Response>("test", &long_body)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("2000 bytes total"));
+    }
+}
@@ -0,0 +1,89 @@
+pub mod stream;
+
+pub use stream::StreamingZip;
+
+use crate::api::BuildPlatform;
+use std::path::Path;
+
+/// Returns whether `path` is a macOS/iOS `.app` bundle directory
+#[must_use]
+pub fn is_app_bundle(path: &Path) -> bool {
+    path.is_dir()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("app"))
+}
+
+/// Infer the platform of an `.app` bundle from its layout
+///
+/// macOS bundles keep their `Info.plist` under `Contents/`, while iOS (simulator)
+/// bundles are flat with `Info.plist` at the root.
+#[must_use]
+pub fn app_bundle_platform(path: &Path) -> Option<BuildPlatform> {
+    if path.join("Contents").join("Info.plist").is_file() {
+        Some(BuildPlatform::Macos)
+    } else if path.join("Info.plist").is_file() {
+        Some(BuildPlatform::IosSimulator)
+    } else {
+        None
+    }
+}
+
+/// Returns whether a directory is a WebGL build: an `index.html` next to WebAssembly,
+/// either at the root or in `Build/` (Unity's layout, possibly `.wasm.gz`/`.wasm.br`)
+#[must_use]
+pub fn is_webgl_build(path: &Path) -> bool {
+    let has_wasm = |dir: &Path| {
+        std::fs::read_dir(dir).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|e| e.file_name().to_string_lossy().contains(".wasm"))
+        })
+    };
+    path.join("index.html").is_file() && (has_wasm(path) || has_wasm(&path.join("Build")))
+}
+
+/// Name of the archive a directory is uploaded as (e.g. `Game.app.zip`)
+#[must_use]
+pub fn directory_archive_name(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map_or_else(|| "archive".into(), |n| n.to_string_lossy());
+    format!("{name}.zip")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_bundle_detection() {
+        let temp = tempfile::tempdir().unwrap();
+        let mac = temp.path().join("Game.app");
+        std::fs::create_dir_all(mac.join("Contents")).unwrap();
+        std::fs::write(mac.join("Contents/Info.plist"), "<plist/>").unwrap();
+        let sim = temp.path().join("Sim.APP");
+        std::fs::create_dir_all(&sim).unwrap();
+        std::fs::write(sim.join("Info.plist"), "<plist/>").unwrap();
+
+        assert!(is_app_bundle(&mac) && is_app_bundle(&sim));
+        assert!(!is_app_bundle(temp.path()));
+        assert!(matches!(
+            app_bundle_platform(&mac),
+            Some(BuildPlatform::Macos)
+        ));
+        assert!(matches!(
+            app_bundle_platform(&sim),
+            Some(BuildPlatform::IosSimulator)
+        ));
+        assert_eq!(directory_archive_name(&mac), "Game.app.zip");
+
+        let web = temp.path().join("WebGL");
+        std::fs::create_dir_all(web.join("Build")).unwrap();
+        std::fs::write(web.join("index.html"), "<html/>").unwrap();
+        assert!(!is_webgl_build(&web));
+        std::fs::write(web.join("Build/WebGL.wasm.br"), b"\0asm").unwrap();
+        assert!(is_webgl_build(&web) && !is_webgl_build(&mac));
+    }
+}
@@ -0,0 +1,558 @@
+use crate::error::{Error, Result};
+use crate::files::IgnoreRules;
+use log::debug;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+
+const LOCAL_HEADER_LEN: u64 = 30;
+const CENTRAL_HEADER_LEN: u64 = 46;
+const ZIP64_END_LEN: u64 = 56;
+const ZIP64_LOCATOR_LEN: u64 = 20;
+const END_LEN: u64 = 22;
+
+/// Sizes and offsets at or above this value need zip64 extra fields
+const ZIP64_LIMIT: u64 = 0xFFFF_FFFF;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// Version needed to extract: 2.0 (data descriptors) and 4.5 (zip64)
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Version made by: Unix host, spec 4.5 (so external attributes carry Unix modes)
+const VERSION_MADE_BY: u16 = (3 << 8) | VERSION_ZIP64;
+
+/// General purpose flags: sizes and CRC in a trailing data descriptor, UTF-8 names
+const FLAGS: u16 = 0x0008 | 0x0800;
+
+/// Fixed MS-DOS timestamp (1980-01-01 00:00) so archives are reproducible
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+const S_IFDIR: u32 = 0o040_000;
+const S_IFREG: u32 = 0o100_000;
+const S_IFLNK: u32 = 0o120_000;
+const MSDOS_DIRECTORY: u32 = 0x10;
+
+#[derive(Debug, Clone)]
+enum EntryKind {
+    Directory,
+    File { path: PathBuf, size: u64 },
+    Symlink { target: Vec<u8> },
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    name: String,
+    kind: EntryKind,
+    mode: u32,
+    offset: u64,
+}
+
+impl Entry {
+    fn data_len(&self) -> u64 {
+        match &self.kind {
+            EntryKind::Directory => 0,
+            EntryKind::File { size, .. } => *size,
+            EntryKind::Symlink { target } => target.len() as u64,
+        }
+    }
+
+    /// Whether sizes need 64-bit fields in the local header and data descriptor
+    fn zip64_data(&self) -> bool {
+        self.data_len() >= ZIP64_LIMIT
+    }
+
+    fn local_len(&self) -> u64 {
+        let extra = if self.zip64_data() { 4 + 16 } else { 0 };
+        let descriptor = if self.zip64_data() { 24 } else { 16 };
+        LOCAL_HEADER_LEN + self.name.len() as u64 + extra + self.data_len() + descriptor
+    }
+
+    /// Number of 64-bit values in the central directory zip64 extra field
+    fn central_zip64_fields(&self) -> u64 {
+        let sizes = if self.zip64_data() { 2 } else { 0 };
+        let offset = u64::from(self.offset >= ZIP64_LIMIT);
+        sizes + offset
+    }
+
+    fn central_len(&self) -> u64 {
+        let fields = self.central_zip64_fields();
+        let extra = if fields > 0 { 4 + 8 * fields } else { 0 };
+        CENTRAL_HEADER_LEN + self.name.len() as u64 + extra
+    }
+
+    fn version_needed(&self) -> u16 {
+        if self.central_zip64_fields() > 0 {
+            VERSION_ZIP64
+        } else {
+            VERSION_DEFAULT
+        }
+    }
+
+    fn external_attributes(&self) -> u32 {
+        match self.kind {
+            EntryKind::Directory => ((S_IFDIR | self.mode) << 16) | MSDOS_DIRECTORY,
+            EntryKind::File { .. } => (S_IFREG | self.mode) << 16,
+            EntryKind::Symlink { .. } => (S_IFLNK | self.mode) << 16,
+        }
+    }
+}
+
+/// A directory archive planned up front and written as a stream
+///
+/// Entries are stored uncompressed with data descriptors and zip64 records where
+/// needed, so the exact archive size is known before any data is read and the
+/// archive can be produced on the fly (e.g. straight into multipart upload parts)
+/// without temp files or a 4 GB limit. Symlinks are stored as links and Unix
+/// permissions are preserved, matching `ditto -c -k --keepParent`.
+#[derive(Debug, Clone)]
+pub struct StreamingZip {
+    entries: Vec<Entry>,
+    central_offset: u64,
+    central_len: u64,
+    size: u64,
+}
+
+impl StreamingZip {
+    /// Walk `dir` and plan an archive rooted at its name, skipping entries matched by `ignore`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be read
+    pub fn plan(dir: &Path, ignore: Option<&IgnoreRules>) -> Result<Self> {
+        let root = dir.file_name().and_then(|n| n.to_str()).ok_or_else(|| {
+            Error::ConfigError(format!("Invalid directory path: {}", dir.display()))
+        })?;
+
+        let mut entries = Vec::new();
+        collect_entries(dir, root, ignore, &mut entries)?;
+
+        let mut offset = 0;
+        for entry in &mut entries {
+            entry.offset = offset;
+            offset += entry.local_len();
+        }
+
+        let central_offset = offset;
+        let central_len: u64 = entries.iter().map(Entry::central_len).sum();
+        let mut size = central_offset + central_len + END_LEN;
+        if needs_zip64_end(entries.len(), central_offset, central_len) {
+            size += ZIP64_END_LEN + ZIP64_LOCATOR_LEN;
+        }
+
+        debug!(
+            "Planned archive of {} with {} entries ({size} bytes)",
+            dir.display(),
+            entries.len()
+        );
+
+        Ok(Self {
+            entries,
+            central_offset,
+            central_len,
+            size,
+        })
+    }
+
+    /// Exact size in bytes of the archive that [`StreamingZip::write_to`] produces
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Number of entries (directories, files and symlinks) in the archive
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the archive has no entries
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the archive sequentially to `writer`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read, changed size since the archive was
+    /// planned, or the writer fails
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let mut out = CountingWriter {
+            inner: writer,
+            written: 0,
+        };
+
+        let mut crcs = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            debug_assert_eq!(out.written, entry.offset);
+            crcs.push(write_local_entry(&mut out, entry)?);
+        }
+
+        debug_assert_eq!(out.written, self.central_offset);
+        for (entry, crc) in self.entries.iter().zip(crcs) {
+            write_central_entry(&mut out, entry, crc)?;
+        }
+
+        self.write_end(&mut out)?;
+        out.flush()?;
+
+        if out.written != self.size {
+            return Err(Error::ArchiveError(format!(
+                "Archive size mismatch: planned {} bytes, wrote {}",
+                self.size, out.written
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Write the (zip64) end of central directory records
+    fn write_end<W: Write>(&self, out: &mut CountingWriter<W>) -> Result<()> {
+        let count = self.entries.len() as u64;
+        if needs_zip64_end(self.entries.len(), self.central_offset, self.central_len) {
+            let zip64_end_offset = out.written;
+            out.u32(ZIP64_END_SIGNATURE)?;
+            out.u64(ZIP64_END_LEN - 12)?;
+            out.u16(VERSION_MADE_BY)?;
+            out.u16(VERSION_ZIP64)?;
+            out.u32(0)?;
+            out.u32(0)?;
+            out.u64(count)?;
+            out.u64(count)?;
+            out.u64(self.central_len)?;
+            out.u64(self.central_offset)?;
+
+            out.u32(ZIP64_LOCATOR_SIGNATURE)?;
+            out.u32(0)?;
+            out.u64(zip64_end_offset)?;
+            out.u32(1)?;
+        }
+
+        let saturate32 = |value: u64| {
+            if value >= ZIP64_LIMIT {
+                u32::MAX
+            } else {
+                low32(value)
+            }
+        };
+        let count16 = u16::try_from(count).unwrap_or(u16::MAX);
+        out.u32(END_SIGNATURE)?;
+        out.u16(0)?;
+        out.u16(0)?;
+        out.u16(count16)?;
+        out.u16(count16)?;
+        out.u32(saturate32(self.central_len))?;
+        out.u32(saturate32(self.central_offset))?;
+        out.u16(0)?;
+        Ok(())
+    }
+}
+
+/// Write an entry's local header, data and data descriptor, returning its CRC-32
+fn write_local_entry<W: Write>(out: &mut CountingWriter<W>, entry: &Entry) -> Result<u32> {
+    let zip64 = entry.zip64_data();
+
+    // Local file header; CRC and sizes follow in the data descriptor
+    out.u32(LOCAL_HEADER_SIGNATURE)?;
+    out.u16(if zip64 {
+        VERSION_ZIP64
+    } else {
+        VERSION_DEFAULT
+    })?;
+    out.u16(FLAGS)?;
+    out.u16(0)?; // stored
+    out.u16(DOS_TIME)?;
+    out.u16(DOS_DATE)?;
+    out.u32(0)?;
+    let placeholder = if zip64 { u32::MAX } else { 0 };
+    out.u32(placeholder)?;
+    out.u32(placeholder)?;
+    out.u16(name_len(&entry.name)?)?;
+    out.u16(if zip64 { 20 } else { 0 })?;
+    out.write_all(entry.name.as_bytes())?;
+    if zip64 {
+        out.u16(ZIP64_EXTRA_ID)?;
+        out.u16(16)?;
+        out.u64(0)?;
+        out.u64(0)?;
+    }
+
+    let mut hasher = crc32fast::Hasher::new();
+    match &entry.kind {
+        EntryKind::Directory => {}
+        EntryKind::Symlink { target } => {
+            hasher.update(target);
+            out.write_all(target)?;
+        }
+        EntryKind::File { path, size } => {
+            let copied = copy_hashing(path, out, &mut hasher)?;
+            if copied != *size {
+                return Err(Error::ArchiveError(format!(
+                    "{} changed size while being archived ({size} -> {copied} bytes)",
+                    path.display()
+                )));
+            }
+        }
+    }
+    let crc = hasher.finalize();
+
+    out.u32(DATA_DESCRIPTOR_SIGNATURE)?;
+    out.u32(crc)?;
+    if zip64 {
+        out.u64(entry.data_len())?;
+        out.u64(entry.data_len())?;
+    } else {
+        out.u32(low32(entry.data_len()))?;
+        out.u32(low32(entry.data_len()))?;
+    }
+    Ok(crc)
+}
+
+/// Write an entry's central directory header
+fn write_central_entry<W: Write>(
+    out: &mut CountingWriter<W>,
+    entry: &Entry,
+    crc: u32,
+) -> Result<()> {
+    let zip64 = entry.zip64_data();
+    let fields = entry.central_zip64_fields();
+
+    out.u32(CENTRAL_HEADER_SIGNATURE)?;
+    out.u16(VERSION_MADE_BY)?;
+    out.u16(entry.version_needed())?;
+    out.u16(FLAGS)?;
+    out.u16(0)?;
+    out.u16(DOS_TIME)?;
+    out.u16(DOS_DATE)?;
+    out.u32(crc)?;
+    let size = if zip64 {
+        u32::MAX
+    } else {
+        low32(entry.data_len())
+    };
+    out.u32(size)?;
+    out.u32(size)?;
+    out.u16(name_len(&entry.name)?)?;
+    #[allow(clippy::cast_possible_truncation)]
+    out.u16(if fields > 0 {
+        (4 + 8 * fields) as u16
+    } else {
+        0
+    })?;
+    out.u16(0)?; // comment
+    out.u16(0)?; // disk number
+    out.u16(0)?; // internal attributes
+    out.u32(entry.external_attributes())?;
+    out.u32(if entry.offset >= ZIP64_LIMIT {
+        u32::MAX
+    } else {
+        low32(entry.offset)
+    })?;
+    out.write_all(entry.name.as_bytes())?;
+
+    if fields > 0 {
+        out.u16(ZIP64_EXTRA_ID)?;
+        #[allow(clippy::cast_possible_truncation)]
+        out.u16((8 * fields) as u16)?;
+        if zip64 {
+            out.u64(entry.data_len())?;
+            out.u64(entry.data_len())?;
+        }
+        if entry.offset >= ZIP64_LIMIT {
+            out.u64(entry.offset)?;
+        }
+    }
+    Ok(())
+}
+
+fn needs_zip64_end(count: usize, central_offset: u64, central_len: u64) -> bool {
+    count >= 0xFFFF || central_offset >= ZIP64_LIMIT || central_len >= ZIP64_LIMIT
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn low32(value: u64) -> u32 {
+    value as u32
+}
+
+fn name_len(name: &str) -> Result<u16> {
+    u16::try_from(name.len())
+        .map_err(|_| Error::ArchiveError(format!("Path too long for zip archive: {name}")))
+}
+
+fn collect_entries(
+    dir: &Path,
+    name: &str,
+    ignore: Option<&IgnoreRules>,
+    entries: &mut Vec<Entry>,
+) -> Result<()> {
+    entries.push(Entry {
+        name: format!("{name}/"),
+        kind: EntryKind::Directory,
+        mode: unix_mode(&std::fs::symlink_metadata(dir)?, 0o755),
+        offset: 0,
+    });
+
+    // Sort entries so archives are reproducible across filesystems
+    let mut children = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    children.sort_by_key(std::fs::DirEntry::file_name);
+
+    for child in children {
+        let path = child.path();
+        let entry_name = format!("{name}/{}", child.file_name().to_string_lossy());
+        let metadata = std::fs::symlink_metadata(&path)?;
+
+        if ignore.is_some_and(|rules| rules.is_ignored(&path, metadata.is_dir())) {
+            debug!("Skipping ignored archive entry {entry_name}");
+            continue;
+        }
+
+        if metadata.file_type().is_symlink() {
+            let target = std::fs::read_link(&path)?;
+            entries.push(Entry {
+                name: entry_name,
+                kind: EntryKind::Symlink {
+                    target: target.to_string_lossy().into_owned().into_bytes(),
+                },
+                mode: 0o777,
+                offset: 0,
+            });
+        } else if metadata.is_dir() {
+            collect_entries(&path, &entry_name, ignore, entries)?;
+        } else {
+            entries.push(Entry {
+                name: entry_name,
+                kind: EntryKind::File {
+                    path,
+                    size: metadata.len(),
+                },
+                mode: unix_mode(&metadata, 0o644),
+                offset: 0,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn copy_hashing<W: Write>(path: &Path, out: &mut W, hasher: &mut crc32fast::Hasher) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut copied = 0;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(copied);
+        }
+        hasher.update(&buffer[..read]);
+        out.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &std::fs::Metadata, _default: u32) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &std::fs::Metadata, default: u32) -> u32 {
+    default
+}
+
+/// Writer that tracks the number of bytes written and encodes little-endian integers
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn u16(&mut self, value: u16) -> std::io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> std::io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> std::io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_streaming_zip_round_trip() {
+        let temp = tempfile::tempdir().unwrap();
+        let app = temp.path().join("Game.app");
+        let macos = app.join("Contents/MacOS");
+        std::fs::create_dir_all(&macos).unwrap();
+        std::fs::write(app.join("Contents/Info.plist"), "<plist/>").unwrap();
+        std::fs::write(macos.join("Game"), "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(macos.join("Game"), std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+        std::os::unix::fs::symlink("MacOS/Game", app.join("Contents/Current")).unwrap();
+
+        let plan = StreamingZip::plan(&app, None).unwrap();
+        let mut bytes = Vec::new();
+        plan.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len() as u64, plan.size());
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut executable = archive.by_name("Game.app/Contents/MacOS/Game").unwrap();
+        assert_eq!(executable.unix_mode().unwrap() & 0o777, 0o755);
+        let mut contents = String::new();
+        executable.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "#!/bin/sh\n");
+        drop(executable);
+
+        let mut link = archive.by_name("Game.app/Contents/Current").unwrap();
+        assert!(link.is_symlink());
+        let mut target = String::new();
+        link.read_to_string(&mut target).unwrap();
+        assert_eq!(target, "MacOS/Game");
+    }
+
+    #[test]
+    fn test_zip64_layout_sizes() {
+        let entry = Entry {
+            name: "big.pak".to_string(),
+            kind: EntryKind::File {
+                path: PathBuf::new(),
+                size: 5 * 1024 * 1024 * 1024,
+            },
+            mode: 0o644,
+            offset: 6 * 1024 * 1024 * 1024,
+        };
+        assert!(entry.zip64_data());
+        assert_eq!(entry.central_zip64_fields(), 3);
+        assert_eq!(entry.central_len(), 46 + 7 + 4 + 24);
+        assert_eq!(entry.local_len(), 30 + 7 + 20 + 5 * 1024 * 1024 * 1024 + 24);
+        assert!(needs_zip64_end(1, 11 * 1024 * 1024 * 1024, 100));
+    }
+}
@@ -0,0 +1,394 @@
+use super::axml::{AndroidManifestInfo, parse_axml_manifest, parse_proto_manifest};
+use crate::error::{Error, Result};
+use crate::util::hex_encode;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const APK_SIG_BLOCK_MAGIC: &[u8; 16] = b"APK Sig Block 42";
+const SCHEME_V2_ID: u32 = 0x7109_871a;
+const SCHEME_V3_ID: u32 = 0xf053_68c0;
+const SCHEME_V31_ID: u32 = 0x1b93_ad61;
+
+/// End of central directory record: fixed part plus the longest possible comment
+const EOCD_SEARCH_LEN: u64 = 22 + 0xFFFF;
+
+/// Signing details of an APK or AAB, attached to the build's metadata
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AndroidSigning {
+    /// Signature schemes present (`v1` for JAR signing, `v2`, `v3`, `v3.1`)
+    pub schemes: Vec<String>,
+    /// SHA-256 digest of the signing certificate, as lowercase hex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate_sha256: Option<String>,
+    /// Signed with the Android SDK's auto-generated debug key
+    pub debug_signed: bool,
+}
+
+impl AndroidSigning {
+    /// Why the artifact is not suitable for a release channel, if it isn't
+    ///
+    /// APKs need a v2 or v3 signature; app bundles are JAR (v1) signed.
+    #[must_use]
+    pub fn release_problem(&self, bundle: bool) -> Option<String> {
+        if self.schemes.is_empty() {
+            return Some("it is not signed".to_string());
+        }
+        if self.debug_signed {
+            return Some("it is signed with the Android debug key".to_string());
+        }
+        if !bundle
+            && !self
+                .schemes
+                .iter()
+                .any(|s| s.starts_with("v2") || s.starts_with("v3"))
+        {
+            return Some("it has no v2/v3 APK signature".to_string());
+        }
+        None
+    }
+}
+
+/// Returns whether `path` is an APK or AAB by extension
+#[must_use]
+pub fn is_android_package(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("apk") || e.eq_ignore_ascii_case("aab"))
+}
+
+/// Check that an app bundle has the structure of an AAB rather than a renamed zip
+///
+/// # Errors
+///
+/// Returns an error if the file is not a zip archive or lacks `BundleConfig.pb`
+pub fn validate_app_bundle(path: &Path) -> Result<()> {
+    let archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|e| Error::ArchiveError(format!("{} is not a valid zip: {e}", path.display())))?;
+    if archive.index_for_name("BundleConfig.pb").is_none() {
+        return Err(Error::ArchiveError(format!(
+            "{} is not an Android App Bundle (no BundleConfig.pb)",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Read the package id, `versionName` and `versionCode` from an APK or AAB manifest
+///
+/// Returns `Ok(None)` if the package has no manifest or it cannot be parsed.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is not a zip archive
+pub fn inspect_android_manifest(path: &Path) -> Result<Option<AndroidManifestInfo>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|e| Error::ArchiveError(format!("{} is not a valid zip: {e}", path.display())))?;
+
+    // APKs hold a binary XML manifest, bundles a protobuf one
+    let Some(name) = ["AndroidManifest.xml", "base/manifest/AndroidManifest.xml"]
+        .into_iter()
+        .find(|name| archive.index_for_name(name).is_some())
+    else {
+        return Ok(None);
+    };
+
+    let mut data = Vec::new();
+    archive
+        .by_name(name)
+        .map_err(|e| Error::ArchiveError(format!("Failed to read {name}: {e}")))?
+        .read_to_end(&mut data)?;
+
+    let info = if name == "AndroidManifest.xml" {
+        parse_axml_manifest(&data)
+    } else {
+        parse_proto_manifest(&data)
+    };
+    if info.is_none() {
+        debug!("Could not parse {name} in {}", path.display());
+    }
+    Ok(info)
+}
+
+/// Read the signature schemes and signing certificate of an APK or AAB
+///
+/// The certificate comes from the APK Signing Block (v3, then v2) when present,
+/// otherwise from the JAR signature in `META-INF/`.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is not a zip archive
+pub fn inspect_android_signing(path: &Path) -> Result<AndroidSigning> {
+    let mut file = File::open(path)?;
+    let mut schemes = Vec::new();
+    let mut certificate = None;
+
+    if let Some(block) = read_signing_block(&mut file)? {
+        for (id, value) in signing_block_pairs(&block) {
+            let scheme = match id {
+                SCHEME_V2_ID => "v2",
+                SCHEME_V3_ID => "v3",
+                SCHEME_V31_ID => "v3.1",
+                _ => continue,
+            };
+            schemes.push(scheme);
+            // Prefer the newest scheme's certificate (key rotation happens in v3)
+            if let Some(cert) = first_scheme_certificate(value)
+                && (certificate.is_none() || id == SCHEME_V3_ID)
+            {
+                certificate = Some(cert.to_vec());
+            }
+        }
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| Error::ArchiveError(format!("{} is not a valid zip: {e}", path.display())))?;
+    let jar_signature = archive
+        .file_names()
+        .find(|name| is_jar_signature(name))
+        .map(str::to_string);
+    if let Some(name) = jar_signature {
+        schemes.insert(0, "v1");
+        if certificate.is_none() {
+            let mut pkcs7 = Vec::new();
+            archive
+                .by_name(&name)
+                .map_err(|e| Error::ArchiveError(format!("Failed to read {name}: {e}")))?
+                .read_to_end(&mut pkcs7)?;
+            certificate = pkcs7_first_certificate(&pkcs7).map(<[u8]>::to_vec);
+        }
+    }
+
+    let schemes: Vec<String> = schemes.into_iter().map(str::to_string).collect();
+    debug!("{} signature schemes: {schemes:?}", path.display());
+
+    Ok(AndroidSigning {
+        schemes,
+        debug_signed: certificate
+            .as_deref()
+            .is_some_and(|cert| contains(cert, b"Android Debug")),
+        certificate_sha256: certificate
+            .map(|cert| hex_encode(ring::digest::digest(&ring::digest::SHA256, &cert).as_ref())),
+    })
+}
+
+fn is_jar_signature(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    upper.starts_with("META-INF/")
+        && !upper[9..].contains('/')
+        && [".RSA", ".DSA", ".EC"]
+            .iter()
+            .any(|ext| upper.ends_with(ext))
+}
+
+/// Read the APK Signing Block that sits right before the central directory
+fn read_signing_block(file: &mut File) -> Result<Option<Vec<u8>>> {
+    let len = file.metadata()?.len();
+    let search = len.min(EOCD_SEARCH_LEN);
+    file.seek(SeekFrom::Start(len - search))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let Some(eocd) = tail.windows(4).rposition(|w| w == b"PK\x05\x06") else {
+        return Ok(None);
+    };
+    let Some(cd_offset) = tail.get(eocd + 16..eocd + 20).map(|b| u64::from(le_u32(b))) else {
+        return Ok(None);
+    };
+    if cd_offset < 32 {
+        return Ok(None);
+    }
+
+    // The block ends with its size (u64) and the magic
+    file.seek(SeekFrom::Start(cd_offset - 24))?;
+    let mut footer = [0u8; 24];
+    file.read_exact(&mut footer)?;
+    if &footer[8..] != APK_SIG_BLOCK_MAGIC {
+        return Ok(None);
+    }
+    let block_size = le_u64(&footer[..8]);
+    if block_size < 24 || block_size + 8 > cd_offset {
+        return Ok(None);
+    }
+
+    // Pairs lie between the leading size field and the footer
+    let pairs_len = usize::try_from(block_size - 24)
+        .map_err(|_| Error::ArchiveError("APK signing block is too large".to_string()))?;
+    file.seek(SeekFrom::Start(cd_offset - block_size))?;
+    let mut pairs = vec![0u8; pairs_len];
+    file.read_exact(&mut pairs)?;
+    Ok(Some(pairs))
+}
+
+/// Iterate the `(id, value)` pairs of an APK Signing Block
+fn signing_block_pairs(mut data: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    std::iter::from_fn(move || {
+        let len = usize::try_from(le_u64(data.get(..8)?)).ok()?;
+        let pair = data.get(8..8 + len)?;
+        data = &data[8 + len..];
+        Some((le_u32(pair.get(..4)?), &pair[4..]))
+    })
+}
+
+/// First certificate of the first signer in a v2/v3 scheme block
+fn first_scheme_certificate(value: &[u8]) -> Option<&[u8]> {
+    let signers = prefixed(value)?.0;
+    let signer = prefixed(signers)?.0;
+    let signed_data = prefixed(signer)?.0;
+    let (_digests, rest) = prefixed(signed_data)?;
+    let certificates = prefixed(rest)?.0;
+    Some(prefixed(certificates)?.0)
+}
+
+/// Split a u32-length-prefixed field from the front of `data`
+fn prefixed(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = usize::try_from(le_u32(data.get(..4)?)).ok()?;
+    let field = data.get(4..4 + len)?;
+    Some((field, &data[4 + len..]))
+}
+
+/// First certificate in a PKCS#7 `SignedData` structure (JAR `.RSA`/`.DSA`/`.EC` files)
+fn pkcs7_first_certificate(der: &[u8]) -> Option<&[u8]> {
+    // ContentInfo ::= SEQUENCE { contentType OID, content [0] EXPLICIT SignedData }
+    let content_info = der_element(der)?.1;
+    let (_, _, rest) = der_element(content_info)?;
+    let signed_data = der_element(der_element(rest)?.1)?.1;
+
+    // SignedData ::= SEQUENCE { version, digestAlgorithms, contentInfo, [0] certificates, ... }
+    let mut rest = signed_data;
+    for _ in 0..3 {
+        rest = der_element(rest)?.2;
+    }
+    let (tag, certificates, _) = der_element(rest)?;
+    if tag != 0xA0 {
+        return None;
+    }
+    let (_, _, after) = der_element(certificates)?;
+    Some(&certificates[..certificates.len() - after.len()])
+}
+
+/// Split one DER element into `(tag, contents, rest)`
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)?;
+    let (len, header) = if first & 0x80 == 0 {
+        (usize::from(first), 2)
+    } else {
+        let count = usize::from(first & 0x7F);
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + count)?
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | usize::from(*b));
+        (len, 2 + count)
+    };
+    let contents = data.get(header..header + len)?;
+    Some((tag, contents, &data[header + len..]))
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    /// Wrap `value` in a u32 length prefix
+    fn lp(value: &[u8]) -> Vec<u8> {
+        let mut out = u32::try_from(value.len()).unwrap().to_le_bytes().to_vec();
+        out.extend_from_slice(value);
+        out
+    }
+
+    /// Build an APK whose signing block holds a v2 signer with `cert`
+    fn signed_apk(path: &Path, cert: &[u8]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        zip.start_file("AndroidManifest.xml", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"manifest").unwrap();
+        zip.finish().unwrap();
+        let zip = std::fs::read(path).unwrap();
+
+        let eocd = zip.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+        let cd_offset = le_u32(&zip[eocd + 16..]) as usize;
+
+        let signed_data = [lp(&[]), lp(&lp(cert))].concat();
+        let v2 = lp(&lp(&lp(&signed_data)));
+        let mut pair = SCHEME_V2_ID.to_le_bytes().to_vec();
+        pair.extend_from_slice(&v2);
+        let mut pairs = (pair.len() as u64).to_le_bytes().to_vec();
+        pairs.extend_from_slice(&pair);
+
+        let block_size = (pairs.len() + 24) as u64;
+        let mut block = block_size.to_le_bytes().to_vec();
+        block.extend_from_slice(&pairs);
+        block.extend_from_slice(&block_size.to_le_bytes());
+        block.extend_from_slice(APK_SIG_BLOCK_MAGIC);
+
+        let mut apk = zip[..cd_offset].to_vec();
+        apk.extend_from_slice(&block);
+        apk.extend_from_slice(&zip[cd_offset..]);
+        let new_eocd = apk.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+        let new_offset = u32::try_from(cd_offset + block.len()).unwrap();
+        apk[new_eocd + 16..new_eocd + 20].copy_from_slice(&new_offset.to_le_bytes());
+        std::fs::write(path, apk).unwrap();
+    }
+
+    #[test]
+    fn test_inspect_android_signing() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let release = dir.path().join("release.apk");
+        signed_apk(&release, b"CN=Studio Release");
+        let signing = inspect_android_signing(&release).unwrap();
+        assert_eq!(signing.schemes, ["v2"]);
+        assert!(!signing.debug_signed);
+        assert_eq!(
+            signing.certificate_sha256.as_ref().map(String::len),
+            Some(64)
+        );
+        assert_eq!(signing.release_problem(false), None);
+
+        let debug = dir.path().join("debug.apk");
+        signed_apk(&debug, b"C=US, O=Android, CN=Android Debug");
+        let signing = inspect_android_signing(&debug).unwrap();
+        assert!(signing.debug_signed);
+        assert!(signing.release_problem(false).is_some());
+
+        let unsigned = dir.path().join("unsigned.apk");
+        let mut zip = zip::ZipWriter::new(File::create(&unsigned).unwrap());
+        zip.start_file("AndroidManifest.xml", SimpleFileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+        let signing = inspect_android_signing(&unsigned).unwrap();
+        assert!(signing.schemes.is_empty());
+        assert!(signing.release_problem(false).is_some());
+        assert!(validate_app_bundle(&unsigned).is_err());
+
+        let bundle = dir.path().join("game.aab");
+        let mut zip = zip::ZipWriter::new(File::create(&bundle).unwrap());
+        for name in ["BundleConfig.pb", "base/manifest/AndroidManifest.xml"] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        }
+        zip.finish().unwrap();
+        validate_app_bundle(&bundle).unwrap();
+    }
+}
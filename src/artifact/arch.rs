@@ -0,0 +1,174 @@
+use super::sniff::{is_universal_binary, read_up_to};
+use crate::api::Arch;
+use crate::error::Result;
+use log::debug;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// COFF machine types
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_FILE_MACHINE_ARM64: u16 = 0xAA64;
+/// Mach-O CPU types (`CPU_ARCH_ABI64` | base type)
+const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+const CPU_TYPE_ARM64: u32 = 0x0100_000C;
+/// ELF `e_machine` values
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+/// Detect the CPU architecture of a PE, Mach-O (including universal) or ELF binary
+///
+/// For `.app` bundles the main executable named by `CFBundleExecutable` is inspected.
+/// Returns `Ok(None)` for other files and for architectures other than x64 and arm64.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read
+pub fn detect_arch(path: &Path) -> Result<Option<Arch>> {
+    if path.is_dir() {
+        return match bundle_executable(path) {
+            Some(executable) => detect_arch(&executable),
+            None => Ok(None),
+        };
+    }
+
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 64];
+    let read = read_up_to(&mut file, &mut header)?;
+    let header = &header[..read];
+
+    let arch = if header.starts_with(b"MZ") {
+        pe_arch(&mut file, header)?
+    } else if is_universal_binary(header) {
+        let count = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        if count > 1 {
+            Some(Arch::Universal)
+        } else {
+            header
+                .get(8..12)
+                .and_then(|t| macho_arch(u32::from_be_bytes(t.try_into().ok()?)))
+        }
+    } else if header.starts_with(&[0xFE, 0xED, 0xFA, 0xCF]) {
+        header
+            .get(4..8)
+            .and_then(|t| macho_arch(u32::from_be_bytes(t.try_into().ok()?)))
+    } else if header.starts_with(&[0xCF, 0xFA, 0xED, 0xFE]) {
+        header
+            .get(4..8)
+            .and_then(|t| macho_arch(u32::from_le_bytes(t.try_into().ok()?)))
+    } else if header.starts_with(b"\x7FELF") {
+        elf_arch(header)
+    } else {
+        None
+    };
+
+    debug!(
+        "Detected architecture of {}: {}",
+        path.display(),
+        arch.map_or("unknown", Arch::as_str)
+    );
+    Ok(arch)
+}
+
+/// Read the machine type from the COFF header that follows the PE signature
+fn pe_arch(file: &mut File, dos: &[u8]) -> Result<Option<Arch>> {
+    let Some(pe_offset) = dos
+        .get(0x3C..0x40)
+        .and_then(|b| Some(u32::from_le_bytes(b.try_into().ok()?)))
+    else {
+        return Ok(None);
+    };
+    file.seek(SeekFrom::Start(u64::from(pe_offset)))?;
+    let mut signature = [0u8; 6];
+    if file.read_exact(&mut signature).is_err() || &signature[..4] != b"PE\0\0" {
+        return Ok(None);
+    }
+    Ok(match u16::from_le_bytes([signature[4], signature[5]]) {
+        IMAGE_FILE_MACHINE_AMD64 => Some(Arch::X64),
+        IMAGE_FILE_MACHINE_ARM64 => Some(Arch::Arm64),
+        _ => None,
+    })
+}
+
+fn macho_arch(cpu_type: u32) -> Option<Arch> {
+    match cpu_type {
+        CPU_TYPE_X86_64 => Some(Arch::X64),
+        CPU_TYPE_ARM64 => Some(Arch::Arm64),
+        _ => None,
+    }
+}
+
+fn elf_arch(header: &[u8]) -> Option<Arch> {
+    let machine = header.get(18..20)?;
+    let machine = match header.get(5)? {
+        1 => u16::from_le_bytes([machine[0], machine[1]]),
+        2 => u16::from_be_bytes([machine[0], machine[1]]),
+        _ => return None,
+    };
+    match machine {
+        EM_X86_64 => Some(Arch::X64),
+        EM_AARCH64 => Some(Arch::Arm64),
+        _ => None,
+    }
+}
+
+/// Main executable of a macOS (`Contents/MacOS/`) or flat iOS `.app` bundle
+fn bundle_executable(bundle: &Path) -> Option<PathBuf> {
+    let (plist, dir) = if bundle.join("Contents/Info.plist").is_file() {
+        (
+            bundle.join("Contents/Info.plist"),
+            bundle.join("Contents/MacOS"),
+        )
+    } else {
+        (bundle.join("Info.plist"), bundle.to_path_buf())
+    };
+    let info = plist::Value::from_file(plist).ok()?;
+    let name = info
+        .as_dictionary()?
+        .get("CFBundleExecutable")?
+        .as_string()?;
+    Some(dir.join(name)).filter(|p| p.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_arch() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut pe = vec![0u8; 0x80];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3C] = 0x40;
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        pe[0x44..0x46].copy_from_slice(&IMAGE_FILE_MACHINE_ARM64.to_le_bytes());
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(b"\x7FELF");
+        elf[5] = 1;
+        elf[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+        let mut fat = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 2];
+        fat.resize(64, 0);
+
+        let app = dir.path().join("Game.app/Contents");
+        std::fs::create_dir_all(app.join("MacOS")).unwrap();
+        let mut info = plist::Dictionary::new();
+        info.insert("CFBundleExecutable".into(), "Game".into());
+        plist::to_file_xml(app.join("Info.plist"), &info).unwrap();
+        std::fs::write(app.join("MacOS/Game"), &fat).unwrap();
+
+        for (name, data, arch) in [
+            ("game.exe", pe, Some(Arch::Arm64)),
+            ("game.x86_64", elf, Some(Arch::X64)),
+            ("notes.txt", b"hello".to_vec(), None),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            assert_eq!(detect_arch(&path).unwrap(), arch, "{name}");
+        }
+        assert_eq!(
+            detect_arch(&dir.path().join("Game.app")).unwrap(),
+            Some(Arch::Universal)
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Minimal readers for compiled Android manifests
+//!
+//! APKs store `AndroidManifest.xml` as binary XML (AXML); app bundles store it as an
+//! aapt2 protobuf `XmlNode`. Only the root `<manifest>` element's attributes are read.
+
+use serde::{Deserialize, Serialize};
+
+const RES_XML_TYPE: u16 = 0x0003;
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const UTF8_FLAG: u32 = 1 << 8;
+const NO_INDEX: u32 = 0xFFFF_FFFF;
+
+/// Typed value types for attributes without a raw string value
+const TYPE_STRING: u8 = 0x03;
+const TYPE_INT_DEC: u8 = 0x10;
+const TYPE_INT_HEX: u8 = 0x11;
+
+/// Framework resource IDs, used when attribute names were stripped by obfuscators
+const ATTR_VERSION_CODE: u32 = 0x0101_021b;
+const ATTR_VERSION_NAME: u32 = 0x0101_021c;
+
+/// App identity and version declared in an Android manifest
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct AndroidManifestInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_code: Option<u64>,
+}
+
+impl AndroidManifestInfo {
+    fn set(&mut self, name: &str, resource_id: Option<u32>, value: String) {
+        match (name, resource_id) {
+            ("package", _) => self.package = Some(value),
+            ("versionName", _) | (_, Some(ATTR_VERSION_NAME)) => self.version_name = Some(value),
+            ("versionCode", _) | (_, Some(ATTR_VERSION_CODE)) => {
+                self.version_code = value.parse().ok();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Read the `<manifest>` attributes from a binary (AXML) manifest
+///
+/// Returns `None` if the data is not a well-formed AXML document.
+#[must_use]
+pub fn parse_axml_manifest(data: &[u8]) -> Option<AndroidManifestInfo> {
+    if u16_at(data, 0)? != RES_XML_TYPE {
+        return None;
+    }
+
+    let mut strings = Vec::new();
+    let mut resource_ids = Vec::new();
+    let mut offset = usize::from(u16_at(data, 2)?);
+
+    while offset + 8 <= data.len() {
+        let chunk_type = u16_at(data, offset)?;
+        let header_size = usize::from(u16_at(data, offset + 2)?);
+        let size = usize::try_from(u32_at(data, offset + 4)?).ok()?;
+        let chunk = data.get(offset..offset + size)?;
+
+        match chunk_type {
+            RES_STRING_POOL_TYPE => strings = read_string_pool(chunk)?,
+            RES_XML_RESOURCE_MAP_TYPE => {
+                resource_ids = chunk
+                    .get(header_size..)?
+                    .chunks_exact(4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+            }
+            RES_XML_START_ELEMENT_TYPE => {
+                return read_manifest_element(chunk, header_size, &strings, &resource_ids);
+            }
+            _ => {}
+        }
+        if size == 0 {
+            return None;
+        }
+        offset += size;
+    }
+    None
+}
+
+fn read_manifest_element(
+    chunk: &[u8],
+    header_size: usize,
+    strings: &[String],
+    resource_ids: &[u32],
+) -> Option<AndroidManifestInfo> {
+    let string = |index: u32| strings.get(usize::try_from(index).ok()?).cloned();
+
+    if string(u32_at(chunk, header_size + 4)?)? != "manifest" {
+        return None;
+    }
+    let attribute_start = usize::from(u16_at(chunk, header_size + 8)?);
+    let attribute_size = usize::from(u16_at(chunk, header_size + 10)?);
+    let attribute_count = usize::from(u16_at(chunk, header_size + 12)?);
+
+    let mut info = AndroidManifestInfo::default();
+    for i in 0..attribute_count {
+        let at = header_size + attribute_start + i * attribute_size;
+        let name_index = u32_at(chunk, at + 4)?;
+        let raw_value = u32_at(chunk, at + 8)?;
+        let data_type = *chunk.get(at + 15)?;
+        let data = u32_at(chunk, at + 16)?;
+
+        let value = if raw_value == NO_INDEX {
+            match data_type {
+                TYPE_STRING => string(data),
+                TYPE_INT_DEC | TYPE_INT_HEX => Some(data.to_string()),
+                _ => None,
+            }
+        } else {
+            string(raw_value)
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        let name = string(name_index).unwrap_or_default();
+        let resource_id = resource_ids.get(usize::try_from(name_index).ok()?).copied();
+        info.set(&name, resource_id, value);
+    }
+    Some(info)
+}
+
+fn read_string_pool(chunk: &[u8]) -> Option<Vec<String>> {
+    let count = usize::try_from(u32_at(chunk, 8)?).ok()?;
+    let flags = u32_at(chunk, 16)?;
+    let strings_start = usize::try_from(u32_at(chunk, 20)?).ok()?;
+    let header_size = usize::from(u16_at(chunk, 2)?);
+
+    (0..count)
+        .map(|i| {
+            let offset = usize::try_from(u32_at(chunk, header_size + i * 4)?).ok()?;
+            let data = chunk.get(strings_start + offset..)?;
+            if flags & UTF8_FLAG != 0 {
+                read_utf8_string(data)
+            } else {
+                read_utf16_string(data)
+            }
+        })
+        .collect()
+}
+
+fn read_utf8_string(data: &[u8]) -> Option<String> {
+    // UTF-16 length, then UTF-8 byte length, each one or two bytes
+    let skip = if data.first()? & 0x80 == 0 { 1 } else { 2 };
+    let data = data.get(skip..)?;
+    let (len, start) = if data.first()? & 0x80 == 0 {
+        (usize::from(data[0]), 1)
+    } else {
+        (
+            (usize::from(data[0] & 0x7F) << 8) | usize::from(*data.get(1)?),
+            2,
+        )
+    };
+    Some(String::from_utf8_lossy(data.get(start..start + len)?).into_owned())
+}
+
+fn read_utf16_string(data: &[u8]) -> Option<String> {
+    let first = u16_at(data, 0)?;
+    let (len, start) = if first & 0x8000 == 0 {
+        (usize::from(first), 2)
+    } else {
+        (
+            (usize::from(first & 0x7FFF) << 16) | usize::from(u16_at(data, 2)?),
+            4,
+        )
+    };
+    let units: Vec<u16> = data
+        .get(start..start + len * 2)?
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Read the `<manifest>` attributes from an app bundle's protobuf manifest
+///
+/// Returns `None` if the data is not a protobuf `XmlNode` with a `manifest` root.
+#[must_use]
+pub fn parse_proto_manifest(data: &[u8]) -> Option<AndroidManifestInfo> {
+    // XmlNode { XmlElement element = 1; ... }
+    let element = proto_fields(data).find(|(field, _)| *field == 1)?.1;
+
+    // XmlElement { string name = 3; repeated XmlAttribute attribute = 4; ... }
+    let mut info = AndroidManifestInfo::default();
+    let mut is_manifest = false;
+    for (field, value) in proto_fields(element) {
+        match field {
+            3 => is_manifest = value == b"manifest",
+            4 => {
+                // XmlAttribute { string name = 2; string value = 3; uint32 resource_id = 5; ... }
+                let mut name = String::new();
+                let mut attr_value = None;
+                for (field, value) in proto_fields(value) {
+                    match field {
+                        2 => name = String::from_utf8_lossy(value).into_owned(),
+                        3 => attr_value = Some(String::from_utf8_lossy(value).into_owned()),
+                        _ => {}
+                    }
+                }
+                if let Some(value) = attr_value {
+                    info.set(&name, None, value);
+                }
+            }
+            _ => {}
+        }
+    }
+    is_manifest.then_some(info)
+}
+
+/// Iterate the length-delimited fields of a protobuf message, skipping scalar ones
+fn proto_fields(mut data: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+    std::iter::from_fn(move || {
+        loop {
+            let key = read_varint(&mut data)?;
+            match key & 7 {
+                0 => {
+                    read_varint(&mut data)?;
+                }
+                1 => data = data.get(8..)?,
+                2 => {
+                    let len = usize::try_from(read_varint(&mut data)?).ok()?;
+                    let value = data.get(..len)?;
+                    data = &data[len..];
+                    return Some((key >> 3, value));
+                }
+                5 => data = data.get(4..)?,
+                _ => return None,
+            }
+        }
+    })
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data.split_first()?;
+        *data = rest;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: u16, header: &[u8], body: &[u8]) -> Vec<u8> {
+        let header_size = u16::try_from(8 + header.len()).unwrap();
+        let size = u32::try_from(8 + header.len() + body.len()).unwrap();
+        let mut out = chunk_type.to_le_bytes().to_vec();
+        out.extend_from_slice(&header_size.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(header);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// Build an AXML document whose root is `<manifest>` with the given string attributes
+    /// and an integer `versionCode`
+    fn axml(attributes: &[(&str, &str)], version_code: u32) -> Vec<u8> {
+        let mut strings = vec!["manifest", "versionCode"];
+        for (name, value) in attributes {
+            strings.extend([*name, *value]);
+        }
+
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for s in &strings {
+            offsets.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes());
+            let units: Vec<u16> = s.encode_utf16().collect();
+            data.extend_from_slice(&u16::try_from(units.len()).unwrap().to_le_bytes());
+            for unit in units {
+                data.extend_from_slice(&unit.to_le_bytes());
+            }
+            data.extend_from_slice(&[0, 0]);
+        }
+        let count = u32::try_from(strings.len()).unwrap();
+        let mut pool_header = count.to_le_bytes().to_vec();
+        pool_header.extend_from_slice(&0u32.to_le_bytes()); // styles
+        pool_header.extend_from_slice(&0u32.to_le_bytes()); // flags (UTF-16)
+        pool_header.extend_from_slice(&(28 + count * 4).to_le_bytes());
+        pool_header.extend_from_slice(&0u32.to_le_bytes());
+        let pool = chunk(
+            RES_STRING_POOL_TYPE,
+            &pool_header,
+            &[offsets, data].concat(),
+        );
+
+        let attribute = |name: u32, raw: u32, data_type: u8, data: u32| {
+            let mut out = NO_INDEX.to_le_bytes().to_vec();
+            out.extend_from_slice(&name.to_le_bytes());
+            out.extend_from_slice(&raw.to_le_bytes());
+            out.extend_from_slice(&[8, 0, 0, data_type]);
+            out.extend_from_slice(&data.to_le_bytes());
+            out
+        };
+        let mut attrs = attribute(1, NO_INDEX, TYPE_INT_DEC, version_code);
+        for i in 0..attributes.len() {
+            let name = u32::try_from(2 + i * 2).unwrap();
+            attrs.extend(attribute(name, name + 1, TYPE_STRING, name + 1));
+        }
+        let mut element_header = 0u32.to_le_bytes().to_vec(); // line
+        element_header.extend_from_slice(&NO_INDEX.to_le_bytes()); // comment
+        let mut ext = NO_INDEX.to_le_bytes().to_vec(); // namespace
+        ext.extend_from_slice(&0u32.to_le_bytes()); // name: "manifest"
+        ext.extend_from_slice(&20u16.to_le_bytes()); // attribute start
+        ext.extend_from_slice(&20u16.to_le_bytes()); // attribute size
+        let attr_count = u16::try_from(attributes.len() + 1).unwrap();
+        ext.extend_from_slice(&attr_count.to_le_bytes());
+        ext.extend_from_slice(&[0; 6]);
+        let element = chunk(
+            RES_XML_START_ELEMENT_TYPE,
+            &element_header,
+            &[ext, attrs].concat(),
+        );
+
+        chunk(RES_XML_TYPE, &[], &[pool, element].concat())
+    }
+
+    fn proto_field(field: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![(field << 3) | 2, u8::try_from(value.len()).unwrap()];
+        out.extend_from_slice(value);
+        out
+    }
+
+    #[test]
+    fn test_parse_manifests() {
+        let data = axml(
+            &[("versionName", "1.4.2"), ("package", "com.studio.game")],
+            42,
+        );
+        assert_eq!(
+            parse_axml_manifest(&data),
+            Some(AndroidManifestInfo {
+                package: Some("com.studio.game".to_string()),
+                version_name: Some("1.4.2".to_string()),
+                version_code: Some(42),
+            })
+        );
+        assert_eq!(parse_axml_manifest(b"PK\x03\x04"), None);
+
+        let attribute = |name: &str, value: &str| {
+            let attr = [
+                proto_field(2, name.as_bytes()),
+                proto_field(3, value.as_bytes()),
+            ]
+            .concat();
+            proto_field(4, &attr)
+        };
+        let element = [
+            proto_field(3, b"manifest"),
+            attribute("versionCode", "7"),
+            attribute("package", "com.studio.game"),
+        ]
+        .concat();
+        let info = parse_proto_manifest(&proto_field(1, &element)).unwrap();
+        assert_eq!(info.version_code, Some(7));
+        assert_eq!(info.package.as_deref(), Some("com.studio.game"));
+    }
+}
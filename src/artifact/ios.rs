@@ -0,0 +1,127 @@
+use crate::error::{Error, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// App identity and version read from an IPA's `Info.plist`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct IosBundleInfo {
+    #[serde(
+        rename(deserialize = "CFBundleIdentifier"),
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bundle_id: Option<String>,
+    /// Marketing version (`CFBundleShortVersionString`)
+    #[serde(
+        rename(deserialize = "CFBundleShortVersionString"),
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub short_version: Option<String>,
+    /// Build version (`CFBundleVersion`)
+    #[serde(
+        rename(deserialize = "CFBundleVersion"),
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bundle_version: Option<String>,
+    #[serde(
+        rename(deserialize = "MinimumOSVersion"),
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub minimum_os_version: Option<String>,
+}
+
+/// Returns whether `path` is an IPA by extension
+#[must_use]
+pub fn is_ipa(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("ipa"))
+}
+
+/// Read the bundle id and versions from the app's `Info.plist` inside an IPA
+///
+/// Both binary and XML property lists are supported. Returns `Ok(None)` if the
+/// IPA contains no app bundle.
+///
+/// # Errors
+///
+/// Returns an error if the file is not a zip archive or the plist cannot be parsed
+pub fn inspect_ipa(path: &Path) -> Result<Option<IosBundleInfo>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|e| Error::ArchiveError(format!("{} is not a valid zip: {e}", path.display())))?;
+
+    let Some(name) = archive
+        .file_names()
+        .find(|name| is_app_info_plist(name))
+        .map(str::to_string)
+    else {
+        debug!("No Payload/*.app/Info.plist in {}", path.display());
+        return Ok(None);
+    };
+
+    let mut data = Vec::new();
+    archive
+        .by_name(&name)
+        .map_err(|e| Error::ArchiveError(format!("Failed to read {name}: {e}")))?
+        .read_to_end(&mut data)?;
+
+    plist::from_bytes(&data)
+        .map(Some)
+        .map_err(|e| Error::ArchiveError(format!("Invalid {name}: {e}")))
+}
+
+/// Matches `Payload/<name>.app/Info.plist`, but not plists of nested bundles
+fn is_app_info_plist(name: &str) -> bool {
+    name.strip_prefix("Payload/")
+        .and_then(|rest| rest.strip_suffix("/Info.plist"))
+        .is_some_and(|bundle| {
+            !bundle.contains('/')
+                && Path::new(bundle)
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("app"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_inspect_ipa() {
+        let mut info = plist::Dictionary::new();
+        info.insert("CFBundleIdentifier".into(), "com.studio.game".into());
+        info.insert("CFBundleShortVersionString".into(), "1.4.2".into());
+        info.insert("CFBundleVersion".into(), "311".into());
+        info.insert("MinimumOSVersion".into(), "15.0".into());
+        let mut binary = Vec::new();
+        plist::to_writer_binary(&mut binary, &info).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let ipa = dir.path().join("Game.ipa");
+        let mut zip = zip::ZipWriter::new(File::create(&ipa).unwrap());
+        for (name, data) in [
+            (
+                "Payload/Game.app/PlugIns/Widget.appex/Info.plist",
+                &b"junk"[..],
+            ),
+            ("Payload/Game.app/Info.plist", &binary),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+
+        assert_eq!(
+            inspect_ipa(&ipa).unwrap(),
+            Some(IosBundleInfo {
+                bundle_id: Some("com.studio.game".to_string()),
+                short_version: Some("1.4.2".to_string()),
+                bundle_version: Some("311".to_string()),
+                minimum_os_version: Some("15.0".to_string()),
+            })
+        );
+    }
+}
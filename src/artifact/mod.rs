@@ -0,0 +1,96 @@
+pub mod android;
+pub mod arch;
+pub mod axml;
+pub mod ios;
+mod msi;
+pub mod notarization;
+pub mod sniff;
+pub mod windows;
+
+pub use android::{
+    AndroidSigning, inspect_android_manifest, inspect_android_signing, is_android_package,
+    validate_app_bundle,
+};
+pub use axml::AndroidManifestInfo;
+pub use ios::{IosBundleInfo, inspect_ipa, is_ipa};
+pub use notarization::{NotarizationStatus, check_notarization, is_notarizable};
+pub use sniff::sniff_platform;
+pub use windows::{WindowsVersionInfo, inspect_windows_version, is_windows_binary};
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What an upload is: a playable build or an auxiliary artifact that belongs to builds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArtifactType {
+    #[default]
+    Build,
+    /// Debug symbols for crash symbolication
+    Symbols,
+    /// Precompiled shader cache (e.g. a PSO or pipeline cache)
+    ShaderCache,
+    /// Downloadable content, asset bundles or patches
+    ContentPack,
+}
+
+impl ArtifactType {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Build => "build",
+            Self::Symbols => "symbols",
+            Self::ShaderCache => "shader-cache",
+            Self::ContentPack => "content-pack",
+        }
+    }
+
+    #[must_use]
+    pub fn is_build(self) -> bool {
+        self == Self::Build
+    }
+}
+
+impl std::str::FromStr for ArtifactType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "build" => Ok(Self::Build),
+            "symbols" => Ok(Self::Symbols),
+            "shader-cache" => Ok(Self::ShaderCache),
+            "content-pack" => Ok(Self::ContentPack),
+            _ => Err(Error::ConfigError(format!(
+                "Invalid artifact type: '{s}'. Valid types are: build, symbols, shader-cache, content-pack"
+            ))),
+        }
+    }
+}
+
+/// Hint sent with the upload so the backend can tell artifact kinds sharing a platform apart
+///
+/// Currently only Android App Bundles (`aab`) are distinguished from APKs.
+#[must_use]
+pub fn artifact_type(file_name: &str) -> Option<&'static str> {
+    Path::new(file_name)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("aab"))
+        .then_some("aab")
+}
+
+/// Details read from the artifact itself, attached to the build's metadata
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ArtifactInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub android_signing: Option<AndroidSigning>,
+    /// Package id and version from the APK/AAB manifest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub android_manifest: Option<AndroidManifestInfo>,
+    /// Bundle id, versions and minimum OS from an IPA's `Info.plist`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ios_bundle: Option<IosBundleInfo>,
+    /// Version resource of an `.exe`, or `Property` table of an `.msi`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub windows_version: Option<WindowsVersionInfo>,
+}
@@ -0,0 +1,343 @@
+//! Minimal reader for the `Property` table of Windows Installer packages
+//!
+//! An MSI is a compound file (OLE/CFB) whose tables are stored as streams with
+//! compressed names; strings live in a shared `_StringPool`/`_StringData` pair.
+
+use super::windows::WindowsVersionInfo;
+use crate::error::Result;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+const FREE_SECTOR: u32 = 0xFFFF_FFFF;
+/// Upper bound on sectors followed in one chain, guarding against cyclic FATs
+const MAX_CHAIN_SECTORS: usize = 1 << 20;
+/// Stream names of MSI tables start with this character
+const TABLE_PREFIX: u16 = 0x4840;
+/// Set in the string pool header when string references are 3 bytes wide
+const LONG_STRING_REFS: u32 = 0x8000_0000;
+
+/// Read `ProductVersion` and `ProductName` from an MSI's `Property` table
+///
+/// Returns `Ok(None)` if the file is not a compound file or lacks the tables.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read
+pub fn read_msi_version(path: &Path) -> Result<Option<WindowsVersionInfo>> {
+    let Some(mut cfb) = CompoundFile::open(File::open(path)?)? else {
+        return Ok(None);
+    };
+
+    let (Some(pool), Some(data), Some(property)) = (
+        cfb.read_stream("!_StringPool")?,
+        cfb.read_stream("!_StringData")?,
+        cfb.read_stream("!Property")?,
+    ) else {
+        return Ok(None);
+    };
+
+    let Some((strings, ref_size)) = parse_string_pool(&pool, &data) else {
+        return Ok(None);
+    };
+    let mut info = WindowsVersionInfo::default();
+    for (name, value) in property_rows(&property, ref_size) {
+        let lookup = |index: usize| strings.get(index.checked_sub(1)?).cloned();
+        let (Some(name), Some(value)) = (lookup(name), lookup(value)) else {
+            continue;
+        };
+        match name.as_str() {
+            "ProductVersion" => info.product_version = Some(value),
+            "ProductName" => info.product_name = Some(value),
+            _ => {}
+        }
+    }
+    Ok(Some(info))
+}
+
+/// Decode the strings of an MSI string pool
+///
+/// Returns the strings (string index `n` is `strings[n - 1]`) and the width in bytes
+/// of string references in tables.
+fn parse_string_pool(pool: &[u8], data: &[u8]) -> Option<(Vec<String>, usize)> {
+    let header = u32_at(pool, 0)?;
+    let ref_size = if header & LONG_STRING_REFS == 0 { 2 } else { 3 };
+
+    let mut strings = Vec::new();
+    let mut offset = 0;
+    let mut entry = 4;
+    while entry + 4 <= pool.len() {
+        let mut len = usize::from(u16_at(pool, entry)?);
+        let refs = u16_at(pool, entry + 2)?;
+        entry += 4;
+        // Strings of 64 KiB or more store their length in the following entry
+        if len == 0 && refs != 0 {
+            len = usize::try_from(u32_at(pool, entry)?).ok()?;
+            entry += 4;
+        }
+        let bytes = data.get(offset..offset + len)?;
+        strings.push(String::from_utf8_lossy(bytes).into_owned());
+        offset += len;
+    }
+    Some((strings, ref_size))
+}
+
+/// Rows of the two-column `Property` table as `(name, value)` string indices
+///
+/// Tables are stored column by column.
+fn property_rows(table: &[u8], ref_size: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let rows = table.len() / (2 * ref_size);
+    let read = move |at: usize| {
+        table[at..at + ref_size]
+            .iter()
+            .rev()
+            .fold(0usize, |acc, b| (acc << 8) | usize::from(*b))
+    };
+    (0..rows).map(move |row| (read(row * ref_size), read((rows + row) * ref_size)))
+}
+
+/// Decode an MSI stream name (pairs of characters packed into one UTF-16 unit)
+fn decode_stream_name(units: &[u16]) -> String {
+    const ALPHABET: &[u8; 64] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz._";
+
+    let mut name = String::new();
+    for &unit in units {
+        match unit {
+            0x3800..0x4800 => {
+                let packed = usize::from(unit - 0x3800);
+                name.push(char::from(ALPHABET[packed & 0x3F]));
+                name.push(char::from(ALPHABET[(packed >> 6) & 0x3F]));
+            }
+            0x4800..TABLE_PREFIX => name.push(char::from(ALPHABET[usize::from(unit - 0x4800)])),
+            TABLE_PREFIX => name.push('!'),
+            _ => name.extend(char::decode_utf16([unit]).map(|c| c.unwrap_or('?'))),
+        }
+    }
+    name
+}
+
+/// Directory entry of a compound file stream
+struct Stream {
+    name: String,
+    start: u32,
+    size: u64,
+}
+
+/// Read-only view of a compound file's streams
+struct CompoundFile {
+    file: File,
+    sector_size: usize,
+    mini_sector_size: usize,
+    mini_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    /// The mini stream holding all streams smaller than `mini_cutoff`
+    mini_stream: Vec<u8>,
+    streams: Vec<Stream>,
+}
+
+impl CompoundFile {
+    fn open(mut file: File) -> Result<Option<Self>> {
+        let mut header = [0u8; 512];
+        if file.read_exact(&mut header).is_err() || header[..8] != CFB_SIGNATURE {
+            return Ok(None);
+        }
+        let (Some(sector_shift), Some(mini_shift)) = (u16_at(&header, 0x1E), u16_at(&header, 0x20))
+        else {
+            return Ok(None);
+        };
+        if !(7..=16).contains(&sector_shift) || mini_shift >= sector_shift {
+            return Ok(None);
+        }
+        let field = |at: usize| u32_at(&header, at).unwrap_or(END_OF_CHAIN);
+
+        let mut cfb = Self {
+            file,
+            sector_size: 1 << sector_shift,
+            mini_sector_size: 1 << mini_shift,
+            mini_cutoff: u64::from(field(0x38)),
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            streams: Vec::new(),
+        };
+
+        // The first 109 FAT sector numbers are in the header, the rest in the DIFAT chain
+        let mut fat_sectors: Vec<u32> = header[0x4C..]
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .filter(|s| *s != FREE_SECTOR)
+            .collect();
+        let mut difat = field(0x44);
+        let per_sector = cfb.sector_size / 4;
+        for _ in 0..MAX_CHAIN_SECTORS {
+            if difat == END_OF_CHAIN || difat == FREE_SECTOR {
+                break;
+            }
+            let sector = cfb.read_sector(difat)?;
+            let mut entries = sector
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+            fat_sectors.extend(
+                entries
+                    .by_ref()
+                    .take(per_sector - 1)
+                    .filter(|s| *s != FREE_SECTOR),
+            );
+            difat = entries.next().unwrap_or(END_OF_CHAIN);
+        }
+        for sector in fat_sectors {
+            let data = cfb.read_sector(sector)?;
+            cfb.fat.extend(
+                data.chunks_exact(4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            );
+        }
+
+        let directory = cfb.read_chain(field(0x30), None)?;
+        let mut root = None;
+        for entry in directory.chunks_exact(128) {
+            let name_len = usize::from(u16_at(entry, 64).unwrap_or(0)).min(64);
+            let units: Vec<u16> = entry[..name_len.saturating_sub(2)]
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .collect();
+            let stream = Stream {
+                name: decode_stream_name(&units),
+                start: u32_at(entry, 116).unwrap_or(END_OF_CHAIN),
+                size: u64_at(entry, 120).unwrap_or(0),
+            };
+            match entry[66] {
+                2 => cfb.streams.push(stream),
+                5 => root = Some(stream),
+                _ => {}
+            }
+        }
+
+        if let Some(root) = root {
+            cfb.mini_fat = cfb
+                .read_chain(field(0x3C), None)?
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            cfb.mini_stream = cfb.read_chain(root.start, Some(root.size))?;
+        }
+        Ok(Some(cfb))
+    }
+
+    /// Read a stream by its decoded name, if present
+    fn read_stream(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        let Some((start, size)) = self
+            .streams
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| (s.start, s.size))
+        else {
+            return Ok(None);
+        };
+
+        if size >= self.mini_cutoff {
+            return self.read_chain(start, Some(size)).map(Some);
+        }
+
+        let mut data = Vec::new();
+        let mut sector = start;
+        for _ in 0..MAX_CHAIN_SECTORS {
+            if sector == END_OF_CHAIN {
+                break;
+            }
+            let at = sector as usize * self.mini_sector_size;
+            let Some(chunk) = self.mini_stream.get(at..at + self.mini_sector_size) else {
+                break;
+            };
+            data.extend_from_slice(chunk);
+            sector = self
+                .mini_fat
+                .get(sector as usize)
+                .copied()
+                .unwrap_or(END_OF_CHAIN);
+        }
+        data.truncate(usize::try_from(size).unwrap_or(usize::MAX));
+        Ok(Some(data))
+    }
+
+    /// Concatenate the sectors of a FAT chain, truncated to `size` if given
+    fn read_chain(&mut self, start: u32, size: Option<u64>) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut sector = start;
+        for _ in 0..MAX_CHAIN_SECTORS {
+            if sector == END_OF_CHAIN || sector == FREE_SECTOR {
+                break;
+            }
+            if size.is_some_and(|size| data.len() as u64 >= size) {
+                break;
+            }
+            data.extend(self.read_sector(sector)?);
+            sector = self
+                .fat
+                .get(sector as usize)
+                .copied()
+                .unwrap_or(END_OF_CHAIN);
+        }
+        if let Some(size) = size {
+            data.truncate(usize::try_from(size).unwrap_or(usize::MAX));
+        }
+        Ok(data)
+    }
+
+    fn read_sector(&mut self, sector: u32) -> Result<Vec<u8>> {
+        let offset = (u64::from(sector) + 1) * self.sector_size as u64;
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut data = vec![0u8; self.sector_size];
+        self.file.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msi_tables() {
+        // "Property" packed two characters per unit, behind the table marker
+        let pack = |a: u16, b: u16| 0x3800 + a + (b << 6);
+        let name = [
+            TABLE_PREFIX,
+            pack(25, 53), // P r
+            pack(50, 51), // o p
+            pack(40, 53), // e r
+            pack(55, 60), // t y
+        ];
+        assert_eq!(decode_stream_name(&name), "!Property");
+
+        let mut pool = 1252u32.to_le_bytes().to_vec();
+        for (len, refs) in [(14u16, 1u16), (5, 1)] {
+            pool.extend_from_slice(&len.to_le_bytes());
+            pool.extend_from_slice(&refs.to_le_bytes());
+        }
+        let (strings, ref_size) = parse_string_pool(&pool, b"ProductVersion1.4.2").unwrap();
+        assert_eq!(strings, ["ProductVersion", "1.4.2"]);
+        assert_eq!(ref_size, 2);
+
+        let table = [1u16, 2]
+            .iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(property_rows(&table, 2).collect::<Vec<_>>(), [(1, 2)]);
+    }
+}
@@ -0,0 +1,132 @@
+use log::debug;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+/// Result of checking a macOS artifact's code signature and notarization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotarizationStatus {
+    /// Signed with a Developer ID and accepted by Gatekeeper as notarized
+    Notarized,
+    /// Signed, but Gatekeeper did not report a notarization ticket
+    SignedNotNotarized,
+    /// Not signed, or the signature was rejected
+    Rejected(String),
+    /// The check could not be performed (e.g. not running on macOS)
+    Unknown(String),
+}
+
+impl NotarizationStatus {
+    #[must_use]
+    pub fn is_notarized(&self) -> bool {
+        matches!(self, Self::Notarized)
+    }
+}
+
+impl fmt::Display for NotarizationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Notarized => write!(f, "notarized"),
+            Self::SignedNotNotarized => write!(f, "signed but not notarized"),
+            Self::Rejected(reason) => write!(f, "rejected by Gatekeeper: {reason}"),
+            Self::Unknown(reason) => write!(f, "unknown ({reason})"),
+        }
+    }
+}
+
+/// Returns whether `path` is a macOS artifact whose notarization can be checked
+/// (`.dmg`, `.pkg` or `.app`)
+#[must_use]
+pub fn is_notarizable(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        ["dmg", "pkg", "app"]
+            .iter()
+            .any(|x| e.eq_ignore_ascii_case(x))
+    })
+}
+
+/// Check a macOS artifact with Gatekeeper (`spctl --assess`)
+///
+/// Disk images are assessed as opened documents, installer packages as installs and
+/// `.app` bundles as executables. On other operating systems the status is
+/// [`NotarizationStatus::Unknown`].
+#[must_use]
+pub fn check_notarization(path: &Path) -> NotarizationStatus {
+    if !cfg!(target_os = "macos") {
+        return NotarizationStatus::Unknown(
+            "notarization can only be verified on macOS".to_string(),
+        );
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let mut args = vec!["--assess", "--verbose=2"];
+    match extension.as_str() {
+        "dmg" => args.extend(["--type", "open", "--context", "context:primary-signature"]),
+        "pkg" => args.extend(["--type", "install"]),
+        _ => args.extend(["--type", "execute"]),
+    }
+
+    debug!("Running spctl {} {}", args.join(" "), path.display());
+    match Command::new("spctl").args(&args).arg(path).output() {
+        Ok(output) => {
+            // spctl reports its verdict on stderr
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            classify_spctl(output.status.success(), &text)
+        }
+        Err(e) => NotarizationStatus::Unknown(format!("failed to run spctl: {e}")),
+    }
+}
+
+/// Interpret the output of `spctl --assess --verbose`
+fn classify_spctl(accepted: bool, output: &str) -> NotarizationStatus {
+    let source = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("source="))
+        .map(str::trim);
+
+    if !accepted {
+        let reason = source
+            .or_else(|| output.lines().map(str::trim).find(|l| !l.is_empty()))
+            .unwrap_or("no usable signature");
+        return NotarizationStatus::Rejected(reason.to_string());
+    }
+
+    match source {
+        Some(source) if source.contains("Notarized") => NotarizationStatus::Notarized,
+        _ => NotarizationStatus::SignedNotNotarized,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_spctl() {
+        assert_eq!(
+            classify_spctl(
+                true,
+                "Game.dmg: accepted\nsource=Notarized Developer ID\norigin=Developer ID Application: Studio (ABCDE12345)\n"
+            ),
+            NotarizationStatus::Notarized
+        );
+        assert_eq!(
+            classify_spctl(true, "Game.pkg: accepted\nsource=Developer ID\n"),
+            NotarizationStatus::SignedNotNotarized
+        );
+        assert_eq!(
+            classify_spctl(false, "Game.dmg: rejected\nsource=no usable signature\n"),
+            NotarizationStatus::Rejected("no usable signature".to_string())
+        );
+        assert!(is_notarizable(Path::new("out/Game.DMG")));
+        assert!(!is_notarizable(Path::new("out/Game.zip")));
+    }
+}
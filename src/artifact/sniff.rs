@@ -0,0 +1,210 @@
+use crate::api::BuildPlatform;
+use crate::error::{Error, Result};
+use log::debug;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Mach-O magic numbers (32/64-bit, both byte orders)
+const MACHO_MAGICS: [[u8; 4]; 4] = [
+    [0xFE, 0xED, 0xFA, 0xCE],
+    [0xFE, 0xED, 0xFA, 0xCF],
+    [0xCE, 0xFA, 0xED, 0xFE],
+    [0xCF, 0xFA, 0xED, 0xFE],
+];
+
+/// Infer a file's platform from its content rather than its extension
+///
+/// Recognizes PE executables (Windows), ELF binaries (Linux), Mach-O and universal
+/// binaries, disk images and installer packages (macOS), zip-based APK, AAB and IPA
+/// packages, and Switch NSP and XCI images. Returns `Ok(None)` if the content is not
+/// recognized.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read
+pub fn sniff_platform(path: &Path) -> Result<Option<BuildPlatform>> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 8];
+    let read = read_up_to(&mut file, &mut header)?;
+    let header = &header[..read];
+
+    let platform = if header.starts_with(b"MZ") {
+        Some(BuildPlatform::Windows)
+    } else if header.starts_with(b"\x7FELF") {
+        Some(BuildPlatform::Linux)
+    } else if MACHO_MAGICS.iter().any(|m| header.starts_with(m)) || is_universal_binary(header) {
+        Some(BuildPlatform::Macos)
+    } else if header.starts_with(b"xar!") {
+        // Flat installer packages (.pkg) are xar archives
+        Some(BuildPlatform::Macos)
+    } else if header.starts_with(b"PK\x03\x04") {
+        sniff_zip(path)?
+    } else if header.starts_with(b"PFS0") {
+        // Switch submission packages (.nsp) are PFS0 partition file systems
+        Some(BuildPlatform::Switch)
+    } else if has_xci_header(&mut file)? {
+        Some(BuildPlatform::Switch)
+    } else if has_dmg_trailer(&mut file)? {
+        Some(BuildPlatform::Macos)
+    } else {
+        None
+    };
+
+    debug!(
+        "Sniffed {} as {}",
+        path.display(),
+        platform.as_ref().map_or("unknown", BuildPlatform::as_str)
+    );
+    Ok(platform)
+}
+
+/// Universal (fat) binaries share `CAFEBABE` with Java class files, which are told
+/// apart by the architecture count (Java versions start at 45)
+pub(super) fn is_universal_binary(header: &[u8]) -> bool {
+    header.len() >= 8
+        && header.starts_with(&[0xCA, 0xFE, 0xBA, 0xBE])
+        && u32::from_be_bytes([header[4], header[5], header[6], header[7]]) < 20
+}
+
+/// Switch game card images (.xci) have a `HEAD` magic after their 256-byte signature
+fn has_xci_header(file: &mut File) -> Result<bool> {
+    if file.metadata()?.len() < 0x104 {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::Start(0x100))?;
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    Ok(&magic == b"HEAD")
+}
+
+/// Disk images end with a 512-byte `koly` trailer
+fn has_dmg_trailer(file: &mut File) -> Result<bool> {
+    let len = file.metadata()?.len();
+    if len < 512 {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::End(-512))?;
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    Ok(&magic == b"koly")
+}
+
+/// Identify zip-based packages from their entry names
+fn sniff_zip(path: &Path) -> Result<Option<BuildPlatform>> {
+    let archive = match zip::ZipArchive::new(File::open(path)?) {
+        Ok(archive) => archive,
+        Err(e) => {
+            debug!("{} looks like a zip but can't be read: {e}", path.display());
+            return Ok(None);
+        }
+    };
+
+    let mut names = archive.file_names();
+    let platform = names.find_map(|name| {
+        if name == "AndroidManifest.xml"
+            || name == "base/manifest/AndroidManifest.xml"
+            || name == "BundleConfig.pb"
+        {
+            Some(BuildPlatform::Android)
+        } else if name.starts_with("Payload/") && name.contains(".app/") {
+            Some(BuildPlatform::IosNative)
+        } else {
+            None
+        }
+    });
+    Ok(platform)
+}
+
+pub(super) fn read_up_to(file: &mut File, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(Error::FileError(e)),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn write_zip(path: &Path, names: &[&str]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for name in names {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"data").unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_sniff_platform() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str, contents: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+
+        let exe = file("game", b"MZ\x90\x00\x03\x00\x00\x00");
+        assert!(matches!(
+            sniff_platform(&exe).unwrap(),
+            Some(BuildPlatform::Windows)
+        ));
+        let elf = file("server", b"\x7FELF\x02\x01\x01\x00");
+        assert!(matches!(
+            sniff_platform(&elf).unwrap(),
+            Some(BuildPlatform::Linux)
+        ));
+        let macho = file("mac", &[0xCF, 0xFA, 0xED, 0xFE, 7, 0, 0, 1]);
+        assert!(matches!(
+            sniff_platform(&macho).unwrap(),
+            Some(BuildPlatform::Macos)
+        ));
+        let class = file("Main.class", &[0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52]);
+        assert!(sniff_platform(&class).unwrap().is_none());
+        let mut dmg = vec![0u8; 1024];
+        dmg[512..516].copy_from_slice(b"koly");
+        let dmg = file("image", &dmg);
+        assert!(matches!(
+            sniff_platform(&dmg).unwrap(),
+            Some(BuildPlatform::Macos)
+        ));
+
+        let apk = dir.path().join("android.zip");
+        write_zip(&apk, &["AndroidManifest.xml", "classes.dex"]);
+        assert!(matches!(
+            sniff_platform(&apk).unwrap(),
+            Some(BuildPlatform::Android)
+        ));
+        let ipa = dir.path().join("ios.zip");
+        write_zip(&ipa, &["Payload/Game.app/Info.plist"]);
+        assert!(matches!(
+            sniff_platform(&ipa).unwrap(),
+            Some(BuildPlatform::IosNative)
+        ));
+        let other = dir.path().join("assets.zip");
+        write_zip(&other, &["textures/a.png"]);
+        assert!(sniff_platform(&other).unwrap().is_none());
+
+        let nsp = file("game.bin", b"PFS0\x04\x00\x00\x00");
+        assert!(matches!(
+            sniff_platform(&nsp).unwrap(),
+            Some(BuildPlatform::Switch)
+        ));
+        let mut xci = vec![0u8; 0x200];
+        xci[0x100..0x104].copy_from_slice(b"HEAD");
+        let xci = file("cartridge", &xci);
+        assert!(matches!(
+            sniff_platform(&xci).unwrap(),
+            Some(BuildPlatform::Switch)
+        ));
+    }
+}
@@ -0,0 +1,356 @@
+use super::msi::read_msi_version;
+use crate::error::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Resource type ID of version information (`RT_VERSION`)
+const RT_VERSION: u32 = 16;
+/// Signature of `VS_FIXEDFILEINFO`
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+/// Resource sections larger than this are not read
+const MAX_RESOURCE_SECTION: u32 = 256 * 1024 * 1024;
+
+/// Version resource of a Windows executable or installer
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowsVersionInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product_name: Option<String>,
+}
+
+impl WindowsVersionInfo {
+    fn is_empty(&self) -> bool {
+        self.file_version.is_none() && self.product_version.is_none() && self.product_name.is_none()
+    }
+}
+
+/// Returns whether `path` is a Windows executable or installer by extension
+#[must_use]
+pub fn is_windows_binary(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("exe") || e.eq_ignore_ascii_case("msi"))
+}
+
+/// Read the version of an `.exe` (PE version resource) or `.msi` (`Property` table)
+///
+/// Returns `Ok(None)` if the file carries no version information.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read
+pub fn inspect_windows_version(path: &Path) -> Result<Option<WindowsVersionInfo>> {
+    let is_msi = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("msi"));
+    let info = if is_msi {
+        read_msi_version(path)?
+    } else {
+        read_pe_version(&mut File::open(path)?)?
+    };
+
+    if info.is_none() {
+        debug!("No version information in {}", path.display());
+    }
+    Ok(info.filter(|i| !i.is_empty()))
+}
+
+/// Locate the `RT_VERSION` resource of a PE file and parse it
+fn read_pe_version(file: &mut File) -> Result<Option<WindowsVersionInfo>> {
+    let mut dos = [0u8; 64];
+    if file.read_exact(&mut dos).is_err() || &dos[..2] != b"MZ" {
+        return Ok(None);
+    }
+    let pe_offset = u64::from(u32_at(&dos, 0x3C).unwrap_or(0));
+
+    // PE signature and COFF header
+    let mut coff = [0u8; 24];
+    file.seek(SeekFrom::Start(pe_offset))?;
+    if file.read_exact(&mut coff).is_err() || &coff[..4] != b"PE\0\0" {
+        return Ok(None);
+    }
+    let section_count = usize::from(u16_at(&coff, 6).unwrap_or(0));
+    let optional_size = usize::from(u16_at(&coff, 20).unwrap_or(0));
+
+    let mut headers = vec![0u8; optional_size + section_count * 40];
+    file.read_exact(&mut headers)?;
+    let (optional, sections) = headers.split_at(optional_size);
+
+    // Data directories follow the PE32 / PE32+ specific fields; resources are entry 2
+    let directories = match u16_at(optional, 0) {
+        Some(0x10b) => 96,
+        Some(0x20b) => 112,
+        _ => return Ok(None),
+    };
+    let Some(resource_rva) = u32_at(optional, directories + 2 * 8).filter(|rva| *rva != 0) else {
+        return Ok(None);
+    };
+
+    let Some((section_rva, raw_offset, raw_size)) = sections.chunks_exact(40).find_map(|s| {
+        let virtual_size = u32_at(s, 8)?;
+        let rva = u32_at(s, 12)?;
+        let raw_size = u32_at(s, 16)?;
+        let raw_offset = u32_at(s, 20)?;
+        let size = virtual_size.max(raw_size);
+        (rva..rva.saturating_add(size))
+            .contains(&resource_rva)
+            .then_some((rva, raw_offset, raw_size))
+    }) else {
+        return Ok(None);
+    };
+    if raw_size > MAX_RESOURCE_SECTION {
+        return Ok(None);
+    }
+
+    let mut section = vec![0u8; raw_size as usize];
+    file.seek(SeekFrom::Start(u64::from(raw_offset)))?;
+    file.read_exact(&mut section)?;
+
+    let root = (resource_rva - section_rva) as usize;
+    let Some((data_rva, size)) = find_version_resource(&section, root) else {
+        return Ok(None);
+    };
+    let start = data_rva.wrapping_sub(section_rva) as usize;
+    Ok(section
+        .get(start..start + size as usize)
+        .and_then(parse_version_info))
+}
+
+/// Walk the resource tree (type, name, language) to the first `RT_VERSION` data entry
+///
+/// Returns the resource's RVA and size.
+fn find_version_resource(section: &[u8], root: usize) -> Option<(u32, u32)> {
+    let entries = |dir: usize| -> Option<Vec<(u32, u32)>> {
+        let named = usize::from(u16_at(section, dir + 12)?);
+        let ids = usize::from(u16_at(section, dir + 14)?);
+        (0..named + ids)
+            .map(|i| {
+                let at = dir + 16 + i * 8;
+                Some((u32_at(section, at)?, u32_at(section, at + 4)?))
+            })
+            .collect()
+    };
+    let subdirectory =
+        |offset: u32| (offset & 0x8000_0000 != 0).then(|| root + (offset & 0x7FFF_FFFF) as usize);
+
+    let types = entries(root)?;
+    let (_, names) = types.into_iter().find(|(id, _)| *id == RT_VERSION)?;
+    let (_, languages) = *entries(subdirectory(names)?)?.first()?;
+    let (_, data) = *entries(subdirectory(languages)?)?.first()?;
+    if data & 0x8000_0000 != 0 {
+        return None;
+    }
+
+    let entry = root + data as usize;
+    Some((u32_at(section, entry)?, u32_at(section, entry + 4)?))
+}
+
+/// Parse a `VS_VERSIONINFO` block
+///
+/// Strings from the first `StringFileInfo` table win; the fixed file info fills in
+/// missing versions.
+fn parse_version_info(data: &[u8]) -> Option<WindowsVersionInfo> {
+    let root = Block::parse(data)?;
+    if root.key != "VS_VERSION_INFO" {
+        return None;
+    }
+
+    let mut info = WindowsVersionInfo::default();
+    for block in root.children() {
+        if block.key != "StringFileInfo" {
+            continue;
+        }
+        let Some(table) = block.children().next() else {
+            continue;
+        };
+        for string in table.children() {
+            let value = utf16_until_nul(string.value).trim().to_string();
+            if value.is_empty() {
+                continue;
+            }
+            match string.key.as_str() {
+                "FileVersion" => info.file_version = Some(value),
+                "ProductVersion" => info.product_version = Some(value),
+                "ProductName" => info.product_name = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    // VS_FIXEDFILEINFO: signature, struct version, then file and product versions
+    let fixed = root.value;
+    if u32_at(fixed, 0) == Some(FIXED_FILE_INFO_SIGNATURE) {
+        let version = |at: usize| {
+            let ms = u32_at(fixed, at)?;
+            let ls = u32_at(fixed, at + 4)?;
+            Some(format!(
+                "{}.{}.{}.{}",
+                ms >> 16,
+                ms & 0xFFFF,
+                ls >> 16,
+                ls & 0xFFFF
+            ))
+        };
+        info.file_version = info.file_version.or_else(|| version(8));
+        info.product_version = info.product_version.or_else(|| version(16));
+    }
+    Some(info)
+}
+
+/// One node of a version resource: `wLength`, `wValueLength`, `wType`, key, value, children
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    children: &'a [u8],
+}
+
+impl<'a> Block<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let length = usize::from(u16_at(data, 0)?);
+        let value_length = usize::from(u16_at(data, 2)?);
+        let is_text = u16_at(data, 4)? == 1;
+        let data = data.get(..length)?;
+
+        let mut key = Vec::new();
+        let mut at = 6;
+        loop {
+            let unit = u16_at(data, at)?;
+            at += 2;
+            if unit == 0 {
+                break;
+            }
+            key.push(unit);
+        }
+        at = align4(at);
+
+        // Text values are measured in UTF-16 units
+        let value_bytes = if is_text {
+            value_length * 2
+        } else {
+            value_length
+        };
+        let value_end = (at + value_bytes).min(data.len());
+        let value = &data[at.min(value_end)..value_end];
+        let children = data.get(align4(value_end)..).unwrap_or_default();
+
+        Some(Self {
+            key: String::from_utf16_lossy(&key),
+            value,
+            children,
+        })
+    }
+
+    fn children(&self) -> impl Iterator<Item = Block<'a>> {
+        let mut rest = self.children;
+        std::iter::from_fn(move || {
+            let length = usize::from(u16_at(rest, 0)?);
+            if length == 0 {
+                return None;
+            }
+            let block = Block::parse(rest)?;
+            rest = rest.get(align4(length)..).unwrap_or_default();
+            Some(block)
+        })
+    }
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+fn utf16_until_nul(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .take_while(|u| *u != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a version resource block with a UTF-16 key
+    fn block(key: &str, value: &[u8], is_text: bool, children: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = vec![0, 0];
+        let value_length = if is_text {
+            value.len() / 2
+        } else {
+            value.len()
+        };
+        out.extend_from_slice(&u16::try_from(value_length).unwrap().to_le_bytes());
+        out.extend_from_slice(&u16::from(is_text).to_le_bytes());
+        for unit in key.encode_utf16().chain([0]) {
+            out.extend_from_slice(&unit.to_le_bytes());
+        }
+        out.resize(align4(out.len()), 0);
+        out.extend_from_slice(value);
+        for child in children {
+            out.resize(align4(out.len()), 0);
+            out.extend_from_slice(child);
+        }
+        let length = u16::try_from(out.len()).unwrap();
+        out[..2].copy_from_slice(&length.to_le_bytes());
+        out
+    }
+
+    fn text(value: &str) -> Vec<u8> {
+        value
+            .encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_version_info() {
+        let mut fixed = FIXED_FILE_INFO_SIGNATURE.to_le_bytes().to_vec();
+        fixed.extend_from_slice(&0x0001_0000u32.to_le_bytes());
+        // 1.4.2.311 as file version, 1.4.0.0 as product version
+        for part in [0x0001_0004u32, 0x0002_0137, 0x0001_0004, 0] {
+            fixed.extend_from_slice(&part.to_le_bytes());
+        }
+        fixed.resize(52, 0);
+
+        let strings = block(
+            "StringFileInfo",
+            &[],
+            true,
+            &[block(
+                "040904b0",
+                &[],
+                true,
+                &[
+                    block("ProductName", &text("Space Game"), true, &[]),
+                    block("ProductVersion", &text("1.4 beta"), true, &[]),
+                ],
+            )],
+        );
+        let data = block("VS_VERSION_INFO", &fixed, false, &[strings]);
+
+        assert_eq!(
+            parse_version_info(&data),
+            Some(WindowsVersionInfo {
+                file_version: Some("1.4.2.311".to_string()),
+                product_version: Some("1.4 beta".to_string()),
+                product_name: Some("Space Game".to_string()),
+            })
+        );
+        assert_eq!(parse_version_info(&block("Other", &[], false, &[])), None);
+    }
+}
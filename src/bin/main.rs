@@ -1,37 +1,114 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
 use futures::stream::{self, StreamExt};
-use glob::glob;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use log::{debug, error, info, warn};
 use nunu_cli::{
-    BuildPlatform, Client, Config, DeletionPolicy, UploadOptions,
-    api::client::{BuildDetails, UploadInfo},
+    Arch, BuildPlatform, Client, Config, DeletionPolicy, StorageHttpVersion, TransportOptions,
+    UploadOptions,
+    api::client::{BuildDetails, BuildSetAttachment, UploadInfo},
+    api::dns::parse_resolve_override,
+    api::proxy::ProxyOptions,
+    artifact::{
+        ArtifactType, arch::detect_arch, check_notarization, inspect_android_manifest,
+        inspect_android_signing, inspect_ipa, inspect_windows_version, is_android_package, is_ipa,
+        is_notarizable, is_windows_binary, validate_app_bundle,
+    },
+    budget::{BudgetAction, SizeBudget, artifact_size, budget_for, check_size_budget},
+    checksums::{ChecksumEntry, ChecksumFormat, render_checksums, sha256_file},
     ci_metadata::collect_ci_metadata,
+    config::DEFAULT_CONNECT_TIMEOUT,
+    credentials::{read_token_file, run_token_command},
+    deprecations::{self, Deprecation},
+    error_report::{BatchFailure, ErrorReport, FileErrorReport},
     file_config::FileConfig,
-    metadata::collect_git_metadata,
+    files::{IgnoreRules, compile_excludes},
+    format::{OutputFormat, format_duration, format_rate, format_size},
+    i18n::{self, Locale},
+    integrations::docker::{
+        API_URL_SECRET, PROJECT_ID_SECRET, TOKEN_SECRET, docker_secret, resolve_env,
+    },
+    integrations::sdk_template::{SdkTemplateOptions, write_sdk_template},
+    links::find_linked_duplicates,
+    logging::{self, LogSpec, parse_log_spec, register_secret},
+    manifest::Manifest,
+    metadata::{
+        VcsOverrides, collect_vcs_metadata_with, parse_custom_metadata, release_notes_from_git,
+        take_collection_warnings, version_from_tag,
+    },
+    naming::generate_build_name,
+    plain::{self, PlainProgress},
+    plugins::{PLUGIN_PREFIX, find_plugin},
+    priority::lower_process_priority,
+    receipts::{self, ReceiptKey},
+    retention::{self, DEFAULT_POLICIES, RetentionPolicy},
+    secrets::fetch_token,
+    session::UploadSession,
+    size_diff::SizeDiff,
+    state::{self, UploadStatus},
+    symbols::{SymbolFile, SymbolsInfo, find_symbols},
+    t,
+    tags::{derive_auto_tags, merge_tags},
+    teamcity::{self, TeamCityProgress},
+    throughput::{self, SlowUploadMonitor},
+    units::{self, ByteSize, HumanDuration},
+    upload::{
+        DEFAULT_URL_BATCH_SIZE, OnUploadInitiated,
+        abort::{ABORT_DEADLINE, InFlightUpload, abort_uploads},
+        shard::Shard,
+        stall::DEFAULT_STALL_TIMEOUT,
+        targets::{
+            PreparedRequest, UploadTarget, apply_project_settings, apply_upload_script,
+            attach_session, expand_globs, file_spec_targets, infer_platform, manifest_targets,
+            prepare_request, prepare_session, target_tags, validate_tags,
+        },
+    },
     upload_file,
+    verify::{DEFAULT_VERIFY_PERCENT, VerifyReport, VerifySampling, verify_uploads},
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
 use std::io::Write;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// Parallel uploads/parts when `--parallel` is not given
+const DEFAULT_PARALLEL: usize = 4;
+
+/// Parallel uploads/parts with `--nice`, leaving bandwidth and disk for other work
+const NICE_PARALLEL: usize = 2;
+
 /// Tracks active uploads for graceful cancellation
-type ActiveUploads = Arc<RwLock<HashMap<String, UploadMetadata>>>;
+type ActiveUploads = Arc<RwLock<HashMap<String, InFlightUpload>>>;
 
-#[derive(Debug, Clone)]
-struct UploadMetadata {
-    build_id: String,
-    upload_id: Option<String>,
-    object_key: String,
+/// `println!` for human-readable output, without emoji and decoration with `--plain`
+macro_rules! outln {
+    () => {
+        println!()
+    };
+    ($($arg:tt)*) => {
+        println!("{}", plain::text(&format!($($arg)*)))
+    };
+}
+
+/// `eprintln!` for human-readable output, without emoji and decoration with `--plain`
+macro_rules! errln {
+    ($($arg:tt)*) => {
+        eprintln!("{}", plain::text(&format!($($arg)*)))
+    };
 }
 
 #[derive(Parser)]
 #[command(name = "nunu-cli")]
 #[command(about = "Upload build artifacts to Nunu.ai", long_about = None)]
 #[command(version)]
+#[command(
+    after_help = "Other commands run a `nunu-cli-<COMMAND>` plugin executable from PATH, with the resolved configuration in NUNU_* environment variables."
+)]
+#[allow(clippy::struct_excessive_bools)]
 struct Cli {
     /// Enable verbose output (shows all logs)
     #[arg(short, long, global = true, action = clap::ArgAction::Count)]
@@ -41,6 +118,44 @@ struct Cli {
     #[arg(short, long, global = true)]
     config: Option<PathBuf>,
 
+    /// Per-subsystem log levels, e.g. `api=debug,upload=info,metadata=off`
+    /// (subsystems: api, upload, metadata, config, files, integrations)
+    #[arg(long, global = true, value_name = "SPEC", value_parser = parse_log_spec)]
+    log: Option<LogSpec>,
+
+    /// Also write full debug logs (timestamped JSON lines, secrets redacted) to this file
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Result format: `human` (sizes in KiB/MiB/GiB) or `json` (one document on stdout, sizes in bytes)
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "human")]
+    format: OutputFormat,
+
+    /// Plain text output without emoji, progress bars or color, for screen readers and
+    /// legacy terminals (also used when TERM=dumb)
+    #[arg(long, global = true, env = "NUNU_PLAIN")]
+    plain: bool,
+
+    /// Language of summaries and common errors: `en`, `ja` or `zh` (simplified Chinese; default: from `LC_ALL`, `LC_MESSAGES` or `LANG`)
+    #[arg(long, global = true, value_name = "LANG", env = "NUNU_LANG")]
+    lang: Option<Locale>,
+
+    /// Fail instead of warning when a deprecated flag, config key, environment variable or
+    /// value is used
+    #[arg(long, global = true, env = "NUNU_STRICT_DEPRECATIONS")]
+    strict_deprecations: bool,
+
+    /// On failure, print a JSON object with the error's category, message, HTTP status,
+    /// build ID and whether retrying may help to stderr (also with `--format json`)
+    #[arg(long, global = true, env = "NUNU_JSON_ERRORS")]
+    json_errors: bool,
+
+    /// Run against a local stub API instead of nunu.ai, storing uploads in memory or in the
+    /// S3-compatible server at `NUNU_E2E_S3_ENDPOINT` (e.g. MinIO)
+    #[cfg(feature = "e2e")]
+    #[arg(long, global = true)]
+    e2e_local: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -49,636 +164,3320 @@ struct Cli {
 enum Commands {
     /// Upload a build artifact
     #[command(override_usage = "<FILES>... [OPTIONS]")]
-    Upload {
-        /// Files to upload (supports glob patterns like *.apk, app?.exe, build[0-9].ipa)
-        #[arg(value_name = "FILES", num_args = 1..)]
-        files: Vec<String>,
+    Upload(Box<UploadArgs>),
 
-        /// API token for authentication
-        #[arg(short, long, env = "NUNU_API_TOKEN")]
-        token: Option<String>,
+    /// Manage uploaded builds
+    Builds {
+        #[command(subcommand)]
+        command: BuildsCommands,
+    },
 
-        /// Project ID
-        #[arg(short, long, env = "NUNU_PROJECT_ID")]
-        project_id: Option<String>,
+    /// See what retention policies would delete before enabling auto-delete
+    Retention {
+        #[command(subcommand)]
+        command: RetentionCommands,
+    },
 
-        /// API base URL
-        #[arg(long, env = "NUNU_API_URL")]
-        api_url: Option<String>,
+    /// Upload debug symbols for crash symbolication
+    Symbols {
+        #[command(subcommand)]
+        command: SymbolsCommands,
+    },
+
+    /// Inspect uploads running on this machine
+    Uploads {
+        #[command(subcommand)]
+        command: UploadsCommands,
+    },
+
+    /// List and verify the signed receipts of uploads made from this machine
+    Receipts {
+        #[command(subcommand)]
+        command: ReceiptsCommands,
+    },
 
-        /// Build name (will be used as template for multiple files)
-        #[arg(short, long)]
-        name: String,
+    /// Helpers for embedding Nunu uploads into other tools
+    Integrations {
+        #[command(subcommand)]
+        command: IntegrationsCommands,
+    },
+
+    /// Any other command is dispatched to a `nunu-cli-<name>` plugin on PATH
+    #[command(external_subcommand)]
+    Plugin(Vec<String>),
+}
+
+impl Commands {
+    /// The API options of the command, for the commands that call the API
+    #[cfg(feature = "e2e")]
+    fn api_args_mut(&mut self) -> Option<&mut ApiArgs> {
+        match self {
+            Self::Upload(args) => Some(&mut args.api),
+            Self::Builds {
+                command: BuildsCommands::Delete { api, .. } | BuildsCommands::Restore { api, .. },
+            }
+            | Self::Retention {
+                command: RetentionCommands::Audit { api, .. },
+            }
+            | Self::Symbols {
+                command: SymbolsCommands::Upload { api, .. },
+            } => Some(api),
+            Self::Receipts {
+                command: ReceiptsCommands::Verify { api, .. },
+            } => Some(api),
+            Self::Uploads { .. }
+            | Self::Receipts { .. }
+            | Self::Integrations { .. }
+            | Self::Plugin(_) => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum BuildsCommands {
+    /// Delete a build (recoverable with `builds restore` until the server's trash window expires)
+    Delete {
+        /// ID of the build to delete
+        build_id: String,
+
+        /// Permanently delete the build immediately instead of moving it to the trash
+        #[arg(long)]
+        purge: bool,
+
+        #[command(flatten)]
+        api: ApiArgs,
+    },
+
+    /// Restore a previously deleted build from the trash
+    Restore {
+        /// ID of the build to restore
+        build_id: String,
 
-        /// Target platform (optional, can be inferred from file extension)
+        #[command(flatten)]
+        api: ApiArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum RetentionCommands {
+    /// List the project's builds with sizes, ages and tags, and what each policy would
+    /// delete (nothing is deleted)
+    Audit {
+        /// Policy to evaluate: `keep-last=N` (newest N builds per branch and platform) or
+        /// `max-age=DURATION` (e.g. `90d`); repeatable (default: keep-last=5, keep-last=20,
+        /// max-age=30d, max-age=90d)
+        #[arg(long = "policy", value_name = "POLICY")]
+        policies: Vec<RetentionPolicy>,
+
+        /// Never delete builds with this tag (repeatable)
+        #[arg(long = "keep-tag", value_name = "TAG")]
+        keep_tags: Vec<String>,
+
+        #[command(flatten)]
+        api: ApiArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum SymbolsCommands {
+    /// Find and upload dSYM bundles, Windows PDBs and ProGuard/R8 mapping files
+    #[command(group(
+        ArgGroup::new("target")
+            .required(true)
+            .args(["build_id", "build_version"])
+    ))]
+    Upload {
+        /// Symbol files, or directories to search for them (supports glob patterns)
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// ID of the build the symbols belong to
+        #[arg(long)]
+        build_id: Option<String>,
+
+        /// Version of the builds the symbols belong to (when the build ID is not known)
+        #[arg(long)]
+        build_version: Option<String>,
+
+        /// Platform of the symbols (default: iOS for dSYMs, Windows for PDBs, Android for mappings)
         #[arg(long, value_parser = clap::value_parser!(BuildPlatform))]
         platform: Option<BuildPlatform>,
 
-        /// Build description (optional)
-        #[arg(short, long)]
-        description: Option<String>,
+        /// Number of files and parts uploaded in parallel
+        #[arg(long, default_value_t = DEFAULT_PARALLEL)]
+        parallel: usize,
+
+        /// List the symbols that would be uploaded without uploading them
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        api: ApiArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum UploadsCommands {
+    /// Follow the progress of uploads started by other nunu-cli processes (e.g. a build script)
+    Watch {
+        /// Build ID of the upload to follow (default: every upload in progress)
+        build_id: Option<String>,
+    },
+}
 
-        /// Upload timeout in minutes (1-1440, default determined by server)
-        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=1440))]
-        upload_timeout: Option<u32>,
+#[derive(Subcommand)]
+enum ReceiptsCommands {
+    /// List stored receipts
+    List,
+
+    /// Check receipts against the server's signing keys
+    Verify {
+        /// Build IDs of the receipts to verify (default: every stored receipt)
+        build_ids: Vec<String>,
+
+        /// Also check that this local file is exactly what was uploaded (one build ID only)
+        #[arg(long)]
+        file: Option<PathBuf>,
 
-        /// Automatically delete old builds if storage limits are exceeded
+        /// Verify against this hex Ed25519 public key instead of fetching the server's keys
         #[arg(long)]
-        auto_delete: bool,
+        public_key: Option<String>,
+
+        #[command(flatten)]
+        api: Box<ApiArgs>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IntegrationsCommands {
+    /// Scaffold a minimal Rust project that uploads builds using the nunu-cli library
+    SdkTemplate {
+        /// Directory to create the project in
+        #[arg(short, long, default_value = "nunu-uploader")]
+        output: PathBuf,
+
+        /// Project ID used by the generated code
+        #[arg(short, long, env = "NUNU_PROJECT_ID")]
+        project_id: Option<String>,
 
-        /// Deletion policy when auto-delete is enabled (`least_recent` or `oldest`)
-        #[arg(long, default_value = "least_recent", requires = "auto_delete", value_parser = clap::value_parser!(DeletionPolicy))]
-        deletion_policy: DeletionPolicy,
+        /// API base URL used by the generated code
+        #[arg(long, env = "NUNU_API_URL")]
+        api_url: Option<String>,
 
-        /// Force multipart upload
+        /// Overwrite existing files
         #[arg(long)]
-        force_multipart: bool,
+        force: bool,
+    },
 
-        /// Number of parallel uploads/parts (1-32, default: 4)
-        #[arg(long, default_value = "4")]
-        parallel: usize,
+    /// Use Docker secrets instead of `-e` environment variables in containerized pipelines
+    Docker {
+        #[command(subcommand)]
+        command: DockerCommands,
+    },
+}
 
-        /// Tags for the build (comma-separated, max 50 chars each)
-        #[arg(long, value_delimiter = ',')]
-        tags: Option<Vec<String>>,
+#[derive(Subcommand)]
+enum DockerCommands {
+    /// Show the API token, project ID and API URL the CLI resolves and where each comes
+    /// from (environment, Docker secret or config file); the token is never printed
+    PrintEnv,
+
+    /// Run a command with `NUNU_API_TOKEN`, `NUNU_PROJECT_ID` and `NUNU_API_URL` set from
+    /// Docker secrets and the config file, for use as a container entrypoint. The variables
+    /// exist only in the command's environment, so `docker inspect` never shows them
+    Entrypoint {
+        /// Command to run, with its arguments
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
     },
 }
 
-/// Infer platform from file extension
+/// Credentials and endpoint shared by all commands that talk to the API
+#[derive(Args, Default)]
+struct ApiArgs {
+    /// API token for authentication
+    #[arg(short, long, env = "NUNU_API_TOKEN")]
+    token: Option<String>,
+
+    /// Read the API token from this file, e.g. one written by a secret manager (used when
+    /// `--token` and `NUNU_API_TOKEN` are not set)
+    #[arg(long, value_name = "PATH", env = "NUNU_API_TOKEN_FILE")]
+    token_file: Option<PathBuf>,
+
+    /// Project ID, or an alias from the config file's `projects`
+    #[arg(short, long, visible_alias = "project", env = "NUNU_PROJECT_ID")]
+    project_id: Option<String>,
+
+    /// API base URL
+    #[arg(long, env = "NUNU_API_URL")]
+    api_url: Option<String>,
+
+    /// Proxy for API requests and storage uploads, e.g. `http://proxy:3128`, used instead of
+    /// `HTTPS_PROXY`/`HTTP_PROXY`
+    #[arg(long, value_name = "URL", env = "NUNU_PROXY")]
+    proxy: Option<String>,
+
+    /// Proxy for storage uploads only, or `direct` for none (overrides `--proxy` for storage)
+    #[arg(long, value_name = "URL", env = "NUNU_STORAGE_PROXY")]
+    storage_proxy: Option<String>,
+
+    /// Proxy credentials, for proxy URLs without their own
+    #[arg(long, value_name = "USER:PASSWORD", env = "NUNU_PROXY_USER")]
+    proxy_user: Option<String>,
+
+    /// Reach a host without a proxy, in addition to those in `NO_PROXY` (repeatable): a
+    /// domain with its subdomains, an IP address or a network such as `10.0.0.0/8`
+    #[arg(
+        long,
+        value_name = "HOST",
+        env = "NUNU_NO_PROXY_FOR",
+        value_delimiter = ','
+    )]
+    no_proxy_for: Vec<String>,
+
+    /// PEM file with extra root certificates to trust, e.g. the internal CA of a
+    /// TLS-intercepting proxy
+    #[arg(long, value_name = "PEM", env = "NUNU_CA_BUNDLE")]
+    cacert: Option<PathBuf>,
+
+    /// PEM client certificate for API gateways that require mutual TLS, including its
+    /// private key unless `--client-key` is given, or a PKCS#12 bundle (.p12/.pfx)
+    #[arg(long, value_name = "FILE", env = "NUNU_CLIENT_CERT")]
+    client_cert: Option<PathBuf>,
+
+    /// PEM private key of `--client-cert`
+    #[arg(long, value_name = "PEM", env = "NUNU_CLIENT_KEY")]
+    client_key: Option<PathBuf>,
+
+    /// Password of a PKCS#12 `--client-cert`
+    #[arg(long, value_name = "PASSWORD", env = "NUNU_CLIENT_CERT_PASSWORD")]
+    client_cert_password: Option<String>,
+
+    /// Skip TLS certificate verification, like curl's `-k` (only for test environments with
+    /// self-signed certificates)
+    #[arg(short = 'k', long, env = "NUNU_INSECURE")]
+    insecure: bool,
+
+    /// How long connecting to the API or storage may take, e.g. 10s (bare numbers are
+    /// seconds; 0 waits as long as the OS does) [default: 30s]
+    #[arg(long, value_name = "DURATION", env = "NUNU_CONNECT_TIMEOUT")]
+    connect_timeout: Option<HumanDuration>,
+
+    /// How long an API request may take in total, e.g. 5m (bare numbers are seconds; by
+    /// default requests may take as long as they need). Uploads use --stall-timeout instead
+    #[arg(long, value_name = "DURATION", env = "NUNU_REQUEST_TIMEOUT")]
+    request_timeout: Option<HumanDuration>,
+}
+
+/// API endpoint and transport resolved from [`ApiArgs`], with the project ID still optional
+/// for commands that can run without it and the token sources kept unresolved until a
+/// request needs the token, so dry runs never run the token command or reach a secret store
+struct ResolvedApi {
+    token: Option<String>,
+    token_file: Option<PathBuf>,
+    project_id: Option<String>,
+    api_url: String,
+    transport: TransportOptions,
+}
+
+impl ResolvedApi {
+    /// API config with the token resolved, failing over to the config file's
+    /// `api_fallback_urls`, which are health-checked at its `api_health_path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the project ID is missing or the token is missing or cannot be
+    /// resolved
+    fn into_config(self, file_config: FileConfig) -> Result<Config> {
+        let project_id = self
+            .project_id
+            .ok_or_else(|| anyhow::anyhow!(t!("error.missing_project")))?;
+        let token = resolve_token(
+            self.token,
+            self.token_file.as_deref(),
+            &file_config,
+            &self.transport,
+        )?
+        .ok_or_else(|| anyhow::anyhow!(t!("error.missing_token")))?;
+        register_secret(&token);
+        Ok(Config::new(token, project_id, self.api_url)?
+            .with_fallback_urls(file_config.api_fallback_urls)
+            .with_health_path(file_config.api_health_path)
+            .with_transport(self.transport))
+    }
+
+    /// Whether a token source is configured, checked without reading the token file, running
+    /// the token command or contacting the secret store
+    fn has_token_source(&self, file_config: &FileConfig) -> bool {
+        self.token.is_some()
+            || self.token_file.is_some()
+            || std::env::var_os("NUNU_API_TOKEN").is_some()
+            || docker_secret(TOKEN_SECRET).is_some()
+            || file_config.api_token.is_some()
+            || file_config.token_secret.is_some()
+            || file_config.token_command.is_some()
+    }
+}
+
+impl ApiArgs {
+    /// Resolve the API config from CLI args, environment variables and the config file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the project ID is missing or the token is missing or cannot be
+    /// resolved
+    fn into_config(self, file_config: FileConfig) -> Result<Config> {
+        self.resolve(&file_config).into_config(file_config)
+    }
+
+    /// Resolve the project ID, API URL and transport from CLI args, environment variables and
+    /// the config file, registering the secrets for redaction
+    fn resolve(self, file_config: &FileConfig) -> ResolvedApi {
+        let transport = TransportOptions {
+            proxy: proxy_options(
+                self.proxy,
+                self.storage_proxy,
+                self.proxy_user.as_deref(),
+                self.no_proxy_for,
+                file_config.proxy.clone(),
+            ),
+            ca_bundle: self.cacert.or_else(|| file_config.ca_bundle.clone()),
+            client_cert: self.client_cert.or_else(|| file_config.client_cert.clone()),
+            client_key: self.client_key.or_else(|| file_config.client_key.clone()),
+            client_cert_password: self.client_cert_password.inspect(|p| register_secret(p)),
+            insecure: self.insecure,
+            connect_timeout: timeout(
+                self.connect_timeout.or(file_config.connect_timeout),
+                Some(DEFAULT_CONNECT_TIMEOUT),
+            ),
+            request_timeout: timeout(self.request_timeout.or(file_config.request_timeout), None),
+            ..TransportOptions::default()
+        };
+        let project_id = self
+            .project_id
+            .or_else(|| std::env::var("NUNU_PROJECT_ID").ok())
+            .or_else(|| docker_secret(PROJECT_ID_SECRET))
+            .or_else(|| file_config.project_id.clone())
+            .map(|p| file_config.resolve_project(p));
+        let api_url = self
+            .api_url
+            .or_else(|| std::env::var("NUNU_API_URL").ok())
+            .or_else(|| docker_secret(API_URL_SECRET))
+            .or_else(|| file_config.api_url.clone())
+            .unwrap_or_else(|| "https://nunu.ai/api".to_string());
+
+        ResolvedApi {
+            token: self.token,
+            token_file: self.token_file,
+            project_id,
+            api_url,
+            transport,
+        }
+    }
+}
+
+/// Transport options from the config file alone, for commands without the API flags
+fn file_transport(file_config: &FileConfig) -> TransportOptions {
+    TransportOptions {
+        proxy: proxy_options(None, None, None, Vec::new(), file_config.proxy.clone()),
+        ca_bundle: file_config.ca_bundle.clone(),
+        client_cert: file_config.client_cert.clone(),
+        client_key: file_config.client_key.clone(),
+        client_cert_password: std::env::var("NUNU_CLIENT_CERT_PASSWORD")
+            .ok()
+            .inspect(|p| register_secret(p)),
+        connect_timeout: timeout(file_config.connect_timeout, Some(DEFAULT_CONNECT_TIMEOUT)),
+        ..TransportOptions::default()
+    }
+}
+
+/// A timeout from the command line or config file, where zero disables it, or `default`
+fn timeout(value: Option<HumanDuration>, default: Option<Duration>) -> Option<Duration> {
+    value.map_or(default, |value| (!value.0.is_zero()).then_some(value.0))
+}
+
+/// Explicit proxies from `--proxy`, `--storage-proxy`, `--proxy-user` and `--no-proxy-for`,
+/// falling back to the config file's `proxy`
+fn proxy_options(
+    proxy: Option<String>,
+    storage_proxy: Option<String>,
+    proxy_user: Option<&str>,
+    no_proxy_for: Vec<String>,
+    file_proxy: Option<ProxyOptions>,
+) -> ProxyOptions {
+    let (username, password) = match proxy_user.map(|user| user.split_once(':')) {
+        Some(Some((username, password))) => {
+            register_secret(password);
+            (Some(username.to_string()), Some(password.to_string()))
+        }
+        Some(None) => (proxy_user.map(str::to_string), None),
+        None => (None, None),
+    };
+    if let Some(password) = file_proxy.as_ref().and_then(|p| p.password.as_deref()) {
+        register_secret(password);
+    }
+    ProxyOptions {
+        url: proxy,
+        storage_url: storage_proxy,
+        username,
+        password,
+        no_proxy: no_proxy_for,
+    }
+    .or(file_proxy.unwrap_or_default())
+}
+
+/// Resolve the API token from `--token`/`NUNU_API_TOKEN`, `--token-file`, the Docker secret,
+/// the config file's `api_token`, its `token_secret` (read with the proxy and TLS settings of
+/// `transport`) and finally its `token_command`
 ///
 /// # Errors
 ///
-/// Returns an error if the platform cannot be inferred from the file extension
-fn infer_platform(file_path: &str) -> Result<BuildPlatform> {
-    let path = Path::new(file_path);
-    let extension = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-
-    match extension.as_str() {
-        "exe" | "msi" => Ok(BuildPlatform::Windows),
-        "dmg" | "pkg" => Ok(BuildPlatform::Macos),
-        "ipa" => Ok(BuildPlatform::IosNative),
-        "apk" => Ok(BuildPlatform::Android),
-        "deb" | "rpm" | "appimage" => Ok(BuildPlatform::Linux),
-        "app" => Err(anyhow::anyhow!(
-            "Cannot infer platform for .app files. Please specify --platform explicitly (macos or ios-simulator)"
-        )),
-        "zip" | "tar" | "gz" | "7z" | "tgz" | "bz2" => Err(anyhow::anyhow!(
-            "Cannot infer platform for archive files (.{}). Please specify --platform explicitly",
-            extension
-        )),
-        _ => Err(anyhow::anyhow!(
-            "Cannot infer platform from file extension '.{}'. Please specify --platform explicitly",
-            extension
-        )),
+/// Returns an error if the token file, the secret store or the token command fails
+fn resolve_token(
+    token: Option<String>,
+    token_file: Option<&Path>,
+    file_config: &FileConfig,
+    transport: &TransportOptions,
+) -> Result<Option<String>> {
+    if let Some(token) = token.or_else(|| std::env::var("NUNU_API_TOKEN").ok()) {
+        return Ok(Some(token));
+    }
+    if let Some(path) = token_file {
+        return Ok(Some(read_token_file(path)?));
+    }
+    if let Some(token) = docker_secret(TOKEN_SECRET).or_else(|| file_config.api_token.clone()) {
+        return Ok(Some(token));
+    }
+    if let Some(secret) = &file_config.token_secret {
+        return Ok(Some(fetch_token(secret, transport)?));
+    }
+    match &file_config.token_command {
+        Some(command) => Ok(Some(run_token_command(command)?)),
+        None => Ok(None),
     }
 }
 
-/// Generate build name from template and filename
-fn generate_build_name(template: &str, file_path: &str, file_count: usize) -> String {
-    let filename = Path::new(file_path)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or(file_path);
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
+struct UploadArgs {
+    /// Files to upload (supports glob patterns like *.apk, app?.exe, build[0-9].ipa).
+    /// Append `=PLATFORM` and/or `::name=NAME` to override per file, e.g. `game.zip=windows`
+    #[arg(
+        value_name = "FILES",
+        num_args = 1..,
+        required_unless_present = "manifest",
+        conflicts_with = "manifest"
+    )]
+    files: Vec<String>,
+
+    /// YAML manifest listing artifacts with their own path, name, platform, description and tags
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    #[command(flatten)]
+    api: ApiArgs,
+
+    /// Skip files matching this glob, checked against the path and file name (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Build name template; supports `{filename}`, `{stem}`, `{platform}`, `{git_sha}`,
+    /// `{branch}`, `{date}` and `{ci_build_number}` (the filename is appended for multiple
+    /// files unless `{filename}` or `{stem}` is used)
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["manifest", "from_session"],
+        conflicts_with = "manifest"
+    )]
+    name: Option<String>,
+
+    /// Target platform (optional, can be inferred from file extension)
+    #[arg(long, value_parser = clap::value_parser!(BuildPlatform))]
+    platform: Option<BuildPlatform>,
+
+    /// CPU architecture: x64, arm64 or universal (detected from PE, Mach-O, ELF and .app
+    /// executables when omitted)
+    #[arg(long, value_parser = clap::value_parser!(Arch))]
+    arch: Option<Arch>,
+
+    /// What is uploaded: build, or an auxiliary artifact (symbols, shader-cache,
+    /// content-pack) that is listed and filtered separately from builds
+    #[arg(long = "type", value_name = "TYPE", default_value = "build", value_parser = clap::value_parser!(ArtifactType))]
+    artifact_type: ArtifactType,
+
+    /// Build description (optional)
+    #[arg(short, long)]
+    description: Option<String>,
+
+    /// Upload timeout, e.g. 90m or 2h (bare numbers are minutes; 1m-24h, default determined by server)
+    #[arg(long, value_name = "DURATION", value_parser = parse_upload_timeout)]
+    upload_timeout: Option<u32>,
+
+    /// Automatically delete old builds if storage limits are exceeded (default: the
+    /// project's setting, or off)
+    #[arg(long)]
+    auto_delete: bool,
+
+    /// Never delete old builds, even if the project enables auto-delete by default
+    #[arg(long, conflicts_with = "auto_delete")]
+    no_auto_delete: bool,
+
+    /// Deletion policy when auto-delete is enabled (`least_recent` or `oldest`; default: the
+    /// project's setting, or `least_recent`)
+    #[arg(long, value_parser = clap::value_parser!(DeletionPolicy))]
+    deletion_policy: Option<DeletionPolicy>,
+
+    /// Force multipart upload
+    #[arg(long)]
+    force_multipart: bool,
+
+    /// Number of parallel uploads/parts (1-32, default: 4, or 2 with --nice)
+    #[arg(long)]
+    parallel: Option<usize>,
+
+    /// Upload files and parts one at a time, in order, with progress advancing once per
+    /// part and no timed progress reports (reproducible output for tests and bug reports)
+    #[arg(long, conflicts_with = "parallel")]
+    deterministic: bool,
+
+    /// Number of parts whose upload URLs are requested in one API call (1-1000); larger
+    /// batches mean fewer requests for big multipart uploads
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_URL_BATCH_SIZE)]
+    url_batch_size: usize,
+
+    /// Log every part of multipart uploads with -vv, instead of every 50th part and
+    /// periodic summaries
+    #[arg(long)]
+    log_every_part: bool,
+
+    /// Upload every part of multipart uploads, instead of letting the server copy parts
+    /// with the same content as ones uploaded from this machine before
+    #[arg(long)]
+    no_part_cache: bool,
+
+    /// Encrypt every part with AES-256-GCM using a data key wrapped by the project's KMS
+    /// key, so the build is never stored or transferred unencrypted (uses multipart upload)
+    #[arg(long)]
+    encrypt: bool,
+
+    /// Upload only this machine's share of the parts, e.g. `1/2` on one agent and `2/2` on
+    /// another reading the same file from shared storage
+    #[arg(long, value_name = "INDEX/COUNT", conflicts_with = "encrypt")]
+    shard: Option<Shard>,
+
+    /// Key all shards of an upload share (default: derived from the file's name, size and
+    /// modification time, which agents on shared storage agree on)
+    #[arg(long, requires = "shard")]
+    shard_key: Option<String>,
+
+    /// How long the server may take to finalize an upload (assembling and processing the
+    /// parts) before giving up, e.g. 1h (bare numbers are seconds)
+    #[arg(long, value_name = "DURATION", default_value = "30m")]
+    finalize_timeout: HumanDuration,
+
+    /// Abort and retry an upload or part that sends no data for this long, e.g. 2m (bare
+    /// numbers are seconds; 0 waits forever) [default: 60s]
+    #[arg(long, value_name = "DURATION")]
+    stall_timeout: Option<HumanDuration>,
+
+    /// Emit TeamCity service messages for upload progress and set the `nunu.build_id` and
+    /// `nunu.build_ids` build parameters
+    #[arg(long)]
+    teamcity: bool,
+
+    /// Lower the CPU and I/O priority of the upload and its default parallelism, so it
+    /// doesn't slow down builds running on the same machine
+    #[arg(long)]
+    nice: bool,
+
+    /// Tags for the build (comma-separated, max 50 chars each)
+    #[arg(long, value_delimiter = ',')]
+    tags: Option<Vec<String>>,
+
+    /// Custom metadata as KEY=VALUE (repeatable), e.g. `--meta unity_version=2022.3.14f1`
+    #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_custom_metadata)]
+    meta: Vec<(String, String)>,
+
+    /// Append tags derived from git, CI and platform (e.g. `branch:main`, `sha:abcdef1`, `ci:github-actions`, `platform:android`)
+    #[arg(long)]
+    auto_tags: bool,
+
+    /// Commit the build was made from, overriding the detected one (for uploads without a
+    /// checkout)
+    #[arg(long, value_name = "SHA")]
+    commit: Option<String>,
+
+    /// Branch the build was made from, overriding the detected one
+    #[arg(long)]
+    branch: Option<String>,
+
+    /// Repository the build was made from, overriding the detected one
+    #[arg(long, value_name = "URL")]
+    repo_url: Option<String>,
+
+    /// Pull/merge request number the build was made for, overriding the detected one
+    #[arg(long, value_name = "NUMBER")]
+    pr: Option<u32>,
+
+    /// Don't attach VCS metadata (commit, author, message, branch, tag) to the build
+    #[arg(long, conflicts_with_all = ["commit", "branch", "repo_url", "pr"])]
+    no_vcs_metadata: bool,
+
+    /// Don't attach CI metadata (system, job, build number and URL) to the build
+    #[arg(long)]
+    no_ci_metadata: bool,
+
+    /// Don't attach the local user name as the build's uploader
+    #[arg(long)]
+    no_uploader: bool,
+
+    /// Preferred storage region for deployments with regional buckets (e.g. eu-west)
+    #[arg(long, value_name = "REGION")]
+    storage_region: Option<String>,
+
+    /// Semantic version of the build (defaults to the release tag in CI, e.g. v1.2.3 → 1.2.3)
+    #[arg(long, value_name = "VERSION", env = "NUNU_BUILD_VERSION")]
+    build_version: Option<String>,
+
+    /// Build number (defaults to the CI build number)
+    #[arg(long, value_name = "NUMBER", env = "NUNU_BUILD_NUMBER")]
+    build_number: Option<String>,
+
+    /// Release notes for the build
+    #[arg(long, value_name = "TEXT", group = "release_notes_source")]
+    release_notes: Option<String>,
+
+    /// Read release notes from a file
+    #[arg(long, value_name = "FILE", group = "release_notes_source")]
+    release_notes_file: Option<PathBuf>,
+
+    /// Generate release notes from `git log` over RANGE (default: since the previous tag)
+    #[arg(
+        long,
+        value_name = "RANGE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        group = "release_notes_source"
+    )]
+    release_notes_from_git: Option<String>,
+
+    /// Check signing and notarization of macOS artifacts (.dmg, .pkg, .app) and warn if not notarized
+    #[arg(long)]
+    check_notarization: bool,
+
+    /// Fail if a macOS artifact is not notarized (implies --check-notarization)
+    #[arg(long)]
+    require_notarized: bool,
+
+    /// Fail if an APK/AAB is unsigned, debug-signed, or (for APKs) lacks a v2/v3 signature
+    #[arg(long)]
+    require_release_signed: bool,
+
+    /// Maximum artifact size per platform, e.g. `android=150MB,ios=200MB` (`ios` covers
+    /// device and simulator builds; overrides the config file's `size_budgets`)
+    #[arg(long, value_delimiter = ',', value_name = "PLATFORM=SIZE")]
+    size_budget: Vec<SizeBudget>,
+
+    /// Whether an artifact over its size budget fails the upload or only warns
+    #[arg(long, value_name = "fail|warn")]
+    size_budget_action: Option<BudgetAction>,
+
+    /// Use HTTP/3 (QUIC) for storage uploads, falling back to HTTP/1.1/2 on failure
+    #[arg(long)]
+    http3: bool,
+
+    /// Use HTTP/2 for storage uploads, multiplexing parts over few connections (by default HTTP/2 is used where the storage offers it)
+    #[arg(long, conflicts_with = "http1")]
+    http2: bool,
+
+    /// Use HTTP/1.1 for storage uploads, e.g. for proxies or firewalls that break HTTP/2
+    #[arg(long)]
+    http1: bool,
+
+    /// How long DNS resolutions are cached across requests, e.g. 5m (bare numbers are seconds; 0 disables caching)
+    #[arg(long, value_name = "DURATION", default_value = "300")]
+    dns_cache_ttl: HumanDuration,
+
+    /// Resolve HOST to IP instead of using DNS, like curl's --resolve (repeatable)
+    #[arg(long, value_name = "HOST:IP", value_parser = parse_resolve_override)]
+    resolve: Vec<(String, SocketAddr)>,
+
+    /// After uploading, write the file, size, SHA-256 and build ID of each upload to PATH
+    /// (`sha256sum` format, or JSON if PATH ends in .json) and attach it to the uploaded builds
+    #[arg(long, value_name = "PATH")]
+    checksum_manifest: Option<PathBuf>,
+
+    /// After uploading, check the uploaded files against the size and SHA-256 the server
+    /// stored; batches of more than 20 files check a sample (see --verify-sample)
+    #[arg(long, conflicts_with = "encrypt")]
+    verify: bool,
+
+    /// Check every uploaded file, however large the batch (implies --verify)
+    #[arg(long, conflicts_with = "encrypt")]
+    verify_all: bool,
+
+    /// Percentage of the files --verify checks in batches of more than 20 files
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = DEFAULT_VERIFY_PERCENT,
+        value_parser = clap::value_parser!(u8).range(0..=100)
+    )]
+    verify_sample: u8,
+
+    /// Files at least this large are always checked by --verify, e.g. 50MB
+    #[arg(long, value_name = "SIZE", default_value = "100MiB")]
+    verify_threshold: ByteSize,
+
+    /// Don't compare each build's size with the previous build on its branch and platform
+    #[arg(long)]
+    no_size_diff: bool,
+
+    /// Lua script whose `on_upload(upload)` function can rename, retag or skip each file
+    /// (requires a build with the `lua` feature)
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+
+    /// Show what would be uploaded (including the request payload) without making any API calls
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Don't upload; record the files' hashes and upload requests in a signed session file
+    /// that `--from-session` uploads from another machine (no network access needed)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["dry_run", "from_session"])]
+    prepare_only: Option<PathBuf>,
+
+    /// Upload the given files as prepared by `--prepare-only`, after checking the session's
+    /// signature and that the files match it
+    #[arg(long, value_name = "PATH")]
+    from_session: Option<PathBuf>,
+
+    /// Shared secret that signs and verifies upload sessions
+    #[arg(long, env = "NUNU_SESSION_KEY", hide_env_values = true)]
+    session_key: Option<String>,
+}
 
-    if file_count == 1 {
-        template.to_string()
-    } else {
-        format!("{template} - {filename}")
+/// Parse `--upload-timeout` into whole minutes, as expected by the API
+///
+/// # Errors
+///
+/// Returns an error if the value is not a duration between one minute and 24 hours
+fn parse_upload_timeout(value: &str) -> Result<u32> {
+    let duration = units::parse_duration(value, Duration::from_mins(1))?;
+    let minutes = duration.as_secs().div_ceil(60);
+    if !(1..=1440).contains(&minutes) {
+        return Err(anyhow::anyhow!(
+            "upload timeout must be between 1m and 24h, got {value}"
+        ));
     }
+    Ok(u32::try_from(minutes)?)
 }
 
-/// Expand glob patterns to file paths
+/// Read version and signing details from mobile and Windows packages and attach them to their targets
 ///
-/// This function handles both regular file paths and glob patterns.
-/// If a pattern doesn't match any files, it's treated as a literal path
-/// (which will fail later with a clear error).
+/// Also sets each target's architecture to `arch`, or to the one detected from the binary.
 ///
 /// # Errors
 ///
-/// Returns an error if glob pattern parsing fails
-fn expand_globs(patterns: &[String]) -> Result<Vec<String>> {
-    let mut expanded_files = Vec::new();
-    let mut seen = std::collections::HashSet::new();
-
-    for pattern in patterns {
-        // Check if pattern contains glob characters
-        if pattern.contains('*') || pattern.contains('?') || pattern.contains('[') {
-            debug!("Expanding glob pattern: {pattern}");
-
-            match glob(pattern) {
-                Ok(paths) => {
-                    let mut matched_any = false;
-                    for entry in paths {
-                        match entry {
-                            Ok(path) => {
-                                matched_any = true;
-                                let path_str = path.to_string_lossy().to_string();
-
-                                // Only add files (skip directories) and avoid duplicates
-                                if path.is_file() && seen.insert(path_str.clone()) {
-                                    expanded_files.push(path_str);
-                                }
-                            }
-                            Err(e) => {
-                                warn!("Error reading glob entry: {e}");
-                            }
-                        }
-                    }
-
-                    if matched_any {
-                        debug!(
-                            "Pattern '{pattern}' matched {} file(s)",
-                            expanded_files.len() - (expanded_files.len() - seen.len())
-                        );
-                    } else {
-                        warn!("Pattern '{pattern}' did not match any files");
-                    }
-                }
-                Err(e) => {
-                    return Err(anyhow::anyhow!("Invalid glob pattern '{pattern}': {e}"));
+/// Returns an error if an AAB is malformed, or if `require_release_signed` is set and an
+/// Android package is not release signed
+fn inspect_artifacts(
+    targets: &mut [UploadTarget],
+    require_release_signed: bool,
+    arch: Option<Arch>,
+) -> Result<()> {
+    for target in targets {
+        let path = Path::new(&target.path);
+        target.arch = arch.or_else(|| {
+            detect_arch(path).unwrap_or_else(|e| {
+                debug!("Could not detect the architecture of {}: {e}", target.path);
+                None
+            })
+        });
+        if path.is_dir() {
+            continue;
+        }
+        if is_android_package(path) {
+            inspect_android(target, require_release_signed)?;
+        } else if is_ipa(path) {
+            match inspect_ipa(path) {
+                Ok(Some(bundle)) => {
+                    debug!(
+                        "{}: bundle {}, version {} ({})",
+                        target.path,
+                        bundle.bundle_id.as_deref().unwrap_or("unknown"),
+                        bundle.short_version.as_deref().unwrap_or("unknown"),
+                        bundle.bundle_version.as_deref().unwrap_or("unknown")
+                    );
+                    target.artifact.get_or_insert_default().ios_bundle = Some(bundle);
                 }
+                Ok(None) => debug!("No app bundle found in {}", target.path),
+                Err(e) => warn!("Could not read the Info.plist of {}: {e}", target.path),
             }
-        } else {
-            // Not a glob pattern, use as-is (deduplicate)
-            if seen.insert(pattern.clone()) {
-                expanded_files.push(pattern.clone());
+        } else if is_windows_binary(path) {
+            match inspect_windows_version(path) {
+                Ok(Some(version)) => {
+                    debug!(
+                        "{}: {} version {}",
+                        target.path,
+                        version.product_name.as_deref().unwrap_or("unknown product"),
+                        version
+                            .product_version
+                            .as_deref()
+                            .or(version.file_version.as_deref())
+                            .unwrap_or("unknown")
+                    );
+                    target.artifact.get_or_insert_default().windows_version = Some(version);
+                }
+                Ok(None) => debug!("No version information in {}", target.path),
+                Err(e) => warn!("Could not read the version of {}: {e}", target.path),
             }
         }
     }
+    Ok(())
+}
 
-    if expanded_files.is_empty() {
-        Err(anyhow::anyhow!("No files matched the provided patterns"))
-    } else {
-        Ok(expanded_files)
+/// Measure every target whose platform has a size budget and record the result
+///
+/// Targets whose platform cannot be inferred yet are skipped; the upload reports that
+/// error later.
+///
+/// # Errors
+///
+/// Returns an error if a target cannot be measured, or if `action` is `fail` and any
+/// target exceeds its budget
+fn check_size_budgets(
+    targets: &mut [UploadTarget],
+    budgets: &BTreeMap<String, u64>,
+    action: BudgetAction,
+    platform: Option<&BuildPlatform>,
+) -> Result<()> {
+    let mut exceeded = Vec::new();
+    for target in targets {
+        let Some(file_platform) = target
+            .platform
+            .clone()
+            .or_else(|| platform.cloned())
+            .or_else(|| infer_platform(&target.path).ok())
+        else {
+            continue;
+        };
+        let Some(budget) = budget_for(budgets, &file_platform) else {
+            continue;
+        };
+
+        let check = check_size_budget(artifact_size(Path::new(&target.path))?, budget);
+        debug!(
+            "{}: {} of {} {} budget",
+            target.path,
+            format_size(check.size),
+            format_size(check.budget),
+            file_platform.as_str()
+        );
+        if check.exceeded {
+            exceeded.push(format!(
+                "{} is {} ({} over the {} {} budget)",
+                target.path,
+                format_size(check.size),
+                format_size(check.size - check.budget),
+                format_size(check.budget),
+                file_platform.as_str()
+            ));
+        }
+        target.size_budget = Some(check);
     }
-}
 
-#[allow(clippy::too_many_lines)]
-#[tokio::main]
-async fn main() -> Result<()> {
-    if let Err(e) = dotenvy::dotenv() {
-        if !e.to_string().contains("not found") {
-            debug!("Error loading .env file: {e}");
+    if exceeded.is_empty() {
+        return Ok(());
+    }
+    match action {
+        BudgetAction::Warn => {
+            for message in &exceeded {
+                warn!("Size budget exceeded: {message}");
+            }
+            Ok(())
         }
-    } else {
-        debug!("Loaded environment from .env file");
+        BudgetAction::Fail => Err(anyhow::anyhow!(
+            "Size budget exceeded:\n  {}\n(use --size-budget-action warn to upload anyway)",
+            exceeded.join("\n  ")
+        )),
     }
+}
 
-    let cli = Cli::parse();
+/// Read the manifest and signing details of an APK/AAB and attach them to its target
+///
+/// # Errors
+///
+/// Returns an error if an AAB is malformed, or if `require_release_signed` is set and the
+/// package is not release signed
+fn inspect_android(target: &mut UploadTarget, require_release_signed: bool) -> Result<()> {
+    let path = Path::new(&target.path);
+    let bundle = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("aab"));
+    if bundle {
+        validate_app_bundle(path)?;
+    }
 
-    // Initialize logger based on verbose flag
-    // 0: warn/error only (clean 2-line display)
-    // 1: info level (general progress)
-    // 2: debug level (detailed debugging)
-    // 3+: trace level (maximum detail)
-    if cli.verbose > 0 {
-        let log_level = match cli.verbose {
-            1 => "info",
-            2 => "debug",
-            _ => "trace",
-        };
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level))
-            .format(|buf, record| writeln!(buf, "[{}] {}", record.level(), record.args()))
-            .init();
-    } else {
-        // In non-verbose mode, only show warnings and errors
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    match inspect_android_manifest(path) {
+        Ok(Some(manifest)) => {
+            debug!(
+                "{}: package {}, version {} ({})",
+                target.path,
+                manifest.package.as_deref().unwrap_or("unknown"),
+                manifest.version_name.as_deref().unwrap_or("unknown"),
+                manifest
+                    .version_code
+                    .map_or_else(|| "unknown".to_string(), |c| c.to_string())
+            );
+            target.artifact.get_or_insert_default().android_manifest = Some(manifest);
+        }
+        Ok(None) => debug!("No readable manifest in {}", target.path),
+        Err(e) => warn!("Could not read the manifest of {}: {e}", target.path),
     }
 
-    let result: Result<String> = match cli.command {
-        Commands::Upload {
-            files,
-            token,
-            project_id,
-            api_url,
-            name,
-            platform,
-            description,
-            upload_timeout,
-            auto_delete,
-            deletion_policy,
-            force_multipart,
-            parallel,
-            tags,
-        } => {
-            if files.is_empty() {
-                return Err(anyhow::anyhow!("No files specified for upload"));
-            }
+    let signing = match inspect_android_signing(path) {
+        Ok(signing) => signing,
+        Err(e) if !require_release_signed => {
+            warn!("Could not read the signature of {}: {e}", target.path);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(problem) = signing.release_problem(bundle) {
+        if require_release_signed {
+            return Err(anyhow::anyhow!(
+                "{} is not release signed: {problem} (remove --require-release-signed to upload anyway)",
+                target.path
+            ));
+        }
+        if signing.debug_signed {
+            warn!("{} is signed with the Android debug key", target.path);
+        }
+    }
+    debug!(
+        "{}: signature schemes {}, certificate SHA-256 {}",
+        target.path,
+        signing.schemes.join(", "),
+        signing.certificate_sha256.as_deref().unwrap_or("unknown")
+    );
+
+    target.artifact.get_or_insert_default().android_signing = Some(signing);
+    Ok(())
+}
 
-            // Expand glob patterns to actual file paths
-            let files = expand_globs(&files)?;
+/// Check that macOS artifacts are notarized before uploading them
+///
+/// # Errors
+///
+/// Returns an error if `require` is set and an artifact is not notarized (or cannot be checked)
+fn verify_notarization(
+    targets: &[UploadTarget],
+    platform: Option<&BuildPlatform>,
+    require: bool,
+) -> Result<()> {
+    for target in targets {
+        let path = Path::new(&target.path);
+        let target_platform = target
+            .platform
+            .clone()
+            .or_else(|| platform.cloned())
+            .or_else(|| infer_platform(&target.path).ok());
+        if !matches!(target_platform, Some(BuildPlatform::Macos)) || !is_notarizable(path) {
+            continue;
+        }
 
-            if cli.verbose > 0 {
-                info!("Found {} file(s) to upload", files.len());
-            }
+        let status = check_notarization(path);
+        if status.is_notarized() {
+            info!("{}: notarized", target.path);
+        } else if require {
+            return Err(anyhow::anyhow!(
+                "{} is not notarized: {status}. External testers won't be able to open it (remove --require-notarized to upload anyway)",
+                target.path
+            ));
+        } else {
+            warn!(
+                "{} is not notarized: {status}. External testers may not be able to open it",
+                target.path
+            );
+        }
+    }
+    Ok(())
+}
 
-            // Validate parallel value
-            if !(1..=32).contains(&parallel) {
-                return Err(anyhow::anyhow!(
-                    "Parallel value must be between 1 and 32, got {parallel}"
-                ));
-            }
+/// Print what would be uploaded for each file without contacting the API
+///
+/// # Errors
+///
+/// Returns an error if a platform cannot be inferred or a file cannot be read
+fn print_dry_run(
+    targets: &[UploadTarget],
+    config: &Config,
+    platform: Option<&BuildPlatform>,
+    base_options: &UploadOptions,
+    auto_tags: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    if !format.is_json() {
+        outln!(
+            "{}",
+            t!(
+                "dry_run.header",
+                count = targets.len(),
+                url = config.base_upload_url()
+            )
+        );
+        outln!("Token: {UNRESOLVED_TOKEN}");
+    }
+    let mut uploads = Vec::new();
+
+    for target in targets {
+        let file_path = &target.path;
+        let PreparedRequest {
+            filename,
+            file_size,
+            directory,
+            multipart,
+            options,
+            request,
+        } = prepare_request(target, platform, base_options, auto_tags)?;
+
+        if format.is_json() {
+            uploads.push(serde_json::json!({
+                "file": file_path,
+                "size": file_size,
+                "multipart": multipart,
+                "request": request,
+            }));
+            continue;
+        }
 
-            // Validate tags (each tag must be 1-50 characters)
-            if let Some(ref tag_list) = tags {
-                for tag in tag_list {
-                    if tag.is_empty() {
-                        return Err(anyhow::anyhow!("Tags cannot be empty"));
-                    }
-                    if tag.len() > 50 {
-                        return Err(anyhow::anyhow!(
-                            "Tag '{}' exceeds maximum length of 50 characters (length: {})",
-                            tag,
-                            tag.len()
-                        ));
-                    }
-                }
+        outln!();
+        outln!("{file_path}");
+        outln!("  Name:     {}", options.name);
+        outln!("  Platform: {}", options.platform);
+        if let Some(arch) = options.arch {
+            outln!("  Arch:     {}", arch.as_str());
+        }
+        outln!("  Size:     {} ({file_size} bytes)", format_size(file_size));
+        if directory {
+            outln!("  Package:  directory streamed as {filename}");
+        }
+        outln!(
+            "  Method:   {}",
+            if multipart {
+                "multipart"
+            } else {
+                "single-part"
             }
+        );
+        outln!("  Request payload:");
+        for line in serde_json::to_string_pretty(&request)?.lines() {
+            outln!("    {line}");
+        }
+    }
 
-            // Load config file with priority:
-            // 1. CLI args (highest)
-            // 2. Environment variables
-            // 3. Config file (--config or default locations)
-            let file_config = FileConfig::load_with_fallback(cli.config.as_ref())?;
-
-            // Resolve final values with priority
-            let final_token = token
-                .or_else(|| std::env::var("NUNU_API_TOKEN").ok())
-                .or(file_config.api_token)
-                .ok_or_else(|| anyhow::anyhow!("API token not provided (use --token, NUNU_API_TOKEN env var, or config file)"))?;
-
-            let final_project_id = project_id
-                .or_else(|| std::env::var("NUNU_PROJECT_ID").ok())
-                .or(file_config.project_id)
-                .ok_or_else(|| anyhow::anyhow!("Project ID not provided (use --project-id, NUNU_PROJECT_ID env var, or config file)"))?;
-
-            let final_api_url = api_url
-                .or_else(|| std::env::var("NUNU_API_URL").ok())
-                .or(file_config.api_url)
-                .unwrap_or_else(|| "https://nunu.ai/api".to_string());
+    if format.is_json() {
+        let report = serde_json::json!({
+            "dry_run": true,
+            "endpoint": format!("{}/upload", config.base_upload_url()),
+            "token": UNRESOLVED_TOKEN,
+            "uploads": uploads,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    Ok(())
+}
 
-            let config = Config::new(final_token, final_project_id, final_api_url)?;
+/// Run the `upload` command
+async fn run_upload(
+    verbose: u8,
+    config_path: Option<&PathBuf>,
+    format: OutputFormat,
+    mut args: UploadArgs,
+) -> Result<String> {
+    if args.teamcity && format.is_json() {
+        return Err(anyhow::anyhow!(
+            "--teamcity cannot be combined with --format json"
+        ));
+    }
 
-            let file_count = files.len();
+    let manifest = args
+        .manifest
+        .take()
+        .map(|path| Manifest::load(&path))
+        .transpose()?;
+    let mut targets = select_upload_targets(&mut args, manifest.as_ref())?;
+    if verbose > 0 {
+        info!("Found {} file(s) to upload", targets.len());
+    }
 
-            // Shared state for tracking active uploads
-            let active_uploads: ActiveUploads = Arc::new(RwLock::new(HashMap::new()));
+    if args.nice
+        && let Err(e) = lower_process_priority()
+    {
+        warn!("Could not lower the process priority: {e}");
+    }
+    let parallel = upload_parallelism(&args)?;
+    if let Some(ref tag_list) = args.tags {
+        validate_tags(tag_list)?;
+    }
 
-            // Create MultiProgress for coordinated progress display
-            let multi_progress = MultiProgress::new();
+    let linked = remove_linked_duplicates(&mut targets);
+    inspect_artifacts(&mut targets, args.require_release_signed, args.arch)?;
+    if args.check_notarization || args.require_notarized {
+        verify_notarization(&targets, args.platform.as_ref(), args.require_notarized)?;
+    }
 
-            // Create a status line for non-verbose mode
-            let status_bar = if cli.verbose == 0 {
-                let bar = multi_progress.insert(0, ProgressBar::new(0));
-                bar.set_style(
-                    ProgressStyle::default_bar()
-                        .template("{msg}")
-                        .unwrap_or_else(|_| ProgressStyle::default_bar()),
-                );
-                Some(bar)
-            } else {
-                None
-            };
+    // Load config file with priority:
+    // 1. CLI args (highest)
+    // 2. Environment variables
+    // 3. Config file (--config or default locations)
+    let file_config = FileConfig::load_with_fallback(config_path)?;
+    let api = resolve_upload_api(&mut args, &file_config);
 
-            // Helper to log messages
-            let log_message = |msg: String| {
-                if let Some(ref bar) = status_bar {
-                    bar.set_message(msg);
-                } else {
-                    info!("{msg}");
-                }
-            };
+    if let Some(ref key) = args.session_key {
+        register_secret(key);
+    }
+    if let Some(ref path) = args.from_session {
+        let key = session_key(args.session_key.as_deref())?;
+        attach_prepared_session(path, key, api.project_id.as_deref(), &mut targets)?;
+    }
 
-            log_message(format!("Using API URL: {}", config.api_url));
-            log_message(format!("Parallel uploads/parts: {parallel}"));
-
-            // Collect build metadata
-            debug!("Collecting build metadata (VCS and CI/CD)");
-            let vcs = collect_git_metadata();
-            let ci = collect_ci_metadata();
-            let upload_info = Some(UploadInfo {
-                method: "cli".to_string(),
-                cli_version: Some(env!("CARGO_PKG_VERSION").to_string()),
-                uploader: std::env::var("USER")
-                    .ok()
-                    .or_else(|| std::env::var("USERNAME").ok()),
-            });
+    if args.http3 && !cfg!(feature = "http3") {
+        warn!("This build of nunu-cli does not include HTTP/3 support; using HTTP/1.1/2 instead");
+    }
 
-            let details = if vcs.is_some() || ci.is_some() || upload_info.is_some() {
-                Some(BuildDetails {
-                    vcs,
-                    ci,
-                    upload: upload_info,
-                })
-            } else {
-                None
-            };
+    let details = collect_build_details(&mut args, &file_config, manifest)?;
+    let explicit_tags = args.tags.is_some();
+    if args.auto_tags {
+        apply_auto_tags(&details, &mut args.tags, &mut targets);
+    }
+    let mut base_options = base_upload_options(&mut args, &file_config, Some(details), parallel);
 
-            if let Some(ref d) = details {
-                if d.vcs.is_some() {
-                    debug!("Collected VCS metadata: Git");
-                }
-                if d.ci.is_some() {
-                    debug!("Collected CI/CD metadata");
-                }
-            }
+    if let Some(script) = &args.script {
+        targets = apply_upload_script(script, targets, args.platform.as_ref(), &base_options)?;
+        if targets.is_empty() {
+            return Err(anyhow::anyhow!("The upload script skipped every file"));
+        }
+    }
+    apply_size_budgets(&mut args, &file_config, &mut targets)?;
+
+    if let Some(ref path) = args.prepare_only {
+        let session = prepare_session(
+            api.project_id,
+            &targets,
+            args.platform.as_ref(),
+            &base_options,
+            args.auto_tags,
+        )?;
+        session.save(path, session_key(args.session_key.as_deref())?)?;
+        print_prepared_session(path, &session, format)?;
+        return Ok(String::new());
+    }
 
-            // Set up signal handlers for graceful shutdown
-            #[cfg(unix)]
-            let mut sigterm = {
-                use tokio::signal::unix::{SignalKind, signal};
-                signal(SignalKind::terminate()).ok()
-            };
+    if args.dry_run {
+        print_upload_dry_run(api, &file_config, &targets, &args, &base_options, format)?;
+        return Ok(String::new());
+    }
 
-            let ctrl_c = tokio::signal::ctrl_c();
-
-            // Process files in parallel using streams
-            let verbose = cli.verbose;
-            let upload_task = async {
-                stream::iter(files)
-                    .map(|file_path| {
-                        let config = config.clone();
-                        let name = name.clone();
-                        let platform = platform.clone();
-                        let description = description.clone();
-                        let deletion_policy = deletion_policy.clone();
-                        let active_uploads = active_uploads.clone();
-                        let multi_progress = multi_progress.clone();
-                        let status_bar = status_bar.clone();
-                        let details = details.clone();
-                        let tags = tags.clone();
-
-                        async move {
-                            // Helper to log messages
-                            let log_msg = |msg: String| {
-                                if verbose == 0 {
-                                    if let Some(ref bar) = status_bar {
-                                        bar.set_message(msg);
-                                    }
-                                } else {
-                                    info!("{msg}");
-                                }
-                            };
-                            // Determine platform (explicit or inferred)
-                            let file_platform = match &platform {
-                                Some(p) => p.clone(),
-                                None => match infer_platform(&file_path) {
-                                    Ok(p) => p,
-                                    Err(e) => {
-                                        return (file_path.clone(), Err(e));
-                                    }
-                                },
-                            };
-
-                            // Generate build name
-                            let build_name = generate_build_name(&name, &file_path, file_count);
-
-                            // Get file size for progress bar
-                            let file_size = match tokio::fs::metadata(&file_path).await {
-                                Ok(metadata) => metadata.len(),
-                                Err(e) => {
-                                    return (file_path.clone(), Err(anyhow::anyhow!("Failed to read file metadata: {e}")));
-                                }
-                            };
-
-                            // Create progress bar for this upload
-                            let pb = multi_progress.add(ProgressBar::new(file_size));
-                            pb.set_style(
-                                ProgressStyle::default_bar()
-                                    .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta}) {msg}")
-                                    .unwrap_or_else(|_| ProgressStyle::default_bar())
-                                    .progress_chars("#>-"),
-                            );
-                            pb.set_message(Path::new(&file_path).file_name().and_then(|n| n.to_str()).unwrap_or(&file_path).to_string());
-
-                            log_msg(format!(
-                                "Uploading {} as {} (platform: {})",
-                                file_path,
-                                build_name,
-                                file_platform.as_str()
-                            ));
-
-                            // Create callback to track upload metadata
-                            let file_path_clone = file_path.clone();
-                            let active_uploads_clone = active_uploads.clone();
-                            let callback = std::sync::Arc::new(
-                                move |build_id: String,
-                                      upload_id: Option<String>,
-                                      object_key: String| {
-                                    let file_path = file_path_clone.clone();
-                                    let active_uploads = active_uploads_clone.clone();
-                                    tokio::spawn(async move {
-                                        let mut uploads = active_uploads.write().await;
-                                        uploads.insert(
-                                            file_path,
-                                            UploadMetadata {
-                                                build_id,
-                                                upload_id,
-                                                object_key,
-                                            },
-                                        );
-                                    });
-                                },
-                            );
-
-                            let options = UploadOptions {
-                                name: build_name,
-                                platform: file_platform.as_str().to_string(),
-                                description: description.clone(),
-                                upload_timeout,
-                                auto_delete,
-                                deletion_policy: Some(deletion_policy.as_str().to_string()),
-                                force_multipart,
-                                parallel,
-                                on_upload_initiated: Some(callback),
-                                progress_bar: Some(pb.clone()),
-                                details: details.clone(),
-                                tags: tags.clone(),
-                            };
-
-                            let result = upload_file(&config, &file_path, options)
-                                .await
-                                .map_err(|e| anyhow::anyhow!("{e}"));
-
-                            // Finish progress bar
-                            if result.is_ok() {
-                                pb.finish_with_message("✓ Complete");
-                            } else {
-                                pb.finish_with_message("✗ Failed");
-                            }
-
-                            // Remove from active uploads on completion (success or failure)
-                            {
-                                let mut uploads = active_uploads.write().await;
-                                uploads.remove(&file_path);
-                            }
-
-                            (file_path, result)
-                        }
-                    })
-                    .buffer_unordered(parallel)
-                    .collect::<Vec<(String, Result<String>)>>()
-                    .await
-            };
+    let config = api.into_config(file_config)?;
+    // Client for project settings, aborts after a termination signal and the checks that
+    // follow the uploads
+    let client = Client::try_new(config.clone())?;
+
+    // Project defaults set centrally by admins, for the options not given explicitly
+    match client.get_project_settings().await {
+        Ok(Some(settings)) => apply_project_settings(
+            &mut base_options,
+            settings,
+            args.auto_delete || args.no_auto_delete,
+            args.deletion_policy.is_some(),
+            explicit_tags,
+        ),
+        Ok(None) => debug!("The server provides no project settings"),
+        Err(e) => warn!("Could not fetch the project settings, using none of its defaults: {e}"),
+    }
 
-            // Wait for either upload completion or termination signal
-            #[cfg(unix)]
-            let results = {
-                tokio::select! {
-                    results = upload_task => results,
-                    _ = ctrl_c => {
-                        eprintln!("\n🛑 Received interrupt signal (SIGINT/Ctrl+C).");
-
-                        // Try to abort all active uploads
-                        let uploads = active_uploads.read().await;
-                        if !uploads.is_empty() {
-                            eprintln!("⏳ Attempting to abort {} active upload(s)...", uploads.len());
-                            let client = Client::new(config.clone());
-
-                            for (file_path, metadata) in uploads.iter() {
-                                debug!("Aborting upload for {file_path}: build_id={}", metadata.build_id);
-                                if let Err(e) = client
-                                    .abort_upload(
-                                        &metadata.build_id,
-                                        metadata.upload_id.as_deref(),
-                                        Some(&metadata.object_key),
-                                    )
-                                    .await
-                                {
-                                    warn!("Failed to abort upload for {file_path}: {e}");
-                                } else {
-                                    debug!("Successfully aborted upload for {file_path}");
-                                }
-                            }
-                            eprintln!("✓ Abort requests sent.");
-                        }
-
-                        eprintln!("⚠️  Upload cancelled.");
-                        std::process::exit(130); // Standard exit code for SIGINT
-                    }
-                    _ = async {
-                        match sigterm.as_mut() {
-                            Some(sig) => sig.recv().await,
-                            None => std::future::pending().await,
-                        }
-                    }, if sigterm.is_some() => {
-                        eprintln!("\n🛑 Received termination signal (SIGTERM).");
-
-                        // Try to abort all active uploads
-                        let uploads = active_uploads.read().await;
-                        if !uploads.is_empty() {
-                            eprintln!("⏳ Attempting to abort {} active upload(s)...", uploads.len());
-                            let client = Client::new(config.clone());
-
-                            for (file_path, metadata) in uploads.iter() {
-                                debug!("Aborting upload for {file_path}: build_id={}", metadata.build_id);
-                                if let Err(e) = client
-                                    .abort_upload(
-                                        &metadata.build_id,
-                                        metadata.upload_id.as_deref(),
-                                        Some(&metadata.object_key),
-                                    )
-                                    .await
-                                {
-                                    warn!("Failed to abort upload for {file_path}: {e}");
-                                } else {
-                                    debug!("Successfully aborted upload for {file_path}");
-                                }
-                            }
-                            eprintln!("✓ Abort requests sent.");
-                        }
-
-                        eprintln!("⚠️  Upload terminated.");
-                        std::process::exit(143); // Standard exit code for SIGTERM (128 + 15)
-                    }
-                }
-            };
+    let batch = UploadBatch {
+        config: &config,
+        platform: args.platform.as_ref(),
+        base_options: &base_options,
+        auto_tags: args.auto_tags,
+        teamcity: args.teamcity,
+        verbose,
+        parallel,
+        format,
+    };
+    let results = batch.run(targets, &client).await;
+    report_upload_results(results, linked, &client, &args, &base_options, format).await
+}
 
-            #[cfg(not(unix))]
-            let results = {
-                tokio::select! {
-                    results = upload_task => results,
-                    _ = ctrl_c => {
-                        eprintln!("\n🛑 Received interrupt signal (Ctrl+C).");
-
-                        // Try to abort all active uploads
-                        let uploads = active_uploads.read().await;
-                        if !uploads.is_empty() {
-                            eprintln!("⏳ Attempting to abort {} active upload(s)...", uploads.len());
-                            let client = Client::new(config.clone());
-
-                            for (file_path, metadata) in uploads.iter() {
-                                debug!("Aborting upload for {file_path}: build_id={}", metadata.build_id);
-                                if let Err(e) = client
-                                    .abort_upload(
-                                        &metadata.build_id,
-                                        metadata.upload_id.as_deref(),
-                                        Some(&metadata.object_key),
-                                    )
-                                    .await
-                                {
-                                    warn!("Failed to abort upload for {file_path}: {e}");
-                                } else {
-                                    debug!("Successfully aborted upload for {file_path}");
-                                }
-                            }
-                            eprintln!("✓ Abort requests sent.");
-                        }
-
-                        eprintln!("⚠️  Upload cancelled.");
-                        std::process::exit(130); // Standard exit code for SIGINT
-                    }
+/// API config of the upload with the storage transport options of the upload flags
+fn resolve_upload_api(args: &mut UploadArgs, file_config: &FileConfig) -> ResolvedApi {
+    let mut api = std::mem::take(&mut args.api).resolve(file_config);
+    api.transport = TransportOptions {
+        http3: args.http3,
+        storage_http: if args.http2 {
+            StorageHttpVersion::Http2
+        } else if args.http1 {
+            StorageHttpVersion::Http1
+        } else {
+            StorageHttpVersion::Auto
+        },
+        dns_cache_ttl: (!args.dns_cache_ttl.0.is_zero()).then_some(args.dns_cache_ttl.0),
+        resolve: std::mem::take(&mut args.resolve),
+        ..api.transport
+    };
+    api
+}
+
+/// Upload `targets` as prepared in the `--from-session` file at `path`
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be loaded or verified, was prepared for another
+/// project, or does not match the files
+fn attach_prepared_session(
+    path: &Path,
+    key: &str,
+    project_id: Option<&str>,
+    targets: &mut [UploadTarget],
+) -> Result<()> {
+    let session = UploadSession::load(path, key)?;
+    if let (Some(prepared), Some(current)) = (&session.project_id, project_id)
+        && prepared != current
+    {
+        return Err(anyhow::anyhow!(
+            "The session was prepared for project {prepared}, not {current}"
+        ));
+    }
+    attach_session(targets, &session)?;
+    info!(
+        "Uploading {} file(s) as prepared on {}",
+        targets.len(),
+        session.created_at
+    );
+    Ok(())
+}
+
+/// Upload targets from the manifest, or from the file arguments and `--name`
+///
+/// # Errors
+///
+/// Returns an error if no files are given or matched, or `--name` is missing
+fn select_upload_targets(
+    args: &mut UploadArgs,
+    manifest: Option<&Manifest>,
+) -> Result<Vec<UploadTarget>> {
+    let excludes = compile_excludes(&args.exclude)?;
+    let ignore = IgnoreRules::load_from_current_dir()?;
+
+    if let Some(manifest) = manifest {
+        return Ok(manifest_targets(manifest, &excludes, ignore.as_ref())?);
+    }
+    if args.files.is_empty() {
+        return Err(anyhow::anyhow!("No files specified for upload"));
+    }
+    // Sessions carry the build names chosen when they were prepared
+    let name = match args.name.take() {
+        Some(name) => name,
+        None if args.from_session.is_some() => String::new(),
+        None => return Err(anyhow::anyhow!("--name is required")),
+    };
+    Ok(file_spec_targets(
+        &args.files,
+        &name,
+        &excludes,
+        ignore.as_ref(),
+    )?)
+}
+
+/// Uploads and parts in flight at once, from `--parallel`, `--nice` and `--deterministic`
+///
+/// # Errors
+///
+/// Returns an error if `--parallel` or `--url-batch-size` is out of range
+fn upload_parallelism(args: &UploadArgs) -> Result<usize> {
+    let parallel = if args.deterministic {
+        1
+    } else {
+        args.parallel.unwrap_or(if args.nice {
+            NICE_PARALLEL
+        } else {
+            DEFAULT_PARALLEL
+        })
+    };
+    if !(1..=32).contains(&parallel) {
+        return Err(anyhow::anyhow!(
+            "Parallel value must be between 1 and 32, got {parallel}"
+        ));
+    }
+    if !(1..=1000).contains(&args.url_batch_size) {
+        return Err(anyhow::anyhow!(
+            "URL batch size must be between 1 and 1000, got {}",
+            args.url_batch_size
+        ));
+    }
+    Ok(parallel)
+}
+
+/// The `--session-key` that signs and verifies upload sessions
+///
+/// # Errors
+///
+/// Returns an error if no session key is given
+fn session_key(key: Option<&str>) -> Result<&str> {
+    key.ok_or_else(|| {
+        anyhow::anyhow!(
+            "A session key is required to sign and verify upload sessions (use --session-key or NUNU_SESSION_KEY)"
+        )
+    })
+}
+
+/// Build metadata from VCS, CI, the uploader and `--meta`, minus the categories turned off
+/// by flags or the config file
+///
+/// Also fills in the release notes and falls back to the release tag and CI build number
+/// for the build version and number when they are not given explicitly.
+///
+/// # Errors
+///
+/// Returns an error if the release notes cannot be read
+fn collect_build_details(
+    args: &mut UploadArgs,
+    file_config: &FileConfig,
+    manifest: Option<Manifest>,
+) -> Result<BuildDetails> {
+    if let Some(path) = &args.release_notes_file {
+        args.release_notes = Some(std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read release notes {}: {e}", path.display())
+        })?);
+    } else if let Some(range) = &args.release_notes_from_git {
+        let range = Some(range.as_str()).filter(|r| !r.is_empty());
+        args.release_notes = Some(release_notes_from_git(range)?);
+    }
+
+    let collect_vcs = !args.no_vcs_metadata && file_config.collect_vcs_metadata != Some(false);
+    let collect_ci = !args.no_ci_metadata && file_config.collect_ci_metadata != Some(false);
+    let collect_uploader = !args.no_uploader && file_config.collect_uploader != Some(false);
+    debug!(
+        "Collecting build metadata (VCS: {collect_vcs}, CI/CD: {collect_ci}, uploader: {collect_uploader})"
+    );
+    let vcs = if collect_vcs {
+        let vcs_overrides = VcsOverrides {
+            commit: args.commit.take(),
+            branch: args.branch.take(),
+            repository_url: args.repo_url.take(),
+            pr_number: args.pr,
+            ..VcsOverrides::default()
+        };
+        collect_vcs_metadata_with(vcs_overrides.or(manifest.map(|m| m.vcs).unwrap_or_default()))
+    } else {
+        None
+    };
+    let ci = if collect_ci {
+        collect_ci_metadata()
+    } else {
+        None
+    };
+    let collection_warnings = take_collection_warnings();
+
+    // Fall back to the release tag and CI build number when not given explicitly
+    if args.build_version.is_none() {
+        args.build_version = vcs
+            .as_ref()
+            .and_then(|v| v.tag.as_deref())
+            .and_then(version_from_tag);
+    }
+    if args.build_number.is_none() {
+        args.build_number = ci.as_ref().and_then(|c| c.build_number.clone());
+    }
+    if let Some(ref version) = args.build_version {
+        debug!("Build version: {version}");
+    }
+    if let Some(ref number) = args.build_number {
+        debug!("Build number: {number}");
+    }
+    if vcs.is_some() {
+        debug!("Collected VCS metadata: Git");
+    }
+    if ci.is_some() {
+        debug!("Collected CI/CD metadata");
+    }
+
+    let upload_info = UploadInfo {
+        method: "cli".to_string(),
+        cli_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        uploader: collect_uploader
+            .then(|| {
+                std::env::var("USER")
+                    .ok()
+                    .or_else(|| std::env::var("USERNAME").ok())
+            })
+            .flatten(),
+    };
+
+    // Config file metadata, overridden by --meta
+    let mut custom = file_config.metadata.clone();
+    custom.extend(std::mem::take(&mut args.meta));
+
+    Ok(BuildDetails {
+        vcs,
+        ci,
+        upload: Some(upload_info),
+        custom,
+        artifact: None,
+        size_budget: None,
+        collection_warnings,
+    })
+}
+
+/// Add the tags derived from the build metadata to `tags` and the targets with their own tags
+fn apply_auto_tags(
+    details: &BuildDetails,
+    tags: &mut Option<Vec<String>>,
+    targets: &mut [UploadTarget],
+) {
+    let derived = derive_auto_tags(details);
+    debug!("Auto tags: {}", derived.join(", "));
+    *tags = merge_tags(tags.take(), &derived);
+    for target in targets {
+        if target.tags.is_some() {
+            target.tags = merge_tags(target.tags.take(), &derived);
+        }
+    }
+}
+
+/// Options shared by every file; name and platform are filled in per file
+fn base_upload_options(
+    args: &mut UploadArgs,
+    file_config: &FileConfig,
+    details: Option<BuildDetails>,
+    parallel: usize,
+) -> UploadOptions {
+    UploadOptions {
+        name: String::new(),
+        platform: String::new(),
+        description: args.description.take(),
+        upload_timeout: args.upload_timeout,
+        auto_delete: args.auto_delete,
+        deletion_policy: Some(
+            args.deletion_policy
+                .unwrap_or(DeletionPolicy::LeastRecent)
+                .as_str()
+                .to_string(),
+        ),
+        force_multipart: args.force_multipart,
+        parallel,
+        url_batch_size: args.url_batch_size,
+        on_upload_initiated: None,
+        progress_bar: None,
+        details,
+        tags: args.tags.take(),
+        report_progress: true,
+        storage_region: args.storage_region.take(),
+        build_version: args.build_version.take(),
+        build_number: args.build_number.take(),
+        release_notes: args.release_notes.take(),
+        symbols: None,
+        arch: None,
+        deterministic: args.deterministic,
+        artifact_type: args.artifact_type,
+        log_every_part: args.log_every_part,
+        encrypt: args.encrypt,
+        shard: args.shard.take().map(|shard| Shard {
+            key: args.shard_key.take(),
+            ..shard
+        }),
+        finalize_timeout: args.finalize_timeout.0,
+        part_cache: !args.no_part_cache,
+        stall_timeout: timeout(
+            args.stall_timeout.or(file_config.stall_timeout),
+            Some(DEFAULT_STALL_TIMEOUT),
+        ),
+    }
+}
+
+/// Check the targets against the size budgets of the config file and `--size-budget`
+///
+/// # Errors
+///
+/// Returns an error if a budget is invalid, or a file exceeds its budget with the `fail`
+/// action
+fn apply_size_budgets(
+    args: &mut UploadArgs,
+    file_config: &FileConfig,
+    targets: &mut [UploadTarget],
+) -> Result<()> {
+    let mut budgets = file_config
+        .size_budgets
+        .iter()
+        .map(|(platform, size)| Ok((SizeBudget::parse_platform(platform)?, size.0)))
+        .collect::<Result<BTreeMap<_, _>>>()?;
+    budgets.extend(
+        std::mem::take(&mut args.size_budget)
+            .into_iter()
+            .map(|b| (b.platform, b.bytes)),
+    );
+    // Budgets and size diffs compare builds; auxiliary artifacts have neither
+    if !budgets.is_empty() && args.artifact_type.is_build() {
+        let action = args
+            .size_budget_action
+            .or(file_config.size_budget_action)
+            .unwrap_or_default();
+        check_size_budgets(targets, &budgets, action, args.platform.as_ref())?;
+    }
+    Ok(())
+}
+
+/// Print where `--prepare-only` recorded the upload session
+///
+/// # Errors
+///
+/// Returns an error if the JSON report cannot be serialized
+fn print_prepared_session(
+    path: &Path,
+    session: &UploadSession,
+    format: OutputFormat,
+) -> Result<()> {
+    if format.is_json() {
+        let report = serde_json::json!({
+            "session": path,
+            "files": session.files.len(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        outln!(
+            "Prepared {} upload(s) in {}. Upload them from a connected machine with:\n  nunu-cli upload --from-session {} <files>",
+            session.files.len(),
+            path.display(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Shown for the API token on dry runs, which never resolve it
+const UNRESOLVED_TOKEN: &str = "<not resolved>";
+
+/// Print what `--dry-run` would upload, warning about the credentials a real upload needs
+///
+/// # Errors
+///
+/// Returns an error if the API URL is invalid or the JSON report cannot be serialized
+fn print_upload_dry_run(
+    api: ResolvedApi,
+    file_config: &FileConfig,
+    targets: &[UploadTarget],
+    args: &UploadArgs,
+    base_options: &UploadOptions,
+    format: OutputFormat,
+) -> Result<()> {
+    if !api.has_token_source(file_config) {
+        warn!(
+            "API token not provided; a real upload would fail (use --token, NUNU_API_TOKEN env var, or config file)"
+        );
+    }
+    if api.project_id.is_none() {
+        warn!(
+            "Project ID not provided; a real upload would fail (use --project-id, NUNU_PROJECT_ID env var, or config file)"
+        );
+    }
+
+    // Only the endpoints are printed, so the token is a placeholder
+    let endpoint_config = Config::new(
+        UNRESOLVED_TOKEN.to_string(),
+        api.project_id.unwrap_or_else(|| "<project-id>".to_string()),
+        api.api_url,
+    )?;
+    print_dry_run(
+        targets,
+        &endpoint_config,
+        args.platform.as_ref(),
+        base_options,
+        args.auto_tags,
+        format,
+    )
+}
+
+/// Settings shared by the uploads of one `upload` run
+struct UploadBatch<'a> {
+    config: &'a Config,
+    platform: Option<&'a BuildPlatform>,
+    base_options: &'a UploadOptions,
+    auto_tags: bool,
+    teamcity: bool,
+    verbose: u8,
+    parallel: usize,
+    format: OutputFormat,
+}
+
+/// Outcome of uploading one file: its path, the result and the build of a failed upload
+type UploadOutcome = (String, Result<UploadedFile>, Option<String>);
+
+/// Progress display and uploads in flight, shared by the uploads of a batch
+struct BatchProgress<'a> {
+    multi_progress: MultiProgress,
+    /// Status line in non-verbose mode
+    status_bar: Option<ProgressBar>,
+    active_uploads: ActiveUploads,
+    slow_monitor: Option<&'a SlowUploadMonitor>,
+}
+
+impl BatchProgress<'_> {
+    /// Progress bar for uploading `file_size` bytes of `file_path`, with its plain-mode
+    /// progress lines
+    fn add_bar(&self, file_path: &str, file_size: u64) -> (ProgressBar, Option<PlainProgress>) {
+        let pb = self.multi_progress.add(ProgressBar::new(file_size));
+        pb.set_style(upload_bar_style());
+        let file_name = Path::new(file_path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(file_path)
+            .to_string();
+        pb.set_message(file_name.clone());
+        let plain_progress = PlainProgress::start(&file_name, pb.clone());
+        if let Some(monitor) = self.slow_monitor {
+            monitor.track(pb.clone());
+        }
+        (pb, plain_progress)
+    }
+
+    /// Show `message` on the status line, or log it in verbose mode
+    fn status(&self, message: String) {
+        if let Some(ref bar) = self.status_bar {
+            bar.set_message(message);
+        } else {
+            info!("{message}");
+        }
+    }
+}
+
+impl UploadBatch<'_> {
+    /// Upload `targets` in parallel, aborting the uploads in flight with `client` if the
+    /// process is interrupted
+    async fn run(&self, targets: Vec<UploadTarget>, client: &Client) -> Vec<UploadOutcome> {
+        let multi_progress = plain::multi_progress();
+        let status_bar = (self.verbose == 0).then(|| {
+            let bar = multi_progress.insert(0, ProgressBar::new(0));
+            bar.set_style(
+                ProgressStyle::default_bar()
+                    .template("{msg}")
+                    .unwrap_or_else(|_| ProgressStyle::default_bar()),
+            );
+            bar
+        });
+
+        // Estimate the duration from this network's throughput history
+        let network = throughput::network_fingerprint(&self.config.api_url).await;
+        let mut throughput_history = throughput::History::load();
+        let typical_throughput = network
+            .as_deref()
+            .and_then(|network| throughput_history.typical(network));
+        if let Some(typical) = typical_throughput
+            && !self.format.is_json()
+        {
+            print_upload_estimate(&targets, typical, &multi_progress);
+        }
+        let slow_upload_monitor = typical_throughput.map(SlowUploadMonitor::start);
+
+        let progress = BatchProgress {
+            multi_progress,
+            status_bar,
+            active_uploads: Arc::new(RwLock::new(HashMap::new())),
+            slow_monitor: slow_upload_monitor.as_ref(),
+        };
+        progress.status(format!("Using API URL: {}", self.config.api_url));
+        progress.status(format!("Parallel uploads/parts: {}", self.parallel));
+
+        let batch_started = Instant::now();
+        let upload_task = stream::iter(targets)
+            .map(|target| self.upload(target, &progress))
+            .buffer_unordered(self.parallel)
+            .collect::<Vec<UploadOutcome>>();
+        let results = until_interrupted(upload_task, client, &progress.active_uploads).await;
+        drop(progress);
+        drop(slow_upload_monitor);
+
+        if let Some(network) = &network {
+            let bytes = results
+                .iter()
+                .filter_map(|(_, result, _)| result.as_ref().ok())
+                .map(|upload| upload.size)
+                .sum();
+            if throughput_history.record(
+                network,
+                bytes,
+                batch_started.elapsed(),
+                nunu_cli::util::unix_now(),
+            ) && let Err(e) = throughput_history.save()
+            {
+                debug!("Failed to store throughput history: {e}");
+            }
+        }
+        results
+    }
+
+    /// Platform (prepared, per file, explicit or inferred) and build name of `target`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the platform cannot be inferred from the file name
+    fn platform_and_name(
+        &self,
+        target: &UploadTarget,
+    ) -> nunu_cli::Result<(BuildPlatform, String)> {
+        let prepared = target.prepared.as_ref();
+        let platform = match prepared
+            .and_then(|r| r.platform.parse::<BuildPlatform>().ok())
+            .or_else(|| target.platform.clone())
+            .or_else(|| self.platform.cloned())
+        {
+            Some(p) => p,
+            None => infer_platform(&target.path)?,
+        };
+        let name = target
+            .name
+            .clone()
+            .or_else(|| prepared.map(|r| r.name.clone()))
+            .unwrap_or_else(|| {
+                generate_build_name(
+                    &target.name_template,
+                    &target.path,
+                    target.file_count,
+                    platform.as_str(),
+                    self.base_options.details.as_ref(),
+                )
+            });
+        Ok((platform, name))
+    }
+
+    /// Upload one target with its own progress bar
+    async fn upload(&self, target: UploadTarget, progress: &BatchProgress<'_>) -> UploadOutcome {
+        let (file_platform, build_name) = match self.platform_and_name(&target) {
+            Ok(resolved) => resolved,
+            Err(e) => return (target.path, Err(e.into()), None),
+        };
+        let UploadTarget {
+            path: file_path,
+            description,
+            tags,
+            metadata,
+            artifact,
+            arch,
+            size_budget,
+            prepared,
+            ..
+        } = target;
+        let base_options = self.base_options.clone();
+
+        // Get file size for progress bar
+        let file_size = match tokio::fs::metadata(&file_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                let message = format!("Failed to read file metadata: {e}");
+                let error = nunu_cli::error::Error::FileError(e);
+                return (
+                    file_path,
+                    Err(anyhow::Error::from(error).context(message)),
+                    None,
+                );
+            }
+        };
+
+        let (pb, plain_progress) = progress.add_bar(&file_path, file_size);
+
+        progress.status(format!(
+            "Uploading {} as {} (platform: {})",
+            file_path,
+            build_name,
+            file_platform.as_str()
+        ));
+
+        let options = UploadOptions {
+            name: build_name,
+            platform: file_platform.as_str().to_string(),
+            on_upload_initiated: Some(track_upload(&progress.active_uploads, &file_path)),
+            progress_bar: Some(pb.clone()),
+            description: description.or(base_options.description),
+            tags: target_tags(
+                tags,
+                base_options.tags.as_ref(),
+                &file_platform,
+                self.auto_tags,
+            ),
+            details: base_options.details.map(|d| {
+                d.with_custom(&metadata)
+                    .with_artifact(artifact)
+                    .with_size_budget(size_budget)
+            }),
+            arch,
+            ..base_options
+        };
+        let options = match &prepared {
+            Some(request) => options.with_request(request),
+            None => options,
+        };
+
+        let started = Instant::now();
+        let teamcity_progress = self
+            .teamcity
+            .then(|| TeamCityProgress::start(&format!("Uploading {file_path}"), pb.clone()));
+        let result = upload_file(self.config, &file_path, options)
+            .await
+            .map(|build_id| UploadedFile {
+                build_id,
+                size: pb.length().unwrap_or(file_size),
+                elapsed: started.elapsed(),
+                platform: file_platform.as_str().to_string(),
+                size_diff: None,
+                linked_to: None,
+            })
+            .map_err(anyhow::Error::from);
+
+        // Finish progress bar
+        if result.is_ok() {
+            pb.finish_with_message("✓ Complete");
+        } else {
+            pb.finish_with_message("✗ Failed");
+        }
+        drop(teamcity_progress);
+        drop(plain_progress);
+
+        // Remove from active uploads on completion (success or failure), keeping
+        // the build of a failed upload for its error report
+        let build_id = {
+            let mut uploads = progress.active_uploads.write().await;
+            uploads.remove(&file_path).map(|upload| upload.build_id)
+        };
+
+        (file_path, result, build_id)
+    }
+}
+
+/// Callback that records the upload of `file_path` in `active_uploads` once it is initiated,
+/// so it can be aborted after a termination signal
+fn track_upload(active_uploads: &ActiveUploads, file_path: &str) -> OnUploadInitiated {
+    let active_uploads = active_uploads.clone();
+    let file_path = file_path.to_string();
+    Arc::new(
+        move |build_id: String, upload_id: Option<String>, object_key: String| {
+            let file_path = file_path.clone();
+            let active_uploads = active_uploads.clone();
+            tokio::spawn(async move {
+                let mut uploads = active_uploads.write().await;
+                uploads.insert(
+                    file_path,
+                    InFlightUpload {
+                        build_id,
+                        upload_id,
+                        object_key,
+                    },
+                );
+            });
+        },
+    )
+}
+
+/// Print the expected duration of uploading `targets` at the `typical` throughput of this
+/// network, unless it is too short to matter
+fn print_upload_estimate(targets: &[UploadTarget], typical: u64, multi_progress: &MultiProgress) {
+    let total_bytes: u64 = targets
+        .iter()
+        .filter_map(|target| std::fs::metadata(&target.path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    let estimate = throughput::estimate(total_bytes, typical);
+    if estimate >= throughput::MIN_ESTIMATE {
+        multi_progress.suspend(|| {
+            errln!(
+                "⏱  {}",
+                t!(
+                    "upload.estimate",
+                    duration = format_duration(estimate),
+                    rate = format_rate(typical, Duration::from_secs(1))
+                )
+            );
+        });
+    }
+}
+
+/// Wait for `uploads`, or abort the uploads in flight and exit on a termination signal
+async fn until_interrupted(
+    uploads: impl Future<Output = Vec<UploadOutcome>>,
+    client: &Client,
+    active_uploads: &ActiveUploads,
+) -> Vec<UploadOutcome> {
+    let ctrl_c = tokio::signal::ctrl_c();
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut sigterm = signal(SignalKind::terminate()).ok();
+        tokio::select! {
+            results = uploads => results,
+            _ = ctrl_c => {
+                errln!("\n🛑 {}", t!("signal.interrupt", signal = "SIGINT/Ctrl+C"));
+
+                abort_active_uploads(client, active_uploads).await;
+
+                errln!("⚠️  {}", t!("upload.cancelled"));
+                std::process::exit(130); // Standard exit code for SIGINT
+            }
+            _ = async {
+                match sigterm.as_mut() {
+                    Some(sig) => sig.recv().await,
+                    None => std::future::pending().await,
                 }
+            }, if sigterm.is_some() => {
+                errln!("\n🛑 {}", t!("signal.terminate"));
+
+                abort_active_uploads(client, active_uploads).await;
+
+                errln!("⚠️  {}", t!("upload.terminated"));
+                std::process::exit(143); // Standard exit code for SIGTERM (128 + 15)
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::select! {
+            results = uploads => results,
+            _ = ctrl_c => {
+                errln!("\n🛑 {}", t!("signal.interrupt", signal = "Ctrl+C"));
+
+                abort_active_uploads(client, active_uploads).await;
+
+                errln!("⚠️  {}", t!("upload.cancelled"));
+                std::process::exit(130); // Standard exit code for SIGINT
+            }
+        }
+    }
+}
+
+/// Report the uploads of a batch, then compare sizes, write checksums and verify the
+/// uploaded files as requested
+///
+/// Returns the first build ID.
+///
+/// # Errors
+///
+/// Returns an error if any upload failed or differs from what the server stored
+async fn report_upload_results(
+    results: Vec<UploadOutcome>,
+    linked: Vec<(String, String)>,
+    client: &Client,
+    args: &UploadArgs,
+    base_options: &UploadOptions,
+    format: OutputFormat,
+) -> Result<String> {
+    let mut uploaded = Vec::new();
+    let mut errors = Vec::new();
+    let mut error_reports = HashMap::new();
+
+    for (file_path, result, build_id) in results {
+        match result {
+            Ok(upload) => {
+                info!(
+                    "✅ {file_path} uploaded successfully - Build ID: {}",
+                    upload.build_id
+                );
+                uploaded.push((file_path, upload));
+            }
+            Err(e) => {
+                let report = ErrorReport {
+                    build_id,
+                    ..ErrorReport::new(e.to_string(), e.as_ref())
+                };
+                error_reports.insert(file_path.clone(), report);
+                errors.push((file_path, e.to_string()));
+            }
+        }
+    }
+    if !args.no_size_diff && args.artifact_type.is_build() {
+        let branch = base_options
+            .details
+            .as_ref()
+            .and_then(|d| d.vcs.as_ref())
+            .and_then(|v| v.branch.as_deref());
+        compare_with_previous_builds(client, &mut uploaded, branch).await;
+    }
+    report_linked_duplicates(&linked, &mut uploaded, &mut errors);
+    let build_ids: Vec<(String, String)> = uploaded
+        .iter()
+        .map(|(file, upload)| (file.clone(), upload.build_id.clone()))
+        .collect();
+
+    print_upload_results(&uploaded, &errors, format)?;
+    if args.teamcity {
+        report_to_teamcity(&build_ids, &errors);
+    }
+
+    if let Some(path) = &args.checksum_manifest
+        && !build_ids.is_empty()
+    {
+        write_checksum_manifest(client, path, &build_ids, format).await?;
+    }
+
+    if (args.verify || args.verify_all) && !build_ids.is_empty() {
+        let sampling = VerifySampling {
+            percent: args.verify_sample,
+            threshold: args.verify_threshold.0,
+            all: args.verify_all,
+        };
+        let report = verify_uploads(client, &build_ids, sampling).await?;
+        print_verify_report(&report, format);
+        if !report.mismatches.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{} uploaded file(s) differ from what the server stored",
+                report.mismatches.len()
+            ));
+        }
+    }
+
+    if !errors.is_empty() {
+        let files = errors
+            .into_iter()
+            .map(|(file, message)| FileErrorReport {
+                error: error_reports
+                    .remove(&file)
+                    .unwrap_or_else(|| ErrorReport::other(message)),
+                file,
+            })
+            .collect();
+        return Err(BatchFailure { files }.into());
+    }
+
+    Ok(build_ids
+        .first()
+        .map(|(_, id)| id.clone())
+        .unwrap_or_default())
+}
+
+/// Abort every in-flight upload after a termination signal, reporting unconfirmed aborts
+async fn abort_active_uploads(client: &Client, active_uploads: &ActiveUploads) {
+    let uploads: Vec<_> = active_uploads
+        .read()
+        .await
+        .iter()
+        .map(|(file, upload)| (file.clone(), upload.clone()))
+        .collect();
+    if uploads.is_empty() {
+        return;
+    }
+
+    errln!("⏳ {}", t!("abort.attempting", count = uploads.len()));
+    let report = abort_uploads(client, uploads, ABORT_DEADLINE).await;
+    if report.unconfirmed.is_empty() {
+        errln!("✓ {}", t!("abort.done", count = report.confirmed.len()));
+    } else {
+        errln!(
+            "⚠️  {}",
+            t!("abort.unconfirmed", count = report.unconfirmed.len())
+        );
+        for (file, reason) in &report.unconfirmed {
+            errln!("  {file}: {reason}");
+        }
+    }
+}
+
+/// Report uploaded build IDs and failures to TeamCity as service messages
+fn report_to_teamcity(build_ids: &[(String, String)], errors: &[(String, String)]) {
+    for (file, build_id) in build_ids {
+        teamcity::emit(
+            "message",
+            &[("text", &format!("Uploaded {file} - Build ID: {build_id}"))],
+        );
+    }
+    for (file, error) in errors {
+        teamcity::emit(
+            "message",
+            &[
+                ("text", &format!("Failed to upload {file}")),
+                ("errorDetails", error),
+                ("status", "ERROR"),
+            ],
+        );
+    }
+    if let Some((_, first)) = build_ids.first() {
+        let all: Vec<&str> = build_ids.iter().map(|(_, id)| id.as_str()).collect();
+        teamcity::emit(
+            "setParameter",
+            &[("name", teamcity::BUILD_ID_PARAMETER), ("value", first)],
+        );
+        teamcity::emit(
+            "setParameter",
+            &[
+                ("name", teamcity::BUILD_IDS_PARAMETER),
+                ("value", &all.join(",")),
+            ],
+        );
+    }
+}
+
+/// Progress bar style shared by every upload display
+fn upload_bar_style() -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta}) {msg}")
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("#>-")
+}
+
+/// A successfully uploaded file
+struct UploadedFile {
+    build_id: String,
+    /// Bytes sent (the archive size for directories)
+    size: u64,
+    elapsed: Duration,
+    platform: String,
+    /// Size change relative to the previous build, filled in by [`compare_with_previous_builds`]
+    size_diff: Option<SizeDiff>,
+    /// The batch file this one is a hard link or reflink of, whose upload it shares
+    linked_to: Option<String>,
+}
+
+/// Drop targets that are hard links or reflinks of an earlier target, so shared data is
+/// uploaded once
+///
+/// Returns `(duplicate, original)` paths so duplicates can be reported with the original's
+/// build ID.
+fn remove_linked_duplicates(targets: &mut Vec<UploadTarget>) -> Vec<(String, String)> {
+    let paths: Vec<String> = targets.iter().map(|t| t.path.clone()).collect();
+    let duplicates = find_linked_duplicates(&paths);
+    if duplicates.is_empty() {
+        return Vec::new();
+    }
+
+    let linked: Vec<(String, String)> = duplicates
+        .iter()
+        .map(|&(duplicate, original)| {
+            info!(
+                "{} is linked to {}; uploading it once",
+                paths[duplicate], paths[original]
+            );
+            (paths[duplicate].clone(), paths[original].clone())
+        })
+        .collect();
+    let mut index = 0;
+    targets.retain(|_| {
+        let keep = !duplicates.iter().any(|&(duplicate, _)| duplicate == index);
+        index += 1;
+        keep
+    });
+    linked
+}
+
+/// Report each linked duplicate with the outcome of the file it shares data with
+fn report_linked_duplicates(
+    linked: &[(String, String)],
+    uploaded: &mut Vec<(String, UploadedFile)>,
+    errors: &mut Vec<(String, String)>,
+) {
+    for (duplicate, original) in linked {
+        let shared = uploaded
+            .iter()
+            .find(|(file, _)| file == original)
+            .map(|(_, upload)| UploadedFile {
+                build_id: upload.build_id.clone(),
+                size: upload.size,
+                elapsed: Duration::ZERO,
+                platform: upload.platform.clone(),
+                size_diff: upload.size_diff.clone(),
+                linked_to: Some(original.clone()),
+            });
+        match shared {
+            Some(upload) => uploaded.push((duplicate.clone(), upload)),
+            None => errors.push((
+                duplicate.clone(),
+                format!("not uploaded because {original}, which it is linked to, failed"),
+            )),
+        }
+    }
+}
+
+/// Compare every uploaded build's size with the previous build on its branch and platform
+///
+/// The difference is attached to the build's details and shown in the summary. Lookups
+/// are best effort: failures are logged and leave the build without a size diff.
+async fn compare_with_previous_builds(
+    client: &Client,
+    uploaded: &mut [(String, UploadedFile)],
+    branch: Option<&str>,
+) {
+    let lookups = uploaded.iter_mut().map(|(file, upload)| async move {
+        let previous = match client
+            .previous_build(&upload.build_id, &upload.platform, branch)
+            .await
+        {
+            Ok(Some(previous)) => previous,
+            Ok(None) => {
+                debug!("No previous build to compare {file} with");
+                return;
+            }
+            Err(e) => {
+                warn!("Could not look up the previous build of {file}: {e}");
+                return;
+            }
+        };
+
+        let diff = SizeDiff::new(previous, upload.size);
+        debug!("{file}: {}", diff.describe());
+        let details = serde_json::json!({ "size_diff": &diff });
+        if let Err(e) = client
+            .update_build_details(&upload.build_id, &details)
+            .await
+        {
+            warn!(
+                "Could not attach the size diff to build {}: {e}",
+                upload.build_id
+            );
+        }
+        upload.size_diff = Some(diff);
+    });
+    futures::future::join_all(lookups).await;
+}
+
+/// Print the outcome of every upload in the batch
+///
+/// # Errors
+///
+/// Returns an error if the JSON report cannot be serialized
+fn print_upload_results(
+    uploaded: &[(String, UploadedFile)],
+    errors: &[(String, String)],
+    format: OutputFormat,
+) -> Result<()> {
+    if format.is_json() {
+        let report = serde_json::json!({
+            "uploaded": uploaded
+                .iter()
+                .map(|(file, upload)| serde_json::json!({
+                    "file": file,
+                    "build_id": upload.build_id,
+                    "size": upload.size,
+                    "duration_secs": upload.elapsed.as_secs_f64(),
+                    "size_diff": upload.size_diff,
+                    "linked_to": upload.linked_to,
+                }))
+                .collect::<Vec<_>>(),
+            "failed": errors
+                .iter()
+                .map(|(file, error)| serde_json::json!({ "file": file, "error": error }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if !uploaded.is_empty() {
+        outln!("\n✅ {}", t!("upload.succeeded", count = uploaded.len()));
+        for (file, upload) in uploaded {
+            if let Some(original) = &upload.linked_to {
+                outln!(
+                    "  {}",
+                    t!(
+                        "upload.linked",
+                        file = file,
+                        build_id = upload.build_id,
+                        original = original
+                    )
+                );
+                continue;
+            }
+            let size_diff = upload
+                .size_diff
+                .as_ref()
+                .map(|diff| format!(", {}", diff.describe()))
+                .unwrap_or_default();
+            outln!(
+                "  {}",
+                t!(
+                    "upload.uploaded",
+                    file = file,
+                    build_id = upload.build_id,
+                    size = format_size(upload.size),
+                    duration = format_duration(upload.elapsed),
+                    rate = format_rate(upload.size, upload.elapsed),
+                    size_diff = size_diff
+                )
+            );
+        }
+    }
+
+    if !errors.is_empty() {
+        errln!("\n❌ {}", t!("upload.errors", count = errors.len()));
+        for (file, error) in errors {
+            errln!("  {file}: {error}");
+        }
+    }
+    Ok(())
+}
+
+/// Print how many uploaded files were verified and which differ from the stored objects
+fn print_verify_report(report: &VerifyReport, format: OutputFormat) {
+    if report.unsupported {
+        return;
+    }
+    let summary = if report.skipped > 0 {
+        t!(
+            "verify.summary_skipped",
+            verified = report.verified,
+            files = report.files,
+            skipped = report.skipped
+        )
+    } else {
+        t!(
+            "verify.summary",
+            verified = report.verified,
+            files = report.files
+        )
+    };
+    if format.is_json() {
+        info!("{summary}");
+    } else if report.mismatches.is_empty() {
+        outln!("🔎 {summary}");
+    } else {
+        errln!(
+            "\n❌ {}",
+            t!(
+                "verify.mismatches",
+                summary = summary,
+                count = report.mismatches.len()
+            )
+        );
+    }
+    for (file, difference) in &report.mismatches {
+        if format.is_json() {
+            error!("{file} differs from the stored upload: {difference}");
+        } else {
+            errln!("  {file}: {difference}");
+        }
+    }
+}
+
+/// Write the checksum manifest of a batch and attach it to the uploaded builds
+///
+/// Failing to attach the manifest only warns, since the builds are already uploaded.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be hashed or the manifest cannot be written
+async fn write_checksum_manifest(
+    client: &Client,
+    path: &Path,
+    build_ids: &[(String, String)],
+    format: OutputFormat,
+) -> Result<()> {
+    let uploads = build_ids.to_vec();
+    let entries = tokio::task::spawn_blocking(move || {
+        uploads
+            .into_iter()
+            .map(|(file, build_id)| {
+                let file_path = Path::new(&file);
+                let size = std::fs::metadata(file_path)?.len();
+                let sha256 = if file_path.is_dir() {
+                    None
+                } else {
+                    Some(sha256_file(file_path)?)
+                };
+                Ok(ChecksumEntry {
+                    file,
+                    size,
+                    sha256,
+                    build_id,
+                })
+            })
+            .collect::<nunu_cli::Result<Vec<_>>>()
+    })
+    .await??;
+
+    let checksum_format = ChecksumFormat::from_path(path);
+    let content = render_checksums(&entries, checksum_format)?;
+    std::fs::write(path, &content).map_err(|e| {
+        anyhow::anyhow!("Failed to write checksum manifest {}: {e}", path.display())
+    })?;
+    if format.is_json() {
+        info!("Checksum manifest written to {}", path.display());
+    } else {
+        outln!("📝 {}", t!("checksums.written", path = path.display()));
+    }
+
+    let attachment = BuildSetAttachment {
+        build_ids: entries.iter().map(|e| e.build_id.clone()).collect(),
+        file_name: path.file_name().map_or_else(
+            || "checksums.txt".into(),
+            |n| n.to_string_lossy().into_owned(),
+        ),
+        content_type: match checksum_format {
+            ChecksumFormat::Json => "application/json",
+            ChecksumFormat::Text => "text/plain",
+        }
+        .to_string(),
+        content,
+    };
+    if let Err(e) = client.upload_build_set_attachment(&attachment).await {
+        warn!("Failed to attach the checksum manifest to the builds: {e}");
+    }
+    Ok(())
+}
+
+/// Run a `builds` subcommand
+///
+/// # Errors
+///
+/// Returns an error if the config cannot be resolved or the API request fails
+async fn run_builds(config_path: Option<&PathBuf>, command: BuildsCommands) -> Result<()> {
+    let file_config = FileConfig::load_with_fallback(config_path)?;
+
+    match command {
+        BuildsCommands::Delete {
+            build_id,
+            purge,
+            api,
+        } => {
+            let client = Client::try_new(api.into_config(file_config)?)?;
+            let response = client.delete_build(&build_id, purge).await?;
+
+            if response.purged {
+                outln!("🗑️  {}", t!("builds.deleted", build_id = build_id));
+            } else {
+                match response.restorable_until {
+                    Some(deadline) => outln!(
+                        "🗑️  {}\n  nunu-cli builds restore {build_id}",
+                        t!(
+                            "builds.trashed_until",
+                            build_id = build_id,
+                            deadline = deadline
+                        )
+                    ),
+                    None => outln!(
+                        "🗑️  {}\n  nunu-cli builds restore {build_id}",
+                        t!("builds.trashed", build_id = build_id)
+                    ),
+                }
+            }
+            Ok(())
+        }
+        BuildsCommands::Restore { build_id, api } => {
+            let client = Client::try_new(api.into_config(file_config)?)?;
+            client.restore_build(&build_id).await?;
+
+            outln!("♻️  {}", t!("builds.restored", build_id = build_id));
+            Ok(())
+        }
+    }
+}
+
+/// Run a `retention` subcommand
+///
+/// # Errors
+///
+/// Returns an error if the config cannot be resolved or the builds cannot be listed
+async fn run_retention(
+    config_path: Option<&PathBuf>,
+    format: OutputFormat,
+    command: RetentionCommands,
+) -> Result<()> {
+    let RetentionCommands::Audit {
+        mut policies,
+        keep_tags,
+        api,
+    } = command;
+    if policies.is_empty() {
+        policies = DEFAULT_POLICIES
+            .iter()
+            .map(|p| p.parse())
+            .collect::<std::result::Result<_, _>>()?;
+    }
+
+    let file_config = FileConfig::load_with_fallback(config_path)?;
+    let client = Client::try_new(api.into_config(file_config)?)?;
+    let builds = client.list_builds().await?;
+    let now = std::time::SystemTime::now();
+    let report = retention::audit(builds, &policies, &keep_tags, now);
+
+    if format.is_json() {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    outln!(
+        "📦 {} build(s), {} in total\n",
+        report.builds.len(),
+        format_size(report.total_bytes)
+    );
+    for build in &report.builds {
+        let age = build
+            .age(now)
+            .map_or_else(|| "?".to_string(), retention::format_age);
+        let tags = if build.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", build.tags.join(", "))
+        };
+        outln!(
+            "  {age:>5}  {:>10}  {:<14} {:<20} {}{tags}",
+            format_size(build.size),
+            build.platform,
+            build.branch.as_deref().unwrap_or("-"),
+            build.name,
+        );
+    }
+
+    outln!();
+    for outcome in &report.policies {
+        outln!(
+            "  {:<16} deletes {} build(s) ({}), keeps {} ({})",
+            outcome.policy,
+            outcome.deleted.len(),
+            format_size(outcome.deleted_bytes),
+            outcome.kept_builds,
+            format_size(outcome.kept_bytes)
+        );
+    }
+    Ok(())
+}
+
+/// Run a `symbols` subcommand
+///
+/// # Errors
+///
+/// Returns an error if no symbols are found, the config cannot be resolved or any
+/// upload fails
+#[allow(clippy::too_many_lines)]
+async fn run_symbols(
+    config_path: Option<&PathBuf>,
+    format: OutputFormat,
+    command: SymbolsCommands,
+) -> Result<()> {
+    let SymbolsCommands::Upload {
+        paths,
+        build_id,
+        build_version,
+        platform,
+        parallel,
+        dry_run,
+        api,
+    } = command;
+
+    let paths: Vec<PathBuf> = expand_globs(&paths)?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+    let symbols = find_symbols(&paths)?;
+    if symbols.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No dSYM bundles, PDBs or ProGuard mapping files found"
+        ));
+    }
+
+    if dry_run {
+        return print_symbols_dry_run(&symbols, format);
+    }
+
+    let file_config = FileConfig::load_with_fallback(config_path)?;
+    let config = api.into_config(file_config)?;
+    let multi_progress = plain::multi_progress();
+
+    let results = stream::iter(symbols)
+        .map(|symbol| {
+            let config = config.clone();
+            let multi_progress = multi_progress.clone();
+            let build_id = build_id.clone();
+            let build_version = build_version.clone();
+            let platform = platform.clone();
+            async move {
+                let file_path = symbol.path.to_string_lossy().into_owned();
+                let name = symbol
+                    .path
+                    .file_name()
+                    .map_or_else(|| file_path.clone(), |n| n.to_string_lossy().into_owned());
+                let size = std::fs::metadata(&symbol.path).map_or(0, |m| m.len());
+
+                let pb = multi_progress.add(ProgressBar::new(size));
+                pb.set_style(upload_bar_style());
+                pb.set_message(name.clone());
+                let plain_progress = PlainProgress::start(&name, pb.clone());
+
+                let platform = platform
+                    .unwrap_or_else(|| symbol.kind.default_platform())
+                    .as_str()
+                    .to_string();
+                let options = UploadOptions {
+                    name,
+                    platform: platform.clone(),
+                    parallel,
+                    progress_bar: Some(pb.clone()),
+                    build_version: build_version.clone(),
+                    symbols: Some(SymbolsInfo {
+                        kind: symbol.kind,
+                        build_id,
+                        build_version,
+                    }),
+                    ..UploadOptions::default()
+                };
+
+                let started = Instant::now();
+                let result = upload_file(&config, &file_path, options)
+                    .await
+                    .map(|build_id| UploadedFile {
+                        build_id,
+                        size: pb.length().unwrap_or(size),
+                        elapsed: started.elapsed(),
+                        platform,
+                        size_diff: None,
+                        linked_to: None,
+                    })
+                    .map_err(anyhow::Error::from);
+                if result.is_ok() {
+                    pb.finish_with_message("✓ Complete");
+                } else {
+                    pb.finish_with_message("✗ Failed");
+                }
+                drop(plain_progress);
+                (file_path, result)
+            }
+        })
+        .buffer_unordered(parallel)
+        .collect::<Vec<(String, Result<UploadedFile>)>>()
+        .await;
+
+    let mut uploaded = Vec::new();
+    let mut errors = Vec::new();
+    for (file_path, result) in results {
+        match result {
+            Ok(upload) => uploaded.push((file_path, upload)),
+            Err(e) => errors.push((file_path, e.to_string())),
+        }
+    }
+    print_upload_results(&uploaded, &errors, format)?;
+
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} symbol file(s) failed to upload",
+            errors.len()
+        ));
+    }
+    Ok(())
+}
+
+/// List the symbols a `symbols upload --dry-run` would upload
+///
+/// # Errors
+///
+/// Returns an error if the JSON report cannot be serialized
+fn print_symbols_dry_run(symbols: &[SymbolFile], format: OutputFormat) -> Result<()> {
+    if format.is_json() {
+        let report = serde_json::json!({
+            "dry_run": true,
+            "symbols": symbols
+                .iter()
+                .map(|s| serde_json::json!({ "file": s.path, "kind": s.kind }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        outln!(
+            "🔍 Dry run: {} symbol file(s) would be uploaded:",
+            symbols.len()
+        );
+        for symbol in symbols {
+            outln!("  [{}] {}", symbol.kind.as_str(), symbol.path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Run a `receipts` subcommand
+///
+/// # Errors
+///
+/// Returns an error if a requested receipt is missing, the signing keys cannot be fetched,
+/// or any receipt fails verification
+async fn run_receipts(
+    config_path: Option<&PathBuf>,
+    format: OutputFormat,
+    command: ReceiptsCommands,
+) -> Result<()> {
+    let ReceiptsCommands::Verify {
+        build_ids,
+        file,
+        public_key,
+        api,
+    } = command
+    else {
+        return print_receipts(format);
+    };
+
+    if file.is_some() && build_ids.len() != 1 {
+        return Err(anyhow::anyhow!("--file requires exactly one build ID"));
+    }
+    let stored = if build_ids.is_empty() {
+        receipts::list_receipts()?
+    } else {
+        build_ids
+            .iter()
+            .map(|id| receipts::load_receipt(id))
+            .collect::<std::result::Result<_, _>>()?
+    };
+    if stored.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No upload receipts in {}",
+            receipts::receipts_dir().display()
+        ));
+    }
+
+    let server_keys = if public_key.is_some() {
+        Vec::new()
+    } else {
+        let file_config = FileConfig::load_with_fallback(config_path)?;
+        Client::try_new(api.into_config(file_config)?)?
+            .receipt_keys()
+            .await?
+    };
+    let file_sha256 = file.as_deref().map(sha256_file).transpose()?;
+
+    let mut results = Vec::new();
+    for receipt in &stored {
+        let keys = match &public_key {
+            Some(key) => vec![ReceiptKey {
+                key_id: receipt.key_id.clone(),
+                public_key: key.trim().to_ascii_lowercase(),
+            }],
+            None => server_keys.clone(),
+        };
+        let mut error = receipt.verify(&keys).err().map(|e| e.to_string());
+        if error.is_none()
+            && let Some(sha256) = &file_sha256
+            && !sha256.eq_ignore_ascii_case(&receipt.sha256)
+        {
+            error = Some(format!(
+                "local file hash {sha256} does not match the receipt ({})",
+                receipt.sha256
+            ));
+        }
+        results.push((receipt, error));
+    }
+
+    let failed = results.iter().filter(|(_, error)| error.is_some()).count();
+    if format.is_json() {
+        let report: Vec<_> = results
+            .iter()
+            .map(|(receipt, error)| {
+                serde_json::json!({
+                    "build_id": receipt.build_id,
+                    "sha256": receipt.sha256,
+                    "completed_at": receipt.completed_at,
+                    "valid": error.is_none(),
+                    "error": error,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for (receipt, error) in &results {
+            match error {
+                None => outln!(
+                    "✓ {} - uploaded {} (sha256 {})",
+                    receipt.build_id,
+                    receipt.completed_at,
+                    receipt.sha256
+                ),
+                Some(error) => outln!("✗ {} - {error}", receipt.build_id),
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{failed} receipt(s) failed verification"));
+    }
+    Ok(())
+}
+
+/// Print every stored receipt, oldest first
+///
+/// # Errors
+///
+/// Returns an error if the receipts directory cannot be read
+fn print_receipts(format: OutputFormat) -> Result<()> {
+    let stored = receipts::list_receipts()?;
+    if format.is_json() {
+        println!("{}", serde_json::to_string_pretty(&stored)?);
+    } else if stored.is_empty() {
+        outln!(
+            "No upload receipts in {}",
+            receipts::receipts_dir().display()
+        );
+    } else {
+        for receipt in &stored {
+            outln!(
+                "{}  {}  {}",
+                receipt.completed_at,
+                receipt.build_id,
+                receipt.sha256
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Run an `uploads` subcommand
+///
+/// # Errors
+///
+/// Returns an error if no matching upload is found
+async fn run_uploads(command: UploadsCommands) -> Result<()> {
+    match command {
+        UploadsCommands::Watch { build_id } => watch_uploads(build_id.as_deref()).await,
+    }
+}
+
+/// Render the progress of uploads owned by other processes until they finish
+///
+/// # Errors
+///
+/// Returns an error if no matching upload is in progress
+async fn watch_uploads(build_id: Option<&str>) -> Result<()> {
+    let uploads = if let Some(build_id) = build_id {
+        let state = state::read_upload(build_id).ok_or_else(|| {
+            anyhow::anyhow!("No upload of build {build_id} found on this machine")
+        })?;
+        vec![state]
+    } else {
+        state::list_uploads()?
+            .into_iter()
+            .filter(|s| s.status == UploadStatus::Running && !s.is_stale())
+            .collect()
+    };
+    if uploads.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No uploads in progress on this machine (state directory: {})",
+            state::state_dir().display()
+        ));
+    }
+
+    let multi_progress = plain::multi_progress();
+    let style = upload_bar_style();
+    let now = nunu_cli::util::unix_now();
+
+    let mut watched: Vec<(String, ProgressBar, Option<PlainProgress>)> = uploads
+        .iter()
+        .map(|upload| {
+            let label = format!("{} ({})", upload.name, upload.file_name);
+            let pb = multi_progress.add(
+                ProgressBar::new(upload.total_bytes)
+                    .with_style(style.clone())
+                    .with_elapsed(Duration::from_secs(now.saturating_sub(upload.started_at)))
+                    .with_message(label.clone()),
+            );
+            pb.set_position(upload.uploaded_bytes);
+            let plain_progress = PlainProgress::start(&label, pb.clone());
+            (upload.build_id.clone(), pb, plain_progress)
+        })
+        .collect();
+
+    while !watched.is_empty() {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // Dropping a finished upload's plain progress prints its outcome
+        watched.retain(|(build_id, pb, _)| {
+            let Some(upload) = state::read_upload(build_id) else {
+                pb.abandon_with_message("? Upload state removed");
+                return false;
             };
+            pb.set_length(upload.total_bytes);
+            pb.set_position(upload.uploaded_bytes);
+
+            match upload.status {
+                UploadStatus::Completed => {
+                    pb.finish_with_message(format!(
+                        "✓ {} complete (build {build_id})",
+                        upload.name
+                    ));
+                    false
+                }
+                UploadStatus::Failed => {
+                    pb.abandon_with_message(format!("✗ {} failed", upload.name));
+                    false
+                }
+                UploadStatus::Running if upload.is_stale() => {
+                    pb.abandon_with_message(format!(
+                        "✗ {} stopped responding (pid {})",
+                        upload.name, upload.pid
+                    ));
+                    false
+                }
+                UploadStatus::Running => true,
+            }
+        });
+    }
 
-            // Process results
-            let mut build_ids = Vec::new();
-            let mut errors = Vec::new();
+    Ok(())
+}
 
-            for (file_path, result) in results {
-                match result {
-                    Ok(build_id) => {
-                        info!("✅ {file_path} uploaded successfully - Build ID: {build_id}");
-                        build_ids.push((file_path, build_id));
+/// Run an `integrations` subcommand
+///
+/// # Errors
+///
+/// Returns an error if the config file cannot be loaded or the integration fails
+fn run_integrations(
+    config_path: Option<&PathBuf>,
+    format: OutputFormat,
+    command: IntegrationsCommands,
+) -> Result<()> {
+    match command {
+        IntegrationsCommands::SdkTemplate {
+            output,
+            project_id,
+            api_url,
+            force,
+        } => {
+            let file_config = FileConfig::load_with_fallback(config_path)?;
+
+            let project_id = project_id
+                .or_else(|| docker_secret(PROJECT_ID_SECRET))
+                .or_else(|| file_config.project_id.clone())
+                .map(|p| file_config.resolve_project(p))
+                .ok_or_else(|| anyhow::anyhow!(t!("error.missing_project")))?;
+            let api_url = api_url
+                .or_else(|| docker_secret(API_URL_SECRET))
+                .or(file_config.api_url)
+                .unwrap_or_else(|| "https://nunu.ai/api".to_string());
+            let crate_name = output
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("nunu-uploader")
+                .to_string();
+
+            let written = write_sdk_template(
+                &output,
+                &SdkTemplateOptions {
+                    crate_name,
+                    project_id,
+                    api_url,
+                },
+                force,
+            )?;
+
+            outln!("Created SDK template project in {}:", output.display());
+            for path in &written {
+                outln!("  {}", path.display());
+            }
+            outln!(
+                "\nBuild it with `cargo build --manifest-path {}`",
+                output.join("Cargo.toml").display()
+            );
+            Ok(())
+        }
+        IntegrationsCommands::Docker { command } => run_docker(config_path, format, command),
+    }
+}
+
+/// Run an `integrations docker` subcommand
+///
+/// # Errors
+///
+/// Returns an error if the config file cannot be loaded or the command cannot be started
+fn run_docker(
+    config_path: Option<&PathBuf>,
+    format: OutputFormat,
+    command: DockerCommands,
+) -> Result<()> {
+    let file_config = FileConfig::load_with_fallback(config_path)?;
+    let vars = resolve_env(&file_config, &file_transport(&file_config));
+
+    match command {
+        DockerCommands::PrintEnv => {
+            let vars: Vec<_> = vars
+                .into_iter()
+                .map(|mut var| {
+                    if var.secret && var.value.is_some() {
+                        var.value = Some("<redacted>".to_string());
                     }
-                    Err(e) => {
-                        errors.push(format!("{file_path}: {e}"));
+                    var
+                })
+                .collect();
+            if format.is_json() {
+                println!("{}", serde_json::to_string_pretty(&vars)?);
+                return Ok(());
+            }
+            for var in &vars {
+                match (&var.value, var.source) {
+                    (Some(value), Some(source)) => {
+                        outln!("{}={value}  # {}", var.name, source.as_str());
                     }
+                    _ => outln!("# {} is not set", var.name),
                 }
             }
-
-            // Report results
-            if !build_ids.is_empty() {
-                println!("\n✅ Successfully uploaded {} file(s):", build_ids.len());
-                for (file, build_id) in &build_ids {
-                    println!("  {file} → Build ID: {build_id}");
+            Ok(())
+        }
+        DockerCommands::Entrypoint { command } => {
+            let (program, args) = command
+                .split_first()
+                .ok_or_else(|| anyhow::anyhow!("No command given"))?;
+            let mut child = std::process::Command::new(program);
+            child.args(args);
+            for var in vars {
+                if let Some(value) = var.value {
+                    child.env(var.name, value);
                 }
             }
+            if let Some(path) = config_path {
+                child.env("NUNU_CONFIG", path);
+            }
 
-            if !errors.is_empty() {
-                eprintln!("\n❌ Failed to upload {} file(s):", errors.len());
-                for error in &errors {
-                    eprintln!("  {error}");
-                }
-                return Err(anyhow::anyhow!("{} file(s) failed to upload", errors.len()));
+            let status = child
+                .status()
+                .map_err(|e| anyhow::anyhow!("Failed to run {program}: {e}"))?;
+            if !status.success() {
+                std::process::exit(status.code().unwrap_or(1));
             }
+            Ok(())
+        }
+    }
+}
 
-            Ok(build_ids
-                .first()
-                .map(|(_, id)| id.clone())
-                .unwrap_or_default())
+/// Run a `nunu-cli-<name>` plugin, exiting with its exit code
+///
+/// Plugins receive the remaining arguments and the resolved context in the environment:
+/// `NUNU_API_TOKEN`, `NUNU_PROJECT_ID` (aliases resolved) and `NUNU_API_URL` when
+/// configured, `NUNU_CONFIG`, `NUNU_CLI` (this executable), `NUNU_CLI_VERSION`,
+/// `NUNU_CLI_FORMAT` and `NUNU_CLI_VERBOSE`.
+///
+/// # Errors
+///
+/// Returns an error if no plugin is found, the config file is invalid, or the plugin
+/// cannot be started
+fn run_plugin(
+    config_path: Option<&PathBuf>,
+    verbose: u8,
+    format: OutputFormat,
+    args: &[String],
+) -> Result<()> {
+    let (name, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("No command given"))?;
+    let plugin = find_plugin(name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown command '{name}' (no {PLUGIN_PREFIX}{name} executable found on PATH); see --help"
+        )
+    })?;
+    debug!("Running plugin {}", plugin.display());
+
+    let file_config = FileConfig::load_with_fallback(config_path)?;
+
+    let mut command = std::process::Command::new(&plugin);
+    command
+        .args(args)
+        .env("NUNU_CLI_VERSION", env!("CARGO_PKG_VERSION"))
+        .env("NUNU_CLI_FORMAT", format.to_string())
+        .env("NUNU_CLI_VERBOSE", verbose.to_string());
+    if let Ok(exe) = std::env::current_exe() {
+        command.env("NUNU_CLI", exe);
+    }
+    if let Some(path) = config_path {
+        command.env("NUNU_CONFIG", path);
+    }
+    for var in resolve_env(&file_config, &file_transport(&file_config)) {
+        if let Some(value) = var.value {
+            command.env(var.name, value);
+        }
+    }
+
+    let status = command
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to run {}: {e}", plugin.display()))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    if let Err(e) = dotenvy::dotenv() {
+        if !e.to_string().contains("not found") {
+            debug!("Error loading .env file: {e}");
+        }
+    } else {
+        debug!("Loaded environment from .env file");
+    }
+
+    // Deprecated values are checked while the command line is parsed
+    deprecations::set_strict(deprecations::strict_requested(
+        std::env::args_os(),
+        |name| std::env::var(name).ok(),
+    ));
+    // Deprecated environment variables are read by the flags of their replacements
+    let renamed_env = deprecations::check_env(|name| std::env::var(name).ok());
+    let cli = parse_cli(renamed_env.as_deref().unwrap_or_default());
+
+    // Initialize logger based on verbose flag
+    // 0: warn/error only (clean 2-line display)
+    // 1: info level (general progress)
+    // 2: debug level (detailed debugging)
+    // 3+: trace level (maximum detail)
+    // `--log` then overrides the level of individual subsystems
+    let log_level = match cli.verbose {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level));
+    if cli.verbose > 0 || cli.log.is_some() {
+        logger.format(|buf, record| writeln!(buf, "[{}] {}", record.level(), record.args()));
+    }
+    i18n::set_locale(
+        cli.lang
+            .unwrap_or_else(|| Locale::detect(|name| std::env::var(name).ok())),
+    );
+    plain::set_plain(cli.plain || std::env::var("TERM").is_ok_and(|term| term == "dumb"));
+    if plain::is_plain() {
+        logger.write_style(env_logger::WriteStyle::Never);
+        logger.format(|buf, record| {
+            let message = plain::strip_decoration(&record.args().to_string());
+            writeln!(buf, "[{}] {message}", record.level())
+        });
+    }
+    if let Some(spec) = &cli.log {
+        if let Some(level) = spec.default {
+            logger.filter_level(level);
+        }
+        for (target, level) in &spec.targets {
+            logger.filter_module(target, *level);
         }
+    }
+    logging::init(logger.build(), cli.log_file.as_deref())?;
+
+    let json_errors = cli.json_errors || cli.format.is_json();
+    deprecations::start_logging();
+    deprecations::check_args(std::env::args_os())
+        .and(renamed_env.map(drop))
+        .unwrap_or_else(|e| exit_with_error(&e.into(), json_errors));
+
+    #[cfg(feature = "e2e")]
+    let mut cli = cli;
+    #[cfg(feature = "e2e")]
+    let stub = if cli.e2e_local {
+        Some(start_e2e_local(&mut cli.command).await?)
+    } else {
+        None
+    };
+
+    let result: Result<()> = match cli.command {
+        Commands::Upload(args) => run_upload(cli.verbose, cli.config.as_ref(), cli.format, *args)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                let message = t!("upload.failed", error = e);
+                e.context(message)
+            }),
+        Commands::Builds { command } => run_builds(cli.config.as_ref(), command).await,
+        Commands::Retention { command } => {
+            run_retention(cli.config.as_ref(), cli.format, command).await
+        }
+        Commands::Symbols { command } => {
+            run_symbols(cli.config.as_ref(), cli.format, command).await
+        }
+        Commands::Uploads { command } => run_uploads(command).await,
+        Commands::Receipts { command } => {
+            run_receipts(cli.config.as_ref(), cli.format, command).await
+        }
+        Commands::Integrations { command } => {
+            run_integrations(cli.config.as_ref(), cli.format, command)
+        }
+        Commands::Plugin(args) => run_plugin(cli.config.as_ref(), cli.verbose, cli.format, &args),
     };
 
+    #[cfg(feature = "e2e")]
+    if let Some(stub) = stub {
+        print_e2e_builds(&stub.builds());
+    }
+
     match result {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            error!("Upload failed: {e}");
-            std::process::exit(1);
+        Ok(()) => Ok(()),
+        Err(e) => exit_with_error(&e, json_errors),
+    }
+}
+
+/// Parse the command line, with the flags whose environment variable is unset reading the
+/// deprecated variable it was `renamed` from instead
+fn parse_cli(renamed: &[&'static Deprecation]) -> Cli {
+    fn read_renamed(command: clap::Command, deprecation: &'static Deprecation) -> clap::Command {
+        command
+            .mut_args(|arg| {
+                if arg.get_env() == Some(OsStr::new(deprecation.replacement)) {
+                    arg.env(deprecation.name)
+                } else {
+                    arg
+                }
+            })
+            .mut_subcommands(|command| read_renamed(command, deprecation))
+    }
+
+    let mut command = renamed.iter().fold(Cli::command(), |command, deprecation| {
+        read_renamed(command, deprecation)
+    });
+    let matches = command.get_matches_mut();
+    Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut command).exit())
+}
+
+/// Report `error` as a log message or, with `json`, as an [`ErrorReport`] on stderr, and exit
+fn exit_with_error(error: &anyhow::Error, json: bool) -> ! {
+    if json {
+        let report = ErrorReport::new(error.to_string(), error.as_ref());
+        match serde_json::to_string(&report) {
+            Ok(report) => eprintln!("{report}"),
+            Err(_) => error!("{error}"),
         }
+    } else {
+        error!("{error}");
+    }
+    std::process::exit(1);
+}
+
+/// Start the stub API of `--e2e-local` and point the API options of `command` at it
+#[cfg(feature = "e2e")]
+async fn start_e2e_local(command: &mut Commands) -> Result<nunu_cli::e2e::StubApi> {
+    use nunu_cli::e2e::{E2E_PROJECT_ID, E2E_TOKEN, Storage, StubApi};
+
+    let storage = Storage::from_env(|name| std::env::var(name).ok());
+    let stub = StubApi::start(storage.clone()).await?;
+    match &storage {
+        Storage::Local => errln!("🧪 e2e mode: stub API at {}", stub.api_url()),
+        Storage::S3(s3) => errln!(
+            "🧪 e2e mode: stub API at {}, storing uploads in {}/{}",
+            stub.api_url(),
+            s3.endpoint,
+            s3.bucket
+        ),
+    }
+    if let Some(api) = command.api_args_mut() {
+        api.api_url = Some(stub.api_url());
+        api.token = Some(E2E_TOKEN.to_string());
+        api.project_id = Some(E2E_PROJECT_ID.to_string());
+    }
+    Ok(stub)
+}
+
+/// List what the `--e2e-local` stub stored, to compare with the uploaded files
+#[cfg(feature = "e2e")]
+fn print_e2e_builds(builds: &[nunu_cli::e2e::StoredBuild]) {
+    for build in builds {
+        errln!(
+            "🧪 stored {} as {} ({}, {}, sha256 {})",
+            build.file_name,
+            build.build_id,
+            format_size(build.size),
+            build
+                .parts
+                .map_or_else(|| "single part".to_string(), |n| format!("{n} parts")),
+            build.sha256
+        );
     }
 }
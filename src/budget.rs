@@ -0,0 +1,155 @@
+//! Per-platform artifact size budgets (`--size-budget android=150MB,ios=200MB`)
+
+use crate::api::BuildPlatform;
+use crate::error::{Error, Result};
+use crate::units::parse_size;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Budget key covering both `ios-native` and `ios-simulator`
+const IOS_FAMILY: &str = "ios";
+
+/// What happens when an artifact exceeds its budget
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// Abort the upload before anything is sent
+    #[default]
+    Fail,
+    /// Log a warning and upload anyway
+    Warn,
+}
+
+impl FromStr for BudgetAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fail" => Ok(Self::Fail),
+            "warn" => Ok(Self::Warn),
+            _ => Err(Error::ConfigError(format!(
+                "Invalid size budget action '{s}' (expected fail or warn)"
+            ))),
+        }
+    }
+}
+
+/// One `PLATFORM=SIZE` budget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeBudget {
+    /// A platform name, or `ios` for every iOS platform
+    pub platform: String,
+    pub bytes: u64,
+}
+
+impl SizeBudget {
+    /// Validate a budget key: a platform name or `ios`, returned in canonical form
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not a known platform
+    pub fn parse_platform(platform: &str) -> Result<String> {
+        let platform = platform.trim();
+        if platform.eq_ignore_ascii_case(IOS_FAMILY) {
+            return Ok(IOS_FAMILY.to_string());
+        }
+        Ok(platform.parse::<BuildPlatform>()?.as_str().to_string())
+    }
+}
+
+impl FromStr for SizeBudget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (platform, size) = s.split_once('=').ok_or_else(|| {
+            Error::ConfigError(format!(
+                "Invalid size budget '{s}' (expected PLATFORM=SIZE, e.g. android=150MB)"
+            ))
+        })?;
+        Ok(Self {
+            platform: Self::parse_platform(platform)?,
+            bytes: parse_size(size)?,
+        })
+    }
+}
+
+/// Measured size of an artifact against its platform budget, attached to build metadata
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeBudgetCheck {
+    pub size: u64,
+    pub budget: u64,
+    pub exceeded: bool,
+}
+
+/// Budget for `platform`: its own entry, else the `ios` entry for iOS platforms
+#[must_use]
+pub fn budget_for(budgets: &BTreeMap<String, u64>, platform: &BuildPlatform) -> Option<u64> {
+    budgets.get(platform.as_str()).copied().or_else(|| {
+        matches!(
+            platform,
+            BuildPlatform::IosNative | BuildPlatform::IosSimulator
+        )
+        .then(|| budgets.get(IOS_FAMILY).copied())
+        .flatten()
+    })
+}
+
+/// Compare an artifact's size with its budget
+#[must_use]
+pub fn check_size_budget(size: u64, budget: u64) -> SizeBudgetCheck {
+    SizeBudgetCheck {
+        size,
+        budget,
+        exceeded: size > budget,
+    }
+}
+
+/// Size of a file, or the total size of the files in a directory (before archiving)
+///
+/// Symlinks are counted as links, not followed.
+///
+/// # Errors
+///
+/// Returns an error if the path or a directory entry cannot be read
+pub fn artifact_size(path: &Path) -> Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        total += artifact_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_budgets() {
+        let budgets: BTreeMap<String, u64> = ["android=150MB", "IOS=200MiB"]
+            .iter()
+            .map(|s| s.parse::<SizeBudget>().map(|b| (b.platform, b.bytes)))
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(
+            budget_for(&budgets, &BuildPlatform::Android),
+            Some(150_000_000)
+        );
+        assert_eq!(
+            budget_for(&budgets, &BuildPlatform::IosSimulator),
+            Some(200 << 20)
+        );
+        assert_eq!(budget_for(&budgets, &BuildPlatform::Windows), None);
+        assert!("android".parse::<SizeBudget>().is_err());
+        assert!("quest=1GB".parse::<SizeBudget>().is_err());
+
+        assert!(check_size_budget(150_000_001, 150_000_000).exceeded);
+        assert!(!check_size_budget(150_000_000, 150_000_000).exceeded);
+    }
+}
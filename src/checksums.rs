@@ -0,0 +1,125 @@
+use crate::error::Result;
+use crate::util::hex_encode;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// One uploaded file in a checksum manifest
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    pub file: String,
+    pub size: u64,
+    /// SHA-256 of the file as lowercase hex (`None` for directories, which are zipped on the fly)
+    pub sha256: Option<String>,
+    pub build_id: String,
+}
+
+/// Output format of a checksum manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumFormat {
+    /// `sha256sum`-compatible lines (`<sha256>  <file>`)
+    Text,
+    /// JSON array of [`ChecksumEntry`]
+    Json,
+}
+
+impl ChecksumFormat {
+    /// Pick the format from the manifest's file extension (`.json` or anything else for text)
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+        {
+            Self::Json
+        } else {
+            Self::Text
+        }
+    }
+}
+
+/// Compute the SHA-256 of a file as lowercase hex
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buf[..read]);
+    }
+
+    Ok(hex_encode(context.finish().as_ref()))
+}
+
+/// Render a checksum manifest, sorted by file name for reproducible output
+///
+/// Entries without a checksum are left out of the text format.
+///
+/// # Errors
+///
+/// Returns an error if JSON serialization fails
+pub fn render_checksums(entries: &[ChecksumEntry], format: ChecksumFormat) -> Result<String> {
+    let mut entries = entries.to_vec();
+    entries.sort_by(|a, b| a.file.cmp(&b.file));
+
+    match format {
+        ChecksumFormat::Json => Ok(serde_json::to_string_pretty(&entries)? + "\n"),
+        ChecksumFormat::Text => Ok(entries
+            .iter()
+            .filter_map(|e| e.sha256.as_ref().map(|sha| format!("{sha}  {}\n", e.file)))
+            .collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.apk");
+        std::fs::write(&path, b"abc").unwrap();
+        let sha = sha256_file(&path).unwrap();
+        assert_eq!(
+            sha,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let entries = vec![
+            ChecksumEntry {
+                file: "mac/Game.app".to_string(),
+                size: 10,
+                sha256: None,
+                build_id: "b2".to_string(),
+            },
+            ChecksumEntry {
+                file: "game.apk".to_string(),
+                size: 3,
+                sha256: Some(sha.clone()),
+                build_id: "b1".to_string(),
+            },
+        ];
+        assert_eq!(
+            render_checksums(&entries, ChecksumFormat::Text).unwrap(),
+            format!("{sha}  game.apk\n")
+        );
+
+        let json: Vec<ChecksumEntry> =
+            serde_json::from_str(&render_checksums(&entries, ChecksumFormat::Json).unwrap())
+                .unwrap();
+        assert_eq!(json[0].build_id, "b1");
+        assert_eq!(
+            ChecksumFormat::from_path(Path::new("out/manifest.json")),
+            ChecksumFormat::Json
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct CiMetadata {
     pub system: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -15,117 +17,1016 @@ pub struct CiMetadata {
     pub triggered_by: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub agent: Option<String>,
+    /// What triggered the run as reported by the CI system (e.g. `push`, `schedule`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+}
+
+impl CiMetadata {
+    /// Whether the run was started by a schedule (nightly/cron) rather than a push or user
+    #[must_use]
+    pub fn is_scheduled(&self) -> bool {
+        self.event.as_deref().is_some_and(|event| {
+            event.eq_ignore_ascii_case("schedule") || event.eq_ignore_ascii_case("cron")
+        })
+    }
+}
+
+/// `NUNU_CI_*` variables that override detected CI metadata, so build farms the CLI does
+/// not detect can describe their runs
+#[derive(Debug, Default)]
+struct CiOverrides {
+    system: Option<String>,
+    build_number: Option<String>,
+    job_name: Option<String>,
+    run_id: Option<String>,
+    run_url: Option<String>,
+    triggered_by: Option<String>,
+    agent: Option<String>,
+    event: Option<String>,
+}
+
+impl CiOverrides {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            system: var("NUNU_CI_SYSTEM"),
+            build_number: var("NUNU_CI_BUILD_NUMBER"),
+            job_name: var("NUNU_CI_JOB_NAME"),
+            run_id: var("NUNU_CI_RUN_ID"),
+            run_url: var("NUNU_CI_RUN_URL"),
+            triggered_by: var("NUNU_CI_TRIGGERED_BY"),
+            agent: var("NUNU_CI_AGENT"),
+            event: var("NUNU_CI_EVENT"),
+        }
+    }
+
+    fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+    }
+
+    /// Apply the overrides on top of `detected`
+    ///
+    /// Without a detected CI system the overrides alone describe the run, with the system
+    /// `custom` unless `NUNU_CI_SYSTEM` names it.
+    fn apply(self, detected: Option<CiMetadata>) -> Option<CiMetadata> {
+        let fields = [
+            self.build_number,
+            self.job_name,
+            self.run_id,
+            self.run_url,
+            self.triggered_by,
+            self.agent,
+            self.event,
+        ];
+        if self.system.is_none() && fields.iter().all(Option::is_none) {
+            return detected;
+        }
+
+        let mut ci = detected.unwrap_or_else(|| CiMetadata {
+            system: "custom".to_string(),
+            ..CiMetadata::default()
+        });
+        if let Some(system) = self.system {
+            ci.system = system;
+        }
+        let [
+            build_number,
+            job_name,
+            run_id,
+            run_url,
+            triggered_by,
+            agent,
+            event,
+        ] = fields;
+        ci.build_number = build_number.or(ci.build_number);
+        ci.job_name = job_name.or(ci.job_name);
+        ci.run_id = run_id.or(ci.run_id);
+        ci.run_url = run_url.or(ci.run_url);
+        ci.triggered_by = triggered_by.or(ci.triggered_by);
+        ci.agent = agent.or(ci.agent);
+        ci.event = event.or(ci.event);
+        Some(ci)
+    }
 }
 
 /// Detect and collect CI/CD metadata from environment variables
+///
+/// `NUNU_CI_*` variables take precedence over what is detected.
 #[must_use]
 pub fn collect_ci_metadata() -> Option<CiMetadata> {
-    // GitHub Actions
-    if std::env::var("GITHUB_ACTIONS").ok().as_deref() == Some("true") {
-        return Some(CiMetadata {
-            system: "github-actions".to_string(),
-            build_number: std::env::var("GITHUB_RUN_NUMBER").ok(),
-            job_name: std::env::var("GITHUB_WORKFLOW").ok(),
-            run_id: std::env::var("GITHUB_RUN_ID").ok(),
-            run_url: std::env::var("GITHUB_SERVER_URL").ok().and_then(|url| {
-                std::env::var("GITHUB_REPOSITORY").ok().and_then(|repo| {
-                    std::env::var("GITHUB_RUN_ID")
-                        .ok()
-                        .map(|id| format!("{url}/{repo}/actions/runs/{id}"))
-                })
+    let detected = detect_ci(|name| std::env::var(name).ok()).or_else(collect_unity_cloud_build);
+    CiOverrides::from_env().apply(detected)
+}
+
+/// The CI system whose variables `var` returns
+///
+/// Systems are tried in order, and those sharing variable names (Woodpecker's `CI_*` with
+/// GitLab's, `BUILD_NUMBER` of Jenkins, TeamCity and Codemagic) are told apart by the
+/// variables only they set.
+fn detect_ci(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    collect_github_actions(&var)
+        .or_else(|| collect_jenkins(&var))
+        .or_else(|| collect_gitlab_ci(&var))
+        .or_else(|| collect_circleci(&var))
+        .or_else(|| collect_travis_ci(&var))
+        .or_else(|| collect_azure_pipelines(&var))
+        .or_else(|| collect_bitrise(&var))
+        .or_else(|| collect_buildkite(&var))
+        .or_else(|| collect_teamcity(&var))
+        .or_else(|| collect_semaphore(&var))
+        .or_else(|| collect_codebuild(&var))
+        .or_else(|| collect_codemagic(&var))
+        .or_else(|| collect_cirrus(&var))
+        .or_else(|| collect_bamboo(&var))
+        .or_else(|| collect_harness(&var))
+        .or_else(|| collect_woodpecker(&var))
+        .or_else(|| collect_xcode_cloud(&var))
+}
+
+fn collect_github_actions(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "GITHUB_ACTIONS", "true") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "github-actions".to_string(),
+        build_number: var("GITHUB_RUN_NUMBER"),
+        job_name: var("GITHUB_WORKFLOW"),
+        run_id: var("GITHUB_RUN_ID"),
+        run_url: var("GITHUB_SERVER_URL").and_then(|url| {
+            var("GITHUB_REPOSITORY").and_then(|repo| {
+                var("GITHUB_RUN_ID").map(|id| format!("{url}/{repo}/actions/runs/{id}"))
+            })
+        }),
+        triggered_by: var("GITHUB_ACTOR"),
+        agent: var("RUNNER_NAME"),
+        event: var("GITHUB_EVENT_NAME"),
+    })
+}
+
+fn collect_jenkins(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_set(&var, "JENKINS_HOME") && !env_set(&var, "JENKINS_URL") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "jenkins".to_string(),
+        build_number: var("BUILD_NUMBER"),
+        job_name: var("JOB_NAME"),
+        run_id: var("BUILD_ID"),
+        run_url: var("BUILD_URL"),
+        triggered_by: var("BUILD_USER"),
+        agent: var("NODE_NAME"),
+        event: None,
+    })
+}
+
+fn collect_gitlab_ci(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "GITLAB_CI", "true") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "gitlab-ci".to_string(),
+        build_number: var("CI_PIPELINE_IID"),
+        job_name: var("CI_JOB_NAME"),
+        run_id: var("CI_PIPELINE_ID"),
+        run_url: var("CI_PIPELINE_URL"),
+        triggered_by: var("GITLAB_USER_LOGIN"),
+        agent: var("CI_RUNNER_DESCRIPTION"),
+        event: var("CI_PIPELINE_SOURCE"),
+    })
+}
+
+fn collect_circleci(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "CIRCLECI", "true") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "circleci".to_string(),
+        build_number: var("CIRCLE_BUILD_NUM"),
+        job_name: var("CIRCLE_JOB"),
+        run_id: var("CIRCLE_WORKFLOW_ID"),
+        run_url: var("CIRCLE_BUILD_URL"),
+        triggered_by: var("CIRCLE_USERNAME"),
+        agent: var("CIRCLE_NODE_INDEX"),
+        event: None,
+    })
+}
+
+fn collect_travis_ci(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "TRAVIS", "true") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "travis".to_string(),
+        build_number: var("TRAVIS_BUILD_NUMBER"),
+        job_name: var("TRAVIS_JOB_NAME"),
+        run_id: var("TRAVIS_JOB_ID"),
+        run_url: var("TRAVIS_BUILD_WEB_URL"),
+        triggered_by: None,
+        agent: None,
+        event: var("TRAVIS_EVENT_TYPE"),
+    })
+}
+
+fn collect_azure_pipelines(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "TF_BUILD", "True") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "azure-pipelines".to_string(),
+        build_number: var("BUILD_BUILDNUMBER"),
+        job_name: var("BUILD_DEFINITIONNAME"),
+        run_id: var("BUILD_BUILDID"),
+        run_url: var("SYSTEM_TEAMFOUNDATIONCOLLECTIONURI").and_then(|uri| {
+            var("SYSTEM_TEAMPROJECT").and_then(|project| {
+                var("BUILD_BUILDID").map(|id| format!("{uri}{project}/_build/results?buildId={id}"))
+            })
+        }),
+        triggered_by: var("BUILD_REQUESTEDFOR"),
+        agent: var("AGENT_NAME"),
+        event: var("BUILD_REASON"),
+    })
+}
+
+fn collect_bitrise(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "BITRISE_IO", "true") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "bitrise".to_string(),
+        build_number: var("BITRISE_BUILD_NUMBER"),
+        job_name: var("BITRISE_TRIGGERED_WORKFLOW_ID"),
+        run_id: var("BITRISE_BUILD_SLUG"),
+        run_url: var("BITRISE_BUILD_URL"),
+        triggered_by: var("BITRISE_TRIGGERED_WORKFLOW_TITLE"),
+        agent: None,
+        event: None,
+    })
+}
+
+fn collect_buildkite(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "BUILDKITE", "true") {
+        return None;
+    }
+
+    let pipeline = var("BUILDKITE_PIPELINE_SLUG");
+    let job_name = match (pipeline, var("BUILDKITE_LABEL")) {
+        (Some(pipeline), Some(label)) => Some(format!("{pipeline}/{label}")),
+        (pipeline, label) => pipeline.or(label),
+    };
+
+    Some(CiMetadata {
+        system: "buildkite".to_string(),
+        build_number: var("BUILDKITE_BUILD_NUMBER"),
+        job_name,
+        run_id: var("BUILDKITE_BUILD_ID"),
+        run_url: var("BUILDKITE_BUILD_URL"),
+        triggered_by: var("BUILDKITE_BUILD_CREATOR"),
+        agent: var("BUILDKITE_AGENT_NAME"),
+        event: var("BUILDKITE_SOURCE"),
+    })
+}
+
+fn collect_teamcity(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_set(&var, "TEAMCITY_VERSION") {
+        return None;
+    }
+
+    // Build and server details are only in the build's properties files
+    let build = var("TEAMCITY_BUILD_PROPERTIES_FILE")
+        .and_then(|path| read_properties(Path::new(&path)))
+        .unwrap_or_default();
+    let configuration = build
+        .get("teamcity.configuration.properties.file")
+        .and_then(|path| read_properties(Path::new(path)))
+        .unwrap_or_default();
+    let build_id = build.get("teamcity.build.id").cloned();
+
+    Some(CiMetadata {
+        system: "teamcity".to_string(),
+        build_number: var("BUILD_NUMBER"),
+        job_name: var("TEAMCITY_BUILDCONF_NAME"),
+        run_url: configuration
+            .get("teamcity.serverUrl")
+            .zip(build_id.as_ref())
+            .map(|(server, id)| {
+                format!("{}/viewLog.html?buildId={id}", server.trim_end_matches('/'))
             }),
-            triggered_by: std::env::var("GITHUB_ACTOR").ok(),
-            agent: std::env::var("RUNNER_NAME").ok(),
+        run_id: build_id,
+        triggered_by: build.get("teamcity.build.triggeredBy").cloned(),
+        agent: build.get("agent.name").cloned(),
+        event: None,
+    })
+}
+
+fn collect_semaphore(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "SEMAPHORE", "true") {
+        return None;
+    }
+
+    let workflow_id = var("SEMAPHORE_WORKFLOW_ID");
+    let run_url = var("SEMAPHORE_ORGANIZATION_URL")
+        .zip(workflow_id.as_ref())
+        .map(|(org, id)| {
+            let url = format!("{}/workflows/{id}", org.trim_end_matches('/'));
+            match var("SEMAPHORE_PIPELINE_ID") {
+                Some(pipeline) => format!("{url}?pipeline_id={pipeline}"),
+                None => url,
+            }
         });
+
+    // The trigger is reported as one boolean per kind; git events are told apart by ref type
+    let event = [
+        ("SEMAPHORE_WORKFLOW_TRIGGERED_BY_SCHEDULE", "schedule"),
+        ("SEMAPHORE_WORKFLOW_TRIGGERED_BY_API", "api"),
+        ("SEMAPHORE_WORKFLOW_TRIGGERED_BY_MANUAL_RUN", "manual"),
+    ]
+    .into_iter()
+    .find(|(name, _)| env_is(&var, name, "true"))
+    .map(|(_, event)| event.to_string())
+    .or_else(|| var("SEMAPHORE_GIT_REF_TYPE"));
+
+    Some(CiMetadata {
+        system: "semaphore".to_string(),
+        build_number: var("SEMAPHORE_WORKFLOW_NUMBER"),
+        job_name: var("SEMAPHORE_JOB_NAME"),
+        run_id: workflow_id,
+        run_url,
+        // Set but empty unless the pipeline was promoted
+        triggered_by: var("SEMAPHORE_PIPELINE_PROMOTED_BY")
+            .filter(|user| !user.is_empty())
+            .or_else(|| var("SEMAPHORE_GIT_COMMIT_AUTHOR"))
+            .filter(|user| !user.is_empty()),
+        agent: var("SEMAPHORE_AGENT_MACHINE_TYPE"),
+        event,
+    })
+}
+
+fn collect_codebuild(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    // `CODEBUILD_BUILD_ID` is `<project>:<uuid>`
+    let build_id = var("CODEBUILD_BUILD_ID")?;
+    let project = build_id
+        .split_once(':')
+        .map(|(project, _)| project.to_string());
+
+    Some(CiMetadata {
+        system: "codebuild".to_string(),
+        build_number: var("CODEBUILD_BUILD_NUMBER"),
+        job_name: project,
+        run_url: var("CODEBUILD_BUILD_URL"),
+        run_id: Some(build_id),
+        // A user or role name, `codepipeline/<pipeline>` or `GitHub-Hookshot/...` for webhooks
+        triggered_by: var("CODEBUILD_INITIATOR"),
+        agent: None,
+        event: var("CODEBUILD_WEBHOOK_EVENT").map(|event| event.to_ascii_lowercase()),
+    })
+}
+
+fn collect_codemagic(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    let build_id = var("CM_BUILD_ID")?;
+
+    Some(CiMetadata {
+        system: "codemagic".to_string(),
+        build_number: var("BUILD_NUMBER"),
+        job_name: var("CM_WORKFLOW_NAME"),
+        run_url: var("CM_PROJECT_ID")
+            .map(|project| format!("https://codemagic.io/app/{project}/build/{build_id}")),
+        run_id: Some(build_id),
+        triggered_by: None,
+        agent: None,
+        event: env_is(&var, "CM_PULL_REQUEST", "true").then(|| "pull_request".to_string()),
+    })
+}
+
+fn collect_cirrus(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    if !env_is(&var, "CIRRUS_CI", "true") {
+        return None;
     }
 
-    // Jenkins
-    if std::env::var("JENKINS_HOME").is_ok() || std::env::var("JENKINS_URL").is_ok() {
-        return Some(CiMetadata {
+    let build_id = var("CIRRUS_BUILD_ID");
+    let event = if env_set(&var, "CIRRUS_CRON") {
+        Some("schedule".to_string())
+    } else if env_set(&var, "CIRRUS_PR") {
+        Some("pull_request".to_string())
+    } else {
+        None
+    };
+
+    Some(CiMetadata {
+        system: "cirrus".to_string(),
+        run_url: build_id
+            .as_ref()
+            .map(|id| format!("https://cirrus-ci.com/build/{id}")),
+        build_number: build_id,
+        job_name: var("CIRRUS_TASK_NAME"),
+        run_id: var("CIRRUS_TASK_ID"),
+        triggered_by: None,
+        agent: None,
+        event,
+    })
+}
+
+fn collect_bamboo(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    // `bamboo_buildKey` is the job key, e.g. `PROJ-PLAN-JOB1`
+    var("bamboo_buildKey")?;
+
+    Some(CiMetadata {
+        system: "bamboo".to_string(),
+        build_number: var("bamboo_buildNumber"),
+        job_name: var("bamboo_planName"),
+        run_id: var("bamboo_buildResultKey"),
+        run_url: var("bamboo_buildResultsUrl"),
+        triggered_by: var("bamboo_ManualBuildTriggerReason_userName"),
+        agent: var("bamboo_agentId"),
+        event: env_set(&var, "bamboo_repository_pr_key").then(|| "pull_request".to_string()),
+    })
+}
+
+fn collect_harness(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    let build_id = var("HARNESS_BUILD_ID")?;
+
+    // Identifiers of the pipeline and the stage running the step, e.g. `release/build_android`
+    let job_name = match (var("HARNESS_PIPELINE_ID"), var("HARNESS_STAGE_ID")) {
+        (Some(pipeline), Some(stage)) => Some(format!("{pipeline}/{stage}")),
+        (pipeline, stage) => pipeline.or(stage),
+    };
+
+    Some(CiMetadata {
+        system: "harness".to_string(),
+        build_number: Some(build_id),
+        job_name,
+        run_id: var("HARNESS_EXECUTION_ID"),
+        // Harness CI also sets Drone's variables for compatibility with Drone plugins
+        run_url: var("CI_BUILD_LINK").or_else(|| var("DRONE_BUILD_LINK")),
+        triggered_by: None,
+        agent: None,
+        event: var("DRONE_BUILD_EVENT"),
+    })
+}
+
+fn collect_woodpecker(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    // Woodpecker's `CI_*` variables overlap with GitLab's; only `CI` tells them apart
+    if !env_is(&var, "CI", "woodpecker") {
+        return None;
+    }
+
+    Some(CiMetadata {
+        system: "woodpecker".to_string(),
+        build_number: var("CI_PIPELINE_NUMBER"),
+        job_name: var("CI_WORKFLOW_NAME"),
+        run_id: None,
+        run_url: var("CI_PIPELINE_URL"),
+        triggered_by: None,
+        agent: var("CI_MACHINE"),
+        event: var("CI_PIPELINE_EVENT"),
+    })
+}
+
+fn collect_xcode_cloud(var: impl Fn(&str) -> Option<String>) -> Option<CiMetadata> {
+    // `CI_XCODEBUILD_ACTION` is unset in post-clone scripts, `CI_WORKFLOW` always set
+    if !env_set(&var, "CI_XCODEBUILD_ACTION") && !env_set(&var, "CI_WORKFLOW") {
+        return None;
+    }
+
+    let event = if env_set(&var, "CI_PULL_REQUEST_NUMBER") {
+        Some("pull_request".to_string())
+    } else if env_set(&var, "CI_TAG") {
+        Some("tag".to_string())
+    } else {
+        var("CI_XCODEBUILD_ACTION")
+    };
+
+    Some(CiMetadata {
+        system: "xcode-cloud".to_string(),
+        build_number: var("CI_BUILD_NUMBER"),
+        job_name: var("CI_WORKFLOW"),
+        run_id: var("CI_BUILD_ID"),
+        run_url: var("CI_BUILD_URL"),
+        triggered_by: None,
+        agent: None,
+        event,
+    })
+}
+
+/// Where Unity Cloud Build (Build Automation) writes its build manifest in the project,
+/// relative to the workspace the build and its post-build scripts run in
+const UNITY_CLOUD_BUILD_MANIFESTS: [&str; 2] = [
+    "Assets/__UnityCloud__/Resources/UnityCloudBuildManifest.json",
+    "Assets/Resources/UnityCloudBuildManifest.json",
+];
+
+/// Build manifest Unity Cloud Build injects into the project (`UnityCloudBuildManifest.json`)
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnityCloudBuildManifest {
+    pub scm_commit_id: Option<String>,
+    pub scm_branch: Option<String>,
+    pub build_number: Option<String>,
+    pub project_id: Option<String>,
+    pub cloud_build_target_name: Option<String>,
+}
+
+/// Read the Unity Cloud Build manifest if the CLI runs inside a cloud build
+pub(crate) fn unity_cloud_build_manifest() -> Option<UnityCloudBuildManifest> {
+    UNITY_CLOUD_BUILD_MANIFESTS.iter().find_map(|path| {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    })
+}
+
+fn collect_unity_cloud_build() -> Option<CiMetadata> {
+    unity_cloud_build_manifest().map(unity_cloud_build_metadata)
+}
+
+fn unity_cloud_build_metadata(manifest: UnityCloudBuildManifest) -> CiMetadata {
+    // Build numbers count per build target, so the project and target identify the run
+    let run_id = match (&manifest.project_id, &manifest.cloud_build_target_name) {
+        (Some(project), Some(target)) => Some(format!(
+            "{project}/{target}/{}",
+            manifest.build_number.as_deref().unwrap_or_default()
+        )),
+        _ => None,
+    };
+
+    CiMetadata {
+        system: "unity-cloud-build".to_string(),
+        build_number: manifest.build_number,
+        job_name: manifest.cloud_build_target_name,
+        run_id,
+        run_url: None,
+        triggered_by: None,
+        agent: None,
+        event: None,
+    }
+}
+
+/// Read a Java properties file (`key=value` lines with backslash escapes)
+fn read_properties(path: &Path) -> Option<HashMap<String, String>> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    Some(
+        contents
+            .lines()
+            .map(str::trim_start)
+            .filter(|line| !line.is_empty() && !line.starts_with(['#', '!']))
+            .filter_map(|line| {
+                let (key, value) = split_property(line)?;
+                Some((unescape_property(key), unescape_property(value)))
+            })
+            .collect(),
+    )
+}
+
+/// Split at the first unescaped `=` or `:`
+fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '=' | ':' if !escaped => {
+                return Some((line[..i].trim_end(), line[i + 1..].trim_start()));
+            }
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape_property(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+fn env_is(var: impl Fn(&str) -> Option<String>, name: &str, value: &str) -> bool {
+    var(name).as_deref() == Some(value)
+}
+
+fn env_set(var: impl Fn(&str) -> Option<String>, name: &str) -> bool {
+    var(name).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lookup of the environment variables in `pairs`
+    fn env(pairs: &'static [(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        |name| {
+            pairs
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| (*v).to_string())
+        }
+    }
+
+    #[test]
+    fn test_collect_buildkite() {
+        let ci = detect_ci(env(&[
+            ("BUILDKITE", "true"),
+            ("BUILDKITE_PIPELINE_SLUG", "game"),
+            ("BUILDKITE_LABEL", ":android: Build"),
+            ("BUILDKITE_BUILD_NUMBER", "88"),
+            ("BUILDKITE_BUILD_ID", "0190-abcd"),
+            (
+                "BUILDKITE_BUILD_URL",
+                "https://buildkite.com/studio/game/builds/88",
+            ),
+            ("BUILDKITE_BUILD_CREATOR", "Ada"),
+            ("BUILDKITE_AGENT_NAME", "mac-3"),
+            ("BUILDKITE_SOURCE", "schedule"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "buildkite");
+        assert_eq!(ci.build_number.as_deref(), Some("88"));
+        assert_eq!(ci.job_name.as_deref(), Some("game/:android: Build"));
+        assert_eq!(ci.run_id.as_deref(), Some("0190-abcd"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://buildkite.com/studio/game/builds/88")
+        );
+        assert_eq!(ci.triggered_by.as_deref(), Some("Ada"));
+        assert_eq!(ci.agent.as_deref(), Some("mac-3"));
+        assert!(ci.is_scheduled());
+
+        let ci = collect_buildkite(env(&[("BUILDKITE", "true"), ("BUILDKITE_LABEL", "Build")]));
+        assert_eq!(ci.unwrap().job_name.as_deref(), Some("Build"));
+        assert!(collect_buildkite(env(&[("BUILDKITE", "false")])).is_none());
+    }
+
+    #[test]
+    fn test_read_properties() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build.properties");
+        std::fs::write(
+            &path,
+            "#TeamCity build properties\nteamcity.build.id=4711\nteamcity.serverUrl=https\\://ci.example.com\\:8111\nagent.name = Agent 7\n",
+        )
+        .unwrap();
+
+        let properties = read_properties(&path).unwrap();
+        assert_eq!(properties["teamcity.build.id"], "4711");
+        assert_eq!(
+            properties["teamcity.serverUrl"],
+            "https://ci.example.com:8111"
+        );
+        assert_eq!(properties["agent.name"], "Agent 7");
+        assert_eq!(properties.len(), 3);
+    }
+
+    #[test]
+    fn test_ci_overrides() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            CiOverrides::from_vars(move |name| {
+                pairs
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| (*v).to_string())
+            })
+        };
+        let detected = CiMetadata {
             system: "jenkins".to_string(),
-            build_number: std::env::var("BUILD_NUMBER").ok(),
-            job_name: std::env::var("JOB_NAME").ok(),
-            run_id: std::env::var("BUILD_ID").ok(),
-            run_url: std::env::var("BUILD_URL").ok(),
-            triggered_by: std::env::var("BUILD_USER").ok(),
-            agent: std::env::var("NODE_NAME").ok(),
-        });
+            build_number: Some("7".to_string()),
+            job_name: Some("nightly".to_string()),
+            ..CiMetadata::default()
+        };
+
+        assert!(vars(&[]).apply(None).is_none());
+        let unchanged = vars(&[]).apply(Some(detected.clone())).unwrap();
+        assert_eq!(unchanged.build_number.as_deref(), Some("7"));
+
+        let merged = vars(&[("NUNU_CI_BUILD_NUMBER", "1234")])
+            .apply(Some(detected))
+            .unwrap();
+        assert_eq!(merged.system, "jenkins");
+        assert_eq!(merged.build_number.as_deref(), Some("1234"));
+        assert_eq!(merged.job_name.as_deref(), Some("nightly"));
+
+        let custom = vars(&[("NUNU_CI_RUN_URL", "https://farm/runs/9")])
+            .apply(None)
+            .unwrap();
+        assert_eq!(custom.system, "custom");
+        assert_eq!(custom.run_url.as_deref(), Some("https://farm/runs/9"));
     }
 
-    // GitLab CI
-    if std::env::var("GITLAB_CI").ok().as_deref() == Some("true") {
-        return Some(CiMetadata {
-            system: "gitlab-ci".to_string(),
-            build_number: std::env::var("CI_PIPELINE_IID").ok(),
-            job_name: std::env::var("CI_JOB_NAME").ok(),
-            run_id: std::env::var("CI_PIPELINE_ID").ok(),
-            run_url: std::env::var("CI_PIPELINE_URL").ok(),
-            triggered_by: std::env::var("GITLAB_USER_LOGIN").ok(),
-            agent: std::env::var("CI_RUNNER_DESCRIPTION").ok(),
-        });
+    #[test]
+    fn test_unity_cloud_build_metadata() {
+        let manifest: UnityCloudBuildManifest = serde_json::from_str(
+            r#"{"scmCommitId": "0123abc", "scmBranch": "main", "buildNumber": "42", "buildStartTime": "2024-03-01T10:00:00Z", "projectId": "space-game", "bundleId": "com.example.space", "unityVersion": "2022.3.14f1", "cloudBuildTargetName": "android-release"}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.scm_branch.as_deref(), Some("main"));
+
+        let ci = unity_cloud_build_metadata(manifest);
+        assert_eq!(ci.build_number.as_deref(), Some("42"));
+        assert_eq!(ci.job_name.as_deref(), Some("android-release"));
+        assert_eq!(ci.run_id.as_deref(), Some("space-game/android-release/42"));
     }
 
-    // CircleCI
-    if std::env::var("CIRCLECI").ok().as_deref() == Some("true") {
-        return Some(CiMetadata {
-            system: "circleci".to_string(),
-            build_number: std::env::var("CIRCLE_BUILD_NUM").ok(),
-            job_name: std::env::var("CIRCLE_JOB").ok(),
-            run_id: std::env::var("CIRCLE_WORKFLOW_ID").ok(),
-            run_url: std::env::var("CIRCLE_BUILD_URL").ok(),
-            triggered_by: std::env::var("CIRCLE_USERNAME").ok(),
-            agent: std::env::var("CIRCLE_NODE_INDEX").ok(),
-        });
+    #[test]
+    fn test_collect_semaphore() {
+        let ci = detect_ci(env(&[
+            ("SEMAPHORE", "true"),
+            ("SEMAPHORE_WORKFLOW_ID", "wf-1"),
+            ("SEMAPHORE_PIPELINE_ID", "pl-2"),
+            (
+                "SEMAPHORE_ORGANIZATION_URL",
+                "https://studio.semaphoreci.com/",
+            ),
+            ("SEMAPHORE_WORKFLOW_NUMBER", "15"),
+            ("SEMAPHORE_JOB_NAME", "Build Android"),
+            ("SEMAPHORE_WORKFLOW_TRIGGERED_BY_SCHEDULE", "true"),
+            ("SEMAPHORE_GIT_REF_TYPE", "branch"),
+            ("SEMAPHORE_PIPELINE_PROMOTED_BY", ""),
+            ("SEMAPHORE_GIT_COMMIT_AUTHOR", "ada"),
+            ("SEMAPHORE_AGENT_MACHINE_TYPE", "e1-standard-2"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "semaphore");
+        assert_eq!(ci.build_number.as_deref(), Some("15"));
+        assert_eq!(ci.job_name.as_deref(), Some("Build Android"));
+        assert_eq!(ci.run_id.as_deref(), Some("wf-1"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://studio.semaphoreci.com/workflows/wf-1?pipeline_id=pl-2")
+        );
+        assert_eq!(ci.triggered_by.as_deref(), Some("ada"));
+        assert_eq!(ci.agent.as_deref(), Some("e1-standard-2"));
+        assert!(ci.is_scheduled());
+
+        // A promotion's user is reported in place of the commit author
+        let ci = collect_semaphore(env(&[
+            ("SEMAPHORE", "true"),
+            ("SEMAPHORE_PIPELINE_PROMOTED_BY", "grace"),
+            ("SEMAPHORE_GIT_COMMIT_AUTHOR", "ada"),
+            ("SEMAPHORE_GIT_REF_TYPE", "tag"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.triggered_by.as_deref(), Some("grace"));
+        assert_eq!(ci.event.as_deref(), Some("tag"));
+        assert_eq!(ci.run_url, None);
     }
 
-    // Travis CI
-    if std::env::var("TRAVIS").ok().as_deref() == Some("true") {
-        return Some(CiMetadata {
-            system: "travis".to_string(),
-            build_number: std::env::var("TRAVIS_BUILD_NUMBER").ok(),
-            job_name: std::env::var("TRAVIS_JOB_NAME").ok(),
-            run_id: std::env::var("TRAVIS_JOB_ID").ok(),
-            run_url: std::env::var("TRAVIS_BUILD_WEB_URL").ok(),
-            triggered_by: None,
-            agent: None,
-        });
+    #[test]
+    fn test_collect_codebuild() {
+        let ci = detect_ci(env(&[
+            ("CODEBUILD_BUILD_ID", "game-android:4f2c-11ee"),
+            ("CODEBUILD_BUILD_NUMBER", "31"),
+            (
+                "CODEBUILD_BUILD_URL",
+                "https://eu-west-1.console.aws.amazon.com/codesuite/codebuild/projects/game-android/build/game-android%3A4f2c-11ee",
+            ),
+            ("CODEBUILD_INITIATOR", "codepipeline/release"),
+            ("CODEBUILD_WEBHOOK_EVENT", "PULL_REQUEST_CREATED"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "codebuild");
+        assert_eq!(ci.build_number.as_deref(), Some("31"));
+        assert_eq!(ci.job_name.as_deref(), Some("game-android"));
+        assert_eq!(ci.run_id.as_deref(), Some("game-android:4f2c-11ee"));
+        assert!(ci.run_url.unwrap().ends_with("game-android%3A4f2c-11ee"));
+        assert_eq!(ci.triggered_by.as_deref(), Some("codepipeline/release"));
+        assert_eq!(ci.event.as_deref(), Some("pull_request_created"));
+
+        assert!(collect_codebuild(env(&[("CODEBUILD_BUILD_NUMBER", "31")])).is_none());
     }
 
-    // Azure Pipelines
-    if std::env::var("TF_BUILD").ok().as_deref() == Some("True") {
-        return Some(CiMetadata {
-            system: "azure-pipelines".to_string(),
-            build_number: std::env::var("BUILD_BUILDNUMBER").ok(),
-            job_name: std::env::var("BUILD_DEFINITIONNAME").ok(),
-            run_id: std::env::var("BUILD_BUILDID").ok(),
-            run_url: std::env::var("SYSTEM_TEAMFOUNDATIONCOLLECTIONURI")
-                .ok()
-                .and_then(|uri| {
-                    std::env::var("SYSTEM_TEAMPROJECT")
-                        .ok()
-                        .and_then(|project| {
-                            std::env::var("BUILD_BUILDID")
-                                .ok()
-                                .map(|id| format!("{uri}{project}/_build/results?buildId={id}"))
-                        })
-                }),
-            triggered_by: std::env::var("BUILD_REQUESTEDFOR").ok(),
-            agent: std::env::var("AGENT_NAME").ok(),
-        });
+    #[test]
+    fn test_collect_codemagic() {
+        let ci = detect_ci(env(&[
+            ("CI", "true"),
+            ("CM_BUILD_ID", "65f0c1"),
+            ("CM_PROJECT_ID", "a1b2"),
+            ("CM_WORKFLOW_NAME", "iOS release"),
+            ("CM_PULL_REQUEST", "true"),
+            ("BUILD_NUMBER", "204"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "codemagic");
+        assert_eq!(ci.build_number.as_deref(), Some("204"));
+        assert_eq!(ci.job_name.as_deref(), Some("iOS release"));
+        assert_eq!(ci.run_id.as_deref(), Some("65f0c1"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://codemagic.io/app/a1b2/build/65f0c1")
+        );
+        assert_eq!(ci.event.as_deref(), Some("pull_request"));
+
+        // `BUILD_NUMBER` alone is Jenkins', TeamCity's and Codemagic's, so it detects nothing
+        assert!(detect_ci(env(&[("BUILD_NUMBER", "204")])).is_none());
+        let ci = detect_ci(env(&[
+            ("JENKINS_URL", "https://jenkins.studio/"),
+            ("BUILD_NUMBER", "12"),
+        ]));
+        assert_eq!(ci.unwrap().system, "jenkins");
+        let ci = detect_ci(env(&[
+            ("TEAMCITY_VERSION", "2024.03"),
+            ("BUILD_NUMBER", "12"),
+        ]));
+        assert_eq!(ci.unwrap().system, "teamcity");
     }
 
-    // Bitrise
-    if std::env::var("BITRISE_IO").ok().as_deref() == Some("true") {
-        return Some(CiMetadata {
-            system: "bitrise".to_string(),
-            build_number: std::env::var("BITRISE_BUILD_NUMBER").ok(),
-            job_name: std::env::var("BITRISE_TRIGGERED_WORKFLOW_ID").ok(),
-            run_id: std::env::var("BITRISE_BUILD_SLUG").ok(),
-            run_url: std::env::var("BITRISE_BUILD_URL").ok(),
-            triggered_by: std::env::var("BITRISE_TRIGGERED_WORKFLOW_TITLE").ok(),
-            agent: None,
-        });
+    #[test]
+    fn test_collect_cirrus() {
+        let ci = detect_ci(env(&[
+            ("CI", "true"),
+            ("CIRRUS_CI", "true"),
+            ("CIRRUS_BUILD_ID", "5123"),
+            ("CIRRUS_TASK_ID", "6234"),
+            ("CIRRUS_TASK_NAME", "macos_build"),
+            ("CIRRUS_CRON", "nightly"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "cirrus");
+        assert_eq!(ci.build_number.as_deref(), Some("5123"));
+        assert_eq!(ci.job_name.as_deref(), Some("macos_build"));
+        assert_eq!(ci.run_id.as_deref(), Some("6234"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://cirrus-ci.com/build/5123")
+        );
+        assert!(ci.is_scheduled());
+
+        let ci = collect_cirrus(env(&[("CIRRUS_CI", "true"), ("CIRRUS_PR", "17")])).unwrap();
+        assert_eq!(ci.event.as_deref(), Some("pull_request"));
+        assert_eq!(ci.run_url, None);
     }
 
-    None
+    #[test]
+    fn test_collect_bamboo() {
+        let ci = detect_ci(env(&[
+            ("bamboo_buildKey", "GAME-NIGHTLY-JOB1"),
+            ("bamboo_buildNumber", "412"),
+            ("bamboo_planName", "Game - Nightly"),
+            ("bamboo_buildResultKey", "GAME-NIGHTLY-JOB1-412"),
+            (
+                "bamboo_buildResultsUrl",
+                "https://bamboo.studio/browse/GAME-NIGHTLY-JOB1-412",
+            ),
+            ("bamboo_ManualBuildTriggerReason_userName", "ada"),
+            ("bamboo_agentId", "131073"),
+            ("bamboo_repository_pr_key", "57"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "bamboo");
+        assert_eq!(ci.build_number.as_deref(), Some("412"));
+        assert_eq!(ci.job_name.as_deref(), Some("Game - Nightly"));
+        assert_eq!(ci.run_id.as_deref(), Some("GAME-NIGHTLY-JOB1-412"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://bamboo.studio/browse/GAME-NIGHTLY-JOB1-412")
+        );
+        assert_eq!(ci.triggered_by.as_deref(), Some("ada"));
+        assert_eq!(ci.agent.as_deref(), Some("131073"));
+        assert_eq!(ci.event.as_deref(), Some("pull_request"));
+
+        assert!(collect_bamboo(env(&[("bamboo_buildNumber", "412")])).is_none());
+    }
+
+    #[test]
+    fn test_collect_harness() {
+        let ci = detect_ci(env(&[
+            ("CI", "true"),
+            ("HARNESS_BUILD_ID", "97"),
+            ("HARNESS_PIPELINE_ID", "release"),
+            ("HARNESS_STAGE_ID", "build_android"),
+            ("HARNESS_EXECUTION_ID", "Zx8kq"),
+            (
+                "DRONE_BUILD_LINK",
+                "https://app.harness.io/ng/account/a/builds/97",
+            ),
+            ("DRONE_BUILD_EVENT", "push"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "harness");
+        assert_eq!(ci.build_number.as_deref(), Some("97"));
+        assert_eq!(ci.job_name.as_deref(), Some("release/build_android"));
+        assert_eq!(ci.run_id.as_deref(), Some("Zx8kq"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://app.harness.io/ng/account/a/builds/97")
+        );
+        assert_eq!(ci.event.as_deref(), Some("push"));
+
+        let ci = collect_harness(env(&[
+            ("HARNESS_BUILD_ID", "97"),
+            ("HARNESS_STAGE_ID", "build_android"),
+            ("CI_BUILD_LINK", "https://harness.studio/builds/97"),
+            (
+                "DRONE_BUILD_LINK",
+                "https://app.harness.io/ng/account/a/builds/97",
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(ci.job_name.as_deref(), Some("build_android"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://harness.studio/builds/97")
+        );
+    }
+
+    #[test]
+    fn test_collect_woodpecker() {
+        let ci = detect_ci(env(&[
+            ("CI", "woodpecker"),
+            ("CI_PIPELINE_NUMBER", "8"),
+            ("CI_PIPELINE_URL", "https://ci.studio/repos/3/pipeline/8"),
+            ("CI_PIPELINE_EVENT", "cron"),
+            ("CI_WORKFLOW_NAME", "build"),
+            ("CI_MACHINE", "runner-2"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "woodpecker");
+        assert_eq!(ci.build_number.as_deref(), Some("8"));
+        assert_eq!(ci.job_name.as_deref(), Some("build"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://ci.studio/repos/3/pipeline/8")
+        );
+        assert_eq!(ci.agent.as_deref(), Some("runner-2"));
+        assert!(ci.is_scheduled());
+    }
+
+    #[test]
+    fn test_woodpecker_and_gitlab_ci_variables() {
+        // Both set `CI_PIPELINE_URL`; only `GITLAB_CI` and `CI` tell them apart
+        let ci = detect_ci(env(&[
+            ("CI", "true"),
+            ("GITLAB_CI", "true"),
+            ("CI_PIPELINE_IID", "311"),
+            (
+                "CI_PIPELINE_URL",
+                "https://gitlab.studio/game/-/pipelines/9001",
+            ),
+            ("CI_JOB_NAME", "build"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "gitlab-ci");
+        assert_eq!(ci.build_number.as_deref(), Some("311"));
+
+        let ci = detect_ci(env(&[
+            ("CI", "woodpecker"),
+            ("CI_PIPELINE_NUMBER", "8"),
+            ("CI_PIPELINE_URL", "https://ci.studio/repos/3/pipeline/8"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "woodpecker");
+        assert_eq!(ci.build_number.as_deref(), Some("8"));
+
+        assert!(detect_ci(env(&[("CI", "true"), ("CI_PIPELINE_URL", "https://ci")])).is_none());
+    }
+
+    #[test]
+    fn test_collect_xcode_cloud() {
+        let ci = detect_ci(env(&[
+            ("CI", "TRUE"),
+            ("CI_XCODEBUILD_ACTION", "archive"),
+            ("CI_WORKFLOW", "Release"),
+            ("CI_BUILD_NUMBER", "77"),
+            ("CI_BUILD_ID", "d1f0-42"),
+            (
+                "CI_BUILD_URL",
+                "https://appstoreconnect.apple.com/teams/t/builds/d1f0-42",
+            ),
+            ("CI_TAG", "v1.4.0"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.system, "xcode-cloud");
+        assert_eq!(ci.build_number.as_deref(), Some("77"));
+        assert_eq!(ci.job_name.as_deref(), Some("Release"));
+        assert_eq!(ci.run_id.as_deref(), Some("d1f0-42"));
+        assert_eq!(
+            ci.run_url.as_deref(),
+            Some("https://appstoreconnect.apple.com/teams/t/builds/d1f0-42")
+        );
+        assert_eq!(ci.event.as_deref(), Some("tag"));
+
+        // Post-clone scripts run before `CI_XCODEBUILD_ACTION` is set
+        let ci = collect_xcode_cloud(env(&[
+            ("CI_WORKFLOW", "Release"),
+            ("CI_PULL_REQUEST_NUMBER", "12"),
+        ]))
+        .unwrap();
+        assert_eq!(ci.event.as_deref(), Some("pull_request"));
+        let ci = collect_xcode_cloud(env(&[("CI_XCODEBUILD_ACTION", "build-for-testing")]));
+        assert_eq!(ci.unwrap().event.as_deref(), Some("build-for-testing"));
+        assert!(collect_xcode_cloud(env(&[("CI_BUILD_NUMBER", "77")])).is_none());
+    }
 }
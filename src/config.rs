@@ -1,10 +1,88 @@
+use crate::api::client::HttpClients;
+use crate::api::proxy::ProxyOptions;
 use crate::error::{Error, Result};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Connect timeout of the CLI unless `--connect-timeout` is given
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Clone, Debug)]
 pub struct Config {
     pub token: String,
     pub project_id: String,
     pub api_url: String,
+    pub failover: ApiFailover,
+    pub transport: TransportOptions,
+    /// HTTP clients of this config, reset whenever the transport settings change
+    pub(crate) http: SharedHttp,
+}
+
+/// Secondary API URLs used when the primary `api_url` is unreachable
+#[derive(Clone, Debug, Default)]
+pub struct ApiFailover {
+    /// Fallback API URLs in the order they are tried
+    pub fallback_urls: Vec<String>,
+    /// Path requested on a fallback API URL to check that it is healthy, `/health` unless
+    /// set
+    pub health_path: Option<String>,
+    /// Index of the API URL in use (0 is `api_url`), shared by every clone of the config
+    /// so a failover sticks for the rest of the run
+    active: Arc<AtomicUsize>,
+}
+
+/// HTTP clients built from the transport options on first use, shared by every clone of the
+/// config so all API clients and uploads reuse one connection pool and its TLS sessions
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SharedHttp {
+    pub(crate) clients: Arc<OnceLock<HttpClients>>,
+}
+
+/// HTTP version of storage uploads over TCP
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageHttpVersion {
+    /// HTTP/2 where the storage server offers it during the TLS handshake, else HTTP/1.1
+    #[default]
+    Auto,
+    /// HTTP/1.1 only, one connection per concurrent upload
+    Http1,
+    /// HTTP/2 over TLS where the storage offers it, and also for cleartext storage URLs
+    /// (by prior knowledge), multiplexing the uploads over few connections
+    Http2,
+}
+
+/// HTTP transport settings used when constructing the API client
+#[derive(Clone, Debug, Default)]
+pub struct TransportOptions {
+    /// Prefer HTTP/3 (QUIC) for storage uploads, falling back to HTTP/1.1 or HTTP/2.
+    /// Only takes effect when built with the `http3` feature.
+    pub http3: bool,
+    /// HTTP version of storage uploads (and of the fallback when HTTP/3 fails)
+    pub storage_http: StorageHttpVersion,
+    /// How long DNS resolutions are cached and reused across requests (`None` disables caching)
+    pub dns_cache_ttl: Option<Duration>,
+    /// Static host to address overrides, like curl's `--resolve`
+    pub resolve: Vec<(String, SocketAddr)>,
+    /// Explicit proxies, used instead of the proxy environment variables when set
+    pub proxy: ProxyOptions,
+    /// PEM file with root certificates trusted in addition to the built-in ones
+    pub ca_bundle: Option<PathBuf>,
+    /// PEM client certificate for mutual TLS, with its private key unless `client_key` is set,
+    /// or a PKCS#12 bundle
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    /// Password of a PKCS#12 `client_cert`
+    pub client_cert_password: Option<String>,
+    /// Accept any TLS certificate, like curl's `-k`; for test environments only
+    pub insecure: bool,
+    /// How long establishing a connection may take (`None` waits as long as the OS does)
+    pub connect_timeout: Option<Duration>,
+    /// How long an API request may take in total, including reading the response.
+    /// Storage uploads are guarded by the upload's stall timeout instead.
+    pub request_timeout: Option<Duration>,
 }
 
 impl Config {
@@ -27,11 +105,140 @@ impl Config {
             token,
             project_id,
             api_url,
+            failover: ApiFailover::default(),
+            transport: TransportOptions::default(),
+            http: SharedHttp::default(),
         })
     }
 
+    /// Replaces the transport settings used by clients created from this config
+    #[must_use]
+    pub fn with_transport(mut self, transport: TransportOptions) -> Self {
+        self.transport = transport;
+        self.http = SharedHttp::default();
+        self
+    }
+
+    /// Sets the API URLs to fail over to when `api_url` is unreachable
+    #[must_use]
+    pub fn with_fallback_urls(mut self, fallback_urls: Vec<String>) -> Self {
+        self.failover.fallback_urls = fallback_urls
+            .into_iter()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .collect();
+        // Proxy bypasses depend on the API URLs
+        self.http = SharedHttp::default();
+        self
+    }
+
+    /// Sets the path requested on a fallback API URL to check that it is healthy, for
+    /// gateways that serve their health check somewhere other than `/health`
+    #[must_use]
+    pub fn with_health_path(mut self, health_path: Option<String>) -> Self {
+        self.failover.health_path = health_path.map(|path| {
+            if path.starts_with('/') {
+                path
+            } else {
+                format!("/{path}")
+            }
+        });
+        self
+    }
+
+    /// The primary API URL followed by the fallbacks
+    pub fn api_urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.api_url.as_str())
+            .chain(self.failover.fallback_urls.iter().map(String::as_str))
+    }
+
+    /// Index of the API URL requests are currently sent to (0 is `api_url`)
+    #[must_use]
+    pub fn active_api_index(&self) -> usize {
+        self.failover.active.load(Ordering::Relaxed)
+    }
+
+    /// The API URL requests are currently sent to
+    #[must_use]
+    pub fn active_api_url(&self) -> &str {
+        self.api_urls()
+            .nth(self.active_api_index())
+            .unwrap_or(&self.api_url)
+    }
+
+    /// Switch from the API URL at index `from` to the one at `to`, unless another request
+    /// switched away from `from` first
+    ///
+    /// Returns the index of the API URL now in use.
+    #[must_use]
+    pub fn switch_api_url(&self, from: usize, to: usize) -> usize {
+        match self
+            .failover
+            .active
+            .compare_exchange(from, to, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => to,
+            Err(active) => active,
+        }
+    }
+
+    /// Split a request URL into the index of the API URL it starts with and the rest
+    #[must_use]
+    pub fn split_api_url<'a>(&self, url: &'a str) -> Option<(usize, &'a str)> {
+        self.api_urls()
+            .enumerate()
+            .filter_map(|(index, api_url)| {
+                let path = url.strip_prefix(api_url)?;
+                (path.is_empty() || path.starts_with('/')).then_some((index, api_url.len(), path))
+            })
+            .max_by_key(|(_, len, _)| *len)
+            .map(|(index, _, path)| (index, path))
+    }
+
+    #[must_use]
+    pub fn project_url(&self) -> String {
+        format!(
+            "{}/nexus/projects/{}",
+            self.active_api_url(),
+            self.project_id
+        )
+    }
+
     #[must_use]
     pub fn base_upload_url(&self) -> String {
-        format!("{}/nexus/projects/{}/builds", self.api_url, self.project_id)
+        format!("{}/builds", self.project_url())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_failover_is_shared_by_clones() {
+        let config = Config::new(
+            "t".to_string(),
+            "p".to_string(),
+            "https://a/api".to_string(),
+        )
+        .unwrap()
+        .with_fallback_urls(vec![
+            "https://b/api/".to_string(),
+            "https://a/api2".to_string(),
+        ]);
+        let clone = config.clone();
+
+        assert_eq!(
+            config.split_api_url("https://a/api2/nexus"),
+            Some((2, "/nexus"))
+        );
+        assert_eq!(config.split_api_url("https://b/api"), Some((1, "")));
+        assert_eq!(config.split_api_url("https://c/api/nexus"), None);
+
+        assert_eq!(config.switch_api_url(0, 1), 1);
+        assert_eq!(clone.switch_api_url(0, 2), 1);
+        assert_eq!(
+            clone.base_upload_url(),
+            "https://b/api/nexus/projects/p/builds"
+        );
     }
 }
@@ -0,0 +1,98 @@
+//! API tokens read from files or fetched from secret managers
+//!
+//! `--token-file` reads a token mounted or written by the pipeline, and the config file's
+//! `token_command` runs a command such as `op read op://ci/nunu/token` or
+//! `vault kv get -field=token secret/nunu` and uses what it prints.
+
+use crate::error::{Error, Result};
+use log::debug;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Read an API token from `path`, ignoring surrounding whitespace
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is empty
+pub fn read_token_file(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        Error::ConfigError(format!(
+            "Failed to read token file '{}': {e}",
+            path.display()
+        ))
+    })?;
+    non_empty_token(&contents)
+        .ok_or_else(|| Error::ConfigError(format!("Token file '{}' is empty", path.display())))
+}
+
+/// Run `command` with the system shell and use the first line it prints as the API token
+///
+/// The command inherits stdin and stderr, so secret managers can prompt for unlocking.
+///
+/// # Errors
+///
+/// Returns an error if the command cannot be started, fails, or prints nothing
+pub fn run_token_command(command: &str) -> Result<String> {
+    debug!("Fetching the API token with the configured token_command");
+    let output = shell(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| Error::ConfigError(format!("Failed to run token_command: {e}")))?;
+    if !output.status.success() {
+        return Err(Error::ConfigError(format!(
+            "token_command failed ({})",
+            output.status
+        )));
+    }
+    non_empty_token(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| Error::ConfigError("token_command printed no token".to_string()))
+}
+
+fn non_empty_token(text: &str) -> Option<String> {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        std::fs::write(&path, "\n  nunu_abc123  \n").unwrap();
+        assert_eq!(read_token_file(&path).unwrap(), "nunu_abc123");
+
+        std::fs::write(&path, " \n").unwrap();
+        assert!(read_token_file(&path).is_err());
+        assert!(read_token_file(&dir.path().join("missing")).is_err());
+
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                run_token_command("echo nunu_abc123").unwrap(),
+                "nunu_abc123"
+            );
+            assert!(run_token_command("exit 1").is_err());
+            assert!(run_token_command("true").is_err());
+        }
+    }
+}
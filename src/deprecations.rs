@@ -0,0 +1,329 @@
+//! Deprecated flags, config keys, environment variables and values
+//!
+//! Deprecated names keep working: the first use of each logs a warning with its replacement,
+//! and with `--strict-deprecations` (for CI hygiene) any use is an error instead.
+//!
+//! To deprecate a name, add it to [`DEPRECATIONS`] and keep accepting it: a renamed flag as
+//! a hidden `alias` of its new name, a value in the parser that reads it (calling
+//! [`check_value`]). Config keys are moved to their new names here; a renamed environment
+//! variable must be the `env` of a flag, which then reads the old name while the new one is
+//! unset (see [`check_env`]).
+
+use crate::error::{Error, Result};
+use log::warn;
+use std::ffi::OsString;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Every deprecated name the CLI still accepts
+pub const DEPRECATIONS: &[Deprecation] = &[Deprecation {
+    kind: Kind::Value("platform"),
+    name: "steamdeck",
+    replacement: "steam-deck",
+    since: "0.1.20",
+}];
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+/// Whether warnings can be logged yet; until then they are kept in `REPORTED`
+static LOGGING: AtomicBool = AtomicBool::new(false);
+/// Deprecations used so far, and whether each was logged
+static REPORTED: Mutex<Vec<(&'static Deprecation, bool)>> = Mutex::new(Vec::new());
+
+/// Where a deprecated name is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A command-line flag, named without the leading `--`
+    Flag,
+    /// A key of the JSON config file
+    ConfigKey,
+    EnvVar,
+    /// A value of the named option, e.g. a platform
+    Value(&'static str),
+}
+
+/// A deprecated name and what replaces it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation {
+    pub kind: Kind,
+    pub name: &'static str,
+    pub replacement: &'static str,
+    /// CLI version that deprecated the name
+    pub since: &'static str,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (old, new) = match self.kind {
+            Kind::Flag => (
+                format!("flag --{}", self.name),
+                format!("--{}", self.replacement),
+            ),
+            Kind::ConfigKey => (
+                format!("config key `{}`", self.name),
+                format!("`{}`", self.replacement),
+            ),
+            Kind::EnvVar => (
+                format!("environment variable {}", self.name),
+                self.replacement.to_string(),
+            ),
+            Kind::Value(option) => (
+                format!("{option} `{}`", self.name),
+                format!("`{}`", self.replacement),
+            ),
+        };
+        write!(
+            f,
+            "{old} is deprecated since {}; use {new} instead",
+            self.since
+        )
+    }
+}
+
+/// Make any use of a deprecated name an error for the rest of the process
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+/// Whether `--strict-deprecations` is among `args` or set in the environment
+///
+/// Values are checked while the command line is parsed, so this is needed before clap
+/// has parsed it.
+pub fn strict_requested(
+    args: impl IntoIterator<Item = OsString>,
+    var: impl Fn(&str) -> Option<String>,
+) -> bool {
+    args.into_iter()
+        .take_while(|arg| arg != "--")
+        .any(|arg| arg == "--strict-deprecations")
+        || var("NUNU_STRICT_DEPRECATIONS").is_some_and(|value| value == "true")
+}
+
+/// Log the warnings reported so far, and further ones as they are reported
+///
+/// Called once the logger is set up.
+pub fn start_logging() {
+    LOGGING.store(true, Ordering::Relaxed);
+    if let Ok(mut reported) = REPORTED.lock() {
+        for (deprecation, logged) in reported.iter_mut().filter(|(_, logged)| !*logged) {
+            warn!("{deprecation}");
+            *logged = true;
+        }
+    }
+}
+
+/// Report a use of `deprecation`: a warning the first time, or an error in strict mode
+///
+/// # Errors
+///
+/// Returns an error with the migration hint with `--strict-deprecations`
+pub fn report(deprecation: &'static Deprecation) -> Result<()> {
+    if STRICT.load(Ordering::Relaxed) {
+        return Err(Error::ConfigError(format!(
+            "{deprecation} (deprecations are errors with --strict-deprecations)"
+        )));
+    }
+    if let Ok(mut reported) = REPORTED.lock()
+        && !reported.iter().any(|(d, _)| *d == deprecation)
+    {
+        let logging = LOGGING.load(Ordering::Relaxed);
+        if logging {
+            warn!("{deprecation}");
+        }
+        reported.push((deprecation, logging));
+    }
+    Ok(())
+}
+
+/// Report `value` of `option` if it is deprecated
+///
+/// # Errors
+///
+/// Returns an error if the value is deprecated and deprecations are strict
+pub fn check_value(option: &'static str, value: &str) -> Result<()> {
+    find(DEPRECATIONS, Kind::Value(option), value).map_or(Ok(()), report)
+}
+
+/// Report the deprecated flags among the command-line `args`
+///
+/// # Errors
+///
+/// Returns an error if a deprecated flag is used and deprecations are strict
+pub fn check_args(args: impl IntoIterator<Item = OsString>) -> Result<()> {
+    deprecated_flags(DEPRECATIONS, args)
+        .into_iter()
+        .try_for_each(report)
+}
+
+/// Report the deprecated environment variables that are set, returning those whose
+/// replacement is not set, so the flags reading the replacement can read them instead
+///
+/// # Errors
+///
+/// Returns an error if a deprecated variable is set and deprecations are strict
+pub fn check_env(var: impl Fn(&str) -> Option<String>) -> Result<Vec<&'static Deprecation>> {
+    let mut renamed = Vec::new();
+    for deprecation in deprecated_vars(DEPRECATIONS, &var) {
+        report(deprecation)?;
+        if var(deprecation.replacement).is_none() {
+            renamed.push(deprecation);
+        }
+    }
+    Ok(renamed)
+}
+
+/// Report the deprecated keys of a config file and move them to their new names (unless
+/// the new name is set too, which takes precedence)
+///
+/// # Errors
+///
+/// Returns an error if a deprecated key is used and deprecations are strict
+pub fn migrate_config(config: &mut serde_json::Value) -> Result<()> {
+    migrate_keys(DEPRECATIONS, config)
+        .into_iter()
+        .try_for_each(report)
+}
+
+fn find(
+    deprecations: &'static [Deprecation],
+    kind: Kind,
+    name: &str,
+) -> Option<&'static Deprecation> {
+    deprecations
+        .iter()
+        .find(|d| d.kind == kind && d.name.eq_ignore_ascii_case(name))
+}
+
+fn deprecated_flags(
+    deprecations: &'static [Deprecation],
+    args: impl IntoIterator<Item = OsString>,
+) -> Vec<&'static Deprecation> {
+    let mut found = Vec::new();
+    for arg in args {
+        // Everything after `--` is a positional argument
+        if arg == "--" {
+            break;
+        }
+        let Some(flag) = arg.to_str().and_then(|arg| arg.strip_prefix("--")) else {
+            continue;
+        };
+        let name = flag.split_once('=').map_or(flag, |(name, _)| name);
+        if let Some(deprecation) = find(deprecations, Kind::Flag, name)
+            && !found.contains(&deprecation)
+        {
+            found.push(deprecation);
+        }
+    }
+    found
+}
+
+fn deprecated_vars(
+    deprecations: &'static [Deprecation],
+    var: impl Fn(&str) -> Option<String>,
+) -> impl Iterator<Item = &'static Deprecation> {
+    deprecations
+        .iter()
+        .filter(move |d| d.kind == Kind::EnvVar && var(d.name).is_some())
+}
+
+fn migrate_keys(
+    deprecations: &'static [Deprecation],
+    config: &mut serde_json::Value,
+) -> Vec<&'static Deprecation> {
+    let Some(config) = config.as_object_mut() else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for deprecation in deprecations.iter().filter(|d| d.kind == Kind::ConfigKey) {
+        if let Some(value) = config.remove(deprecation.name) {
+            config.entry(deprecation.replacement).or_insert(value);
+            found.push(deprecation);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DEPRECATIONS: &[Deprecation] = &[
+        Deprecation {
+            kind: Kind::Flag,
+            name: "project-name",
+            replacement: "project",
+            since: "0.1.20",
+        },
+        Deprecation {
+            kind: Kind::ConfigKey,
+            name: "token",
+            replacement: "api_token",
+            since: "0.1.20",
+        },
+        Deprecation {
+            kind: Kind::EnvVar,
+            name: "NUNU_TOKEN",
+            replacement: "NUNU_API_TOKEN",
+            since: "0.1.20",
+        },
+    ];
+
+    #[test]
+    fn test_deprecations() {
+        let args = [
+            "nunu-cli",
+            "upload",
+            "--project-name=x",
+            "--project-name",
+            "y",
+            "--",
+        ]
+        .into_iter()
+        .chain(["--project-name"])
+        .map(OsString::from);
+        let flags = deprecated_flags(TEST_DEPRECATIONS, args);
+        assert_eq!(flags, [&TEST_DEPRECATIONS[0]]);
+        assert_eq!(
+            flags[0].to_string(),
+            "flag --project-name is deprecated since 0.1.20; use --project instead"
+        );
+
+        let mut config = serde_json::json!({"token": "old", "project_id": "p"});
+        assert_eq!(
+            migrate_keys(TEST_DEPRECATIONS, &mut config),
+            [&TEST_DEPRECATIONS[1]]
+        );
+        assert_eq!(
+            config,
+            serde_json::json!({"api_token": "old", "project_id": "p"})
+        );
+        let mut config = serde_json::json!({"token": "old", "api_token": "new"});
+        migrate_keys(TEST_DEPRECATIONS, &mut config);
+        assert_eq!(config, serde_json::json!({"api_token": "new"}));
+
+        let set = |name: &str| (name == "NUNU_TOKEN").then(|| "old".to_string());
+        assert_eq!(
+            deprecated_vars(TEST_DEPRECATIONS, set).collect::<Vec<_>>(),
+            [&TEST_DEPRECATIONS[2]]
+        );
+        assert_eq!(deprecated_vars(TEST_DEPRECATIONS, |_| None).count(), 0);
+
+        assert_eq!(
+            find(DEPRECATIONS, Kind::Value("platform"), "SteamDeck").map(|d| d.replacement),
+            Some("steam-deck")
+        );
+        assert!(find(DEPRECATIONS, Kind::Value("platform"), "steam-deck").is_none());
+
+        let var = |value: &'static str| move |_: &str| Some(value.to_string());
+        assert!(strict_requested(
+            ["nunu-cli", "--strict-deprecations"].map(OsString::from),
+            |_| None
+        ));
+        assert!(!strict_requested(
+            ["nunu-cli", "--", "--strict-deprecations"].map(OsString::from),
+            |_| None
+        ));
+        assert!(strict_requested([], var("true")));
+        assert!(!strict_requested([], var("false")));
+    }
+}
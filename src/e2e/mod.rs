@@ -0,0 +1,590 @@
+//! Local stand-in for the nunu.ai API, for end-to-end tests (`--e2e-local`)
+//!
+//! [`StubApi`] answers the upload endpoints the CLI uses and hands out upload URLs for
+//! either its own in-memory storage or an S3-compatible server such as MinIO, so single-part
+//! and multipart uploads, including sharded ones, can run from start to finish without
+//! credentials for the real service. Completed uploads are hashed so tests can compare them with the local files.
+//! Compiled in with the `e2e` feature.
+
+use crate::error::{Error, Result};
+use crate::upload::shard::Shard;
+use crate::util::sha256_hex;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use server::{Request, Response};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+mod s3;
+mod server;
+
+/// API token the stub accepts
+pub const E2E_TOKEN: &str = "nunu-e2e-token";
+
+/// Project all stub builds belong to
+pub const E2E_PROJECT_ID: &str = "e2e";
+
+/// Size of multipart upload parts, the smallest S3 accepts
+pub const E2E_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Where the stub stores uploaded files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// In the stub's memory, served by the stub itself
+    Local,
+    /// In a bucket of an S3-compatible server
+    S3(S3Config),
+}
+
+impl Storage {
+    /// S3 when `NUNU_E2E_S3_ENDPOINT` is set, local storage otherwise
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        S3Config::from_env(var).map_or(Self::Local, Self::S3)
+    }
+}
+
+/// Connection to an S3-compatible server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Config {
+    /// Base URL of the server, e.g. `http://127.0.0.1:9000`
+    pub endpoint: String,
+    /// Bucket uploads are stored in; created if missing
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl S3Config {
+    /// Configuration from the `NUNU_E2E_S3_*` variables, defaulting to the root
+    /// credentials of MinIO; `None` unless `NUNU_E2E_S3_ENDPOINT` is set
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let endpoint = var("NUNU_E2E_S3_ENDPOINT").filter(|e| !e.is_empty())?;
+        Some(Self {
+            endpoint,
+            bucket: var("NUNU_E2E_S3_BUCKET").unwrap_or_else(|| "nunu-e2e".to_string()),
+            region: var("NUNU_E2E_S3_REGION").unwrap_or_else(|| "us-east-1".to_string()),
+            access_key_id: var("NUNU_E2E_S3_ACCESS_KEY")
+                .unwrap_or_else(|| "minioadmin".to_string()),
+            secret_access_key: var("NUNU_E2E_S3_SECRET_KEY")
+                .unwrap_or_else(|| "minioadmin".to_string()),
+        })
+    }
+}
+
+/// A completed upload as stored by the stub
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredBuild {
+    pub build_id: String,
+    pub name: String,
+    pub file_name: String,
+    pub object_key: String,
+    pub size: u64,
+    /// SHA-256 of the stored object as lowercase hex
+    pub sha256: String,
+    /// Number of parts for multipart uploads, `None` for single-part uploads
+    pub parts: Option<usize>,
+}
+
+/// The stub API, serving until dropped
+pub struct StubApi {
+    addr: SocketAddr,
+    state: Arc<State>,
+    task: JoinHandle<()>,
+}
+
+impl StubApi {
+    /// Start serving on a free port of 127.0.0.1
+    ///
+    /// Must be called within a Tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no port can be bound or the S3 bucket cannot be created
+    pub async fn start(storage: Storage) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let backend = match storage {
+            Storage::Local => Backend::Local,
+            Storage::S3(config) => {
+                let s3 = s3::S3::new(config)?;
+                s3.ensure_bucket().await?;
+                Backend::S3(Box::new(s3))
+            }
+        };
+        let state = Arc::new(State {
+            base_url: format!("http://{addr}"),
+            backend,
+            inner: Mutex::default(),
+        });
+
+        let handler_state = Arc::clone(&state);
+        let task = tokio::spawn(server::serve(listener, move |request| {
+            let state = Arc::clone(&handler_state);
+            async move { state.handle(request).await }
+        }));
+        debug!("e2e stub API listening on {addr}");
+        Ok(Self { addr, state, task })
+    }
+
+    /// URL to pass as `--api-url`
+    #[must_use]
+    pub fn api_url(&self) -> String {
+        format!("http://{}/api", self.addr)
+    }
+
+    /// Uploads completed so far, oldest first
+    #[must_use]
+    pub fn builds(&self) -> Vec<StoredBuild> {
+        self.state.lock().builds.clone()
+    }
+}
+
+impl Drop for StubApi {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+enum Backend {
+    Local,
+    S3(Box<s3::S3>),
+}
+
+struct State {
+    /// `http://127.0.0.1:<port>`, which local storage URLs point to
+    base_url: String,
+    backend: Backend,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    /// Uploads started but not completed, by build ID
+    uploads: HashMap<String, PendingUpload>,
+    /// Objects and parts in local storage, by object key and part (0 for whole objects)
+    objects: HashMap<(String, u64), Vec<u8>>,
+    builds: Vec<StoredBuild>,
+}
+
+struct PendingUpload {
+    name: String,
+    file_name: String,
+    object_key: String,
+    upload_id: Option<String>,
+    /// Response to the request that started the upload, returned to the shards joining it
+    response: Value,
+    shard: Option<Shard>,
+    /// Parts reported by the shards of a sharded upload, by shard index
+    shard_parts: BTreeMap<u32, Vec<(u64, String)>>,
+}
+
+#[derive(Deserialize)]
+struct UploadRequest {
+    name: String,
+    file_name: String,
+    file_size: u64,
+    multipart: bool,
+    #[serde(default)]
+    storage_region: Option<String>,
+    #[serde(default)]
+    shard: Option<Shard>,
+}
+
+#[derive(Deserialize)]
+struct CompleteRequest {
+    build_id: String,
+    #[serde(default)]
+    parts: Vec<CompletedPart>,
+}
+
+#[derive(Deserialize)]
+struct CompleteShardRequest {
+    build_id: String,
+    shard: u32,
+    parts: Vec<CompletedPart>,
+}
+
+#[derive(Deserialize)]
+struct CompletedPart {
+    part_number: u64,
+    etag: String,
+}
+
+impl State {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    async fn handle(&self, request: Request) -> Response {
+        debug!("e2e stub: {} {}", request.method, request.path);
+        if let Some(key) = request.path.strip_prefix("/storage/") {
+            return self.store(key, &request);
+        }
+        if request
+            .header("x-api-key")
+            .is_some_and(|token| token != E2E_TOKEN)
+        {
+            return Response::error(401, "Invalid API token");
+        }
+        if request.path == "/api/health" {
+            return Response::json(200, &json!({ "status": "ok" }));
+        }
+
+        let builds = format!("/api/nexus/projects/{E2E_PROJECT_ID}/builds");
+        let Some(route) = request.path.strip_prefix(&builds) else {
+            return Response::error(404, "Not found");
+        };
+        let result = match (request.method.as_str(), route) {
+            ("POST", "/upload") => self.start_upload(&request).await,
+            ("GET", "/upload/parts") => self.part_urls(&request),
+            ("POST", "/upload/complete") => self.complete(&request).await,
+            ("POST", "/upload/shards/complete") => self.complete_shard(&request).await,
+            ("POST", "/upload/progress" | "/attachments") => Ok(json!({})),
+            ("DELETE", "/upload") => self.abort(&request).await,
+            ("GET", route) => match route
+                .strip_prefix('/')
+                .and_then(|r| r.strip_suffix("/upload/object"))
+            {
+                Some(build_id) => return self.stored_object(build_id),
+                None => return Response::error(404, "Not supported by the e2e stub API"),
+            },
+            _ => return Response::error(404, "Not supported by the e2e stub API"),
+        };
+        match result {
+            Ok(body) => Response::json(200, &body),
+            Err(e) => Response::error(400, &e.to_string()),
+        }
+    }
+
+    async fn start_upload(&self, request: &Request) -> Result<Value> {
+        let upload: UploadRequest = serde_json::from_slice(&request.body)?;
+        if let Some(joined) = self.shard_upload(&upload) {
+            return Ok(joined);
+        }
+        let (build_id, object_key) = {
+            let mut inner = self.lock();
+            inner.next_id += 1;
+            let build_id = format!("e2e-{}", inner.next_id);
+            let object_key = format!(
+                "{E2E_PROJECT_ID}/{build_id}/{}",
+                storage_name(&upload.file_name)
+            );
+            (build_id, object_key)
+        };
+
+        let response = if upload.multipart {
+            let upload_id = match &self.backend {
+                Backend::Local => format!("local-{build_id}"),
+                Backend::S3(s3) => s3.create_multipart_upload(&object_key).await?,
+            };
+            let total_parts = upload.file_size.div_ceil(E2E_PART_SIZE).max(1);
+            let response = json!({
+                "build_id": build_id,
+                "upload_id": upload_id,
+                "object_key": object_key,
+                "total_parts": total_parts,
+                "part_size": E2E_PART_SIZE,
+                "storage_region": upload.storage_region,
+            });
+            self.insert_upload(&build_id, upload, &object_key, Some(upload_id), &response);
+            response
+        } else {
+            let response = json!({
+                "build_id": build_id,
+                "upload_url": self.upload_url(&object_key, None),
+                "object_key": object_key,
+                "storage_region": upload.storage_region,
+            });
+            self.insert_upload(&build_id, upload, &object_key, None, &response);
+            response
+        };
+        debug!("e2e stub: started upload of {object_key}");
+        Ok(response)
+    }
+
+    /// The upload another shard with the same key already started for the same file
+    fn shard_upload(&self, upload: &UploadRequest) -> Option<Value> {
+        let key = upload.shard.as_ref()?.key.as_ref()?;
+        self.lock()
+            .uploads
+            .values()
+            .find(|pending| {
+                pending.file_name == upload.file_name
+                    && pending.shard.as_ref().and_then(|s| s.key.as_ref()) == Some(key)
+            })
+            .map(|pending| pending.response.clone())
+    }
+
+    fn insert_upload(
+        &self,
+        build_id: &str,
+        request: UploadRequest,
+        object_key: &str,
+        upload_id: Option<String>,
+        response: &Value,
+    ) {
+        self.lock().uploads.insert(
+            build_id.to_string(),
+            PendingUpload {
+                name: request.name,
+                file_name: request.file_name,
+                object_key: object_key.to_string(),
+                upload_id,
+                response: response.clone(),
+                shard: request.shard,
+                shard_parts: BTreeMap::new(),
+            },
+        );
+    }
+
+    fn part_urls(&self, request: &Request) -> Result<Value> {
+        let (Some(upload_id), Some(object_key), Some(numbers)) = (
+            request.query("upload_id"),
+            request.query("object_key"),
+            request.query("part_numbers"),
+        ) else {
+            return Err(Error::UploadError(
+                "upload_id, object_key and part_numbers are required".to_string(),
+            ));
+        };
+        let urls = numbers
+            .split(',')
+            .map(|number| {
+                let number: u64 = number
+                    .trim()
+                    .parse()
+                    .map_err(|_| Error::UploadError(format!("Invalid part number '{number}'")))?;
+                Ok(json!({
+                    "part_number": number,
+                    "url": self.upload_url(object_key, Some((upload_id, number))),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(json!({ "upload_urls": urls }))
+    }
+
+    fn upload_url(&self, object_key: &str, part: Option<(&str, u64)>) -> String {
+        match &self.backend {
+            Backend::Local => match part {
+                Some((upload_id, number)) => format!(
+                    "{}/storage/{object_key}?uploadId={upload_id}&partNumber={number}",
+                    self.base_url
+                ),
+                None => format!("{}/storage/{object_key}", self.base_url),
+            },
+            Backend::S3(s3) => s3.presign_put(object_key, part),
+        }
+    }
+
+    /// A PUT to local storage
+    fn store(&self, key: &str, request: &Request) -> Response {
+        if request.method != "PUT" {
+            return Response::error(405, "Storage only accepts PUT");
+        }
+        let part = request
+            .query("partNumber")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or_default();
+        let etag = format!("\"{}\"", &sha256_hex(&request.body)[..32]);
+        self.lock()
+            .objects
+            .insert((key.to_string(), part), request.body.clone());
+        Response {
+            status: 200,
+            headers: vec![("etag", etag)],
+            body: Vec::new(),
+        }
+    }
+
+    async fn complete(&self, request: &Request) -> Result<Value> {
+        let complete: CompleteRequest = serde_json::from_slice(&request.body)?;
+        let upload = self
+            .lock()
+            .uploads
+            .remove(&complete.build_id)
+            .ok_or_else(|| {
+                Error::UploadError(format!("No upload for build {}", complete.build_id))
+            })?;
+        let parts = complete
+            .parts
+            .into_iter()
+            .map(|part| (part.part_number, part.etag))
+            .collect();
+        self.finish(complete.build_id, upload, parts).await?;
+        Ok(json!({}))
+    }
+
+    /// Record the parts of one shard, completing the upload once every shard has reported
+    async fn complete_shard(&self, request: &Request) -> Result<Value> {
+        let complete: CompleteShardRequest = serde_json::from_slice(&request.body)?;
+        let (pending_shards, upload) = {
+            let mut inner = self.lock();
+            let upload = inner.uploads.get_mut(&complete.build_id).ok_or_else(|| {
+                Error::UploadError(format!("No upload for build {}", complete.build_id))
+            })?;
+            let count = upload.shard.as_ref().map_or(1, |shard| shard.count);
+            upload.shard_parts.insert(
+                complete.shard,
+                complete
+                    .parts
+                    .into_iter()
+                    .map(|part| (part.part_number, part.etag))
+                    .collect(),
+            );
+            let pending_shards: Vec<u32> = (1..=count)
+                .filter(|index| !upload.shard_parts.contains_key(index))
+                .collect();
+            let upload = if pending_shards.is_empty() {
+                inner.uploads.remove(&complete.build_id)
+            } else {
+                None
+            };
+            (pending_shards, upload)
+        };
+
+        let Some(mut upload) = upload else {
+            return Ok(json!({ "completed": false, "pending_shards": pending_shards }));
+        };
+        let parts = std::mem::take(&mut upload.shard_parts)
+            .into_values()
+            .flatten()
+            .collect();
+        self.finish(complete.build_id, upload, parts).await?;
+        Ok(json!({ "completed": true, "pending_shards": [] }))
+    }
+
+    /// Assemble the uploaded object and store it as a build
+    async fn finish(
+        &self,
+        build_id: String,
+        upload: PendingUpload,
+        mut parts: Vec<(u64, String)>,
+    ) -> Result<()> {
+        parts.sort_unstable();
+        let data = match (&self.backend, &upload.upload_id) {
+            (Backend::Local, None) => self.lock().objects.remove(&(upload.object_key.clone(), 0)),
+            (Backend::Local, Some(_)) => Some(self.assemble_local(&upload.object_key, &parts)?),
+            (Backend::S3(s3), upload_id) => {
+                if let Some(upload_id) = upload_id {
+                    s3.complete_multipart_upload(&upload.object_key, upload_id, &parts)
+                        .await?;
+                }
+                s3.get_object(&upload.object_key).await?
+            }
+        };
+        let data = data
+            .ok_or_else(|| Error::UploadError(format!("{} was not uploaded", upload.object_key)))?;
+
+        debug!(
+            "e2e stub: completed {} ({} bytes)",
+            upload.object_key,
+            data.len()
+        );
+        self.lock().builds.push(StoredBuild {
+            build_id,
+            name: upload.name,
+            file_name: upload.file_name,
+            object_key: upload.object_key,
+            size: data.len() as u64,
+            sha256: sha256_hex(&data),
+            parts: upload.upload_id.map(|_| parts.len()),
+        });
+        Ok(())
+    }
+
+    /// Join the parts of a local multipart upload, checking their `ETag`s like S3 does
+    fn assemble_local(&self, object_key: &str, parts: &[(u64, String)]) -> Result<Vec<u8>> {
+        let mut inner = self.lock();
+        let mut data = Vec::new();
+        for (number, etag) in parts {
+            let part = inner
+                .objects
+                .remove(&(object_key.to_string(), *number))
+                .ok_or_else(|| Error::UploadError(format!("Part {number} was not uploaded")))?;
+            if etag.trim_matches('"') != &sha256_hex(&part)[..32] {
+                return Err(Error::UploadError(format!(
+                    "ETag of part {number} does not match"
+                )));
+            }
+            data.extend_from_slice(&part);
+        }
+        Ok(data)
+    }
+
+    async fn abort(&self, request: &Request) -> Result<Value> {
+        let Some(build_id) = request.query("build_id") else {
+            return Err(Error::UploadError("build_id is required".to_string()));
+        };
+        let Some(upload) = self.lock().uploads.remove(build_id) else {
+            return Ok(json!({}));
+        };
+        match (&self.backend, &upload.upload_id) {
+            (Backend::S3(s3), Some(upload_id)) => {
+                s3.abort_multipart_upload(&upload.object_key, upload_id)
+                    .await?;
+            }
+            (Backend::S3(_), None) => {}
+            (Backend::Local, _) => self
+                .lock()
+                .objects
+                .retain(|(key, _), _| *key != upload.object_key),
+        }
+        debug!("e2e stub: aborted upload of {}", upload.object_key);
+        Ok(json!({}))
+    }
+
+    fn stored_object(&self, build_id: &str) -> Response {
+        let inner = self.lock();
+        match inner.builds.iter().find(|b| b.build_id == build_id) {
+            Some(build) => {
+                Response::json(200, &json!({ "size": build.size, "sha256": build.sha256 }))
+            }
+            None => Response::error(404, "Build not found"),
+        }
+    }
+}
+
+/// `file_name` with characters that would need escaping in storage URLs replaced
+fn storage_name(file_name: &str) -> String {
+    file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_e2e_storage_config() {
+        assert_eq!(Storage::from_env(|_| None), Storage::Local);
+
+        let vars = HashMap::from([
+            ("NUNU_E2E_S3_ENDPOINT", "http://127.0.0.1:9000"),
+            ("NUNU_E2E_S3_BUCKET", "builds"),
+        ]);
+        let Storage::S3(config) = Storage::from_env(|name| vars.get(name).map(ToString::to_string))
+        else {
+            panic!("expected S3 storage");
+        };
+        assert_eq!(config.endpoint, "http://127.0.0.1:9000");
+        assert_eq!(config.bucket, "builds");
+        assert_eq!(config.region, "us-east-1");
+        assert_eq!(config.access_key_id, "minioadmin");
+
+        assert_eq!(storage_name("My Game (v1).zip"), "My_Game__v1_.zip");
+    }
+}
@@ -0,0 +1,251 @@
+//! Storage in an S3-compatible server such as MinIO
+//!
+//! Uploads go to presigned URLs exactly as with the real API, so the CLI's storage requests
+//! are checked by a real S3 implementation.
+
+use super::S3Config;
+use crate::error::{Error, Result};
+use crate::sigv4::{
+    Credentials, Request, amz_date, authorization, canonical_query, presigned_query, uri_encode,
+};
+use crate::util::sha256_hex;
+use reqwest::{Client, Method, Response};
+use std::fmt::Write as _;
+use std::time::{Duration, SystemTime};
+
+/// How long presigned URLs are valid
+const PRESIGN_EXPIRY: Duration = Duration::from_hours(1);
+
+pub(super) struct S3 {
+    config: S3Config,
+    credentials: Credentials,
+    http: Client,
+    /// `host[:port]` of the endpoint, as sent in the `Host` header
+    host: String,
+}
+
+impl S3 {
+    pub fn new(config: S3Config) -> Result<Self> {
+        let endpoint = url::Url::parse(&config.endpoint).map_err(|e| {
+            Error::ConfigError(format!("Invalid S3 endpoint {}: {e}", config.endpoint))
+        })?;
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => {
+                return Err(Error::ConfigError(format!(
+                    "S3 endpoint {} has no host",
+                    config.endpoint
+                )));
+            }
+        };
+        Ok(Self {
+            credentials: Credentials {
+                access_key_id: config.access_key_id.clone(),
+                secret_access_key: config.secret_access_key.clone(),
+                session_token: None,
+            },
+            http: Client::new(),
+            host,
+            config,
+        })
+    }
+
+    /// Create the bucket unless it exists
+    pub async fn ensure_bucket(&self) -> Result<()> {
+        let response = self.send(Method::PUT, None, &[], Vec::new()).await?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if status.is_success() || body.contains("BucketAlreadyOwnedByYou") {
+            return Ok(());
+        }
+        Err(Error::UploadError(format!(
+            "Creating bucket {} failed with {status}: {body}",
+            self.config.bucket
+        )))
+    }
+
+    /// Presigned URL uploading `key`, or one part of the multipart upload `upload_id`
+    pub fn presign_put(&self, key: &str, part: Option<(&str, u64)>) -> String {
+        let query: Vec<(&str, String)> = part
+            .map(|(upload_id, number)| {
+                vec![
+                    ("partNumber", number.to_string()),
+                    ("uploadId", upload_id.to_string()),
+                ]
+            })
+            .unwrap_or_default();
+        let path = self.path(Some(key));
+        let query = presigned_query(
+            &self.credentials,
+            &self.config.region,
+            "s3",
+            "PUT",
+            &self.host,
+            &path,
+            &query,
+            SystemTime::now(),
+            PRESIGN_EXPIRY,
+        );
+        format!("{}{path}?{query}", self.endpoint())
+    }
+
+    /// Start a multipart upload of `key` and return its upload ID
+    pub async fn create_multipart_upload(&self, key: &str) -> Result<String> {
+        let response = self
+            .send(
+                Method::POST,
+                Some(key),
+                &[("uploads", String::new())],
+                Vec::new(),
+            )
+            .await?;
+        let body = checked_body("CreateMultipartUpload", response).await?;
+        xml_element(&body, "UploadId").ok_or_else(|| {
+            Error::UploadError(format!(
+                "CreateMultipartUpload returned no upload ID: {body}"
+            ))
+        })
+    }
+
+    /// Assemble the uploaded `parts` (number and `ETag`) into `key`
+    pub async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[(u64, String)],
+    ) -> Result<()> {
+        let mut xml = String::from("<CompleteMultipartUpload>");
+        for (number, etag) in parts {
+            let _ = write!(
+                xml,
+                "<Part><PartNumber>{number}</PartNumber><ETag>{etag}</ETag></Part>"
+            );
+        }
+        xml.push_str("</CompleteMultipartUpload>");
+        let response = self
+            .send(
+                Method::POST,
+                Some(key),
+                &[("uploadId", upload_id.to_string())],
+                xml.into_bytes(),
+            )
+            .await?;
+        // Failures after the upload started are reported in a 200 response
+        let body = checked_body("CompleteMultipartUpload", response).await?;
+        if body.contains("<Error>") {
+            return Err(Error::UploadError(format!(
+                "CompleteMultipartUpload failed: {body}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Abandon the multipart upload `upload_id`, discarding its parts
+    pub async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<()> {
+        let response = self
+            .send(
+                Method::DELETE,
+                Some(key),
+                &[("uploadId", upload_id.to_string())],
+                Vec::new(),
+            )
+            .await?;
+        checked_body("AbortMultipartUpload", response).await?;
+        Ok(())
+    }
+
+    /// Contents of the stored object `key`, or `None` if there is none
+    pub async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let response = self.send(Method::GET, Some(key), &[], Vec::new()).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(Error::UploadError(format!(
+                "Reading object {key} failed with {}",
+                response.status()
+            )));
+        }
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// Send a request signed in the `Authorization` header to the bucket or object `key`
+    async fn send(
+        &self,
+        method: Method,
+        key: Option<&str>,
+        query: &[(&str, String)],
+        body: Vec<u8>,
+    ) -> Result<Response> {
+        let path = self.path(key);
+        let query = canonical_query(query);
+        let payload_hash = sha256_hex(&body);
+        let amz_date = amz_date(SystemTime::now());
+        let headers = [
+            ("host", self.host.clone()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        let authorization = authorization(
+            &self.credentials,
+            &self.config.region,
+            "s3",
+            &amz_date,
+            &Request {
+                method: method.as_str(),
+                path: &path,
+                query: &query,
+                headers: &headers,
+                payload_hash: &payload_hash,
+            },
+        );
+
+        let url = if query.is_empty() {
+            format!("{}{path}", self.endpoint())
+        } else {
+            format!("{}{path}?{query}", self.endpoint())
+        };
+        Ok(self
+            .http
+            .request(method, url)
+            .header("authorization", authorization)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .body(body)
+            .send()
+            .await?)
+    }
+
+    fn endpoint(&self) -> &str {
+        self.config.endpoint.trim_end_matches('/')
+    }
+
+    /// Path-style path of the bucket or an object in it
+    fn path(&self, key: Option<&str>) -> String {
+        let bucket = uri_encode(&self.config.bucket, true);
+        match key {
+            Some(key) => format!("/{bucket}/{}", uri_encode(key, false)),
+            None => format!("/{bucket}"),
+        }
+    }
+}
+
+async fn checked_body(operation: &str, response: Response) -> Result<String> {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    if status.is_success() {
+        Ok(body)
+    } else {
+        Err(Error::UploadError(format!(
+            "{operation} failed with {status}: {body}"
+        )))
+    }
+}
+
+/// Text of the first `<name>` element of an XML document
+fn xml_element(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+    Some(xml[start..end].to_string())
+}
@@ -0,0 +1,178 @@
+//! Per-part encryption of uploads with licensed or regulated content
+//!
+//! Each encrypted upload gets a fresh AES-256 data key from the project's key management
+//! service: the server returns the key once in plaintext, together with the same key
+//! wrapped by the project key. Every part is sealed with AES-256-GCM before it leaves the
+//! machine and only the wrapped key is stored with the build, so leaked storage
+//! credentials expose ciphertext only.
+
+use crate::error::{Error, Result};
+use crate::util::{hex_decode, hex_encode};
+use bytes::Bytes;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+/// Algorithm recorded with encrypted builds
+pub const ENCRYPTION_ALGORITHM: &str = "AES-256-GCM";
+
+/// Bytes the authentication tag adds to every encrypted part
+pub const PART_TAG_LEN: u64 = 16;
+
+/// Length of the random nonce prefix shared by all parts of an upload
+const NONCE_PREFIX_LEN: usize = NONCE_LEN - 4;
+
+/// Data key generated by the project's key management service for one upload
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DataKey {
+    /// ID of the project key that wrapped the data key
+    pub key_id: String,
+    /// The AES-256 key as hex, used for this upload only and never stored
+    plaintext_key: String,
+    /// The data key encrypted with the project key, stored with the build
+    pub wrapped_key: String,
+}
+
+impl std::fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataKey")
+            .field("key_id", &self.key_id)
+            .field("plaintext_key", &"<redacted>")
+            .field("wrapped_key", &self.wrapped_key)
+            .finish()
+    }
+}
+
+/// How an upload was encrypted, sent with the upload request so it can be decrypted later
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct EncryptionInfo {
+    pub algorithm: String,
+    pub key_id: String,
+    pub wrapped_key: String,
+    /// Hex nonce prefix; each part's nonce is the prefix followed by its big-endian
+    /// 32-bit part number
+    pub nonce_prefix: String,
+    /// Bytes the authentication tag appends to each part
+    pub tag_size: u64,
+}
+
+/// Seals the parts of one upload with its data key
+pub struct PartCipher {
+    key: LessSafeKey,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+}
+
+impl PartCipher {
+    /// Create a cipher for `data_key` with a random nonce prefix
+    ///
+    /// Returns the cipher and the description of the encryption to send with the upload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data key is not a 256-bit hex key or no randomness is
+    /// available
+    pub fn new(data_key: &DataKey) -> Result<(Self, EncryptionInfo)> {
+        let key = hex_decode(&data_key.plaintext_key)
+            .and_then(|key| UnboundKey::new(&AES_256_GCM, &key).ok())
+            .ok_or_else(|| {
+                Error::EncryptionError(format!(
+                    "data key {} is not a 256-bit hex key",
+                    data_key.key_id
+                ))
+            })?;
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce_prefix)
+            .map_err(|_| Error::EncryptionError("no secure randomness available".to_string()))?;
+
+        let info = EncryptionInfo {
+            algorithm: ENCRYPTION_ALGORITHM.to_string(),
+            key_id: data_key.key_id.clone(),
+            wrapped_key: data_key.wrapped_key.clone(),
+            nonce_prefix: hex_encode(&nonce_prefix),
+            tag_size: PART_TAG_LEN,
+        };
+
+        Ok((
+            Self {
+                key: LessSafeKey::new(key),
+                nonce_prefix,
+            },
+            info,
+        ))
+    }
+
+    /// Encrypt one part, appending its authentication tag
+    ///
+    /// The nonce is derived from the part number, so parts cannot be reordered or swapped
+    /// without failing authentication.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the part number does not fit the nonce
+    pub fn seal(&self, part_number: u64, data: &[u8]) -> Result<Bytes> {
+        let mut out = Vec::with_capacity(data.len() + AES_256_GCM.tag_len());
+        out.extend_from_slice(data);
+        self.key
+            .seal_in_place_append_tag(self.nonce(part_number)?, Aad::empty(), &mut out)
+            .map_err(|_| Error::EncryptionError(format!("could not seal part {part_number}")))?;
+        Ok(Bytes::from(out))
+    }
+
+    fn nonce(&self, part_number: u64) -> Result<Nonce> {
+        let counter = u32::try_from(part_number).map_err(|_| {
+            Error::EncryptionError(format!("part number {part_number} is out of range"))
+        })?;
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_LEN..].copy_from_slice(&counter.to_be_bytes());
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_parts() {
+        let data_key = DataKey {
+            key_id: "kms-1".to_string(),
+            plaintext_key: "42".repeat(32),
+            wrapped_key: "wrapped".to_string(),
+        };
+        let (cipher, info) = PartCipher::new(&data_key).unwrap();
+        assert_eq!(info.algorithm, ENCRYPTION_ALGORITHM);
+        assert_eq!(info.nonce_prefix.len(), NONCE_PREFIX_LEN * 2);
+        assert!(!format!("{data_key:?}").contains(&"42".repeat(32)));
+
+        let first = cipher.seal(1, b"licensed content").unwrap();
+        let second = cipher.seal(2, b"licensed content").unwrap();
+        assert_eq!(first.len() as u64, 16 + PART_TAG_LEN);
+        assert_ne!(first, second);
+
+        let mut opened = first.to_vec();
+        let plain = cipher
+            .key
+            .open_in_place(cipher.nonce(1).unwrap(), Aad::empty(), &mut opened)
+            .unwrap();
+        assert_eq!(plain, b"licensed content");
+
+        let mut swapped = second.to_vec();
+        assert!(
+            cipher
+                .key
+                .open_in_place(cipher.nonce(1).unwrap(), Aad::empty(), &mut swapped)
+                .is_err()
+        );
+
+        let short = DataKey {
+            plaintext_key: "42".repeat(16),
+            ..data_key
+        };
+        assert!(PartCipher::new(&short).is_err());
+    }
+}
@@ -5,6 +5,15 @@ pub enum Error {
     #[error("API request failed: {0}")]
     ApiError(String),
 
+    /// The API answered with an error status
+    #[error("API request failed: {message}")]
+    ApiStatus {
+        status: reqwest::StatusCode,
+        /// Error code from the response body, e.g. `storage_limit_exceeded`
+        code: Option<String>,
+        message: String,
+    },
+
     #[error("File error: {0}")]
     FileError(#[from] std::io::Error),
 
@@ -14,11 +23,33 @@ pub enum Error {
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    #[error("Archive error: {0}")]
+    ArchiveError(String),
+
     #[error("Invalid configuration: {0}")]
     ConfigError(String),
 
     #[error("Upload failed: {0}")]
     UploadError(String),
+
+    /// Storage answered an upload with an error status
+    #[error("Upload failed: {message}")]
+    StorageStatus {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+
+    #[error("Upload script failed: {0}")]
+    ScriptError(String),
+
+    #[error("Invalid upload receipt: {0}")]
+    ReceiptError(String),
+
+    #[error("Invalid upload session: {0}")]
+    SessionError(String),
+
+    #[error("Encryption failed: {0}")]
+    EncryptionError(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,265 @@
+//! Machine-readable reports of failures, for `--json-errors`
+//!
+//! Orchestrators triaging failed runs get one JSON object on stderr instead of a message
+//! to pattern-match: a broad category, the HTTP status and API error code when a server
+//! refused a request, the build the failure belongs to and whether retrying the same
+//! command may succeed.
+
+use crate::error::Error;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::fmt;
+
+/// Broad cause of a failure
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Invalid flags, config file or environment
+    Config,
+    /// The API token is missing, invalid or lacks access
+    Auth,
+    /// The project or build does not exist
+    NotFound,
+    /// The project ran out of storage or quota
+    Quota,
+    RateLimited,
+    /// The API failed or answered with something the CLI cannot use
+    Api,
+    /// The API or storage could not be reached, or the connection broke
+    Network,
+    /// Storage rejected an upload or the upload did not complete
+    Storage,
+    /// A local file could not be read or written
+    File,
+    Archive,
+    Script,
+    Encryption,
+    /// A receipt, session or JSON document is invalid
+    InvalidData,
+    Other,
+}
+
+/// What failed and what to do about it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub category: ErrorCategory,
+    pub message: String,
+    /// Status of the HTTP response that reported the failure, if any
+    pub http_status: Option<u16>,
+    /// Error code of the API response, e.g. `storage_limit_exceeded`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Build the failure belongs to, if one was created
+    pub build_id: Option<String>,
+    /// Whether running the same command again may succeed
+    pub retryable: bool,
+    /// Failures of the individual files of a batch
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileErrorReport>,
+}
+
+/// Failure of one file of a batch
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileErrorReport {
+    pub file: String,
+    #[serde(flatten)]
+    pub error: ErrorReport,
+}
+
+/// Error of a batch in which some files failed, with a report of each
+#[derive(Debug)]
+pub struct BatchFailure {
+    pub files: Vec<FileErrorReport>,
+}
+
+impl fmt::Display for BatchFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} file(s) failed to upload", self.files.len())
+    }
+}
+
+impl std::error::Error for BatchFailure {}
+
+impl ErrorReport {
+    /// Report of `error` shown as `message`, classified by the first error in its source
+    /// chain that this crate knows
+    #[must_use]
+    pub fn new(message: String, error: &(dyn std::error::Error + 'static)) -> Self {
+        let mut source = Some(error);
+        while let Some(error) = source {
+            if let Some(batch) = error.downcast_ref::<BatchFailure>() {
+                return Self::batch(message, batch);
+            }
+            if let Some(error) = error.downcast_ref::<Error>() {
+                return Self::classify(message, error);
+            }
+            if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+                return Self::http(message, error);
+            }
+            source = error.source();
+        }
+        Self::other(message)
+    }
+
+    /// Report of a failure without a known cause
+    #[must_use]
+    pub fn other(message: String) -> Self {
+        Self {
+            category: ErrorCategory::Other,
+            message,
+            http_status: None,
+            code: None,
+            build_id: None,
+            retryable: false,
+            files: Vec::new(),
+        }
+    }
+
+    fn classify(message: String, error: &Error) -> Self {
+        let category = match error {
+            Error::ApiStatus { status, code, .. } => {
+                return Self::status(message, *status, code.clone(), ErrorCategory::Api);
+            }
+            Error::StorageStatus { status, .. } => {
+                return Self::status(message, *status, None, ErrorCategory::Storage);
+            }
+            Error::HttpError(error) => return Self::http(message, error),
+            Error::UploadError(_) => {
+                // Incomplete uploads, stalls and other storage hiccups
+                return Self {
+                    category: ErrorCategory::Storage,
+                    retryable: true,
+                    ..Self::other(message)
+                };
+            }
+            Error::ApiError(_) => ErrorCategory::Api,
+            Error::FileError(_) => ErrorCategory::File,
+            Error::ArchiveError(_) => ErrorCategory::Archive,
+            Error::ConfigError(_) => ErrorCategory::Config,
+            Error::ScriptError(_) => ErrorCategory::Script,
+            Error::EncryptionError(_) => ErrorCategory::Encryption,
+            Error::JsonError(_) | Error::ReceiptError(_) | Error::SessionError(_) => {
+                ErrorCategory::InvalidData
+            }
+        };
+        Self {
+            category,
+            ..Self::other(message)
+        }
+    }
+
+    fn http(message: String, error: &reqwest::Error) -> Self {
+        if let Some(status) = error.status() {
+            return Self::status(message, status, None, ErrorCategory::Api);
+        }
+        Self {
+            category: ErrorCategory::Network,
+            retryable: error.is_connect() || error.is_timeout() || error.is_request(),
+            ..Self::other(message)
+        }
+    }
+
+    /// Report of an error response, categorized by its status and API error code
+    fn status(
+        message: String,
+        status: StatusCode,
+        code: Option<String>,
+        fallback: ErrorCategory,
+    ) -> Self {
+        let category = match (code.as_deref(), status) {
+            (Some("storage_limit_exceeded" | "quota_exceeded"), _) => ErrorCategory::Quota,
+            (_, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => ErrorCategory::Auth,
+            (_, StatusCode::NOT_FOUND) => ErrorCategory::NotFound,
+            (_, StatusCode::TOO_MANY_REQUESTS) => ErrorCategory::RateLimited,
+            _ => fallback,
+        };
+        Self {
+            category,
+            http_status: Some(status.as_u16()),
+            code,
+            retryable: status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS
+                || status.is_server_error(),
+            ..Self::other(message)
+        }
+    }
+
+    /// Report of a batch: the files' category and status if they share them, retryable if
+    /// every file is
+    fn batch(message: String, batch: &BatchFailure) -> Self {
+        let reports = || batch.files.iter().map(|file| &file.error);
+        Self {
+            category: shared(reports().map(|r| r.category)).unwrap_or(ErrorCategory::Other),
+            http_status: shared(reports().map(|r| r.http_status)).flatten(),
+            code: shared(reports().map(|r| r.code.clone())).flatten(),
+            retryable: !batch.files.is_empty() && reports().all(|r| r.retryable),
+            files: batch.files.clone(),
+            ..Self::other(message)
+        }
+    }
+}
+
+/// The value all of `values` have, if there are any and they agree
+fn shared<T: PartialEq>(mut values: impl Iterator<Item = T>) -> Option<T> {
+    let first = values.next()?;
+    values.all(|value| value == first).then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_report() {
+        let quota = Error::ApiStatus {
+            status: StatusCode::FORBIDDEN,
+            code: Some("storage_limit_exceeded".to_string()),
+            message: "storage is full".to_string(),
+        };
+        let report = ErrorReport::new(quota.to_string(), &quota);
+        assert_eq!(report.category, ErrorCategory::Quota);
+        assert_eq!(report.http_status, Some(403));
+        assert!(!report.retryable);
+
+        let unavailable = Error::StorageStatus {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: "Status 503 Service Unavailable: SlowDown".to_string(),
+        };
+        let report = ErrorReport::new(unavailable.to_string(), &unavailable);
+        assert_eq!(report.category, ErrorCategory::Storage);
+        assert!(report.retryable);
+
+        let config = Error::ConfigError("bad".to_string());
+        assert_eq!(
+            ErrorReport::new(config.to_string(), &config).category,
+            ErrorCategory::Config
+        );
+
+        let file = |name: &str, error: &Error| FileErrorReport {
+            file: name.to_string(),
+            error: ErrorReport {
+                build_id: Some(format!("build-{name}")),
+                ..ErrorReport::new(error.to_string(), error)
+            },
+        };
+        let stalled = Error::UploadError("Part 2 stalled".to_string());
+        let batch = BatchFailure {
+            files: vec![file("a.apk", &unavailable), file("b.apk", &stalled)],
+        };
+        let report = ErrorReport::new(format!("Upload failed: {batch}"), &batch);
+        assert_eq!(report.category, ErrorCategory::Storage);
+        assert_eq!(report.http_status, None);
+        assert!(report.retryable);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["files"][0],
+            serde_json::json!({
+                "file": "a.apk",
+                "category": "storage",
+                "message": "Upload failed: Status 503 Service Unavailable: SlowDown",
+                "http_status": 503,
+                "build_id": "build-a.apk",
+                "retryable": true,
+            })
+        );
+    }
+}
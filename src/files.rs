@@ -0,0 +1,255 @@
+use crate::api::BuildPlatform;
+use crate::error::{Error, Result};
+use glob::Pattern;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::debug;
+use std::path::{Path, PathBuf};
+
+/// Name of the ignore file read from the project root
+pub const NUNUIGNORE_FILE: &str = ".nunuignore";
+
+/// Paths excluded by a `.nunuignore` file (gitignore syntax)
+#[derive(Debug)]
+pub struct IgnoreRules {
+    root: PathBuf,
+    matcher: Gitignore,
+}
+
+impl IgnoreRules {
+    /// Load `.nunuignore` from `root`, returning `None` if the file does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(NUNUIGNORE_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let root = std::path::absolute(root)?;
+        let mut builder = GitignoreBuilder::new(&root);
+        if let Some(e) = builder.add(&path) {
+            return Err(Error::ConfigError(format!(
+                "Failed to parse {}: {e}",
+                path.display()
+            )));
+        }
+        let matcher = builder
+            .build()
+            .map_err(|e| Error::ConfigError(format!("Failed to parse {}: {e}", path.display())))?;
+
+        debug!(
+            "Loaded {} ignore rule(s) from {}",
+            matcher.len(),
+            path.display()
+        );
+        Ok(Some(Self { root, matcher }))
+    }
+
+    /// Load `.nunuignore` from the current working directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub fn load_from_current_dir() -> Result<Option<Self>> {
+        Self::load(&std::env::current_dir()?)
+    }
+
+    /// Returns whether `path` (or one of its parent directories) is ignored
+    ///
+    /// Paths outside the project root are never ignored.
+    #[must_use]
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(absolute) = std::path::absolute(path) else {
+            return false;
+        };
+        let Ok(relative) = absolute.strip_prefix(&self.root) else {
+            return false;
+        };
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+
+        self.matcher
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+    }
+}
+
+/// Remove paths ignored by `.nunuignore` rules
+#[must_use]
+pub fn filter_ignored(paths: Vec<String>, rules: &IgnoreRules) -> Vec<String> {
+    paths
+        .into_iter()
+        .filter(|path| {
+            let path_ref = Path::new(path);
+            let skip = rules.is_ignored(path_ref, path_ref.is_dir());
+            if skip {
+                debug!("Ignoring {path} ({NUNUIGNORE_FILE})");
+            }
+            !skip
+        })
+        .collect()
+}
+
+/// A file argument with optional per-file overrides
+///
+/// Accepts `PATH`, `PATH=PLATFORM`, `PATH::name=NAME` and `PATH=PLATFORM::name=NAME`,
+/// e.g. `game.zip=windows` or `editor.zip=linux::name=Editor {branch}`.
+#[derive(Debug, Clone)]
+pub struct FileSpec {
+    /// File path or glob pattern
+    pub pattern: String,
+    /// Platform for the matched files, overriding `--platform` and inference
+    pub platform: Option<BuildPlatform>,
+    /// Build name template for the matched files, overriding `--name`
+    pub name: Option<String>,
+}
+
+/// Parse a file argument with optional `=PLATFORM` and `::name=NAME` overrides
+///
+/// # Errors
+///
+/// Returns an error if an override is malformed or names an unknown platform
+pub fn parse_file_spec(value: &str) -> Result<FileSpec> {
+    let (path_part, overrides) = match value.split_once("::") {
+        Some((path, overrides)) => (path, Some(overrides)),
+        None => (value, None),
+    };
+
+    let mut name = None;
+    if let Some(overrides) = overrides {
+        match overrides.split_once('=') {
+            Some(("name", value)) if !value.trim().is_empty() => {
+                name = Some(value.to_string());
+            }
+            _ => {
+                return Err(Error::ConfigError(format!(
+                    "Invalid file override '::{overrides}' in '{value}'. Expected PATH::name=NAME"
+                )));
+            }
+        }
+    }
+
+    let (pattern, platform) = match path_part.rsplit_once('=') {
+        Some((path, platform)) if !path.is_empty() => match platform.parse::<BuildPlatform>() {
+            Ok(platform) => (path.to_string(), Some(platform)),
+            // A literal `=` in a path, unless the suffix clearly meant to be a platform
+            Err(_) if Path::new(path_part).exists() || platform.contains(['.', '/', '\\']) => {
+                (path_part.to_string(), None)
+            }
+            Err(e) => return Err(e),
+        },
+        _ => (path_part.to_string(), None),
+    };
+
+    Ok(FileSpec {
+        pattern,
+        platform,
+        name,
+    })
+}
+
+/// Compile `--exclude` glob patterns
+///
+/// # Errors
+///
+/// Returns an error if a pattern is not a valid glob
+pub fn compile_excludes(patterns: &[String]) -> Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|p| {
+            Pattern::new(p)
+                .map_err(|e| Error::ConfigError(format!("Invalid exclude pattern '{p}': {e}")))
+        })
+        .collect()
+}
+
+/// Returns whether `path` matches any of the exclude patterns
+///
+/// Patterns are matched against both the full path and the file name, so
+/// `*-debug.apk` excludes `build/outputs/app-debug.apk`.
+#[must_use]
+pub fn is_excluded(path: &str, excludes: &[Pattern]) -> bool {
+    let file_name = Path::new(path).file_name().and_then(|n| n.to_str());
+    excludes
+        .iter()
+        .any(|pattern| pattern.matches(path) || file_name.is_some_and(|n| pattern.matches(n)))
+}
+
+/// Remove paths matching any of the exclude patterns
+#[must_use]
+pub fn filter_excluded(paths: Vec<String>, excludes: &[Pattern]) -> Vec<String> {
+    paths
+        .into_iter()
+        .filter(|path| {
+            let skip = is_excluded(path, excludes);
+            if skip {
+                debug!("Excluding {path}");
+            }
+            !skip
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_excluded() {
+        let excludes =
+            compile_excludes(&["*-debug.apk".to_string(), "build/tmp/*".to_string()]).unwrap();
+        let paths = vec![
+            "build/outputs/app-release.apk".to_string(),
+            "build/outputs/app-debug.apk".to_string(),
+            "build/tmp/app.apk".to_string(),
+            "app-debug.apk".to_string(),
+        ];
+
+        assert_eq!(
+            filter_excluded(paths, &excludes),
+            vec!["build/outputs/app-release.apk".to_string()]
+        );
+        assert!(compile_excludes(&["[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_file_spec() {
+        let spec = parse_file_spec("game.zip").unwrap();
+        assert_eq!(spec.pattern, "game.zip");
+        assert!(spec.platform.is_none() && spec.name.is_none());
+
+        let spec = parse_file_spec("game.zip=windows").unwrap();
+        assert_eq!(spec.pattern, "game.zip");
+        assert!(matches!(spec.platform, Some(BuildPlatform::Windows)));
+
+        let spec = parse_file_spec("out/*.zip=linux::name=Editor {branch}").unwrap();
+        assert_eq!(spec.pattern, "out/*.zip");
+        assert!(matches!(spec.platform, Some(BuildPlatform::Linux)));
+        assert_eq!(spec.name.as_deref(), Some("Editor {branch}"));
+
+        let spec = parse_file_spec("a=b/game.zip").unwrap();
+        assert_eq!(spec.pattern, "a=b/game.zip");
+
+        assert!(parse_file_spec("game.zip=widnows").is_err());
+        assert!(parse_file_spec("game.zip::title=x").is_err());
+    }
+
+    #[test]
+    fn test_nunuignore() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        std::fs::write(root.join(NUNUIGNORE_FILE), "*.pdb\nbuild/tmp/\n!keep.pdb\n").unwrap();
+
+        let rules = IgnoreRules::load(root).unwrap().unwrap();
+        assert!(rules.is_ignored(&root.join("game.pdb"), false));
+        assert!(!rules.is_ignored(&root.join("keep.pdb"), false));
+        assert!(rules.is_ignored(&root.join("build/tmp/app.apk"), false));
+        assert!(!rules.is_ignored(&root.join("build/app.apk"), false));
+        assert!(!rules.is_ignored(Path::new("/elsewhere/game.pdb"), false));
+
+        assert!(IgnoreRules::load(&root.join("missing")).unwrap().is_none());
+    }
+}
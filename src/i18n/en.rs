@@ -0,0 +1,88 @@
+//! English messages, the fallback for keys other catalogs lack
+
+pub(super) const MESSAGES: &[(&str, &str)] = &[
+    ("upload.failed", "Upload failed: {error}"),
+    ("upload.succeeded", "Successfully uploaded {count} file(s):"),
+    (
+        "upload.uploaded",
+        "{file} → Build ID: {build_id} ({size} in {duration}, {rate}{size_diff})",
+    ),
+    (
+        "upload.linked",
+        "{file} → Build ID: {build_id} (linked to {original}, uploaded once)",
+    ),
+    ("upload.errors", "Failed to upload {count} file(s):"),
+    (
+        "upload.estimate",
+        "Estimated upload time: ~{duration} at your typical {rate} on this network",
+    ),
+    ("upload.cancelled", "Upload cancelled."),
+    ("upload.terminated", "Upload terminated."),
+    ("signal.interrupt", "Received interrupt signal ({signal})."),
+    ("signal.terminate", "Received termination signal (SIGTERM)."),
+    (
+        "abort.attempting",
+        "Attempting to abort {count} active upload(s)...",
+    ),
+    ("abort.done", "{count} upload(s) aborted."),
+    (
+        "abort.unconfirmed",
+        "Could not confirm aborting {count} upload(s):",
+    ),
+    (
+        "dry_run.header",
+        "Dry run: {count} file(s) would be uploaded via {url}/upload",
+    ),
+    (
+        "verify.summary",
+        "Verified {verified} of {files} uploaded file(s)",
+    ),
+    (
+        "verify.summary_skipped",
+        "Verified {verified} of {files} uploaded file(s), {skipped} directory upload(s) skipped",
+    ),
+    ("verify.mismatches", "{summary}; {count} differ:"),
+    ("checksums.written", "Checksum manifest written to {path}"),
+    ("builds.deleted", "Build {build_id} permanently deleted."),
+    (
+        "builds.trashed_until",
+        "Build {build_id} moved to trash. Restore it until {deadline} with:",
+    ),
+    (
+        "builds.trashed",
+        "Build {build_id} moved to trash. Restore it with:",
+    ),
+    ("builds.restored", "Build {build_id} restored."),
+    (
+        "error.missing_token",
+        "API token not provided (use --token, NUNU_API_TOKEN env var, --token-file, or config file)",
+    ),
+    (
+        "error.missing_project",
+        "Project ID not provided (use --project-id, NUNU_PROJECT_ID env var, or config file)",
+    ),
+    (
+        "error.storage_connect",
+        "Cannot connect to storage. Possible causes:\n\
+         - Firewall blocking *.r2.cloudflarestorage.com\n\
+         - Network proxy required (use --proxy or set HTTPS_PROXY)\n\
+         - DNS resolution failure\n\
+         Error details: {error}",
+    ),
+    (
+        "error.storage_request",
+        "Request failed. This may indicate:\n\
+         - Network interruption during upload\n\
+         - Proxy interfering with the request\n\
+         - SSL/TLS issue\n\
+         Error details: {error}",
+    ),
+    (
+        "error.storage_request_after",
+        "Request failed after uploading {bytes} bytes. This may indicate:\n\
+         - Network interruption during upload\n\
+         - Proxy interfering with the request\n\
+         - SSL/TLS issue\n\
+         Error details: {error}",
+    ),
+];
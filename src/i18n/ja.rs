@@ -0,0 +1,103 @@
+//! Japanese messages
+
+pub(super) const MESSAGES: &[(&str, &str)] = &[
+    ("upload.failed", "アップロードに失敗しました: {error}"),
+    (
+        "upload.succeeded",
+        "{count} 件のファイルをアップロードしました:",
+    ),
+    (
+        "upload.uploaded",
+        "{file} → ビルド ID: {build_id} ({size}、{duration}、{rate}{size_diff})",
+    ),
+    (
+        "upload.linked",
+        "{file} → ビルド ID: {build_id} ({original} にリンク、アップロードは 1 回のみ)",
+    ),
+    (
+        "upload.errors",
+        "{count} 件のファイルのアップロードに失敗しました:",
+    ),
+    (
+        "upload.estimate",
+        "推定アップロード時間: 約 {duration}（このネットワークでの通常の速度 {rate}）",
+    ),
+    ("upload.cancelled", "アップロードをキャンセルしました。"),
+    ("upload.terminated", "アップロードを終了しました。"),
+    (
+        "signal.interrupt",
+        "割り込みシグナル ({signal}) を受信しました。",
+    ),
+    (
+        "signal.terminate",
+        "終了シグナル (SIGTERM) を受信しました。",
+    ),
+    (
+        "abort.attempting",
+        "実行中の {count} 件のアップロードを中止しています...",
+    ),
+    ("abort.done", "{count} 件のアップロードを中止しました。"),
+    (
+        "abort.unconfirmed",
+        "{count} 件のアップロードの中止を確認できませんでした:",
+    ),
+    (
+        "dry_run.header",
+        "ドライラン: {count} 件のファイルが {url}/upload にアップロードされます",
+    ),
+    (
+        "verify.summary",
+        "アップロードした {files} 件のうち {verified} 件のファイルを検証しました",
+    ),
+    (
+        "verify.summary_skipped",
+        "アップロードした {files} 件のうち {verified} 件のファイルを検証しました (ディレクトリのアップロード {skipped} 件はスキップ)",
+    ),
+    ("verify.mismatches", "{summary}。{count} 件が一致しません:"),
+    (
+        "checksums.written",
+        "チェックサムマニフェストを {path} に書き込みました",
+    ),
+    ("builds.deleted", "ビルド {build_id} を完全に削除しました。"),
+    (
+        "builds.trashed_until",
+        "ビルド {build_id} をゴミ箱に移動しました。{deadline} まで次のコマンドで復元できます:",
+    ),
+    (
+        "builds.trashed",
+        "ビルド {build_id} をゴミ箱に移動しました。次のコマンドで復元できます:",
+    ),
+    ("builds.restored", "ビルド {build_id} を復元しました。"),
+    (
+        "error.missing_token",
+        "API トークンが指定されていません (--token、環境変数 NUNU_API_TOKEN、--token-file、または設定ファイルで指定してください)",
+    ),
+    (
+        "error.missing_project",
+        "プロジェクト ID が指定されていません (--project-id、環境変数 NUNU_PROJECT_ID、または設定ファイルで指定してください)",
+    ),
+    (
+        "error.storage_connect",
+        "ストレージに接続できません。考えられる原因:\n\
+         - ファイアウォールが *.r2.cloudflarestorage.com をブロックしている\n\
+         - ネットワークプロキシが必要 (--proxy を使うか HTTPS_PROXY を設定してください)\n\
+         - DNS の名前解決に失敗した\n\
+         エラーの詳細: {error}",
+    ),
+    (
+        "error.storage_request",
+        "リクエストに失敗しました。考えられる原因:\n\
+         - アップロード中にネットワークが切断された\n\
+         - プロキシがリクエストに干渉している\n\
+         - SSL/TLS の問題\n\
+         エラーの詳細: {error}",
+    ),
+    (
+        "error.storage_request_after",
+        "{bytes} バイトをアップロードした後にリクエストが失敗しました。考えられる原因:\n\
+         - アップロード中にネットワークが切断された\n\
+         - プロキシがリクエストに干渉している\n\
+         - SSL/TLS の問題\n\
+         エラーの詳細: {error}",
+    ),
+];
@@ -0,0 +1,251 @@
+//! Translated user-facing messages
+//!
+//! Upload, verification and build management summaries, cancellation prompts and the most
+//! common setup and storage connection errors are looked up by key in the catalog of the
+//! active locale, falling back to English for keys a catalog lacks. Other errors and log
+//! output stay in English so they match what support sees. The locale comes from `--lang`,
+//! `NUNU_LANG` or the usual `LC_ALL`, `LC_MESSAGES` and `LANG` variables.
+//!
+//! Messages name their placeholders (`{count}`) so translations can reorder them:
+//!
+//! ```
+//! use nunu_cli::t;
+//! let message = t!("upload.succeeded", count = 2);
+//! ```
+
+mod en;
+mod ja;
+mod zh;
+
+use std::fmt::{Display, Write as _};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+/// A language with a message catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Locale {
+    En,
+    Ja,
+    /// Chinese, in simplified characters
+    Zh,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::En, Locale::Ja, Locale::Zh];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Ja => "ja",
+            Locale::Zh => "zh",
+        }
+    }
+
+    /// Locale of a language tag or POSIX locale name such as `ja`, `zh-CN` or
+    /// `ja_JP.UTF-8`, or `None` for languages without a catalog
+    ///
+    /// Chinese as written in Taiwan, Hong Kong and Macau (traditional characters) has no
+    /// catalog, so only simplified Chinese tags map to [`Locale::Zh`].
+    #[must_use]
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag
+            .split(['.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut subtags = tag.split(['_', '-']);
+        let language = subtags.next().unwrap_or_default();
+        let subtags: Vec<&str> = subtags.collect();
+        match language {
+            "en" | "c" | "posix" => Some(Locale::En),
+            "ja" => Some(Locale::Ja),
+            "zh" if subtags.contains(&"hans") => Some(Locale::Zh),
+            "zh" if subtags
+                .iter()
+                .any(|subtag| matches!(*subtag, "hant" | "tw" | "hk" | "mo")) =>
+            {
+                None
+            }
+            "zh" => Some(Locale::Zh),
+            _ => None,
+        }
+    }
+
+    /// Locale requested by `NUNU_LANG`, `LC_ALL`, `LC_MESSAGES` or `LANG`, in that order
+    ///
+    /// The first variable that is set decides, as with gettext; languages without a
+    /// catalog get English.
+    #[must_use]
+    pub fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        ["NUNU_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .find_map(|name| var(name).filter(|value| !value.is_empty()))
+            .and_then(|tag| Self::from_tag(&tag))
+            .unwrap_or(Locale::En)
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::En => en::MESSAGES,
+            Locale::Ja => ja::MESSAGES,
+            Locale::Zh => zh::MESSAGES,
+        }
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_tag(s).ok_or_else(|| {
+            format!(
+                "unsupported language '{s}' (supported: {})",
+                Self::ALL.map(Locale::as_str).join(", ")
+            )
+        })
+    }
+}
+
+/// Show messages in `locale` for the rest of the process
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn locale() -> Locale {
+    Locale::ALL
+        .into_iter()
+        .find(|locale| *locale as u8 == LOCALE.load(Ordering::Relaxed))
+        .unwrap_or(Locale::En)
+}
+
+/// Message `key` in the active locale with its `{name}` placeholders filled in from `args`
+///
+/// Use [`t!`](crate::t) rather than calling this directly.
+#[must_use]
+pub fn translate(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    translate_in(locale(), key, args)
+}
+
+fn translate_in(locale: Locale, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let Some(template) = lookup(locale, key).or_else(|| lookup(Locale::En, key)) else {
+        return key.to_string();
+    };
+    // One pass, so placeholders in the values are left alone
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let value = placeholder.find('}').and_then(|end| {
+            let name = &placeholder[1..end];
+            args.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| (end, value))
+        });
+        if let Some((end, value)) = value {
+            let _ = write!(message, "{value}");
+            rest = &placeholder[end + 1..];
+        } else {
+            message.push('{');
+            rest = &placeholder[1..];
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+fn lookup(locale: Locale, key: &str) -> Option<&'static str> {
+    locale
+        .catalog()
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, text)| *text)
+}
+
+/// Translated message: `t!("key")` or `t!("key", name = value, ...)`
+#[macro_export]
+macro_rules! t {
+    ($key:literal) => {
+        $crate::i18n::translate($key, &[])
+    };
+    ($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate(
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names of the `{placeholders}` in a message, sorted
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_catalogs() {
+        for locale in Locale::ALL {
+            let catalog = locale.catalog();
+            assert_eq!(catalog.len(), en::MESSAGES.len(), "{locale:?}");
+            for (key, english) in en::MESSAGES {
+                let text = lookup(locale, key).unwrap_or_else(|| panic!("{locale:?}: {key}"));
+                assert_eq!(
+                    placeholders(text),
+                    placeholders(english),
+                    "{locale:?}: {key}"
+                );
+            }
+        }
+
+        assert_eq!(
+            translate_in(Locale::Ja, "upload.succeeded", &[("count", &2)]),
+            "2 件のファイルをアップロードしました:"
+        );
+        assert_eq!(
+            translate_in(Locale::En, "upload.failed", &[("error", &"bad {error}")]),
+            "Upload failed: bad {error}"
+        );
+        assert_eq!(translate_in(Locale::Zh, "unknown.key", &[]), "unknown.key");
+
+        let env = |values: &'static [(&str, &str)]| {
+            move |name: &str| {
+                values
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| (*v).to_string())
+            }
+        };
+        assert_eq!(Locale::detect(env(&[("LANG", "ja_JP.UTF-8")])), Locale::Ja);
+        assert_eq!(
+            Locale::detect(env(&[("LC_ALL", "zh_CN.UTF-8"), ("LANG", "ja_JP.UTF-8")])),
+            Locale::Zh
+        );
+        assert_eq!(
+            Locale::detect(env(&[("NUNU_LANG", "en"), ("LC_ALL", "zh_TW")])),
+            Locale::En
+        );
+        assert_eq!(Locale::detect(env(&[("LANG", "de_DE.UTF-8")])), Locale::En);
+        assert_eq!(Locale::detect(env(&[("LANG", "zh_TW.UTF-8")])), Locale::En);
+        assert_eq!(Locale::detect(env(&[("LANG", "zh_HK")])), Locale::En);
+        assert_eq!(Locale::detect(env(&[("NUNU_LANG", "zh-Hant")])), Locale::En);
+        assert_eq!(Locale::detect(env(&[("LANG", "zh_SG.UTF-8")])), Locale::Zh);
+        assert_eq!(Locale::from_tag("zh-Hans-HK"), Some(Locale::Zh));
+        assert_eq!(
+            Locale::detect(env(&[("LC_ALL", ""), ("LANG", "ja")])),
+            Locale::Ja
+        );
+    }
+}
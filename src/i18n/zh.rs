@@ -0,0 +1,82 @@
+//! Chinese messages, in simplified characters
+
+pub(super) const MESSAGES: &[(&str, &str)] = &[
+    ("upload.failed", "上传失败: {error}"),
+    ("upload.succeeded", "已成功上传 {count} 个文件:"),
+    (
+        "upload.uploaded",
+        "{file} → 构建 ID: {build_id} ({size}，用时 {duration}，{rate}{size_diff})",
+    ),
+    (
+        "upload.linked",
+        "{file} → 构建 ID: {build_id} (链接到 {original}，仅上传一次)",
+    ),
+    ("upload.errors", "{count} 个文件上传失败:"),
+    (
+        "upload.estimate",
+        "预计上传时间：约 {duration}（按此网络的常见速度 {rate}）",
+    ),
+    ("upload.cancelled", "上传已取消。"),
+    ("upload.terminated", "上传已终止。"),
+    ("signal.interrupt", "收到中断信号 ({signal})。"),
+    ("signal.terminate", "收到终止信号 (SIGTERM)。"),
+    ("abort.attempting", "正在中止 {count} 个进行中的上传..."),
+    ("abort.done", "已中止 {count} 个上传。"),
+    ("abort.unconfirmed", "无法确认是否已中止 {count} 个上传:"),
+    (
+        "dry_run.header",
+        "试运行: 将通过 {url}/upload 上传 {count} 个文件",
+    ),
+    (
+        "verify.summary",
+        "已校验 {files} 个已上传文件中的 {verified} 个",
+    ),
+    (
+        "verify.summary_skipped",
+        "已校验 {files} 个已上传文件中的 {verified} 个，跳过了 {skipped} 个目录上传",
+    ),
+    ("verify.mismatches", "{summary}；{count} 个不一致:"),
+    ("checksums.written", "校验和清单已写入 {path}"),
+    ("builds.deleted", "构建 {build_id} 已永久删除。"),
+    (
+        "builds.trashed_until",
+        "构建 {build_id} 已移至回收站。在 {deadline} 之前可使用以下命令恢复:",
+    ),
+    (
+        "builds.trashed",
+        "构建 {build_id} 已移至回收站。可使用以下命令恢复:",
+    ),
+    ("builds.restored", "构建 {build_id} 已恢复。"),
+    (
+        "error.missing_token",
+        "未提供 API 令牌 (请使用 --token、NUNU_API_TOKEN 环境变量、--token-file 或配置文件)",
+    ),
+    (
+        "error.missing_project",
+        "未提供项目 ID (请使用 --project-id、NUNU_PROJECT_ID 环境变量或配置文件)",
+    ),
+    (
+        "error.storage_connect",
+        "无法连接到存储。可能的原因:\n\
+         - 防火墙拦截了 *.r2.cloudflarestorage.com\n\
+         - 需要网络代理 (请使用 --proxy 或设置 HTTPS_PROXY)\n\
+         - DNS 解析失败\n\
+         错误详情: {error}",
+    ),
+    (
+        "error.storage_request",
+        "请求失败。可能的原因:\n\
+         - 上传过程中网络中断\n\
+         - 代理干扰了请求\n\
+         - SSL/TLS 问题\n\
+         错误详情: {error}",
+    ),
+    (
+        "error.storage_request_after",
+        "已上传 {bytes} 字节后请求失败。可能的原因:\n\
+         - 上传过程中网络中断\n\
+         - 代理干扰了请求\n\
+         - SSL/TLS 问题\n\
+         错误详情: {error}",
+    ),
+];
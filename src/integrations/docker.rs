@@ -0,0 +1,215 @@
+//! Credentials from Docker secrets for containerized pipelines
+//!
+//! Environment variables passed with `docker run -e` are visible to anyone who can run
+//! `docker inspect`. Secrets mounted under `/run/secrets` (`nunu_token`, `nunu_project_id`,
+//! `nunu_api_url`) are not, and are read after the environment and before the config file.
+//! A config file mounted as the `nunu_config` secret is used when no other config file is
+//! found.
+
+use crate::config::TransportOptions;
+use crate::credentials::run_token_command;
+use crate::file_config::FileConfig;
+use crate::secrets::fetch_token;
+use log::debug;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Where Docker and Docker Swarm mount secrets
+pub const DOCKER_SECRETS_DIR: &str = "/run/secrets";
+
+/// Secret holding the API token
+pub const TOKEN_SECRET: &str = "nunu_token";
+
+/// Secret holding the project ID or alias
+pub const PROJECT_ID_SECRET: &str = "nunu_project_id";
+
+/// Secret holding the API base URL
+pub const API_URL_SECRET: &str = "nunu_api_url";
+
+/// Secret holding a config file
+pub const CONFIG_SECRET: &str = "nunu_config";
+
+/// Directory secrets are read from; `NUNU_SECRETS_DIR` overrides it for runtimes that mount
+/// secrets elsewhere
+fn secrets_dir() -> PathBuf {
+    std::env::var_os("NUNU_SECRETS_DIR")
+        .map_or_else(|| PathBuf::from(DOCKER_SECRETS_DIR), PathBuf::from)
+}
+
+/// Read the secret `name`, trimmed, if it is mounted and not empty
+#[must_use]
+pub fn docker_secret(name: &str) -> Option<String> {
+    read_secret(&secrets_dir(), name)
+}
+
+fn read_secret(dir: &Path, name: &str) -> Option<String> {
+    let path = dir.join(name);
+    let value = std::fs::read_to_string(&path).ok()?;
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    debug!("Read {name} from {}", path.display());
+    Some(value.to_string())
+}
+
+/// Path of the config file mounted as a secret, if there is one
+#[must_use]
+pub fn mounted_config_path() -> Option<PathBuf> {
+    let path = secrets_dir().join(CONFIG_SECRET);
+    path.is_file().then_some(path)
+}
+
+/// Where a resolved value came from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValueSource {
+    Environment,
+    DockerSecret,
+    ConfigFile,
+    /// The secret store named by the config file's `token_secret`
+    SecretStore,
+    /// The config file's `token_command`
+    TokenCommand,
+}
+
+impl ValueSource {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Environment => "environment",
+            Self::DockerSecret => "docker secret",
+            Self::ConfigFile => "config file",
+            Self::SecretStore => "secret store",
+            Self::TokenCommand => "token_command",
+        }
+    }
+}
+
+/// A `NUNU_*` variable as the CLI resolves it
+#[derive(Serialize, Debug, Clone)]
+pub struct ResolvedVar {
+    pub name: &'static str,
+    pub value: Option<String>,
+    pub source: Option<ValueSource>,
+    /// Whether the value must never be printed
+    pub secret: bool,
+}
+
+/// Resolve the API token, project ID and API URL from the environment, Docker secrets and
+/// `file_config`, in that order, reading the config's `token_secret` or running its
+/// `token_command` if no token is found
+///
+/// Project aliases are resolved. Values that are not set anywhere are `None`. The secret
+/// store is reached with the proxy and TLS settings of `transport`.
+#[must_use]
+pub fn resolve_env(file_config: &FileConfig, transport: &TransportOptions) -> Vec<ResolvedVar> {
+    resolve_vars(
+        file_config,
+        transport,
+        |name| std::env::var(name).ok(),
+        &secrets_dir(),
+    )
+}
+
+fn resolve_vars(
+    file_config: &FileConfig,
+    transport: &TransportOptions,
+    var: impl Fn(&str) -> Option<String>,
+    secrets_dir: &Path,
+) -> Vec<ResolvedVar> {
+    let resolve = |name: &'static str, secret_name: &str, from_file: Option<&String>, secret| {
+        let (value, source) = if let Some(value) = var(name) {
+            (Some(value), Some(ValueSource::Environment))
+        } else if let Some(value) = read_secret(secrets_dir, secret_name) {
+            (Some(value), Some(ValueSource::DockerSecret))
+        } else if let Some(value) = from_file {
+            (Some(value.clone()), Some(ValueSource::ConfigFile))
+        } else {
+            (None, None)
+        };
+        ResolvedVar {
+            name,
+            value,
+            source,
+            secret,
+        }
+    };
+
+    let mut project_id = resolve(
+        "NUNU_PROJECT_ID",
+        PROJECT_ID_SECRET,
+        file_config.project_id.as_ref(),
+        false,
+    );
+    project_id.value = project_id.value.map(|p| file_config.resolve_project(p));
+
+    let mut token = resolve(
+        "NUNU_API_TOKEN",
+        TOKEN_SECRET,
+        file_config.api_token.as_ref(),
+        true,
+    );
+    if token.value.is_none()
+        && let Some(secret) = &file_config.token_secret
+    {
+        match fetch_token(secret, transport) {
+            Ok(value) => {
+                token.value = Some(value);
+                token.source = Some(ValueSource::SecretStore);
+            }
+            Err(e) => debug!("{e}"),
+        }
+    }
+    if token.value.is_none()
+        && let Some(command) = &file_config.token_command
+    {
+        match run_token_command(command) {
+            Ok(value) => {
+                token.value = Some(value);
+                token.source = Some(ValueSource::TokenCommand);
+            }
+            Err(e) => debug!("{e}"),
+        }
+    }
+
+    vec![
+        token,
+        project_id,
+        resolve(
+            "NUNU_API_URL",
+            API_URL_SECRET,
+            file_config.api_url.as_ref(),
+            false,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_env_reads_docker_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(TOKEN_SECRET), "secret-token\n").unwrap();
+        std::fs::write(dir.path().join(PROJECT_ID_SECRET), "  \n").unwrap();
+        std::fs::write(dir.path().join(API_URL_SECRET), "https://secret/api").unwrap();
+
+        let file_config = FileConfig {
+            project_id: Some("shooter-dev".to_string()),
+            projects: [("shooter-dev".to_string(), "proj_abc123".to_string())].into(),
+            ..FileConfig::default()
+        };
+        let env = |name: &str| (name == "NUNU_API_URL").then(|| "https://env/api".to_string());
+        let vars = resolve_vars(&file_config, &TransportOptions::default(), env, dir.path());
+
+        assert_eq!(vars[0].value.as_deref(), Some("secret-token"));
+        assert_eq!(vars[0].source, Some(ValueSource::DockerSecret));
+        assert!(vars[0].secret);
+        assert_eq!(vars[1].value.as_deref(), Some("proj_abc123"));
+        assert_eq!(vars[1].source, Some(ValueSource::ConfigFile));
+        assert_eq!(vars[2].value.as_deref(), Some("https://env/api"));
+        assert_eq!(vars[2].source, Some(ValueSource::Environment));
+    }
+}
@@ -0,0 +1,4 @@
+//! Helpers for integrating Nunu uploads into other tools
+
+pub mod docker;
+pub mod sdk_template;
@@ -0,0 +1,152 @@
+use crate::error::{Error, Result};
+use log::debug;
+use std::path::{Path, PathBuf};
+
+const CARGO_TOML_TEMPLATE: &str = r#"[package]
+name = "{{crate_name}}"
+version = "0.1.0"
+edition = "2024"
+publish = false
+
+[dependencies]
+nunu-cli = { git = "https://github.com/nunu-ai/nunu-cli", tag = "v{{cli_version}}" }
+tokio = { version = "1", features = ["macros", "rt-multi-thread"] }
+"#;
+
+const MAIN_RS_TEMPLATE: &str = r#"//! Minimal uploader built on the nunu-cli library.
+//!
+//! Usage: {{crate_name}} <FILE> <BUILD_NAME> [PLATFORM]
+//! The API token is read from the NUNU_API_TOKEN environment variable.
+
+use nunu_cli::{BuildPlatform, Config, UploadOptions, upload_file};
+
+const PROJECT_ID: &str = "{{project_id}}";
+const API_URL: &str = "{{api_url}}";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(file), Some(name)) = (args.next(), args.next()) else {
+        eprintln!("Usage: {{crate_name}} <FILE> <BUILD_NAME> [PLATFORM]");
+        std::process::exit(2);
+    };
+    let platform: BuildPlatform = args.next().as_deref().unwrap_or("windows").parse()?;
+
+    let token = std::env::var("NUNU_API_TOKEN")
+        .map_err(|_| "NUNU_API_TOKEN environment variable is not set")?;
+    let config = Config::new(token, PROJECT_ID.to_string(), API_URL.to_string())?;
+
+    let options = UploadOptions {
+        name,
+        platform: platform.as_str().to_string(),
+        ..UploadOptions::default()
+    };
+
+    let build_id = upload_file(&config, &file, options).await?;
+    println!("Uploaded build {build_id}");
+
+    Ok(())
+}
+"#;
+
+/// Settings used to render the SDK template project
+#[derive(Debug, Clone)]
+pub struct SdkTemplateOptions {
+    pub crate_name: String,
+    pub project_id: String,
+    pub api_url: String,
+}
+
+/// Render the template files as `(relative path, contents)` pairs
+#[must_use]
+pub fn render_sdk_template(options: &SdkTemplateOptions) -> Vec<(PathBuf, String)> {
+    // Values end up inside Rust string literals, so escape them
+    let project_id = options.project_id.escape_default().to_string();
+    let api_url = options.api_url.escape_default().to_string();
+
+    let render = |template: &str| {
+        template
+            .replace("{{crate_name}}", &options.crate_name)
+            .replace("{{project_id}}", &project_id)
+            .replace("{{api_url}}", &api_url)
+            .replace("{{cli_version}}", env!("CARGO_PKG_VERSION"))
+    };
+
+    vec![
+        (PathBuf::from("Cargo.toml"), render(CARGO_TOML_TEMPLATE)),
+        (PathBuf::from("src/main.rs"), render(MAIN_RS_TEMPLATE)),
+    ]
+}
+
+/// Write the SDK template project into `dir`, returning the paths that were created
+///
+/// # Errors
+///
+/// Returns an error if a target file already exists (and `force` is not set) or cannot be written
+pub fn write_sdk_template(
+    dir: &Path,
+    options: &SdkTemplateOptions,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    let valid_name = !options.crate_name.is_empty()
+        && options
+            .crate_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        return Err(Error::ConfigError(format!(
+            "Invalid crate name '{}': use only letters, digits, '-' and '_'",
+            options.crate_name
+        )));
+    }
+
+    let files = render_sdk_template(options);
+
+    if !force {
+        for (relative, _) in &files {
+            let path = dir.join(relative);
+            if path.exists() {
+                return Err(Error::ConfigError(format!(
+                    "'{}' already exists (use --force to overwrite)",
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    let mut written = Vec::new();
+    for (relative, contents) in files {
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        debug!("Writing {}", path.display());
+        std::fs::write(&path, contents)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_sdk_template() {
+        let files = render_sdk_template(&SdkTemplateOptions {
+            crate_name: "my-uploader".to_string(),
+            project_id: "proj_123".to_string(),
+            api_url: "https://example.com/api".to_string(),
+        });
+
+        let (_, cargo_toml) = &files[0];
+        assert!(cargo_toml.contains("name = \"my-uploader\""));
+        assert!(cargo_toml.contains(concat!("tag = \"v", env!("CARGO_PKG_VERSION"), "\"")));
+
+        let (_, main_rs) = &files[1];
+        assert!(main_rs.contains("const PROJECT_ID: &str = \"proj_123\";"));
+        assert!(main_rs.contains("const API_URL: &str = \"https://example.com/api\";"));
+        assert!(!main_rs.contains("{{"));
+    }
+}
@@ -1,17 +1,55 @@
 //! Nunu CLI library for uploading build artifacts
 
+pub mod archive;
+pub mod artifact;
+pub mod budget;
+pub mod checksums;
 pub mod ci_metadata;
 pub mod config;
+pub mod credentials;
+pub mod deprecations;
+#[cfg(feature = "e2e")]
+pub mod e2e;
+pub mod encryption;
 pub mod error;
+pub mod error_report;
 pub mod file_config;
+pub mod files;
+pub mod format;
+pub mod i18n;
+pub mod integrations;
+pub mod links;
+pub mod logging;
+pub mod manifest;
 pub mod metadata;
+pub mod naming;
+pub mod plain;
+pub mod plugins;
+pub mod priority;
+pub mod receipts;
+pub mod retention;
+#[cfg(feature = "lua")]
+pub mod scripting;
+pub mod secrets;
+pub mod session;
+#[cfg(any(feature = "aws-secrets-manager", feature = "e2e"))]
+mod sigv4;
+pub mod size_diff;
+pub mod state;
+pub mod symbols;
+pub mod tags;
+pub mod teamcity;
+pub mod throughput;
+pub mod units;
+pub mod util;
+pub mod verify;
 
 pub mod api;
 pub mod upload;
 
-pub use config::Config;
+pub use config::{ApiFailover, Config, StorageHttpVersion, TransportOptions};
 pub use error::{Error, Result};
 
 // Re-export commonly used types
-pub use api::{BuildPlatform, Client, DeletionPolicy};
+pub use api::{Arch, BuildPlatform, Client, DeletionPolicy};
 pub use upload::{UploadOptions, upload_file};
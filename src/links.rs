@@ -0,0 +1,249 @@
+//! Detection of batch files that are hard links or reflinks of each other
+//!
+//! Build farms often deduplicate outputs by linking identical files. Such files share
+//! their data on disk, so uploading one of them is enough.
+
+use log::debug;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Identity of a file's data on disk: device and inode (volume and file index on Windows)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileIdentity {
+    device: u64,
+    inode: u64,
+}
+
+/// Identity of a regular file, or `None` for directories and unreadable files
+#[must_use]
+pub fn file_identity(path: &Path) -> Option<FileIdentity> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    imp::identity(path, &metadata)
+}
+
+/// Find files that share their data with an earlier file in `paths`
+///
+/// Hard links are recognized on every platform by their file identity. Reflinks (copy on
+/// write clones, e.g. on Btrfs or XFS) are recognized on Linux when both files consist of
+/// exactly the same shared extents. Files that merely have equal contents are not matched.
+///
+/// Returns `(duplicate, original)` index pairs, where the original is the first path
+/// sharing the duplicate's data.
+#[must_use]
+pub fn find_linked_duplicates(paths: &[String]) -> Vec<(usize, usize)> {
+    let mut duplicates = Vec::new();
+    let mut by_identity: HashMap<FileIdentity, usize> = HashMap::new();
+    let mut originals: Vec<(usize, u64)> = Vec::new();
+
+    for (index, path) in paths.iter().enumerate() {
+        let path = Path::new(path);
+        let Some(identity) = file_identity(path) else {
+            continue;
+        };
+        if let Some(&original) = by_identity.get(&identity) {
+            debug!("{} is a hard link of {}", path.display(), paths[original]);
+            duplicates.push((index, original));
+            continue;
+        }
+        by_identity.insert(identity, index);
+
+        let size = std::fs::metadata(path).map_or(0, |m| m.len());
+        let reflink_of = originals.iter().find(|(original, original_size)| {
+            *original_size == size
+                && size > 0
+                && imp::same_extents(Path::new(&paths[*original]), path)
+        });
+        if let Some(&(original, _)) = reflink_of {
+            debug!("{} is a reflink of {}", path.display(), paths[original]);
+            duplicates.push((index, original));
+        } else {
+            originals.push((index, size));
+        }
+    }
+    duplicates
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::FileIdentity;
+    use std::fs::Metadata;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    #[allow(clippy::unnecessary_wraps)] // fallible on other platforms
+    pub(super) fn identity(_path: &Path, metadata: &Metadata) -> Option<FileIdentity> {
+        Some(FileIdentity {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn same_extents(a: &Path, b: &Path) -> bool {
+        match (fiemap::shared_extents(a), fiemap::shared_extents(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn same_extents(_a: &Path, _b: &Path) -> bool {
+        false
+    }
+
+    /// Extent maps through the `FS_IOC_FIEMAP` ioctl
+    #[cfg(target_os = "linux")]
+    mod fiemap {
+        use std::fs::File;
+        use std::os::fd::AsRawFd;
+        use std::path::Path;
+
+        /// Files with more extents than this are never treated as reflinks
+        const MAX_EXTENTS: u32 = 64;
+        /// `_IOWR('f', 11, struct fiemap)`
+        const FS_IOC_FIEMAP: libc::c_ulong = 0xC020_660B;
+        const FIEMAP_FLAG_SYNC: u32 = 0x1;
+        const FIEMAP_EXTENT_LAST: u32 = 0x1;
+        const FIEMAP_EXTENT_UNKNOWN: u32 = 0x2;
+        const FIEMAP_EXTENT_DELALLOC: u32 = 0x4;
+        const FIEMAP_EXTENT_DATA_INLINE: u32 = 0x200;
+        const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+
+        #[repr(C)]
+        #[derive(Clone, Copy, Default)]
+        struct Extent {
+            logical: u64,
+            physical: u64,
+            length: u64,
+            reserved64: [u64; 2],
+            flags: u32,
+            reserved: [u32; 3],
+        }
+
+        #[repr(C)]
+        struct Request {
+            start: u64,
+            length: u64,
+            flags: u32,
+            mapped_extents: u32,
+            extent_count: u32,
+            reserved: u32,
+            extents: [Extent; MAX_EXTENTS as usize],
+        }
+
+        /// `(logical, physical, length)` of every extent, if all of them are shared with
+        /// another file and the map is complete
+        pub(super) fn shared_extents(path: &Path) -> Option<Vec<(u64, u64, u64)>> {
+            let file = File::open(path).ok()?;
+            let mut request = Box::new(Request {
+                start: 0,
+                length: u64::MAX,
+                flags: FIEMAP_FLAG_SYNC,
+                mapped_extents: 0,
+                extent_count: MAX_EXTENTS,
+                reserved: 0,
+                extents: [Extent::default(); MAX_EXTENTS as usize],
+            });
+            // SAFETY: the request is a valid, writable `struct fiemap` with room for
+            // `extent_count` extents, and the descriptor stays open for the call
+            let result = unsafe {
+                libc::ioctl(
+                    file.as_raw_fd(),
+                    FS_IOC_FIEMAP as _,
+                    std::ptr::from_mut::<Request>(&mut request),
+                )
+            };
+            if result != 0 {
+                return None;
+            }
+
+            let extents = request.extents.get(..request.mapped_extents as usize)?;
+            let unreliable =
+                FIEMAP_EXTENT_UNKNOWN | FIEMAP_EXTENT_DELALLOC | FIEMAP_EXTENT_DATA_INLINE;
+            let complete = extents
+                .last()
+                .is_some_and(|e| e.flags & FIEMAP_EXTENT_LAST != 0);
+            let all_shared = extents
+                .iter()
+                .all(|e| e.flags & FIEMAP_EXTENT_SHARED != 0 && e.flags & unreliable == 0);
+            (complete && all_shared).then(|| {
+                extents
+                    .iter()
+                    .map(|e| (e.logical, e.physical, e.length))
+                    .collect()
+            })
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::FileIdentity;
+    use std::fs::{File, Metadata};
+    use std::os::windows::io::AsRawHandle;
+    use std::path::Path;
+    use windows_sys::Win32::Storage::FileSystem::{
+        BY_HANDLE_FILE_INFORMATION, GetFileInformationByHandle,
+    };
+
+    pub(super) fn identity(path: &Path, _metadata: &Metadata) -> Option<FileIdentity> {
+        let file = File::open(path).ok()?;
+        // SAFETY: the handle is valid while `file` is open and `info` is writable
+        let info = unsafe {
+            let mut info: BY_HANDLE_FILE_INFORMATION = std::mem::zeroed();
+            if GetFileInformationByHandle(file.as_raw_handle(), &mut info) == 0 {
+                return None;
+            }
+            info
+        };
+        Some(FileIdentity {
+            device: u64::from(info.dwVolumeSerialNumber),
+            inode: (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow),
+        })
+    }
+
+    pub(super) fn same_extents(_a: &Path, _b: &Path) -> bool {
+        false
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use super::FileIdentity;
+    use std::fs::Metadata;
+    use std::path::Path;
+
+    pub(super) fn identity(_path: &Path, _metadata: &Metadata) -> Option<FileIdentity> {
+        None
+    }
+
+    pub(super) fn same_extents(_a: &Path, _b: &Path) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find_linked_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        fs::write(path("game.apk"), b"apk contents").unwrap();
+        fs::hard_link(path("game.apk"), path("linked.apk")).unwrap();
+        fs::write(path("copy.apk"), b"apk contents").unwrap();
+
+        let paths = [path("game.apk"), path("copy.apk"), path("linked.apk")];
+        assert_eq!(find_linked_duplicates(&paths), [(2, 0)]);
+        assert_eq!(
+            file_identity(Path::new(&paths[0])),
+            file_identity(Path::new(&paths[2]))
+        );
+        assert_eq!(file_identity(dir.path()), None);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::error::{Error, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
+
+/// Level captured in `--log-file` for this crate's own targets
+const LOG_FILE_LEVEL: LevelFilter = LevelFilter::Debug;
+
+/// Level captured in `--log-file` for dependencies (HTTP stack, TLS, ...)
+const LOG_FILE_DEPENDENCY_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Query parameters whose values are masked in the log file, matched case-insensitively
+/// against the last segment of the name, so `X-Amz-Signature`, `api_key` and `accessToken`
+/// are masked but `monkey` and `key_count` are not
+const SENSITIVE_PARAMS: &[&str] = &[
+    "signature",
+    "credential",
+    "token",
+    "key",
+    "apikey",
+    "secret",
+    "password",
+    "passwd",
+];
+
+/// Secrets registered at runtime (e.g. the API token) that are masked wherever they appear
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Subsystems accepted by `--log` and the log targets (module paths) they cover
+///
+/// Every module that logs must be covered, which `test_subsystems_cover_logging_modules`
+/// checks; list new subsystems in the README too.
+pub const SUBSYSTEMS: &[(&str, &[&str])] = &[
+    ("api", &["nunu_cli::api"]),
+    (
+        "upload",
+        &[
+            "nunu_cli::upload",
+            "nunu_cli::archive",
+            "nunu_cli::throughput",
+            "nunu_cli::priority",
+            "nunu_cli::state",
+        ],
+    ),
+    ("metadata", &["nunu_cli::metadata", "nunu_cli::ci_metadata"]),
+    (
+        "config",
+        &[
+            "nunu_cli::config",
+            "nunu_cli::file_config",
+            "nunu_cli::manifest",
+            "nunu_cli::deprecations",
+        ],
+    ),
+    (
+        "files",
+        &[
+            "nunu_cli::files",
+            "nunu_cli::artifact",
+            "nunu_cli::links",
+            "nunu_cli::symbols",
+        ],
+    ),
+    (
+        "credentials",
+        &["nunu_cli::credentials", "nunu_cli::secrets"],
+    ),
+    ("verify", &["nunu_cli::verify", "nunu_cli::receipts"]),
+    ("integrations", &["nunu_cli::integrations"]),
+    ("e2e", &["nunu_cli::e2e"]),
+];
+
+/// Parsed `--log` specification such as `api=debug,upload=info,metadata=off`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LogSpec {
+    /// Level for everything not covered by a subsystem entry (from a bare `debug` entry)
+    pub default: Option<LevelFilter>,
+    /// Log targets and the level to apply to each
+    pub targets: Vec<(&'static str, LevelFilter)>,
+}
+
+/// Parse a comma-separated `subsystem=level` list, optionally with a bare default level
+///
+/// # Errors
+///
+/// Returns an error for unknown subsystems or levels
+pub fn parse_log_spec(spec: &str) -> Result<LogSpec> {
+    let mut parsed = LogSpec::default();
+
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((name, level)) => {
+                let level = parse_level(level.trim())?;
+                let name = name.trim().to_ascii_lowercase();
+                let (_, targets) = SUBSYSTEMS
+                    .iter()
+                    .find(|(subsystem, _)| *subsystem == name)
+                    .ok_or_else(|| {
+                        let valid: Vec<&str> = SUBSYSTEMS.iter().map(|(s, _)| *s).collect();
+                        Error::ConfigError(format!(
+                            "Unknown log subsystem '{name}'. Valid subsystems are: {}",
+                            valid.join(", ")
+                        ))
+                    })?;
+                parsed
+                    .targets
+                    .extend(targets.iter().map(|target| (*target, level)));
+            }
+            None => parsed.default = Some(parse_level(entry)?),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter> {
+    level.parse().map_err(|_| {
+        Error::ConfigError(format!(
+            "Invalid log level '{level}'. Valid levels are: off, error, warn, info, debug, trace"
+        ))
+    })
+}
+
+/// Register a secret that must never appear in the log file
+pub fn register_secret(secret: &str) {
+    if secret.len() < 4 {
+        return;
+    }
+    if let Ok(mut secrets) = SECRETS.write()
+        && !secrets.iter().any(|s| s == secret)
+    {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Mask registered secrets and sensitive URL query parameters (presigned URL signatures,
+/// credentials, tokens) in a log message
+#[must_use]
+pub fn redact(message: &str) -> String {
+    let mut redacted = message.to_string();
+
+    if let Ok(secrets) = SECRETS.read() {
+        for secret in secrets.iter() {
+            redacted = redacted.replace(secret.as_str(), "[REDACTED]");
+        }
+    }
+
+    let mut output = String::with_capacity(redacted.len());
+    let mut rest = redacted.as_str();
+    while let Some(pos) = rest.find(['?', '&']) {
+        let (head, tail) = rest.split_at(pos + 1);
+        output.push_str(head);
+        rest = tail;
+
+        let Some(eq) = rest.find('=') else { continue };
+        let name = &rest[..eq];
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '&') {
+            continue;
+        }
+        if !is_sensitive_param(name) {
+            continue;
+        }
+
+        let value_len = rest[eq + 1..]
+            .find(|c: char| c == '&' || c == '"' || c == '\'' || c.is_whitespace())
+            .unwrap_or(rest.len() - eq - 1);
+        output.push_str(name);
+        output.push_str("=[REDACTED]");
+        rest = &rest[eq + 1 + value_len..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Whether the last segment of the query parameter `name`, split at punctuation and
+/// camelCase humps, is one of [`SENSITIVE_PARAMS`]
+fn is_sensitive_param(name: &str) -> bool {
+    let mut start = 0;
+    let mut prev_lower = false;
+    for (i, c) in name.char_indices() {
+        if !c.is_ascii_alphanumeric() {
+            start = i + c.len_utf8();
+        } else if c.is_ascii_uppercase() && prev_lower {
+            start = i;
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+    }
+    let last = &name[start..];
+    SENSITIVE_PARAMS
+        .iter()
+        .any(|p| last.eq_ignore_ascii_case(p))
+}
+
+/// A single line in the log file
+#[derive(Serialize)]
+struct LogLine<'a> {
+    ts: String,
+    level: &'a str,
+    target: &'a str,
+    msg: String,
+}
+
+/// Logger that writes to the console and, optionally, to a JSON-lines log file
+///
+/// The log file always captures debug logs regardless of console verbosity so CI
+/// failures can be investigated without re-running with `-vvv`.
+struct TeeLogger {
+    console: env_logger::Logger,
+    file: Option<Mutex<File>>,
+}
+
+impl TeeLogger {
+    fn file_enabled(metadata: &Metadata) -> bool {
+        let level = if metadata.target().starts_with("nunu_cli") {
+            LOG_FILE_LEVEL
+        } else {
+            LOG_FILE_DEPENDENCY_LEVEL
+        };
+        metadata.level() <= level
+    }
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata) || (self.file.is_some() && Self::file_enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        self.console.log(record);
+
+        if let Some(file) = &self.file
+            && Self::file_enabled(record.metadata())
+        {
+            let line = LogLine {
+                ts: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+                level: record.level().as_str(),
+                target: record.target(),
+                msg: redact(&record.args().to_string()),
+            };
+            if let Ok(line) = serde_json::to_string(&line)
+                && let Ok(mut file) = file.lock()
+            {
+                // Logging must never fail the upload
+                let _ = writeln!(file, "{line}");
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Install the global logger, optionally teeing redacted debug logs to `log_file`
+///
+/// The log file is appended to, so several invocations in one CI job share a file.
+///
+/// # Errors
+///
+/// Returns an error if the log file cannot be opened or a logger is already installed
+pub fn init(console: env_logger::Logger, log_file: Option<&Path>) -> Result<()> {
+    let file = log_file
+        .map(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| {
+                    Error::ConfigError(format!("Failed to open log file {}: {e}", path.display()))
+                })
+        })
+        .transpose()?;
+
+    let max_level = if file.is_some() {
+        console.filter().max(LOG_FILE_LEVEL)
+    } else {
+        console.filter()
+    };
+
+    log::set_boxed_logger(Box::new(TeeLogger {
+        console,
+        file: file.map(Mutex::new),
+    }))
+    .map_err(|e| Error::ConfigError(format!("Failed to initialize logging: {e}")))?;
+    log::set_max_level(max_level);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_spec() {
+        let spec = parse_log_spec("api=debug, upload=info,metadata=off,warn").unwrap();
+        assert_eq!(spec.default, Some(LevelFilter::Warn));
+        assert!(
+            spec.targets
+                .contains(&("nunu_cli::api", LevelFilter::Debug))
+        );
+        assert!(
+            spec.targets
+                .contains(&("nunu_cli::archive", LevelFilter::Info))
+        );
+        assert!(
+            spec.targets
+                .contains(&("nunu_cli::ci_metadata", LevelFilter::Off))
+        );
+
+        assert!(parse_log_spec("network=debug").is_err());
+        assert!(parse_log_spec("api=loud").is_err());
+    }
+
+    /// Whether any Rust file at `path` (a file or module directory) calls a log macro
+    fn logs(path: &Path) -> bool {
+        if path.is_dir() {
+            return std::fs::read_dir(path)
+                .unwrap()
+                .any(|entry| logs(&entry.unwrap().path()));
+        }
+        let source = std::fs::read_to_string(path).unwrap_or_default();
+        ["trace!(", "debug!(", "info!(", "warn!(", "error!("]
+            .iter()
+            .any(|call| source.contains(call))
+    }
+
+    #[test]
+    fn test_subsystems_cover_logging_modules() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let lib = std::fs::read_to_string(src.join("lib.rs")).unwrap();
+        let modules = lib.lines().filter_map(|line| {
+            let line = line
+                .trim_start_matches("pub ")
+                .trim_start_matches("pub(crate) ");
+            line.strip_prefix("mod ")?.strip_suffix(';')
+        });
+
+        for module in modules {
+            let file = src.join(format!("{module}.rs"));
+            let path = if file.exists() {
+                file
+            } else {
+                src.join(module)
+            };
+            if module == "logging" || !logs(&path) {
+                continue;
+            }
+            let target = format!("nunu_cli::{module}");
+            assert!(
+                SUBSYSTEMS
+                    .iter()
+                    .any(|(_, targets)| targets.contains(&target.as_str())),
+                "{target} logs but is not in a --log subsystem"
+            );
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let url = "https://bucket.s3.amazonaws.com/key?X-Amz-Credential=AKIA%2F123&X-Amz-Date=20250101&X-Amz-Signature=abcdef";
+        assert_eq!(
+            redact(&format!("Upload URL: {url}")),
+            "Upload URL: https://bucket.s3.amazonaws.com/key?X-Amz-Credential=[REDACTED]&X-Amz-Date=20250101&X-Amz-Signature=[REDACTED]"
+        );
+
+        assert_eq!(
+            redact("GET /builds?api_key=k1&apiKey=k2&access_token=t&X-Amz-Security-Token=s&page=2"),
+            "GET /builds?api_key=[REDACTED]&apiKey=[REDACTED]&access_token=[REDACTED]&X-Amz-Security-Token=[REDACTED]&page=2"
+        );
+        let harmless = "GET /stats?monkey=1&keyboard_layout=de&build_key_count=3&tokens_used=9&key";
+        assert_eq!(redact(harmless), harmless);
+
+        register_secret("nunu_secret_token");
+        assert_eq!(redact("token is nunu_secret_token"), "token is [REDACTED]");
+        assert_eq!(redact("no secrets here?"), "no secrets here?");
+    }
+}
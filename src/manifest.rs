@@ -0,0 +1,178 @@
+use crate::api::BuildPlatform;
+use crate::error::{Error, Result};
+use crate::metadata::VcsOverrides;
+use log::debug;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Batch upload manifest (`nunu-cli upload --manifest nunu-builds.yaml`)
+///
+/// ```yaml
+/// artifacts:
+///   - path: build/windows/Game.exe
+///     name: Game (Windows)
+///     tags: [release]
+///   - path: build/android/*.apk
+///     name: Game (Android)
+///     platform: android
+///     description: Play Store candidate
+///     metadata:
+///       store_track: beta
+/// vcs:
+///   commit: 4f2a9c1e
+///   branch: release/1.4
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub artifacts: Vec<ManifestArtifact>,
+    /// Commit, branch, repository and PR of the builds, for batches uploaded away from
+    /// their checkout; overridden by the upload flags
+    #[serde(default)]
+    pub vcs: VcsOverrides,
+}
+
+/// A single artifact entry in a manifest
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestArtifact {
+    /// File path or glob pattern, relative to the manifest's directory
+    pub path: String,
+    /// Build name (used as a template if `path` matches multiple files)
+    pub name: String,
+    /// Target platform (inferred from the file extension if omitted)
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// Custom metadata, merged over `--meta` and the config file
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ManifestArtifact {
+    /// Parse the platform, if one is set
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the platform is not a valid [`BuildPlatform`]
+    pub fn platform(&self) -> Result<Option<BuildPlatform>> {
+        self.platform.as_deref().map(str::parse).transpose()
+    }
+}
+
+impl Manifest {
+    /// Parse a manifest from YAML
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the YAML is invalid, has no artifacts, or contains an invalid platform
+    pub fn from_yaml(contents: &str) -> Result<Self> {
+        let manifest: Manifest = serde_yaml::from_str(contents)
+            .map_err(|e| Error::ConfigError(format!("Invalid manifest: {e}")))?;
+
+        if manifest.artifacts.is_empty() {
+            return Err(Error::ConfigError(
+                "Manifest does not list any artifacts".to_string(),
+            ));
+        }
+
+        for artifact in &manifest.artifacts {
+            if artifact.path.trim().is_empty() {
+                return Err(Error::ConfigError(
+                    "Manifest artifact is missing a path".to_string(),
+                ));
+            }
+            if artifact.name.trim().is_empty() {
+                return Err(Error::ConfigError(format!(
+                    "Manifest artifact '{}' is missing a name",
+                    artifact.path
+                )));
+            }
+            artifact.platform()?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// Load a manifest file, resolving artifact paths relative to its directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid manifest
+    pub fn load(path: &Path) -> Result<Self> {
+        debug!("Loading manifest from {}", path.display());
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::ConfigError(format!("Failed to read manifest {}: {e}", path.display()))
+        })?;
+
+        let mut manifest = Self::from_yaml(&contents)?;
+        let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        for artifact in &mut manifest.artifacts {
+            if Path::new(&artifact.path).is_relative() && !base.as_os_str().is_empty() {
+                artifact.path = base.join(&artifact.path).to_string_lossy().to_string();
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::from_yaml(
+            r"
+artifacts:
+  - path: build/Game.exe
+    name: Windows
+    tags: [release, win]
+  - path: build/*.apk
+    name: Android
+    platform: Android
+    description: Play Store candidate
+    metadata:
+      store_track: beta
+vcs:
+  commit: 4f2a9c1e
+  pr: 12
+",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.artifacts.len(), 2);
+        assert!(manifest.artifacts[0].platform().unwrap().is_none());
+        assert!(matches!(
+            manifest.artifacts[1].platform().unwrap(),
+            Some(BuildPlatform::Android)
+        ));
+        assert_eq!(
+            manifest.artifacts[0].tags.as_deref(),
+            Some(&["release".to_string(), "win".to_string()][..])
+        );
+
+        assert_eq!(
+            manifest.artifacts[1]
+                .metadata
+                .get("store_track")
+                .map(String::as_str),
+            Some("beta")
+        );
+
+        assert_eq!(manifest.vcs.commit.as_deref(), Some("4f2a9c1e"));
+        assert_eq!(manifest.vcs.pr_number, Some(12));
+
+        assert!(Manifest::from_yaml("artifacts: []").is_err());
+        assert!(
+            Manifest::from_yaml("artifacts:\n  - path: a.apk\n    name: A\n    platform: dos\n")
+                .is_err()
+        );
+        assert!(Manifest::from_yaml("artifacts:\n  - path: a.apk\n    nmae: A\n").is_err());
+    }
+}
@@ -1,5 +1,57 @@
+use crate::ci_metadata::unity_cloud_build_manifest;
+use crate::error::{Error, Result};
+use log::debug;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::io::{self, Read};
+use std::process::{Command, Output, Stdio};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Longest a single git, hg, svn or cm command may run while collecting metadata
+const VCS_COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Total time a VCS tool's commands may take while collecting metadata; later ones are
+/// skipped
+const VCS_METADATA_BUDGET: Duration = Duration::from_secs(15);
+
+/// Metadata git commands run so far, shared so a broken git is only reported once
+static GIT: Mutex<ToolState> = Mutex::new(ToolState::new());
+
+/// Metadata Mercurial commands run so far
+static HG: Mutex<ToolState> = Mutex::new(ToolState::new());
+
+/// Metadata Subversion commands run so far
+static SVN: Mutex<ToolState> = Mutex::new(ToolState::new());
+
+/// Metadata Plastic SCM commands run so far
+static CM: Mutex<ToolState> = Mutex::new(ToolState::new());
+
+/// Format of `cm log` printing a changeset's owner, date and comment, one per line
+const CM_LOG_FORMAT: &str = "{owner}{newline}{date}{newline}{comment}";
+
+/// What could not be collected, reported in `details.collection_warnings`
+static COLLECTION_WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Template printing the details of the working directory's parent, one per line
+const HG_LOG_TEMPLATE: &str =
+    "{node}\n{branch}\n{activebookmark}\n{tags}\n{author}\n{date|rfc3339date}\n{desc|firstline}\n";
+
+struct ToolState {
+    /// Set once the tool turned out to be missing, blocked or too slow; later commands are
+    /// skipped
+    unavailable: bool,
+    spent: Duration,
+}
+
+impl ToolState {
+    const fn new() -> Self {
+        Self {
+            unavailable: false,
+            spent: Duration::ZERO,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VcsMetadata {
@@ -43,14 +95,213 @@ pub struct PullRequestInfo {
     pub target_branch: Option<String>,
 }
 
-/// Collects VCS (Version Control System) metadata from the current Git repository
+/// Collects VCS (Version Control System) metadata from the current checkout
 ///
-/// Tries CI environment variables first (Jenkins, GitHub Actions, GitLab CI),
-/// then falls back to running git commands.
+/// Tries CI environment variables first (Jenkins, GitHub Actions, GitLab CI, Buildkite,
+/// Semaphore, CodeBuild, Codemagic, Cirrus CI, Bamboo, Harness, Woodpecker, Xcode Cloud,
+/// Unity Cloud Build), then falls back to reading the git repository directly (or running
+/// git commands if that fails), hg commands in a Mercurial repository, svn commands in a
+/// Subversion working copy or cm commands in a Plastic SCM workspace. `NUNU_VCS_*`
+/// variables take precedence over all of these; with `NUNU_VCS_COMMIT` set, no VCS commands
+/// are run at all.
 ///
-/// Returns `None` if not in a Git repository or if Git metadata cannot be collected
+/// Git repository URLs are normalized to browsable `https://` URLs.
+///
+/// Returns `None` if not in a repository or if its metadata cannot be collected
+#[must_use]
+pub fn collect_vcs_metadata() -> Option<VcsMetadata> {
+    collect_vcs_metadata_with(VcsOverrides::default())
+}
+
+/// Collects VCS metadata from the current checkout
+#[deprecated(since = "0.1.20", note = "renamed to `collect_vcs_metadata`")]
 #[must_use]
 pub fn collect_git_metadata() -> Option<VcsMetadata> {
+    collect_vcs_metadata()
+}
+
+/// Collects metadata from a local checkout, named for the debug log
+type CheckoutCollector = (&'static str, fn() -> Option<VcsMetadata>);
+
+/// Checkout collectors in the order they are tried when no CI system is detected
+const CHECKOUT_COLLECTORS: [CheckoutCollector; 5] = [
+    ("git repository", collect_git_metadata_from_repository),
+    ("git commands", collect_git_metadata_from_commands),
+    ("hg commands", collect_hg_metadata_from_commands),
+    ("svn commands", collect_svn_metadata_from_commands),
+    ("cm commands", collect_plastic_metadata_from_commands),
+];
+
+/// Metadata from the first collector that finds any, without running the later ones
+fn first_collected(collectors: &[CheckoutCollector]) -> Option<VcsMetadata> {
+    collectors.iter().find_map(|(source, collect)| {
+        let vcs = collect()?;
+        debug!("Collected {} metadata from {source}", vcs.vcs_type);
+        Some(vcs)
+    })
+}
+
+/// [`collect_vcs_metadata`] with `overrides` (from flags or a manifest) taking precedence
+/// over the `NUNU_VCS_*` variables
+#[must_use]
+pub fn collect_vcs_metadata_with(overrides: VcsOverrides) -> Option<VcsMetadata> {
+    let overrides = overrides.or(VcsOverrides::from_env());
+    let detected = collect_ci_git_metadata().or_else(|| {
+        // The orchestrator already named the commit, and git may not even be installed
+        if overrides.commit.is_some() {
+            None
+        } else {
+            first_collected(&CHECKOUT_COLLECTORS)
+        }
+    });
+    overrides.apply(detected).map(|mut vcs| {
+        if vcs.vcs_type == "git" {
+            vcs.repository_url = vcs.repository_url.as_deref().map(normalize_repository_url);
+        }
+        vcs
+    })
+}
+
+/// VCS metadata overriding what is detected, so any orchestrator can describe the checkout
+/// it built, even where the upload runs without one
+///
+/// Set through `NUNU_VCS_*` variables, upload flags (`--commit`, `--branch`, `--repo-url`,
+/// `--pr`) or the `vcs` section of a manifest.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct VcsOverrides {
+    #[serde(default, rename = "type")]
+    pub vcs_type: Option<String>,
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub commit_message: Option<String>,
+    #[serde(default)]
+    pub commit_author: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default, rename = "repo_url")]
+    pub repository_url: Option<String>,
+    #[serde(default, rename = "pr")]
+    pub pr_number: Option<u32>,
+    #[serde(default)]
+    pub pr_url: Option<String>,
+    #[serde(default)]
+    pub pr_target_branch: Option<String>,
+}
+
+impl VcsOverrides {
+    /// These overrides, with the ones not set taken from `fallback`
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            vcs_type: self.vcs_type.or(fallback.vcs_type),
+            commit: self.commit.or(fallback.commit),
+            commit_message: self.commit_message.or(fallback.commit_message),
+            commit_author: self.commit_author.or(fallback.commit_author),
+            branch: self.branch.or(fallback.branch),
+            tag: self.tag.or(fallback.tag),
+            repository_url: self.repository_url.or(fallback.repository_url),
+            pr_number: self.pr_number.or(fallback.pr_number),
+            pr_url: self.pr_url.or(fallback.pr_url),
+            pr_target_branch: self.pr_target_branch.or(fallback.pr_target_branch),
+        }
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            vcs_type: var("NUNU_VCS_TYPE"),
+            commit: var("NUNU_VCS_COMMIT"),
+            commit_message: var("NUNU_VCS_COMMIT_MESSAGE"),
+            commit_author: var("NUNU_VCS_COMMIT_AUTHOR"),
+            branch: var("NUNU_VCS_BRANCH"),
+            tag: var("NUNU_VCS_TAG"),
+            repository_url: var("NUNU_VCS_REPOSITORY_URL"),
+            pr_number: var("NUNU_VCS_PR_NUMBER").and_then(|n| n.parse().ok()),
+            pr_url: var("NUNU_VCS_PR_URL"),
+            pr_target_branch: var("NUNU_VCS_PR_TARGET_BRANCH"),
+        }
+    }
+
+    fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+    }
+
+    /// Apply the overrides on top of `detected`
+    ///
+    /// Without detected metadata the overrides need at least `NUNU_VCS_COMMIT`. Details
+    /// of a detected commit other than the overriding one are dropped.
+    fn apply(self, detected: Option<VcsMetadata>) -> Option<VcsMetadata> {
+        let mut vcs = match (detected, &self.commit) {
+            (Some(vcs), _) => vcs,
+            (None, Some(commit)) => VcsMetadata {
+                vcs_type: "git".to_string(),
+                provider: None,
+                repository_url: None,
+                commit: CommitInfo {
+                    hash: commit.clone(),
+                    short_hash: String::new(),
+                    message: None,
+                    author: None,
+                    timestamp: None,
+                },
+                branch: None,
+                tag: None,
+                pr: None,
+            },
+            (None, None) => return None,
+        };
+
+        if let Some(commit) = self.commit
+            && commit != vcs.commit.hash
+        {
+            vcs.commit = CommitInfo {
+                short_hash: commit.chars().take(7).collect(),
+                hash: commit,
+                message: None,
+                author: None,
+                timestamp: None,
+            };
+        }
+        if vcs.commit.short_hash.is_empty() {
+            vcs.commit.short_hash = vcs.commit.hash.chars().take(7).collect();
+        }
+        vcs.commit.message = self.commit_message.or(vcs.commit.message);
+        vcs.commit.author = self.commit_author.or(vcs.commit.author);
+        vcs.vcs_type = self.vcs_type.unwrap_or(vcs.vcs_type);
+        vcs.branch = self.branch.or(vcs.branch);
+        vcs.tag = self.tag.or(vcs.tag);
+        if let Some(url) = self.repository_url {
+            vcs.provider = detect_git_provider(&url).or(vcs.provider);
+            vcs.repository_url = Some(url);
+        }
+
+        if let Some(number) = self.pr_number {
+            let pr = vcs
+                .pr
+                .take()
+                .filter(|pr| pr.number == number)
+                .unwrap_or(PullRequestInfo {
+                    number,
+                    title: None,
+                    url: None,
+                    source_branch: vcs.branch.clone(),
+                    target_branch: None,
+                });
+            vcs.pr = Some(pr);
+        }
+        if let Some(pr) = &mut vcs.pr {
+            pr.url = self.pr_url.or(pr.url.take());
+            pr.target_branch = self.pr_target_branch.or(pr.target_branch.take());
+        }
+        Some(vcs)
+    }
+}
+
+/// VCS metadata from the environment variables of a detected CI system
+fn collect_ci_git_metadata() -> Option<VcsMetadata> {
     // Try Jenkins Git Plugin first (highest priority for Jenkins environments)
     if let Some(vcs) = collect_jenkins_git_metadata() {
         return Some(vcs);
@@ -66,8 +317,53 @@ pub fn collect_git_metadata() -> Option<VcsMetadata> {
         return Some(vcs);
     }
 
-    // Fall back to running git commands
-    collect_git_metadata_from_commands()
+    // Try Buildkite
+    if let Some(vcs) = collect_buildkite_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Semaphore
+    if let Some(vcs) = collect_semaphore_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try AWS CodeBuild
+    if let Some(vcs) = collect_codebuild_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Codemagic
+    if let Some(vcs) = collect_codemagic_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Cirrus CI
+    if let Some(vcs) = collect_cirrus_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Bamboo
+    if let Some(vcs) = collect_bamboo_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Harness
+    if let Some(vcs) = collect_harness_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Woodpecker
+    if let Some(vcs) = collect_woodpecker_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Xcode Cloud
+    if let Some(vcs) = collect_xcode_cloud_git_metadata() {
+        return Some(vcs);
+    }
+
+    // Try Unity Cloud Build
+    collect_unity_cloud_build_git_metadata()
 }
 
 /// Collect Jenkins Git Plugin metadata from environment variables
@@ -252,7 +548,568 @@ fn collect_gitlab_ci_git_metadata() -> Option<VcsMetadata> {
     })
 }
 
-/// Fall back to running git commands when not in CI or CI doesn't provide git info
+/// Collect Buildkite metadata from environment variables
+fn collect_buildkite_git_metadata() -> Option<VcsMetadata> {
+    if std::env::var("BUILDKITE").ok()?.as_str() != "true" {
+        return None;
+    }
+
+    // Builds started without a specific commit report `HEAD`; let git resolve it
+    let commit_sha = std::env::var("BUILDKITE_COMMIT")
+        .ok()
+        .filter(|sha| !sha.is_empty() && sha != "HEAD")?;
+    let repository_url = std::env::var("BUILDKITE_REPO").ok();
+
+    // `BUILDKITE_PULL_REQUEST` is `false` outside of pull request builds
+    let pr = std::env::var("BUILDKITE_PULL_REQUEST")
+        .ok()
+        .and_then(|number| number.parse::<u32>().ok())
+        .map(|number| PullRequestInfo {
+            number,
+            title: None,
+            url: None,
+            source_branch: std::env::var("BUILDKITE_BRANCH").ok(),
+            target_branch: std::env::var("BUILDKITE_PULL_REQUEST_BASE_BRANCH").ok(),
+        });
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: std::env::var("BUILDKITE_MESSAGE").ok(),
+            author: std::env::var("BUILDKITE_BUILD_AUTHOR_EMAIL")
+                .ok()
+                .or_else(|| std::env::var("BUILDKITE_BUILD_AUTHOR").ok()),
+            timestamp: None,
+        },
+        branch: std::env::var("BUILDKITE_BRANCH").ok(),
+        tag: std::env::var("BUILDKITE_TAG")
+            .ok()
+            .filter(|t| !t.is_empty()),
+        pr,
+    })
+}
+
+/// Collect Semaphore metadata from environment variables
+fn collect_semaphore_git_metadata() -> Option<VcsMetadata> {
+    if std::env::var("SEMAPHORE").ok()?.as_str() != "true" {
+        return None;
+    }
+
+    let commit_sha = std::env::var("SEMAPHORE_GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())?;
+    let repository_url = std::env::var("SEMAPHORE_GIT_URL").ok();
+    let is_pr = std::env::var("SEMAPHORE_GIT_REF_TYPE").ok().as_deref() == Some("pull-request");
+
+    // In pull request builds `SEMAPHORE_GIT_BRANCH` is the target branch
+    let pr = std::env::var("SEMAPHORE_GIT_PR_NUMBER")
+        .ok()
+        .filter(|_| is_pr)
+        .and_then(|number| number.parse::<u32>().ok())
+        .map(|number| PullRequestInfo {
+            number,
+            title: std::env::var("SEMAPHORE_GIT_PR_NAME").ok(),
+            url: None,
+            source_branch: std::env::var("SEMAPHORE_GIT_PR_BRANCH").ok(),
+            target_branch: std::env::var("SEMAPHORE_GIT_BRANCH").ok(),
+        });
+    let branch = if is_pr {
+        std::env::var("SEMAPHORE_GIT_PR_BRANCH").ok()
+    } else {
+        std::env::var("SEMAPHORE_GIT_BRANCH").ok()
+    };
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: None,
+            author: std::env::var("SEMAPHORE_GIT_COMMIT_AUTHOR").ok(),
+            timestamp: None,
+        },
+        branch,
+        tag: std::env::var("SEMAPHORE_GIT_TAG_NAME")
+            .ok()
+            .filter(|t| !t.is_empty()),
+        pr,
+    })
+}
+
+/// Collect AWS CodeBuild metadata from environment variables
+fn collect_codebuild_git_metadata() -> Option<VcsMetadata> {
+    std::env::var("CODEBUILD_BUILD_ID").ok()?;
+
+    // The requested source version is a commit, a ref or `pr/<number>`; the resolved
+    // version is always the commit that was built
+    let source_version = std::env::var("CODEBUILD_SOURCE_VERSION").ok();
+    let commit_sha = std::env::var("CODEBUILD_RESOLVED_SOURCE_VERSION")
+        .ok()
+        .or_else(|| {
+            source_version
+                .clone()
+                .filter(|v| v.len() == 40 && v.chars().all(|c| c.is_ascii_hexdigit()))
+        })
+        .filter(|sha| !sha.is_empty())?;
+    let repository_url = std::env::var("CODEBUILD_SOURCE_REPO_URL").ok();
+
+    // Webhook builds name their trigger `pr/<number>`, `branch/<name>` or `tag/<name>`
+    let trigger = std::env::var("CODEBUILD_WEBHOOK_TRIGGER").ok();
+    let head_ref = std::env::var("CODEBUILD_WEBHOOK_HEAD_REF").ok();
+    let branch = head_ref
+        .as_deref()
+        .and_then(|r| r.strip_prefix("refs/heads/"))
+        .or_else(|| trigger.as_deref()?.strip_prefix("branch/"))
+        .map(str::to_string);
+    let tag = head_ref
+        .as_deref()
+        .and_then(|r| r.strip_prefix("refs/tags/"))
+        .or_else(|| trigger.as_deref()?.strip_prefix("tag/"))
+        .map(str::to_string);
+
+    let pr = [source_version.as_deref(), trigger.as_deref()]
+        .into_iter()
+        .flatten()
+        .find_map(parse_codebuild_pr)
+        .map(|number| PullRequestInfo {
+            number,
+            title: None,
+            url: None,
+            source_branch: branch.clone(),
+            target_branch: std::env::var("CODEBUILD_WEBHOOK_BASE_REF")
+                .ok()
+                .map(|r| r.strip_prefix("refs/heads/").unwrap_or(&r).to_string()),
+        });
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: None,
+            author: None,
+            timestamp: None,
+        },
+        branch,
+        tag,
+        pr,
+    })
+}
+
+/// Collect Codemagic metadata from environment variables
+fn collect_codemagic_git_metadata() -> Option<VcsMetadata> {
+    std::env::var("CM_BUILD_ID").ok()?;
+
+    let commit_sha = std::env::var("CM_COMMIT")
+        .ok()
+        .filter(|sha| !sha.is_empty())?;
+    let branch = std::env::var("CM_BRANCH").ok().filter(|b| !b.is_empty());
+
+    // In pull request builds `CM_BRANCH` is the source branch
+    let pr = std::env::var("CM_PULL_REQUEST_NUMBER")
+        .ok()
+        .and_then(|number| number.parse::<u32>().ok())
+        .map(|number| PullRequestInfo {
+            number,
+            title: None,
+            url: None,
+            source_branch: branch.clone(),
+            target_branch: std::env::var("CM_PULL_REQUEST_DEST").ok(),
+        });
+
+    // Codemagic only exposes the `owner/repo` slug; the clone knows the remote URL
+    let repository_url = git_command(&["config", "--get", "remote.origin.url"]);
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: None,
+            author: None,
+            timestamp: None,
+        },
+        branch,
+        tag: std::env::var("CM_TAG").ok().filter(|t| !t.is_empty()),
+        pr,
+    })
+}
+
+/// Collect Cirrus CI metadata from environment variables
+fn collect_cirrus_git_metadata() -> Option<VcsMetadata> {
+    if std::env::var("CIRRUS_CI").ok()?.as_str() != "true" {
+        return None;
+    }
+
+    let commit_sha = std::env::var("CIRRUS_CHANGE_IN_REPO")
+        .ok()
+        .filter(|sha| !sha.is_empty())?;
+    let repository_url = std::env::var("CIRRUS_REPO_CLONE_URL").ok();
+
+    // In pull request builds `CIRRUS_BRANCH` is `pull/<number>`; the head branch is the real one
+    let pr_number = std::env::var("CIRRUS_PR")
+        .ok()
+        .and_then(|number| number.parse::<u32>().ok());
+    let branch = if pr_number.is_some() {
+        std::env::var("CIRRUS_HEAD_BRANCH").ok()
+    } else {
+        std::env::var("CIRRUS_BRANCH").ok()
+    }
+    .filter(|b| !b.is_empty());
+
+    let pr = pr_number.map(|number| PullRequestInfo {
+        number,
+        title: std::env::var("CIRRUS_CHANGE_TITLE").ok(),
+        // Cirrus CI only builds GitHub repositories
+        url: std::env::var("CIRRUS_REPO_FULL_NAME")
+            .ok()
+            .map(|repo| format!("https://github.com/{repo}/pull/{number}")),
+        source_branch: branch.clone(),
+        target_branch: std::env::var("CIRRUS_BASE_BRANCH").ok(),
+    });
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: std::env::var("CIRRUS_CHANGE_MESSAGE").ok(),
+            author: None,
+            timestamp: None,
+        },
+        branch,
+        tag: std::env::var("CIRRUS_TAG").ok().filter(|t| !t.is_empty()),
+        pr,
+    })
+}
+
+/// Collect Bamboo metadata from the plan repository's environment variables
+fn collect_bamboo_git_metadata() -> Option<VcsMetadata> {
+    std::env::var("bamboo_buildKey").ok()?;
+
+    // Non-git repositories (e.g. Subversion) also set a revision; only trust git ones
+    let commit_sha = std::env::var("bamboo_planRepository_revision")
+        .ok()
+        .filter(|sha| matches!(sha.len(), 40 | 64) && sha.chars().all(|c| c.is_ascii_hexdigit()))?;
+    let repository_url = std::env::var("bamboo_planRepository_repositoryUrl").ok();
+    let branch = std::env::var("bamboo_planRepository_branchName")
+        .or_else(|_| std::env::var("bamboo_planRepository_branch"))
+        .ok()
+        .filter(|b| !b.is_empty());
+
+    // Pull request builds of plan branches carry the PR key and both branches
+    let pr = std::env::var("bamboo_repository_pr_key")
+        .ok()
+        .and_then(|number| number.parse::<u32>().ok())
+        .map(|number| PullRequestInfo {
+            number,
+            title: None,
+            url: None,
+            source_branch: std::env::var("bamboo_repository_pr_sourceBranch")
+                .ok()
+                .or_else(|| branch.clone()),
+            target_branch: std::env::var("bamboo_repository_pr_targetBranch").ok(),
+        });
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: None,
+            author: None,
+            timestamp: None,
+        },
+        branch,
+        tag: None,
+        pr,
+    })
+}
+
+/// Collect Harness CI metadata from the Drone-compatible environment variables it sets
+fn collect_harness_git_metadata() -> Option<VcsMetadata> {
+    std::env::var("HARNESS_BUILD_ID").ok()?;
+
+    let commit_sha = std::env::var("DRONE_COMMIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())?;
+    let repository_url = std::env::var("DRONE_GIT_HTTP_URL")
+        .or_else(|_| std::env::var("DRONE_REPO_LINK"))
+        .ok()
+        .filter(|url| !url.is_empty());
+    let branch = std::env::var("DRONE_SOURCE_BRANCH")
+        .or_else(|_| std::env::var("DRONE_COMMIT_BRANCH"))
+        .ok()
+        .filter(|b| !b.is_empty());
+
+    let pr = std::env::var("DRONE_PULL_REQUEST")
+        .ok()
+        .and_then(|number| number.parse::<u32>().ok())
+        .map(|number| PullRequestInfo {
+            number,
+            title: std::env::var("DRONE_PULL_REQUEST_TITLE").ok(),
+            url: None,
+            source_branch: branch.clone(),
+            target_branch: std::env::var("DRONE_TARGET_BRANCH").ok(),
+        });
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: std::env::var("DRONE_COMMIT_MESSAGE").ok(),
+            author: std::env::var("DRONE_COMMIT_AUTHOR_EMAIL")
+                .or_else(|_| std::env::var("DRONE_COMMIT_AUTHOR"))
+                .ok()
+                .filter(|a| !a.is_empty()),
+            timestamp: None,
+        },
+        branch,
+        tag: std::env::var("DRONE_TAG").ok().filter(|t| !t.is_empty()),
+        pr,
+    })
+}
+
+/// Collect Woodpecker CI metadata from environment variables
+fn collect_woodpecker_git_metadata() -> Option<VcsMetadata> {
+    if std::env::var("CI").ok()?.as_str() != "woodpecker" {
+        return None;
+    }
+
+    let commit_sha = std::env::var("CI_COMMIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())?;
+    let repository_url = std::env::var("CI_REPO_URL").ok().filter(|u| !u.is_empty());
+    // Woodpecker names the forge it is connected to (github, gitea, forgejo, ...)
+    let provider = repository_url
+        .as_deref()
+        .and_then(detect_git_provider)
+        .or_else(|| std::env::var("CI_FORGE_TYPE").ok());
+
+    let pr = std::env::var("CI_COMMIT_PULL_REQUEST")
+        .ok()
+        .and_then(|number| number.parse::<u32>().ok())
+        .map(|number| PullRequestInfo {
+            number,
+            title: None,
+            // The forge link of a pull request pipeline points at the pull request
+            url: std::env::var("CI_PIPELINE_FORGE_URL").ok(),
+            source_branch: std::env::var("CI_COMMIT_SOURCE_BRANCH").ok(),
+            target_branch: std::env::var("CI_COMMIT_TARGET_BRANCH").ok(),
+        });
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider,
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: std::env::var("CI_COMMIT_MESSAGE").ok(),
+            author: std::env::var("CI_COMMIT_AUTHOR_EMAIL")
+                .or_else(|_| std::env::var("CI_COMMIT_AUTHOR"))
+                .ok()
+                .filter(|a| !a.is_empty()),
+            timestamp: None,
+        },
+        branch: std::env::var("CI_COMMIT_SOURCE_BRANCH")
+            .or_else(|_| std::env::var("CI_COMMIT_BRANCH"))
+            .ok()
+            .filter(|b| !b.is_empty()),
+        tag: std::env::var("CI_COMMIT_TAG")
+            .ok()
+            .filter(|t| !t.is_empty()),
+        pr,
+    })
+}
+
+/// Collect Xcode Cloud metadata from environment variables
+fn collect_xcode_cloud_git_metadata() -> Option<VcsMetadata> {
+    if std::env::var("CI_XCODEBUILD_ACTION").is_err() && std::env::var("CI_WORKFLOW").is_err() {
+        return None;
+    }
+
+    let commit_sha = std::env::var("CI_COMMIT")
+        .ok()
+        .filter(|sha| !sha.is_empty())?;
+
+    // Xcode Cloud has no repository URL variable; the clone knows its remote
+    let repository_url = git_command(&["config", "--get", "remote.origin.url"]);
+
+    let pr = std::env::var("CI_PULL_REQUEST_NUMBER")
+        .ok()
+        .and_then(|number| number.parse::<u32>().ok())
+        .map(|number| PullRequestInfo {
+            number,
+            title: None,
+            url: std::env::var("CI_PULL_REQUEST_HTML_URL").ok(),
+            source_branch: std::env::var("CI_PULL_REQUEST_SOURCE_BRANCH").ok(),
+            target_branch: std::env::var("CI_PULL_REQUEST_TARGET_BRANCH").ok(),
+        });
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: None,
+            author: None,
+            timestamp: None,
+        },
+        branch: std::env::var("CI_BRANCH")
+            .or_else(|_| std::env::var("CI_PULL_REQUEST_SOURCE_BRANCH"))
+            .ok()
+            .filter(|b| !b.is_empty()),
+        tag: std::env::var("CI_TAG").ok().filter(|t| !t.is_empty()),
+        pr,
+    })
+}
+
+/// Collect Unity Cloud Build metadata from its build manifest
+fn collect_unity_cloud_build_git_metadata() -> Option<VcsMetadata> {
+    let manifest = unity_cloud_build_manifest()?;
+    let commit_sha = manifest.scm_commit_id.filter(|sha| !sha.is_empty())?;
+
+    // The manifest has no repository; the checkout may still know its remote
+    let repository_url = git_command(&["config", "--get", "remote.origin.url"]);
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: commit_sha.chars().take(7).collect(),
+            hash: commit_sha,
+            message: None,
+            author: None,
+            timestamp: None,
+        },
+        branch: manifest.scm_branch.filter(|b| !b.is_empty()),
+        tag: None,
+        pr: None,
+    })
+}
+
+/// Parse a CodeBuild `pr/<number>` source version or webhook trigger
+fn parse_codebuild_pr(value: &str) -> Option<u32> {
+    value.strip_prefix("pr/")?.parse().ok()
+}
+
+/// Read git metadata from the repository with gix when not in CI or CI doesn't provide
+/// git info
+///
+/// Works without a git binary and without spawning processes. Honours `GIT_DIR` and the
+/// other variables git itself reads to find the repository.
+fn collect_git_metadata_from_repository() -> Option<VcsMetadata> {
+    let repo = match gix::discover_with_environment_overrides(".") {
+        Ok(repo) => repo,
+        Err(e) => {
+            debug!("No git repository found: {e}");
+            return None;
+        }
+    };
+    let head = match repo.head_id() {
+        Ok(head) => head,
+        Err(e) => {
+            debug!("Could not read the HEAD commit with gix: {e}");
+            return None;
+        }
+    };
+    let commit = repo.find_commit(head).ok()?;
+
+    let remote_url = repo
+        .config_snapshot()
+        .string("remote.origin.url")
+        .map(|url| url.to_string());
+    let provider = remote_url.as_deref().and_then(detect_git_provider);
+
+    // `git describe --tags --exact-match`: a tag pointing at HEAD, annotated or not
+    let mut tags: Vec<String> = repo
+        .references()
+        .ok()
+        .and_then(|refs| {
+            let tags = refs.tags().ok()?.flatten();
+            Some(
+                tags.filter_map(|mut tag| {
+                    (tag.peel_to_id().ok()?.detach() == head.detach())
+                        .then(|| tag.name().shorten().to_string())
+                })
+                .collect(),
+            )
+        })
+        .unwrap_or_default();
+    tags.sort();
+
+    Some(VcsMetadata {
+        vcs_type: "git".to_string(),
+        provider,
+        repository_url: remote_url,
+        commit: CommitInfo {
+            hash: head.to_string(),
+            short_hash: head.shorten_or_id().to_string(),
+            message: commit
+                .message()
+                .ok()
+                .map(|m| m.summary().to_string())
+                .filter(|m| !m.is_empty()),
+            author: commit
+                .author()
+                .ok()
+                .map(|a| format!("{} <{}>", a.name, a.email)),
+            timestamp: commit
+                .time()
+                .ok()
+                .map(|t| t.format(gix::date::time::format::ISO8601_STRICT)),
+        },
+        branch: match repo.head_name() {
+            Ok(Some(name)) => Some(name.shorten().to_string()),
+            Ok(None) => detached_head_branch(
+                |name| std::env::var(name).ok(),
+                &branches_pointing_at(&repo, head.detach()),
+            ),
+            Err(_) => None,
+        },
+        tag: tags.into_iter().next(),
+        pr: None,
+    })
+}
+
+/// Full names of the local and remote-tracking branches pointing at `id`
+fn branches_pointing_at(repo: &gix::Repository, id: gix::ObjectId) -> Vec<String> {
+    let Ok(refs) = repo.references() else {
+        return Vec::new();
+    };
+    [refs.local_branches(), refs.remote_branches()]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .flatten()
+        .filter_map(|mut branch| {
+            (branch.peel_to_id().ok()?.detach() == id).then(|| branch.name().as_bstr().to_string())
+        })
+        .collect()
+}
+
+/// Fall back to running git commands if the repository cannot be read directly
 fn collect_git_metadata_from_commands() -> Option<VcsMetadata> {
     if !is_git_repo() {
         return None;
@@ -276,14 +1133,447 @@ fn collect_git_metadata_from_commands() -> Option<VcsMetadata> {
             author: git_command(&["log", "-1", "--pretty=%an <%ae>"]),
             timestamp: git_command(&["log", "-1", "--pretty=%cI"]),
         },
-        branch: git_command(&["rev-parse", "--abbrev-ref", "HEAD"]),
-        tag: git_command(&["describe", "--tags", "--exact-match"]),
+        branch: git_command(&["rev-parse", "--abbrev-ref", "HEAD"]).and_then(|branch| {
+            if branch != "HEAD" {
+                return Some(branch);
+            }
+            let refs = git_command(&[
+                "for-each-ref",
+                "--points-at",
+                "HEAD",
+                "--format=%(refname)",
+                "refs/heads",
+                "refs/remotes",
+            ])
+            .unwrap_or_default();
+            let refs: Vec<String> = refs.lines().map(str::to_string).collect();
+            detached_head_branch(|name| std::env::var(name).ok(), &refs)
+        }),
+        // Unlike `git describe`, this works in shallow clones without the tag's history
+        tag: git_command(&["tag", "--points-at", "HEAD"])
+            .and_then(|tags| tags.lines().next().map(str::to_string)),
         pr: None, // PR info not available from git commands alone
     })
 }
 
+/// Branch variables of CI systems, in order of preference; pull request source branches
+/// come first since their builds check out a merge commit
+const CI_BRANCH_VARS: [&str; 20] = [
+    "GITHUB_HEAD_REF",
+    "CI_MERGE_REQUEST_SOURCE_BRANCH_NAME",
+    "CI_COMMIT_BRANCH",
+    "CHANGE_BRANCH",
+    "BRANCH_NAME",
+    "GIT_BRANCH",
+    "CIRCLE_BRANCH",
+    "TRAVIS_PULL_REQUEST_BRANCH",
+    "TRAVIS_BRANCH",
+    "SYSTEM_PULLREQUEST_SOURCEBRANCH",
+    "BUILD_SOURCEBRANCH",
+    "BITRISE_GIT_BRANCH",
+    "BUILDKITE_BRANCH",
+    "BITBUCKET_BRANCH",
+    "DRONE_SOURCE_BRANCH",
+    "DRONE_BRANCH",
+    "CODEBUILD_WEBHOOK_HEAD_REF",
+    "CM_BRANCH",
+    "CIRRUS_BRANCH",
+    "CI_BRANCH",
+];
+
+/// The branch of a detached HEAD, which is how CI systems check out a build's commit
+///
+/// The CI system's branch variables name the branch that was built; failing that, the
+/// branch pointing at the commit (`refs`, full ref names) is used, preferring local
+/// branches over remote-tracking ones. `None` if the commit is on no known branch, rather
+/// than the meaningless `HEAD`.
+fn detached_head_branch(var: impl Fn(&str) -> Option<String>, refs: &[String]) -> Option<String> {
+    // `GITHUB_REF_NAME` is a tag or `<number>/merge` outside branch builds
+    let github_branch = var("GITHUB_REF")
+        .filter(|r| r.starts_with("refs/heads/"))
+        .and_then(|_| var("GITHUB_REF_NAME"));
+    let from_ci = github_branch
+        .into_iter()
+        .chain(CI_BRANCH_VARS.iter().filter_map(|name| var(name)))
+        .map(|branch| {
+            let branch = branch.trim();
+            // Jenkins' `GIT_BRANCH` names the remote-tracking branch (`origin/main`)
+            let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+            branch.strip_prefix("origin/").unwrap_or(branch).to_string()
+        })
+        .find(|branch| !branch.is_empty() && branch != "HEAD");
+    if from_ci.is_some() {
+        return from_ci;
+    }
+
+    let local = refs.iter().find_map(|r| r.strip_prefix("refs/heads/"));
+    let remote = || {
+        refs.iter()
+            .filter_map(|r| r.strip_prefix("refs/remotes/"))
+            .filter_map(|r| r.split_once('/').map(|(_, branch)| branch))
+            .find(|branch| *branch != "HEAD")
+    };
+    let branch = local.or_else(remote).map(str::to_string);
+    if branch.is_none() {
+        debug!("HEAD is detached and no branch points at it");
+    }
+    branch
+}
+
+/// Collect Mercurial metadata with hg commands in a Mercurial working directory
+fn collect_hg_metadata_from_commands() -> Option<VcsMetadata> {
+    // Only start hg inside a repository, so its absence elsewhere is not reported
+    let cwd = std::env::current_dir().ok()?;
+    if !cwd.ancestors().any(|dir| dir.join(".hg").is_dir()) {
+        return None;
+    }
+
+    let log = hg_command(&["log", "-r", ".", "--template", HG_LOG_TEMPLATE])?;
+    parse_hg_log(&log, hg_command(&["paths", "default"]))
+}
+
+/// Parse the output of `hg log` with [`HG_LOG_TEMPLATE`]
+///
+/// The active bookmark, Mercurial's equivalent of a git branch, is reported as the branch
+/// when set, and the named branch otherwise.
+fn parse_hg_log(log: &str, repository_url: Option<String>) -> Option<VcsMetadata> {
+    let mut lines = log.lines();
+    let mut field = || {
+        lines
+            .next()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+
+    // The null revision of an empty repository is all zeros
+    let hash = field().filter(|h| h.len() == 40 && h.chars().any(|c| c != '0'))?;
+    let branch = field();
+    let bookmark = field();
+    let tag = field().and_then(|tags| {
+        tags.split_whitespace()
+            .find(|t| *t != "tip")
+            .map(str::to_string)
+    });
+    let author = field();
+    let timestamp = field();
+    let message = field();
+
+    Some(VcsMetadata {
+        vcs_type: "hg".to_string(),
+        provider: repository_url.as_deref().and_then(detect_git_provider),
+        repository_url,
+        commit: CommitInfo {
+            short_hash: hash.chars().take(12).collect(),
+            hash,
+            message,
+            author,
+            timestamp,
+        },
+        branch: bookmark.or(branch),
+        tag,
+        pr: None,
+    })
+}
+
+/// Collect Subversion metadata with `svn info` in a Subversion working copy
+///
+/// Only local information is read, so no server round trip is needed.
+fn collect_svn_metadata_from_commands() -> Option<VcsMetadata> {
+    let cwd = std::env::current_dir().ok()?;
+    if !cwd.ancestors().any(|dir| dir.join(".svn").is_dir()) {
+        return None;
+    }
+
+    parse_svn_info(&svn_command(&["info"])?)
+}
+
+/// Parse the `Key: value` output of `svn info`
+///
+/// The commit is the last revision that changed the working copy, so its author and date
+/// belong to it. Branches and tags follow the standard `trunk`, `branches/<name>` and
+/// `tags/<name>` layout.
+fn parse_svn_info(info: &str) -> Option<VcsMetadata> {
+    let field = |key: &str| {
+        info.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+
+    let revision = field("Last Changed Rev").or_else(|| field("Revision"))?;
+    let url = field("URL");
+    let relative_url = field("Relative URL").or_else(|| {
+        let root = field("Repository Root")?;
+        Some(format!("^{}", url.as_deref()?.strip_prefix(&root)?))
+    });
+
+    let mut path = relative_url
+        .as_deref()
+        .and_then(|p| p.strip_prefix("^/"))
+        .unwrap_or_default()
+        .split('/');
+    let (branch, tag) = match (path.next(), path.next()) {
+        (Some("trunk"), _) => (Some("trunk".to_string()), None),
+        (Some("branches"), Some(name)) => (Some(name.to_string()), None),
+        (Some("tags"), Some(name)) => (None, Some(name.to_string())),
+        _ => (None, None),
+    };
+
+    Some(VcsMetadata {
+        vcs_type: "svn".to_string(),
+        provider: url.as_deref().and_then(detect_git_provider),
+        repository_url: url,
+        commit: CommitInfo {
+            short_hash: format!("r{revision}"),
+            hash: revision,
+            message: None,
+            author: field("Last Changed Author"),
+            timestamp: field("Last Changed Date"),
+        },
+        branch,
+        tag,
+        pr: None,
+    })
+}
+
+/// Collect Plastic SCM (Unity Version Control) metadata with cm commands in a workspace
+fn collect_plastic_metadata_from_commands() -> Option<VcsMetadata> {
+    let cwd = std::env::current_dir().ok()?;
+    if !cwd.ancestors().any(|dir| dir.join(".plastic").is_dir()) {
+        return None;
+    }
+
+    let status = cm_command(&[
+        "status",
+        "--header",
+        "--machinereadable",
+        "--fieldseparator=;",
+    ])?;
+    let mut vcs = parse_cm_status(&status)?;
+    if let Some(log) = cm_command(&[
+        "log",
+        &format!("cs:{}", vcs.commit.hash),
+        &format!("--csformat={CM_LOG_FORMAT}"),
+    ]) {
+        let mut lines = log.lines().map(str::trim).filter(|l| !l.is_empty());
+        vcs.commit.author = lines.next().map(str::to_string);
+        vcs.commit.timestamp = lines.next().map(str::to_string);
+        vcs.commit.message = lines.next().map(str::to_string);
+    }
+    Some(vcs)
+}
+
+/// Parse the header of `cm status --machinereadable --fieldseparator=;`
+///
+/// The header is `STATUS;<changeset>;<branch>;<repository>;<server>`. The repository is
+/// reported as `repository@server`, the spec Plastic uses to address it.
+fn parse_cm_status(status: &str) -> Option<VcsMetadata> {
+    let header = status
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("STATUS;"))?;
+    let mut fields = header.split(';').map(str::trim);
+    let changeset = fields.next().filter(|c| c.parse::<u64>().is_ok())?;
+    let branch = fields.next().filter(|b| !b.is_empty());
+    let repository = fields.next().filter(|r| !r.is_empty());
+    let server = fields.next().filter(|s| !s.is_empty());
+
+    Some(VcsMetadata {
+        vcs_type: "plastic".to_string(),
+        provider: None,
+        repository_url: repository.map(|repo| match server {
+            Some(server) => format!("{repo}@{server}"),
+            None => repo.to_string(),
+        }),
+        commit: CommitInfo {
+            hash: changeset.to_string(),
+            short_hash: format!("cs:{changeset}"),
+            message: None,
+            author: None,
+            timestamp: None,
+        },
+        branch: branch.map(str::to_string),
+        tag: None,
+        pr: None,
+    })
+}
+
+/// Parse a `key=value` custom metadata argument (`--meta unity_version=2022.3.14f1`)
+///
+/// # Errors
+///
+/// Returns an error if the `=` is missing or the key is empty or contains characters
+/// other than letters, digits, `_`, `-` and `.`
+pub fn parse_custom_metadata(value: &str) -> Result<(String, String)> {
+    let (key, val) = value.split_once('=').ok_or_else(|| {
+        Error::ConfigError(format!("Invalid metadata '{value}': expected KEY=VALUE"))
+    })?;
+    let key = key.trim();
+
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err(Error::ConfigError(format!(
+            "Invalid metadata key '{key}': use letters, digits, '_', '-' or '.'"
+        )));
+    }
+
+    Ok((key.to_string(), val.to_string()))
+}
+
+/// Generate release notes from `git log`, one line per commit (`- subject (short-sha)`)
+///
+/// Without a `range`, covers the commits since the tag preceding `HEAD`.
+///
+/// # Errors
+///
+/// Returns an error if git fails, no previous tag exists and no range was given,
+/// or the range contains no commits
+pub fn release_notes_from_git(range: Option<&str>) -> Result<String> {
+    let range = if let Some(range) = range {
+        range.to_string()
+    } else {
+        let previous =
+            git_command(&["describe", "--tags", "--abbrev=0", "HEAD^"]).ok_or_else(|| {
+                // CI checkouts are often shallow, with neither the history nor the tags
+                let shallow = git_command(&["rev-parse", "--is-shallow-repository"])
+                    .is_some_and(|shallow| shallow == "true");
+                Error::ConfigError(if shallow {
+                    "No previous tag found for release notes in this shallow clone; fetch the \
+                     history and tags (`git fetch --unshallow --tags`, or `fetch-depth: 0` with \
+                     actions/checkout) or pass a range such as v1.2.0..HEAD"
+                        .to_string()
+                } else {
+                    "No previous tag found for release notes; pass a range such as v1.2.0..HEAD"
+                        .to_string()
+                })
+            })?;
+        format!("{previous}..HEAD")
+    };
+
+    let output = Command::new("git")
+        .args(["log", "--no-merges", "--pretty=format:- %s (%h)", &range])
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::ConfigError(format!(
+            "git log {range} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let notes = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if notes.is_empty() {
+        return Err(Error::ConfigError(format!(
+            "No commits in {range} to generate release notes from"
+        )));
+    }
+    Ok(notes)
+}
+
+/// Derive a semantic version from a release tag (`v1.2.3` → `1.2.3`)
+///
+/// Returns `None` for tags that don't start with a version number, such as `nightly`.
+#[must_use]
+pub fn version_from_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim();
+    let version = tag
+        .strip_prefix('v')
+        .or_else(|| tag.strip_prefix('V'))
+        .unwrap_or(tag);
+
+    if version.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(version.to_string())
+    } else {
+        None
+    }
+}
+
+/// Return and clear the problems met while collecting metadata
+///
+/// Collection is best effort: in sandboxed or locked-down environments (no git binary,
+/// untrusted checkout, slow file systems) the affected details are skipped quietly and
+/// recorded here instead of failing or logging errors.
+pub fn take_collection_warnings() -> Vec<String> {
+    COLLECTION_WARNINGS
+        .lock()
+        .map(|mut warnings| std::mem::take(&mut *warnings))
+        .unwrap_or_default()
+}
+
+fn record_collection_warning(warning: String) {
+    debug!("Metadata collection: {warning}");
+    if let Ok(mut warnings) = COLLECTION_WARNINGS.lock()
+        && !warnings.contains(&warning)
+    {
+        warnings.push(warning);
+    }
+}
+
+/// Run a git command for metadata, returning its trimmed output if it succeeded
+///
+/// Each command is time-boxed, and once git is found to be unusable (missing, blocked by
+/// `safe.directory`, or out of time) no further commands are started.
 fn git_command(args: &[&str]) -> Option<String> {
-    let output = Command::new("git").args(args).output().ok()?;
+    tool_command(&GIT, "git", args)
+}
+
+/// Run a Mercurial command for metadata, like [`git_command`]
+fn hg_command(args: &[&str]) -> Option<String> {
+    tool_command(&HG, "hg", args)
+}
+
+/// Run a Subversion command for metadata, like [`git_command`]
+fn svn_command(args: &[&str]) -> Option<String> {
+    tool_command(&SVN, "svn", args)
+}
+
+/// Run a Plastic SCM command for metadata, like [`git_command`]
+fn cm_command(args: &[&str]) -> Option<String> {
+    tool_command(&CM, "cm", args)
+}
+
+fn tool_command(state: &Mutex<ToolState>, program: &str, args: &[&str]) -> Option<String> {
+    let mut tool = state.lock().ok()?;
+    if tool.unavailable {
+        return None;
+    }
+    if tool.spent >= VCS_METADATA_BUDGET {
+        tool.unavailable = true;
+        record_collection_warning(format!(
+            "{program} commands took longer than {}s in total; remaining {program} details skipped",
+            VCS_METADATA_BUDGET.as_secs()
+        ));
+        return None;
+    }
+
+    let started = Instant::now();
+    let result = run_tool(program, args);
+    tool.spent += started.elapsed();
+
+    let output = match result {
+        Ok(Some(output)) => output,
+        Ok(None) => {
+            tool.unavailable = true;
+            record_collection_warning(format!(
+                "`{program} {}` timed out after {}s; {program} details skipped",
+                args.join(" "),
+                VCS_COMMAND_TIMEOUT.as_secs()
+            ));
+            return None;
+        }
+        Err(e) => {
+            tool.unavailable = true;
+            record_collection_warning(if e.kind() == io::ErrorKind::NotFound {
+                format!("{program} is not installed; commit details are limited to CI variables")
+            } else {
+                format!("{program} could not be run ({e}); {program} details skipped")
+            });
+            return None;
+        }
+    };
 
     if output.status.success() {
         let result = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -293,28 +1583,435 @@ fn git_command(args: &[&str]) -> Option<String> {
             Some(result)
         }
     } else {
+        // Other failures are expected, e.g. no tag on HEAD or no remote configured
+        if String::from_utf8_lossy(&output.stderr).contains("dubious ownership") {
+            tool.unavailable = true;
+            record_collection_warning(
+                "git refused the checkout as owned by another user (safe.directory); git details skipped"
+                    .to_string(),
+            );
+        }
         None
     }
 }
 
+/// Run a VCS tool with [`VCS_COMMAND_TIMEOUT`], returning `None` if it was killed for
+/// taking too long
+fn run_tool(program: &str, args: &[&str]) -> io::Result<Option<Output>> {
+    let mut child = Command::new(program)
+        .args(args)
+        // Keeps Mercurial's output stable regardless of user aliases and localization
+        .env("HGPLAIN", "1")
+        // svn info labels and git's error messages are translated otherwise
+        .env("LC_MESSAGES", "C")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drain the pipes while waiting so a large output can't block the tool
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let deadline = Instant::now() + VCS_COMMAND_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Some(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
 fn is_git_repo() -> bool {
-    Command::new("git")
-        .args(["rev-parse", "--git-dir"])
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
+    git_command(&["rev-parse", "--git-dir"]).is_some()
 }
 
+/// Provider hosting a git remote, from well-known hosts or, for self-hosted GitLab,
+/// GitHub Enterprise, Gitea and Forgejo, the product name in the host
 fn detect_git_provider(url: &str) -> Option<String> {
-    if url.contains("github.com") {
-        Some("github".to_string())
-    } else if url.contains("gitlab.com") {
-        Some("gitlab".to_string())
-    } else if url.contains("bitbucket.org") {
-        Some("bitbucket".to_string())
-    } else if url.contains("dev.azure.com") || url.contains("visualstudio.com") {
-        Some("azure-devops".to_string())
+    let normalized = normalize_repository_url(url);
+    let host = url::Url::parse(&normalized)
+        .ok()?
+        .host_str()?
+        .to_ascii_lowercase();
+    let labels: Vec<&str> = host.split(['.', '-']).collect();
+    let has_label = |names: &[&str]| labels.iter().any(|l| names.contains(l));
+
+    let provider = match host.as_str() {
+        "github.com" => "github",
+        "gitlab.com" => "gitlab",
+        "bitbucket.org" => "bitbucket",
+        "codeberg.org" => "codeberg",
+        "git.sr.ht" | "hg.sr.ht" => "sourcehut",
+        "dev.azure.com" => "azure-devops",
+        h if h.ends_with(".visualstudio.com") => "azure-devops",
+        h if h.starts_with("git-codecommit.") && h.ends_with(".amazonaws.com") => "codecommit",
+        h if h.ends_with(".ghe.com") => "github",
+        _ if has_label(&["gitlab"]) => "gitlab",
+        _ if has_label(&["github", "ghe"]) => "github",
+        _ if has_label(&["gitea"]) => "gitea",
+        _ if has_label(&["forgejo"]) => "forgejo",
+        _ => return None,
+    };
+    Some(provider.to_string())
+}
+
+/// Turn a git remote into the `https://` URL of the repository
+///
+/// SSH remotes (`git@host:org/repo.git`, `ssh://git@host:22/org/repo`), `codecommit::`
+/// remotes and Azure DevOps SSH remotes are converted, credentials are dropped and a
+/// trailing `.git` is removed. Anything that is not a recognizable remote is returned
+/// unchanged.
+fn normalize_repository_url(url: &str) -> String {
+    let url = url.trim();
+
+    // git-remote-codecommit: codecommit::<region>://[<profile>@]<repository>
+    if let Some(rest) = url.strip_prefix("codecommit::")
+        && let Some((region, repo)) = rest.split_once("://")
+    {
+        let repo = repo.rsplit('@').next().unwrap_or(repo);
+        return format!("https://git-codecommit.{region}.amazonaws.com/v1/repos/{repo}");
+    }
+
+    let (scheme, host, port, path) = if url.contains("://") {
+        let Ok(parsed) = url::Url::parse(url) else {
+            return url.to_string();
+        };
+        let Some(host) = parsed.host_str() else {
+            return url.to_string();
+        };
+        let scheme = match parsed.scheme() {
+            "http" => "http",
+            "https" | "ssh" | "git" | "git+ssh" | "ssh+git" => "https",
+            _ => return url.to_string(),
+        };
+        // SSH ports say nothing about where the repository is browsed
+        let port = parsed
+            .port()
+            .filter(|_| parsed.scheme().starts_with("http"));
+        (scheme, host.to_string(), port, parsed.path().to_string())
     } else {
-        None
+        // scp-like syntax: [user@]host:path, where the host contains no slash
+        let Some((authority, path)) = url.split_once(':') else {
+            return url.to_string();
+        };
+        if authority.contains('/') || path.starts_with("//") {
+            return url.to_string();
+        }
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        ("https", host.to_string(), None, format!("/{path}"))
+    };
+
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if host.eq_ignore_ascii_case("ssh.dev.azure.com") {
+        // v3/<org>/<project>/<repo> is browsed at dev.azure.com/<org>/<project>/_git/<repo>
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        if let ["v3", org, project, repo] = parts.as_slice() {
+            return format!("https://dev.azure.com/{org}/{project}/_git/{repo}");
+        }
+    }
+    match port {
+        Some(port) => format!("{scheme}://{host}:{port}{path}"),
+        None => format!("{scheme}://{host}{path}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_from_tag() {
+        assert_eq!(version_from_tag("v1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(
+            version_from_tag("2.0.0-rc.1").as_deref(),
+            Some("2.0.0-rc.1")
+        );
+        assert_eq!(version_from_tag("nightly"), None);
+        assert_eq!(version_from_tag("v"), None);
+    }
+
+    #[test]
+    fn test_parse_codebuild_pr() {
+        assert_eq!(parse_codebuild_pr("pr/1523"), Some(1523));
+        assert_eq!(parse_codebuild_pr("pr/abc"), None);
+        assert_eq!(parse_codebuild_pr("branch/main"), None);
+        assert_eq!(
+            parse_codebuild_pr("0123456789abcdef0123456789abcdef01234567"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_hg_log() {
+        let log = "0123456789abcdef0123456789abcdef01234567\ndefault\nfeature-x\nv1.4 tip\nAda <ada@example.com>\n2024-03-01T10:00:00+01:00\nFix shader cache";
+        let vcs = parse_hg_log(log, Some("https://bitbucket.org/studio/game".to_string())).unwrap();
+        assert_eq!(vcs.vcs_type, "hg");
+        assert_eq!(vcs.commit.short_hash, "0123456789ab");
+        assert_eq!(vcs.branch.as_deref(), Some("feature-x"));
+        assert_eq!(vcs.tag.as_deref(), Some("v1.4"));
+        assert_eq!(vcs.commit.message.as_deref(), Some("Fix shader cache"));
+        assert_eq!(vcs.provider.as_deref(), Some("bitbucket"));
+
+        let untagged = parse_hg_log(
+            "0123456789abcdef0123456789abcdef01234567\ndefault\n\ntip\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(untagged.branch.as_deref(), Some("default"));
+        assert_eq!(untagged.tag, None);
+        assert!(parse_hg_log(&"0".repeat(40), None).is_none());
+    }
+
+    #[test]
+    fn test_parse_svn_info() {
+        let info = "Path: .\nWorking Copy Root Path: /builds/game\nURL: https://svn.example.com/game/branches/release-1.4\nRelative URL: ^/branches/release-1.4\nRepository Root: https://svn.example.com/game\nRevision: 4312\nNode Kind: directory\nLast Changed Author: ada\nLast Changed Rev: 4310\nLast Changed Date: 2024-03-01 10:00:00 +0100 (Fri, 01 Mar 2024)\n";
+        let vcs = parse_svn_info(info).unwrap();
+        assert_eq!(vcs.vcs_type, "svn");
+        assert_eq!(vcs.commit.hash, "4310");
+        assert_eq!(vcs.commit.short_hash, "r4310");
+        assert_eq!(vcs.commit.author.as_deref(), Some("ada"));
+        assert_eq!(vcs.branch.as_deref(), Some("release-1.4"));
+        assert_eq!(
+            vcs.repository_url.as_deref(),
+            Some("https://svn.example.com/game/branches/release-1.4")
+        );
+
+        // svn before 1.8 has no relative URL
+        let tagged = parse_svn_info(
+            "URL: https://svn.example.com/game/tags/v1.4/Assets\nRepository Root: https://svn.example.com/game\nRevision: 12\n",
+        )
+        .unwrap();
+        assert_eq!(tagged.tag.as_deref(), Some("v1.4"));
+        assert_eq!(tagged.branch, None);
+        assert_eq!(tagged.commit.hash, "12");
+        assert!(parse_svn_info("svn: E155007: not a working copy").is_none());
+    }
+
+    #[test]
+    fn test_checkout_collector_order() {
+        let sources: Vec<_> = CHECKOUT_COLLECTORS
+            .iter()
+            .map(|(source, _)| *source)
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                "git repository",
+                "git commands",
+                "hg commands",
+                "svn commands",
+                "cm commands"
+            ]
+        );
+
+        let hg = || {
+            parse_hg_log(
+                "0123456789abcdef0123456789abcdef01234567\ndefault\n\ntip\n",
+                None,
+            )
+        };
+        let svn = || parse_svn_info("URL: https://svn.example.com/game/trunk\nRevision: 12\n");
+        let unreachable = || -> Option<VcsMetadata> { panic!("later collectors must not run") };
+        let vcs = first_collected(&[
+            ("git repository", || None),
+            ("git commands", || None),
+            ("hg commands", hg),
+            ("svn commands", unreachable),
+        ])
+        .unwrap();
+        assert_eq!(vcs.vcs_type, "hg");
+
+        // Whichever collector finds a checkout first wins, even if later ones would too
+        let vcs = first_collected(&[("svn commands", svn), ("cm commands", unreachable)]).unwrap();
+        assert_eq!(vcs.vcs_type, "svn");
+        assert!(
+            first_collected(&[("git repository", || None), ("cm commands", || None)]).is_none()
+        );
+    }
+
+    #[test]
+    fn test_parse_cm_status() {
+        let status = "STATUS;1742;/main/release-1.4;shooter;studio@cloud\nCH;/Assets/Player.cs\n";
+        let vcs = parse_cm_status(status).unwrap();
+        assert_eq!(vcs.vcs_type, "plastic");
+        assert_eq!(vcs.commit.hash, "1742");
+        assert_eq!(vcs.commit.short_hash, "cs:1742");
+        assert_eq!(vcs.branch.as_deref(), Some("/main/release-1.4"));
+        assert_eq!(vcs.repository_url.as_deref(), Some("shooter@studio@cloud"));
+
+        assert!(parse_cm_status("STATUS;;/main;shooter;localhost:8087").is_none());
+        assert!(parse_cm_status("The selected path is not in a workspace.").is_none());
+    }
+
+    #[test]
+    fn test_git_providers_and_urls() {
+        let cases = [
+            (
+                "git@github.com:studio/game.git",
+                "https://github.com/studio/game",
+                Some("github"),
+            ),
+            (
+                "https://token@github.example.com/studio/game",
+                "https://github.example.com/studio/game",
+                Some("github"),
+            ),
+            (
+                "ssh://git@gitlab.studio.internal:2222/game/client.git",
+                "https://gitlab.studio.internal/game/client",
+                Some("gitlab"),
+            ),
+            (
+                "https://codeberg.org/studio/game.git",
+                "https://codeberg.org/studio/game",
+                Some("codeberg"),
+            ),
+            (
+                "git@git.sr.ht:~ada/game",
+                "https://git.sr.ht/~ada/game",
+                Some("sourcehut"),
+            ),
+            (
+                "codecommit::eu-west-1://ci@game",
+                "https://git-codecommit.eu-west-1.amazonaws.com/v1/repos/game",
+                Some("codecommit"),
+            ),
+            (
+                "git@ssh.dev.azure.com:v3/studio/Game/client",
+                "https://dev.azure.com/studio/Game/_git/client",
+                Some("azure-devops"),
+            ),
+            (
+                "http://gitea.local:3000/studio/game.git",
+                "http://gitea.local:3000/studio/game",
+                Some("gitea"),
+            ),
+            (
+                "https://git.example.com/studio/game",
+                "https://git.example.com/studio/game",
+                None,
+            ),
+            ("/srv/git/game.git", "/srv/git/game.git", None),
+        ];
+        for (remote, normalized, provider) in cases {
+            assert_eq!(normalize_repository_url(remote), normalized, "{remote}");
+            assert_eq!(detect_git_provider(remote).as_deref(), provider, "{remote}");
+        }
+    }
+
+    #[test]
+    fn test_detached_head_branch() {
+        let refs = [
+            "refs/remotes/origin/HEAD".to_string(),
+            "refs/remotes/origin/release/1.4".to_string(),
+        ];
+        let no_vars = |_: &str| None;
+        assert_eq!(
+            detached_head_branch(no_vars, &refs).as_deref(),
+            Some("release/1.4")
+        );
+        let local = ["refs/heads/main".to_string(), refs[1].clone()];
+        assert_eq!(
+            detached_head_branch(no_vars, &local).as_deref(),
+            Some("main")
+        );
+        assert_eq!(detached_head_branch(no_vars, &[]), None);
+
+        let jenkins = |name: &str| (name == "GIT_BRANCH").then(|| "origin/develop".to_string());
+        assert_eq!(
+            detached_head_branch(jenkins, &local).as_deref(),
+            Some("develop")
+        );
+
+        let github_tag = |name: &str| match name {
+            "GITHUB_REF" => Some("refs/tags/v1.4.0".to_string()),
+            "GITHUB_REF_NAME" => Some("v1.4.0".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            detached_head_branch(github_tag, &refs).as_deref(),
+            Some("release/1.4")
+        );
+        let github_branch = |name: &str| match name {
+            "GITHUB_REF" => Some("refs/heads/feature/login".to_string()),
+            "GITHUB_REF_NAME" => Some("feature/login".to_string()),
+            "GITHUB_HEAD_REF" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!(
+            detached_head_branch(github_branch, &refs).as_deref(),
+            Some("feature/login")
+        );
+    }
+
+    #[test]
+    fn test_vcs_overrides() {
+        let overrides = VcsOverrides {
+            commit: Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+            branch: Some("release/1.4".to_string()),
+            pr_number: Some(88),
+            ..VcsOverrides::default()
+        };
+        let vcs = overrides.apply(None).unwrap();
+        assert_eq!(vcs.vcs_type, "git");
+        assert_eq!(vcs.commit.short_hash, "0123456");
+        let pr = vcs.pr.unwrap();
+        assert_eq!(pr.number, 88);
+        assert_eq!(pr.source_branch.as_deref(), Some("release/1.4"));
+
+        assert!(VcsOverrides::default().apply(None).is_none());
+
+        // Flags win over the manifest, which wins over detection
+        let manifest: VcsOverrides =
+            serde_yaml::from_str("commit: abc1234\nbranch: main\nrepo_url: git@github.com:o/r.git")
+                .unwrap();
+        let flags = VcsOverrides {
+            branch: Some("hotfix".to_string()),
+            pr_number: Some(7),
+            ..VcsOverrides::default()
+        };
+        let vcs = flags.or(manifest).apply(None).unwrap();
+        assert_eq!(vcs.commit.hash, "abc1234");
+        assert_eq!(vcs.branch.as_deref(), Some("hotfix"));
+        assert_eq!(vcs.provider.as_deref(), Some("github"));
+        assert_eq!(vcs.pr.map(|pr| pr.number), Some(7));
+        assert!(serde_yaml::from_str::<VcsOverrides>("sha: abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_custom_metadata() {
+        assert_eq!(
+            parse_custom_metadata("unity_version=2022.3.14f1").unwrap(),
+            ("unity_version".to_string(), "2022.3.14f1".to_string())
+        );
+        assert_eq!(
+            parse_custom_metadata("flags=a=b").unwrap(),
+            ("flags".to_string(), "a=b".to_string())
+        );
+        assert!(parse_custom_metadata("no_value").is_err());
+        assert!(parse_custom_metadata("=x").is_err());
+        assert!(parse_custom_metadata("bad key=x").is_err());
     }
 }
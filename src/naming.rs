@@ -0,0 +1,146 @@
+use crate::api::client::BuildDetails;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Tokens that differ per file, so names containing them are already unique
+const FILE_TOKENS: &[&str] = &["{filename}", "{stem}"];
+
+/// Value substituted for tokens whose metadata is not available
+const UNKNOWN: &str = "unknown";
+
+/// Render the template tokens in a build name
+///
+/// Supported tokens: `{filename}`, `{stem}`, `{platform}`, `{git_sha}`, `{branch}`,
+/// `{date}` (UTC, `YYYY-MM-DD`) and `{ci_build_number}`. Tokens without a value
+/// (e.g. `{branch}` outside a repository) render as `unknown`.
+#[must_use]
+pub fn render_name_template(
+    template: &str,
+    file_path: &str,
+    platform: &str,
+    details: Option<&BuildDetails>,
+) -> String {
+    if !template.contains('{') {
+        return template.to_string();
+    }
+
+    let path = Path::new(file_path);
+    let filename = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(file_path);
+    let stem = path
+        .file_stem()
+        .and_then(|n| n.to_str())
+        .unwrap_or(filename);
+
+    let vcs = details.and_then(|d| d.vcs.as_ref());
+    let ci = details.and_then(|d| d.ci.as_ref());
+    let git_sha = vcs.map(|v| v.commit.short_hash.as_str());
+    let branch = vcs.and_then(|v| v.branch.as_deref());
+    let build_number = ci.and_then(|c| c.build_number.as_deref());
+
+    let date = humantime::format_rfc3339(SystemTime::now()).to_string();
+    let date = date.get(..10).unwrap_or(&date);
+
+    template
+        .replace("{filename}", filename)
+        .replace("{stem}", stem)
+        .replace("{platform}", platform)
+        .replace("{git_sha}", git_sha.unwrap_or(UNKNOWN))
+        .replace("{branch}", branch.unwrap_or(UNKNOWN))
+        .replace("{date}", date)
+        .replace("{ci_build_number}", build_number.unwrap_or(UNKNOWN))
+}
+
+/// Generate a build name from a template and filename
+///
+/// When several files share one template the filename is appended to keep names
+/// distinct, unless the template already contains a per-file token.
+#[must_use]
+pub fn generate_build_name(
+    template: &str,
+    file_path: &str,
+    file_count: usize,
+    platform: &str,
+    details: Option<&BuildDetails>,
+) -> String {
+    let name = render_name_template(template, file_path, platform, details);
+
+    if file_count == 1 || FILE_TOKENS.iter().any(|token| template.contains(token)) {
+        name
+    } else {
+        let filename = Path::new(file_path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(file_path);
+        format!("{name} - {filename}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ci_metadata::CiMetadata;
+    use crate::metadata::{CommitInfo, VcsMetadata};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_generate_build_name() {
+        let details = BuildDetails {
+            vcs: Some(VcsMetadata {
+                vcs_type: "git".to_string(),
+                provider: None,
+                repository_url: None,
+                commit: CommitInfo {
+                    hash: "abcdef1234567890".to_string(),
+                    short_hash: "abcdef1".to_string(),
+                    message: None,
+                    author: None,
+                    timestamp: None,
+                },
+                branch: Some("main".to_string()),
+                tag: None,
+                pr: None,
+            }),
+            ci: Some(CiMetadata {
+                system: "github_actions".to_string(),
+                build_number: Some("42".to_string()),
+                job_name: None,
+                run_id: None,
+                run_url: None,
+                triggered_by: None,
+                agent: None,
+                event: None,
+            }),
+            upload: None,
+            custom: BTreeMap::new(),
+            artifact: None,
+            size_budget: None,
+            collection_warnings: Vec::new(),
+        };
+
+        assert_eq!(
+            generate_build_name(
+                "MyGame {branch} #{ci_build_number} ({git_sha})",
+                "out/game.apk",
+                1,
+                "android",
+                Some(&details)
+            ),
+            "MyGame main #42 (abcdef1)"
+        );
+        assert_eq!(
+            generate_build_name("{stem} on {platform}", "out/game.apk", 2, "android", None),
+            "game on android"
+        );
+        assert_eq!(
+            generate_build_name("Nightly {branch}", "out/game.apk", 2, "android", None),
+            "Nightly unknown - game.apk"
+        );
+        assert_eq!(
+            generate_build_name("{date}", "a.apk", 1, "android", None).len(),
+            10
+        );
+    }
+}
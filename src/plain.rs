@@ -0,0 +1,187 @@
+//! Plain output (`--plain`) for screen readers and legacy terminals
+//!
+//! In plain mode emoji are dropped and other decorative symbols (box drawing, arrows, check
+//! marks, typographic punctuation) are replaced with ASCII, progress bars and spinners are
+//! hidden in favour of a line for every tenth of progress, and log output has no color.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// How often plain progress checks whether another tenth is done
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Turn plain mode on or off for the rest of the process
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// `text` as it should be printed: unchanged, or without decoration in plain mode
+#[must_use]
+pub fn text(text: &str) -> Cow<'_, str> {
+    if is_plain() {
+        Cow::Owned(strip_decoration(text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// `text` without emoji and with other decorative symbols replaced by ASCII
+///
+/// The space after a dropped symbol at the start of a line or after other whitespace is
+/// dropped too, so `"✓ Complete"` becomes `"Complete"`.
+#[must_use]
+pub fn strip_decoration(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut dropped = false;
+    for c in text.chars() {
+        if dropped && c == ' ' && (plain.is_empty() || plain.ends_with([' ', '\n'])) {
+            continue;
+        }
+        dropped = false;
+        match ascii_replacement(c) {
+            Some("") => dropped = true,
+            Some(replacement) => plain.push_str(replacement),
+            None => plain.push(c),
+        }
+    }
+    plain
+}
+
+/// ASCII for a decorative character: empty for emoji, `None` for characters that stay
+fn ascii_replacement(c: char) -> Option<&'static str> {
+    Some(match c {
+        '─' | '━' | '═' | '╌' | '┄' | '–' | '—' | '•' | '·' => "-",
+        '│' | '┃' | '║' | '╎' | '┆' => "|",
+        '\u{2500}'..='\u{257F}' => "+",
+        '\u{2580}'..='\u{259F}' => "#",
+        '←' | '⇐' => "<-",
+        '→' | '⇒' | '➜' | '➔' | '\u{2190}'..='\u{21FF}' => "->",
+        '…' => "...",
+        '‘' | '’' => "'",
+        '“' | '”' => "\"",
+        '×' => "x",
+        '≈' => "~",
+        '≤' => "<=",
+        '≥' => ">=",
+        // Pictographs, dingbats (check marks, crosses), technical symbols (⏱, ⌛) and the
+        // joiners and selectors that combine them into emoji
+        '\u{1F000}'..='\u{1FAFF}'
+        | '\u{2300}'..='\u{23FF}'
+        | '\u{2600}'..='\u{27BF}'
+        | '\u{2B00}'..='\u{2BFF}'
+        | '\u{200D}'
+        | '\u{20E3}'
+        | '\u{FE0E}'
+        | '\u{FE0F}' => "",
+        _ => return None,
+    })
+}
+
+/// A progress display: drawn bars, or hidden ones reported by [`PlainProgress`]
+#[must_use]
+pub fn multi_progress() -> MultiProgress {
+    if is_plain() {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    }
+}
+
+/// Hide `pb` in plain mode
+pub fn hide_in_plain_mode(pb: &ProgressBar) {
+    if is_plain() {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
+}
+
+/// Line-oriented progress of a hidden bar for plain mode
+///
+/// Prints `label: 30%` to stderr each time another tenth is done and, once the bar is
+/// finished, `label: <final message>`.
+pub struct PlainProgress {
+    label: String,
+    pb: ProgressBar,
+    handle: JoinHandle<()>,
+}
+
+impl PlainProgress {
+    /// Start reporting `pb` as `label`, in plain mode only
+    #[must_use]
+    pub fn start(label: &str, pb: ProgressBar) -> Option<Self> {
+        if !is_plain() {
+            return None;
+        }
+        let label = strip_decoration(label);
+        eprintln!("{label}: started");
+        let handle = tokio::spawn({
+            let label = label.clone();
+            let pb = pb.clone();
+            async move {
+                let mut reported = 0;
+                let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let tenths = pb
+                        .length()
+                        .and_then(|total| (pb.position().min(total) * 10).checked_div(total))
+                        .unwrap_or(0);
+                    if tenths > reported && tenths < 10 {
+                        reported = tenths;
+                        eprintln!("{label}: {}%", tenths * 10);
+                    }
+                }
+            }
+        });
+        Some(Self { label, pb, handle })
+    }
+}
+
+impl Drop for PlainProgress {
+    fn drop(&mut self) {
+        self.handle.abort();
+        if self.pb.is_finished() {
+            eprintln!("{}: {}", self.label, strip_decoration(&self.pb.message()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_decoration() {
+        assert_eq!(strip_decoration("✓ Complete"), "Complete");
+        assert_eq!(
+            strip_decoration("\n✅ Successfully uploaded 2 file(s):"),
+            "\nSuccessfully uploaded 2 file(s):"
+        );
+        assert_eq!(
+            strip_decoration("⚠️  Upload cancelled."),
+            "Upload cancelled."
+        );
+        assert_eq!(
+            strip_decoration("🗑️  Build b1 deleted."),
+            "Build b1 deleted."
+        );
+        assert_eq!(
+            strip_decoration("  game.zip → build b1 (12.0 MiB, +1.5 MiB)"),
+            "  game.zip -> build b1 (12.0 MiB, +1.5 MiB)"
+        );
+        assert_eq!(strip_decoration("┌─┐ │ … —"), "+-+ | ... -");
+        assert_eq!(
+            strip_decoration("Spiel für Ärzte 日本"),
+            "Spiel für Ärzte 日本"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Discovery of external `nunu-cli-<name>` subcommands (plugins) on `PATH`
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Prefix of plugin executables: `nunu-cli smoke-test` runs `nunu-cli-smoke-test`
+pub const PLUGIN_PREFIX: &str = "nunu-cli-";
+
+/// Find the executable implementing `nunu-cli <name>` on `PATH`
+#[must_use]
+pub fn find_plugin(name: &str) -> Option<PathBuf> {
+    find_plugin_in(name, &std::env::var_os("PATH")?)
+}
+
+/// Find the plugin `name` in the directories of a `PATH`-style list, first match winning
+#[must_use]
+pub fn find_plugin_in(name: &str, search_path: &OsStr) -> Option<PathBuf> {
+    // Names are single path components, so a plugin can't escape the search path
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    let file_name = format!("{PLUGIN_PREFIX}{name}{}", std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(search_path)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_plugin_in() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let file_name = format!("nunu-cli-smoke-test{}", std::env::consts::EXE_SUFFIX);
+        let plugin = second.path().join(&file_name);
+        std::fs::write(&plugin, b"#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            // Not executable: skipped in favor of later directories
+            std::fs::write(first.path().join(&file_name), b"").unwrap();
+            std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let search_path = std::env::join_paths([first.path(), second.path()]).unwrap();
+        assert_eq!(find_plugin_in("smoke-test", &search_path), Some(plugin));
+        assert_eq!(find_plugin_in("missing", &search_path), None);
+        assert_eq!(find_plugin_in("../smoke-test", &search_path), None);
+    }
+}
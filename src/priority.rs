@@ -0,0 +1,117 @@
+//! Lowering the process priority for background uploads (`--nice`)
+
+use crate::error::Result;
+use log::debug;
+
+/// Nice value applied on Unix (0 is normal, 19 the lowest priority)
+#[cfg(unix)]
+const NICE_LEVEL: libc::c_int = 10;
+
+/// Lower the CPU and I/O priority of the whole process so uploads yield to other work
+///
+/// On Linux every existing thread is reniced and moved to the lowest best-effort I/O
+/// priority (threads started later inherit it); on macOS the process is also put in the
+/// background band, which throttles its disk and network I/O. On Windows the process
+/// enters background processing mode.
+///
+/// # Errors
+///
+/// Returns an error if the operating system rejects the priority change
+pub fn lower_process_priority() -> Result<()> {
+    imp::lower()?;
+    debug!("Lowered process CPU and I/O priority");
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::NICE_LEVEL;
+    use std::io;
+
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_BE: libc::c_long = 2;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+    const IOPRIO_LOWEST_BE: libc::c_long = (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7;
+
+    pub(super) fn lower() -> io::Result<()> {
+        // Linux applies both priorities per thread, and the runtime's threads already exist
+        for entry in std::fs::read_dir("/proc/self/task")? {
+            let Some(tid) = entry?
+                .file_name()
+                .to_str()
+                .and_then(|t| t.parse::<libc::id_t>().ok())
+            else {
+                continue;
+            };
+
+            // SAFETY: plain syscalls on our own thread IDs, no memory is passed
+            let niced = unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, NICE_LEVEL) };
+            if niced != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let ioprio = unsafe {
+                libc::syscall(
+                    libc::SYS_ioprio_set,
+                    IOPRIO_WHO_PROCESS,
+                    libc::c_long::from(tid),
+                    IOPRIO_LOWEST_BE,
+                )
+            };
+            if ioprio != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod imp {
+    use super::NICE_LEVEL;
+    use std::io;
+
+    pub(super) fn lower() -> io::Result<()> {
+        // SAFETY: plain syscall on our own process, no memory is passed
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICE_LEVEL) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        #[cfg(target_os = "macos")]
+        {
+            // PRIO_DARWIN_PROCESS / PRIO_DARWIN_BG from <sys/resource.h>
+            const PRIO_DARWIN_PROCESS: libc::c_int = 4;
+            const PRIO_DARWIN_BG: libc::c_int = 0x1000;
+            // SAFETY: as above
+            if unsafe { libc::setpriority(PRIO_DARWIN_PROCESS, 0, PRIO_DARWIN_BG) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, PROCESS_MODE_BACKGROUND_BEGIN, SetPriorityClass,
+    };
+
+    pub(super) fn lower() -> io::Result<()> {
+        // Background mode lowers CPU, I/O and memory priority together
+        // SAFETY: the pseudo handle of the current process is always valid
+        if unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    pub(super) fn lower() -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "changing the process priority is not supported on this platform",
+        ))
+    }
+}
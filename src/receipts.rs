@@ -0,0 +1,211 @@
+//! Signed upload receipts, kept on this machine as an audit trail of what was uploaded when
+//!
+//! When an upload completes the server returns a receipt signed with its Ed25519 key. The
+//! CLI stores it as `<build_id>.json` in the receipts directory, and `nunu-cli receipts
+//! verify` later checks the signature (and optionally a local file's hash) against it.
+
+use crate::error::{Error, Result};
+use crate::util::{data_dir, hex_decode};
+use log::debug;
+use ring::signature::{ED25519, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Domain separation prefix of the signed message, bumped if its layout ever changes
+const SIGNED_MESSAGE_PREFIX: &str = "nunu-upload-receipt-v1";
+
+/// Server-signed proof that a build's upload completed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UploadReceipt {
+    pub build_id: String,
+    /// SHA-256 of the stored object as lowercase hex, computed by the server
+    pub sha256: String,
+    /// When the server completed the upload (RFC 3339 timestamp)
+    pub completed_at: String,
+    /// ID of the server key that signed the receipt
+    pub key_id: String,
+    /// Ed25519 signature over [`UploadReceipt::signed_message`] as lowercase hex
+    pub signature: String,
+}
+
+/// A public key the server signs receipts with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptKey {
+    pub key_id: String,
+    /// Raw 32-byte Ed25519 public key as lowercase hex
+    pub public_key: String,
+}
+
+impl UploadReceipt {
+    /// The bytes covered by the signature: a version prefix, build ID, hash and timestamp,
+    /// one per line
+    #[must_use]
+    pub fn signed_message(&self) -> String {
+        format!(
+            "{SIGNED_MESSAGE_PREFIX}\n{}\n{}\n{}",
+            self.build_id, self.sha256, self.completed_at
+        )
+    }
+
+    /// Check the receipt's signature with the matching key from `keys`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no key matches the receipt's key ID, a key or the signature is
+    /// not valid hex, or the signature does not match
+    pub fn verify(&self, keys: &[ReceiptKey]) -> Result<()> {
+        let key = keys
+            .iter()
+            .find(|k| k.key_id == self.key_id)
+            .ok_or_else(|| {
+                Error::ReceiptError(format!(
+                    "no public key with ID '{}' for build {}",
+                    self.key_id, self.build_id
+                ))
+            })?;
+        let public_key = hex_decode(&key.public_key).ok_or_else(|| {
+            Error::ReceiptError(format!("public key '{}' is not valid hex", key.key_id))
+        })?;
+        let signature = hex_decode(&self.signature).ok_or_else(|| {
+            Error::ReceiptError(format!(
+                "signature of build {} is not valid hex",
+                self.build_id
+            ))
+        })?;
+
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(self.signed_message().as_bytes(), &signature)
+            .map_err(|_| {
+                Error::ReceiptError(format!(
+                    "signature of build {} does not match its contents",
+                    self.build_id
+                ))
+            })
+    }
+}
+
+/// Directory holding the receipts of uploads made from this machine
+///
+/// Uses `NUNU_RECEIPTS_DIR` if set, otherwise the user data directory.
+#[must_use]
+pub fn receipts_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("NUNU_RECEIPTS_DIR") {
+        return PathBuf::from(dir);
+    }
+    data_dir().join("receipts")
+}
+
+/// Store a receipt in the receipts directory, replacing any earlier one for the build
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or the file cannot be written
+pub fn save_receipt(receipt: &UploadReceipt) -> Result<PathBuf> {
+    let dir = receipts_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = receipt_path(&dir, &receipt.build_id);
+    std::fs::write(&path, serde_json::to_string_pretty(receipt)?)?;
+    debug!("Stored upload receipt {}", path.display());
+    Ok(path)
+}
+
+/// Read the stored receipt of a build
+///
+/// # Errors
+///
+/// Returns an error if there is no receipt for the build or it cannot be parsed
+pub fn load_receipt(build_id: &str) -> Result<UploadReceipt> {
+    let path = receipt_path(&receipts_dir(), build_id);
+    let contents = std::fs::read_to_string(&path).map_err(|e| {
+        Error::ReceiptError(format!(
+            "no receipt for build {build_id} in {} ({e})",
+            receipts_dir().display()
+        ))
+    })?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Read every stored receipt, oldest completion first
+///
+/// Files that are not valid receipts are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the receipts directory exists but cannot be read
+pub fn list_receipts() -> Result<Vec<UploadReceipt>> {
+    let dir = receipts_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut receipts = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        if let Some(receipt) = read_receipt(&path) {
+            receipts.push(receipt);
+        }
+    }
+
+    receipts.sort_by(|a, b| a.completed_at.cmp(&b.completed_at));
+    Ok(receipts)
+}
+
+fn read_receipt(path: &Path) -> Option<UploadReceipt> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents)
+        .inspect_err(|e| debug!("Ignoring unreadable receipt {}: {e}", path.display()))
+        .ok()
+}
+
+fn receipt_path(dir: &Path, build_id: &str) -> PathBuf {
+    let safe: String = build_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!("{safe}.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex_encode;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_verify_receipt() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let keys = [ReceiptKey {
+            key_id: "k1".to_string(),
+            public_key: hex_encode(pair.public_key().as_ref()),
+        }];
+
+        let mut receipt = UploadReceipt {
+            build_id: "b-123".to_string(),
+            sha256: "ab".repeat(32),
+            completed_at: "2026-10-16T12:00:00Z".to_string(),
+            key_id: "k1".to_string(),
+            signature: String::new(),
+        };
+        receipt.signature = hex_encode(pair.sign(receipt.signed_message().as_bytes()).as_ref());
+        receipt.verify(&keys).unwrap();
+
+        let mut tampered = receipt.clone();
+        tampered.sha256 = "cd".repeat(32);
+        assert!(tampered.verify(&keys).is_err());
+
+        let mut unknown_key = receipt;
+        unknown_key.key_id = "k2".to_string();
+        assert!(unknown_key.verify(&keys).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! What retention policies would delete from a project (`retention audit`)
+//!
+//! Policies are evaluated locally against the project's build list; nothing is deleted.
+
+use crate::error::{Error, Result};
+use crate::format::format_duration;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// Policies audited when none are given
+pub const DEFAULT_POLICIES: [&str; 4] =
+    ["keep-last=5", "keep-last=20", "max-age=30d", "max-age=90d"];
+
+/// Seconds in a day, the unit build ages are shown in
+const DAY: u64 = 24 * 60 * 60;
+
+/// A build as listed by the API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct BuildSummary {
+    pub build_id: String,
+    pub name: String,
+    pub platform: String,
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Stored size in bytes
+    pub size: u64,
+    /// Upload time (RFC 3339 timestamp)
+    pub created_at: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl BuildSummary {
+    /// Time since the build was uploaded, or `None` if its timestamp cannot be parsed
+    #[must_use]
+    pub fn age(&self, now: SystemTime) -> Option<Duration> {
+        let created = humantime::parse_rfc3339_weak(&self.created_at).ok()?;
+        Some(now.duration_since(created).unwrap_or_default())
+    }
+}
+
+/// A rule deciding which builds are deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionPolicy {
+    /// Keep the newest N builds of every branch and platform
+    KeepLast(usize),
+    /// Delete builds older than this
+    MaxAge(Duration),
+}
+
+impl FromStr for RetentionPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::ConfigError(format!(
+                "Invalid retention policy '{s}' (expected keep-last=N or max-age=DURATION, e.g. max-age=90d)"
+            ))
+        };
+        match s.trim().split_once('=').ok_or_else(invalid)? {
+            ("keep-last", n) => n
+                .trim()
+                .parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(Self::KeepLast)
+                .ok_or_else(invalid),
+            ("max-age", age) => humantime::parse_duration(age.trim())
+                .map(Self::MaxAge)
+                .map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for RetentionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeepLast(n) => write!(f, "keep-last={n}"),
+            Self::MaxAge(age) if age.as_secs().is_multiple_of(DAY) && !age.is_zero() => {
+                write!(f, "max-age={}d", age.as_secs() / DAY)
+            }
+            Self::MaxAge(age) => write!(f, "max-age={}", humantime::format_duration(*age)),
+        }
+    }
+}
+
+/// What one policy would delete
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyOutcome {
+    pub policy: String,
+    /// IDs of the builds the policy deletes, oldest first
+    pub deleted: Vec<String>,
+    pub deleted_bytes: u64,
+    pub kept_builds: usize,
+    pub kept_bytes: u64,
+}
+
+/// Builds of a project with the outcome of every audited policy
+#[derive(Serialize, Debug, Clone)]
+pub struct RetentionReport {
+    pub builds: Vec<BuildSummary>,
+    pub total_bytes: u64,
+    pub policies: Vec<PolicyOutcome>,
+}
+
+/// Evaluate `policies` against `builds`, never deleting builds tagged with one of `keep_tags`
+///
+/// Builds are listed newest first. Protected builds do not count towards `keep-last`, and
+/// builds whose upload time is unknown are never deleted by `max-age`.
+#[must_use]
+pub fn audit(
+    mut builds: Vec<BuildSummary>,
+    policies: &[RetentionPolicy],
+    keep_tags: &[String],
+    now: SystemTime,
+) -> RetentionReport {
+    builds.sort_by_key(|b| b.age(now).unwrap_or(Duration::MAX));
+
+    let policies = policies
+        .iter()
+        .map(|policy| evaluate(&builds, *policy, keep_tags, now))
+        .collect();
+    RetentionReport {
+        total_bytes: builds.iter().map(|b| b.size).sum(),
+        builds,
+        policies,
+    }
+}
+
+/// What `policy` deletes from `builds`, which are sorted newest first
+fn evaluate(
+    builds: &[BuildSummary],
+    policy: RetentionPolicy,
+    keep_tags: &[String],
+    now: SystemTime,
+) -> PolicyOutcome {
+    let mut kept_per_lineage: HashMap<(&str, Option<&str>), usize> = HashMap::new();
+    let mut outcome = PolicyOutcome {
+        policy: policy.to_string(),
+        deleted: Vec::new(),
+        deleted_bytes: 0,
+        kept_builds: 0,
+        kept_bytes: 0,
+    };
+
+    for build in builds {
+        let protected = build.tags.iter().any(|t| keep_tags.contains(t));
+        let delete = !protected
+            && match policy {
+                RetentionPolicy::KeepLast(n) => {
+                    let kept = kept_per_lineage
+                        .entry((build.platform.as_str(), build.branch.as_deref()))
+                        .or_default();
+                    *kept += 1;
+                    *kept > n
+                }
+                RetentionPolicy::MaxAge(max) => build.age(now).is_some_and(|age| age > max),
+            };
+
+        if delete {
+            outcome.deleted.push(build.build_id.clone());
+            outcome.deleted_bytes += build.size;
+        } else {
+            outcome.kept_builds += 1;
+            outcome.kept_bytes += build.size;
+        }
+    }
+
+    outcome.deleted.reverse();
+    outcome
+}
+
+/// Format a build age in whole days once it is at least a day old (`12d`), like
+/// `format_duration` otherwise
+#[must_use]
+pub fn format_age(age: Duration) -> String {
+    if age.as_secs() >= DAY {
+        format!("{}d", age.as_secs() / DAY)
+    } else {
+        format_duration(age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(id: &str, branch: &str, days_old: u64, tags: &[&str]) -> BuildSummary {
+        let created = SystemTime::UNIX_EPOCH + Duration::from_secs((100 - days_old) * DAY);
+        BuildSummary {
+            build_id: id.to_string(),
+            name: id.to_string(),
+            platform: "android".to_string(),
+            branch: Some(branch.to_string()),
+            size: 10,
+            created_at: humantime::format_rfc3339_seconds(created).to_string(),
+            tags: tags.iter().map(|t| (*t).to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_audit_policies() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100 * DAY);
+        let builds = vec![
+            build("m1", "main", 40, &["release"]),
+            build("m2", "main", 20, &[]),
+            build("m3", "main", 10, &[]),
+            build("m4", "main", 1, &[]),
+            build("f1", "feature", 50, &[]),
+        ];
+        let policies = ["keep-last=2", "max-age=30d"].map(|p| p.parse().unwrap());
+        let report = audit(builds, &policies, &["release".to_string()], now);
+
+        assert_eq!(report.builds[0].build_id, "m4");
+        assert_eq!(report.total_bytes, 50);
+
+        let keep_last = &report.policies[0];
+        assert_eq!(keep_last.policy, "keep-last=2");
+        assert_eq!(keep_last.deleted, ["m2"]);
+        assert_eq!(keep_last.kept_builds, 4);
+
+        let max_age = &report.policies[1];
+        assert_eq!(max_age.policy, "max-age=30d");
+        assert_eq!(max_age.deleted, ["f1"]);
+        assert_eq!(max_age.deleted_bytes, 10);
+
+        assert!("keep-last=0".parse::<RetentionPolicy>().is_err());
+        assert!("oldest".parse::<RetentionPolicy>().is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Lua upload scripts (`--script`) for renaming, retagging and skipping files
+//!
+//! A script defines a global `on_upload(upload)` function that is called once per file
+//! before anything is uploaded. It may change the `name`, `platform`, `description`,
+//! `tags` and `metadata` fields of the `upload` table (`file` is informational) and
+//! returns `false` to skip the file.
+
+use crate::error::{Error, Result};
+use mlua::{Function, Lua, Table, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Global function every upload script must define
+pub const HOOK_FUNCTION: &str = "on_upload";
+
+/// Settings of one file as seen and modified by the script
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptedUpload {
+    pub file: String,
+    pub name: String,
+    pub platform: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// A loaded upload script
+pub struct UploadScript {
+    lua: Lua,
+}
+
+impl UploadScript {
+    /// Load and run the script at `path`, which must define [`HOOK_FUNCTION`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, fails to run, or lacks the hook
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| Error::ScriptError(format!("Failed to read {}: {e}", path.display())))?;
+        Self::from_source(&source, &path.display().to_string())
+    }
+
+    /// Load a script from source; `name` is used in error messages
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script fails to run or does not define [`HOOK_FUNCTION`]
+    pub fn from_source(source: &str, name: &str) -> Result<Self> {
+        let lua = Lua::new();
+        lua.load(source)
+            .set_name(name)
+            .exec()
+            .map_err(script_error)?;
+        if !matches!(
+            lua.globals().get::<_, Value>(HOOK_FUNCTION),
+            Ok(Value::Function(_))
+        ) {
+            return Err(Error::ScriptError(format!(
+                "{name} does not define a global `{HOOK_FUNCTION}(upload)` function"
+            )));
+        }
+        Ok(Self { lua })
+    }
+
+    /// Run the hook for one file, applying its changes to `upload`
+    ///
+    /// Returns `false` if the script skipped the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hook raises an error or leaves a field with the wrong type
+    pub fn apply(&self, upload: &mut ScriptedUpload) -> Result<bool> {
+        let hook: Function = self
+            .lua
+            .globals()
+            .get(HOOK_FUNCTION)
+            .map_err(script_error)?;
+        let table = self.to_table(upload).map_err(script_error)?;
+
+        let result: Value = hook.call(table.clone()).map_err(|e| {
+            Error::ScriptError(format!("{HOOK_FUNCTION} failed for {}: {e}", upload.file))
+        })?;
+        if matches!(result, Value::Boolean(false)) {
+            return Ok(false);
+        }
+
+        read_table(&table, upload).map_err(|e| {
+            Error::ScriptError(format!(
+                "{HOOK_FUNCTION} returned invalid settings for {}: {e}",
+                upload.file
+            ))
+        })?;
+        Ok(true)
+    }
+
+    fn to_table(&self, upload: &ScriptedUpload) -> mlua::Result<Table<'_>> {
+        let table = self.lua.create_table()?;
+        table.set("file", upload.file.as_str())?;
+        table.set("name", upload.name.as_str())?;
+        table.set("platform", upload.platform.as_str())?;
+        table.set("description", upload.description.as_deref())?;
+        table.set(
+            "tags",
+            self.lua.create_sequence_from(upload.tags.iter().cloned())?,
+        )?;
+        table.set(
+            "metadata",
+            self.lua.create_table_from(upload.metadata.clone())?,
+        )?;
+        Ok(table)
+    }
+}
+
+fn read_table(table: &Table<'_>, upload: &mut ScriptedUpload) -> mlua::Result<()> {
+    upload.name = table.get("name")?;
+    upload.platform = table.get("platform")?;
+    upload.description = table.get("description")?;
+    upload.tags = table
+        .get::<_, Option<Vec<String>>>("tags")?
+        .unwrap_or_default();
+    upload.metadata = table
+        .get::<_, Option<BTreeMap<String, String>>>("metadata")?
+        .unwrap_or_default();
+    Ok(())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn script_error(e: mlua::Error) -> Error {
+    Error::ScriptError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_script() {
+        let script = UploadScript::from_source(
+            r#"
+            function on_upload(upload)
+                if upload.file:match("%.pdb$") then
+                    return false
+                end
+                upload.name = "nightly-" .. upload.name
+                table.insert(upload.tags, "studio:berlin")
+                upload.metadata.engine = "unreal"
+            end
+            "#,
+            "test.lua",
+        )
+        .unwrap();
+
+        let mut upload = ScriptedUpload {
+            file: "build/Game.exe".to_string(),
+            name: "Game".to_string(),
+            platform: "windows".to_string(),
+            tags: vec!["qa".to_string()],
+            ..ScriptedUpload::default()
+        };
+        assert!(script.apply(&mut upload).unwrap());
+        assert_eq!(upload.name, "nightly-Game");
+        assert_eq!(upload.tags, ["qa", "studio:berlin"]);
+        assert_eq!(upload.metadata["engine"], "unreal");
+
+        let mut symbols = ScriptedUpload {
+            file: "build/Game.pdb".to_string(),
+            ..ScriptedUpload::default()
+        };
+        assert!(!script.apply(&mut symbols).unwrap());
+        assert!(UploadScript::from_source("x = 1", "empty.lua").is_err());
+    }
+}
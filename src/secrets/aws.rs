@@ -0,0 +1,196 @@
+//! Reading the API token from AWS Secrets Manager
+
+use super::{AwsSecret, Fetched, REQUEST_TIMEOUT};
+use crate::api::client::blocking_http_client;
+use crate::config::TransportOptions;
+use crate::error::{Error, Result};
+use crate::sigv4::{Credentials, Request, amz_date, authorization};
+use crate::util::sha256_hex;
+use log::debug;
+use reqwest::blocking::Client;
+use serde_json::{Value, json};
+use std::time::{Duration, SystemTime};
+
+const SERVICE: &str = "secretsmanager";
+
+/// Container credentials endpoint of ECS and `CodeBuild`
+const CONTAINER_CREDENTIALS_HOST: &str = "http://169.254.170.2";
+
+/// EC2 instance metadata service
+const INSTANCE_METADATA_HOST: &str = "http://169.254.169.254";
+
+/// How long to wait for the instance metadata service, which is absent outside EC2
+const INSTANCE_METADATA_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Read `secret` with `GetSecretValue`
+pub(super) fn fetch(secret: &AwsSecret, transport: &TransportOptions) -> Result<Fetched> {
+    let region = secret
+        .region
+        .clone()
+        .or_else(|| arn_region(&secret.secret_id))
+        .or_else(|| std::env::var("AWS_REGION").ok())
+        .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
+        .ok_or_else(|| {
+            Error::ConfigError(
+                "No AWS region (set `region` in token_secret or AWS_REGION)".to_string(),
+            )
+        })?;
+    let http = blocking_http_client(transport, REQUEST_TIMEOUT, Vec::new())?;
+    let credentials = load_credentials(&http)?;
+
+    let host = format!("{SERVICE}.{region}.amazonaws.com");
+    let body = json!({ "SecretId": secret.secret_id }).to_string();
+    let amz_date = amz_date(SystemTime::now());
+    let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1".to_string()),
+        ("host", host.clone()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    headers.push(("x-amz-target", "secretsmanager.GetSecretValue".to_string()));
+    let authorization = authorization(
+        &credentials,
+        &region,
+        SERVICE,
+        &amz_date,
+        &Request {
+            method: "POST",
+            path: "/",
+            query: "",
+            headers: &headers,
+            payload_hash: &sha256_hex(body.as_bytes()),
+        },
+    );
+
+    let mut request = http
+        .post(format!("https://{host}/"))
+        .header("authorization", authorization)
+        .body(body);
+    for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+        request = request.header(*name, value);
+    }
+    let response = request.send()?;
+    let status = response.status();
+    let response: Value = response.json().unwrap_or_default();
+    if !status.is_success() {
+        return Err(Error::ConfigError(format!(
+            "AWS Secrets Manager returned {status}: {}",
+            response["message"]
+                .as_str()
+                .or_else(|| response["Message"].as_str())
+                .or_else(|| response["__type"].as_str())
+                .unwrap_or("no details")
+        )));
+    }
+
+    let secret_string = response["SecretString"].as_str().ok_or_else(|| {
+        Error::ConfigError(format!(
+            "AWS secret {} has no string value",
+            secret.secret_id
+        ))
+    })?;
+    let value = match &secret.field {
+        Some(field) => serde_json::from_str::<Value>(secret_string)
+            .ok()
+            .and_then(|fields| fields[field].as_str().map(str::to_string))
+            .ok_or_else(|| {
+                Error::ConfigError(format!(
+                    "AWS secret {} has no field '{field}'",
+                    secret.secret_id
+                ))
+            })?,
+        None => secret_string.trim().to_string(),
+    };
+    Ok(Fetched { value, lease: None })
+}
+
+/// Region of a secret given by ARN (`arn:aws:secretsmanager:<region>:...`)
+fn arn_region(secret_id: &str) -> Option<String> {
+    let mut parts = secret_id.strip_prefix("arn:")?.split(':');
+    parts
+        .nth(2)
+        .filter(|region| !region.is_empty())
+        .map(str::to_string)
+}
+
+/// Credentials from the environment, the container credentials endpoint or the instance
+/// profile, in that order
+fn load_credentials(http: &Client) -> Result<Credentials> {
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        std::env::var("AWS_ACCESS_KEY_ID"),
+        std::env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Ok(Credentials {
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        });
+    }
+
+    let container_uri = std::env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI")
+        .ok()
+        .or_else(|| {
+            std::env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI")
+                .ok()
+                .map(|uri| format!("{CONTAINER_CREDENTIALS_HOST}{uri}"))
+        });
+    if let Some(uri) = container_uri {
+        debug!("Reading AWS credentials from the container credentials endpoint");
+        let mut request = http.get(uri);
+        if let Ok(token) = std::env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+            request = request.header("authorization", token);
+        }
+        return Ok(request.send()?.error_for_status()?.json()?);
+    }
+
+    debug!("Reading AWS credentials from the instance profile");
+    instance_credentials(http).map_err(|e| {
+        Error::ConfigError(format!(
+            "No AWS credentials (set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, or run with an IAM role): {e}"
+        ))
+    })
+}
+
+/// Credentials of the instance profile through `IMDSv2`
+fn instance_credentials(http: &Client) -> Result<Credentials> {
+    let token = http
+        .put(format!("{INSTANCE_METADATA_HOST}/latest/api/token"))
+        .header("x-aws-ec2-metadata-token-ttl-seconds", "300")
+        .timeout(INSTANCE_METADATA_TIMEOUT)
+        .send()?
+        .error_for_status()?
+        .text()?;
+    let credentials_url =
+        format!("{INSTANCE_METADATA_HOST}/latest/meta-data/iam/security-credentials/");
+    let role = http
+        .get(&credentials_url)
+        .header("x-aws-ec2-metadata-token", &token)
+        .timeout(INSTANCE_METADATA_TIMEOUT)
+        .send()?
+        .error_for_status()?
+        .text()?;
+    let role = role.lines().next().unwrap_or_default().trim();
+    Ok(http
+        .get(format!("{credentials_url}{role}"))
+        .header("x-aws-ec2-metadata-token", &token)
+        .timeout(INSTANCE_METADATA_TIMEOUT)
+        .send()?
+        .error_for_status()?
+        .json()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arn_region() {
+        assert_eq!(
+            arn_region("arn:aws:secretsmanager:eu-west-1:123456789012:secret:ci/nunu-AbCdEf"),
+            Some("eu-west-1".to_string())
+        );
+        assert_eq!(arn_region("ci/nunu"), None);
+    }
+}
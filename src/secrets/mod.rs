@@ -0,0 +1,392 @@
+//! API tokens read from Vault or AWS Secrets Manager
+//!
+//! The config file's `token_secret` names the secret holding the token. Stores are reached
+//! with the proxy and TLS settings of the API client. With `cache_ttl`, fetched tokens are
+//! cached in the user cache directory, readable only by the user, until the secret's lease
+//! or `cache_ttl` runs out, so a pipeline running the CLI many times asks the store once.
+//! Each store is compiled in with its feature (`vault`, `aws-secrets-manager`).
+
+use crate::config::TransportOptions;
+use crate::error::{Error, Result};
+use crate::units::HumanDuration;
+use crate::util::{cache_dir, hex_encode, unix_now};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(feature = "aws-secrets-manager")]
+mod aws;
+#[cfg(feature = "vault")]
+mod vault;
+
+/// How long a fetched token is reused when `cache_ttl` is not set: not at all, as cached
+/// tokens are stored unencrypted
+pub const DEFAULT_CACHE_TTL: Duration = Duration::ZERO;
+
+/// How long one request to a secret store may take
+#[cfg(any(feature = "vault", feature = "aws-secrets-manager"))]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The secret holding the API token (`token_secret` in the config file)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TokenSecret {
+    #[serde(flatten)]
+    pub store: SecretStore,
+
+    /// How long the fetched token is reused, e.g. `1h`; fetched on every run when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<HumanDuration>,
+}
+
+/// Secret store and the secret to read from it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "backend", rename_all = "kebab-case")]
+pub enum SecretStore {
+    Vault(VaultSecret),
+    AwsSecretsManager(AwsSecret),
+}
+
+/// A field of a secret in Vault's KV secrets engine (version 1 or 2)
+///
+/// Vault is authenticated with `VAULT_TOKEN`, with `VAULT_ROLE_ID` and `VAULT_SECRET_ID`
+/// (`AppRole`), or with the token `vault login` saves in `~/.vault-token`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VaultSecret {
+    /// Vault server; `VAULT_ADDR` when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// API path of the secret, e.g. `secret/data/ci/nunu` for KV version 2
+    pub path: String,
+
+    #[serde(default = "default_field")]
+    pub field: String,
+
+    /// Vault Enterprise namespace; `VAULT_NAMESPACE` when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// Mount path of the `AppRole` auth method
+    #[serde(default = "default_approle_mount")]
+    pub approle_mount: String,
+}
+
+/// A secret in AWS Secrets Manager
+///
+/// AWS is authenticated with `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`, container
+/// credentials (ECS, `CodeBuild`) or the EC2 instance profile.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AwsSecret {
+    /// Name or ARN of the secret
+    pub secret_id: String,
+
+    /// Region of the secret; taken from the ARN, `AWS_REGION` or `AWS_DEFAULT_REGION` when
+    /// not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// Key of the token when the secret holds a JSON object; the whole secret otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+fn default_field() -> String {
+    "token".to_string()
+}
+
+fn default_approle_mount() -> String {
+    "approle".to_string()
+}
+
+impl SecretStore {
+    /// Identifies the secret in the token cache
+    fn cache_key(&self) -> String {
+        match self {
+            Self::Vault(secret) => format!(
+                "vault:{}:{}:{}#{}",
+                secret
+                    .address
+                    .clone()
+                    .or_else(|| std::env::var("VAULT_ADDR").ok())
+                    .unwrap_or_default(),
+                secret.namespace.as_deref().unwrap_or_default(),
+                secret.path,
+                secret.field
+            ),
+            Self::AwsSecretsManager(secret) => format!(
+                "aws:{}:{}#{}",
+                secret.region.as_deref().unwrap_or_default(),
+                secret.secret_id,
+                secret.field.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Read the secret, caching store logins if `cache` is set
+    #[cfg_attr(
+        not(all(feature = "vault", feature = "aws-secrets-manager")),
+        allow(unused_variables)
+    )]
+    fn fetch(&self, transport: &TransportOptions, cache: bool) -> Result<Fetched> {
+        match self {
+            #[cfg(feature = "vault")]
+            Self::Vault(secret) => vault::fetch(secret, transport, cache),
+            #[cfg(not(feature = "vault"))]
+            Self::Vault(_) => Err(self.not_compiled_in("vault")),
+            #[cfg(feature = "aws-secrets-manager")]
+            Self::AwsSecretsManager(secret) => aws::fetch(secret, transport),
+            #[cfg(not(feature = "aws-secrets-manager"))]
+            Self::AwsSecretsManager(_) => Err(self.not_compiled_in("aws-secrets-manager")),
+        }
+    }
+
+    #[cfg(not(all(feature = "vault", feature = "aws-secrets-manager")))]
+    fn not_compiled_in(&self, feature: &str) -> Error {
+        Error::ConfigError(format!(
+            "Reading the API token from {self} requires nunu-cli built with the `{feature}` feature"
+        ))
+    }
+}
+
+impl fmt::Display for SecretStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Vault(secret) => write!(f, "Vault secret {}", secret.path),
+            Self::AwsSecretsManager(secret) => {
+                write!(f, "AWS Secrets Manager secret {}", secret.secret_id)
+            }
+        }
+    }
+}
+
+/// A secret as read from its store
+struct Fetched {
+    value: String,
+    /// How long the store allows the value to be used, if it says
+    lease: Option<Duration>,
+}
+
+/// Fetch the API token from `secret` with the proxy and TLS settings of `transport`, or
+/// reuse the token fetched by an earlier run when caching is enabled
+///
+/// # Errors
+///
+/// Returns an error if the store is not compiled in, cannot be authenticated with or does
+/// not hold the secret
+pub fn fetch_token(secret: &TokenSecret, transport: &TransportOptions) -> Result<String> {
+    let configured_ttl = secret.cache_ttl.map(|t| t.0);
+    let cache = !cache_ttl(configured_ttl, None).is_zero();
+    let key = secret.store.cache_key();
+    if cache && let Some(cached) = read_cache(&key) {
+        debug!("Using the cached API token from {}", secret.store);
+        return Ok(cached.value);
+    }
+
+    debug!("Fetching the API token from {}", secret.store);
+    // reqwest's blocking client panics on a thread driving an async runtime
+    let fetched = std::thread::scope(|s| s.spawn(|| secret.store.fetch(transport, cache)).join())
+        .map_err(|_| Error::ConfigError(format!("Reading {} panicked", secret.store)))??;
+
+    let ttl = cache_ttl(configured_ttl, fetched.lease);
+    if !ttl.is_zero() {
+        write_cache(
+            &key,
+            &CacheEntry {
+                value: fetched.value.clone(),
+                expires_at: unix_now() + ttl.as_secs(),
+                lease: ttl.as_secs(),
+                renewable: false,
+            },
+        );
+    }
+    Ok(fetched.value)
+}
+
+/// How long a fetched secret is reused: `cache_ttl`, but never beyond the secret's lease
+fn cache_ttl(configured: Option<Duration>, lease: Option<Duration>) -> Duration {
+    let configured = configured.unwrap_or(DEFAULT_CACHE_TTL);
+    lease.map_or(configured, |lease| lease.min(configured))
+}
+
+/// A cached secret or store login token
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CacheEntry {
+    value: String,
+    /// Unix timestamp (seconds) after which the value is not used
+    expires_at: u64,
+    /// Seconds the value was valid for when cached
+    #[serde(default)]
+    lease: u64,
+    /// Whether the store can extend the lease
+    #[serde(default)]
+    renewable: bool,
+}
+
+impl CacheEntry {
+    /// Seconds until the value expires
+    #[cfg_attr(not(feature = "vault"), allow(dead_code))]
+    fn remaining(&self) -> u64 {
+        self.expires_at.saturating_sub(unix_now())
+    }
+}
+
+/// Read the unexpired cache entry for `key`
+fn read_cache(key: &str) -> Option<CacheEntry> {
+    read_cache_file(&cache_path(key))
+}
+
+/// Read the cache entry in `path`, removing it if it expired or other users can read it
+fn read_cache_file(path: &Path) -> Option<CacheEntry> {
+    if !is_private(path) {
+        debug!(
+            "Ignoring cached secret {} readable by other users",
+            path.display()
+        );
+        let _ = std::fs::remove_file(path);
+        return None;
+    }
+    let contents = std::fs::read_to_string(path).ok()?;
+    let entry: CacheEntry = serde_json::from_str(&contents).ok()?;
+    if entry.expires_at <= unix_now() {
+        let _ = std::fs::remove_file(path);
+        return None;
+    }
+    Some(entry)
+}
+
+/// Whether only the user can access `path` (always true where permissions are not checked)
+#[cfg(unix)]
+fn is_private(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    // No group or other permission bits
+    #[allow(clippy::verbose_bit_mask)]
+    std::fs::metadata(path).is_ok_and(|meta| meta.permissions().mode() & 0o077 == 0)
+}
+
+#[cfg(not(unix))]
+fn is_private(_path: &Path) -> bool {
+    true
+}
+
+/// Cache `entry` under `key` in a file only the user can read; failures are only logged
+fn write_cache(key: &str, entry: &CacheEntry) {
+    let path = cache_path(key);
+    if let Err(e) = try_write_cache(&path, entry) {
+        debug!("Failed to cache secret in {}: {e}", path.display());
+    }
+}
+
+fn try_write_cache(path: &Path, entry: &CacheEntry) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        create_private_dir(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    // The mode only applies to new files; tighten one created by an older version
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    serde_json::to_writer(file, entry)?;
+    Ok(())
+}
+
+/// Create `dir` and its missing parents, with `dir` accessible only by the user
+fn create_private_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(not(unix))]
+    std::fs::create_dir_all(dir)?;
+    Ok(())
+}
+
+fn cache_path(key: &str) -> PathBuf {
+    let digest = ring::digest::digest(&ring::digest::SHA256, key.as_bytes());
+    let name = hex_encode(&digest.as_ref()[..16]);
+    cache_dir().join("secrets").join(format!("{name}.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_secret_config() {
+        let secret: TokenSecret = serde_json::from_str(
+            r#"{"backend": "vault", "path": "secret/data/ci/nunu", "cache_ttl": "1h"}"#,
+        )
+        .unwrap();
+        let SecretStore::Vault(vault) = &secret.store else {
+            panic!("expected a Vault secret");
+        };
+        assert_eq!(vault.field, "token");
+        assert_eq!(vault.approle_mount, "approle");
+        assert_eq!(
+            secret.cache_ttl,
+            Some(HumanDuration(Duration::from_hours(1)))
+        );
+
+        let secret: TokenSecret = serde_json::from_str(
+            r#"{"backend": "aws-secrets-manager", "secret_id": "ci/nunu", "field": "token"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            secret.store.to_string(),
+            "AWS Secrets Manager secret ci/nunu"
+        );
+        assert!(serde_json::from_str::<TokenSecret>(r#"{"backend": "keychain"}"#).is_err());
+
+        let hour = Duration::from_hours(1);
+        assert!(cache_ttl(None, None).is_zero());
+        assert!(cache_ttl(None, Some(hour)).is_zero());
+        assert_eq!(cache_ttl(Some(hour), Some(hour * 2)), hour);
+        assert_eq!(
+            cache_ttl(Some(hour), Some(Duration::from_mins(5))),
+            Duration::from_mins(5)
+        );
+        assert!(cache_ttl(Some(Duration::ZERO), None).is_zero());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_cache_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = dir.path().join("cache").join("secrets");
+        let path = secrets.join("entry.json");
+        let entry = CacheEntry {
+            value: "nunu_abc123".to_string(),
+            expires_at: unix_now() + 60,
+            lease: 60,
+            renewable: false,
+        };
+        try_write_cache(&path, &entry).unwrap();
+        assert_eq!(mode(&secrets), 0o700);
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(read_cache_file(&path).unwrap().value, "nunu_abc123");
+
+        // A file others can read is not trusted, and is tightened when rewritten
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(read_cache_file(&path).is_none());
+        assert!(!path.exists());
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        try_write_cache(&path, &entry).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+}
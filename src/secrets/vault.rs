@@ -0,0 +1,199 @@
+//! Reading the API token from Vault
+
+use super::{CacheEntry, Fetched, REQUEST_TIMEOUT, VaultSecret, read_cache, write_cache};
+use crate::api::client::blocking_http_client;
+use crate::api::tls::read_ca_bundle;
+use crate::config::TransportOptions;
+use crate::error::{Error, Result};
+use crate::util::unix_now;
+use directories::BaseDirs;
+use log::debug;
+use reqwest::blocking::{Client, RequestBuilder};
+use serde_json::{Value, json};
+use std::path::Path;
+use std::time::Duration;
+
+/// Read `secret`, logging in to Vault first if needed
+///
+/// Vault's CA certificate is trusted from `VAULT_CACERT` in addition to the transport's
+/// CA bundle. With `cache`, `AppRole` logins are cached and reused.
+pub(super) fn fetch(
+    secret: &VaultSecret,
+    transport: &TransportOptions,
+    cache: bool,
+) -> Result<Fetched> {
+    let address = secret
+        .address
+        .clone()
+        .or_else(|| std::env::var("VAULT_ADDR").ok())
+        .ok_or_else(|| {
+            Error::ConfigError(
+                "No Vault address (set `address` in token_secret or VAULT_ADDR)".to_string(),
+            )
+        })?;
+    let vault_ca = match std::env::var_os("VAULT_CACERT") {
+        Some(path) => read_ca_bundle(Path::new(&path))?,
+        None => Vec::new(),
+    };
+    let vault = Vault {
+        http: blocking_http_client(transport, REQUEST_TIMEOUT, vault_ca)?,
+        cache,
+        address: address.trim_end_matches('/').to_string(),
+        namespace: secret
+            .namespace
+            .clone()
+            .or_else(|| std::env::var("VAULT_NAMESPACE").ok()),
+    };
+
+    let token = vault.client_token(&secret.approle_mount)?;
+    let path = secret.path.trim_start_matches('/');
+    let response = vault.send(vault.http.get(vault.url(path)), Some(&token))?;
+    let data = &response["data"];
+    // KV version 2 nests the fields under data.data, next to the version's metadata
+    let fields = if data["metadata"].is_object() {
+        &data["data"]
+    } else {
+        data
+    };
+    let value = fields[&secret.field].as_str().ok_or_else(|| {
+        Error::ConfigError(format!(
+            "Vault secret {path} has no field '{}'",
+            secret.field
+        ))
+    })?;
+
+    Ok(Fetched {
+        value: value.to_string(),
+        lease: response["lease_duration"]
+            .as_u64()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs),
+    })
+}
+
+struct Vault {
+    http: Client,
+    /// Whether `AppRole` logins are cached
+    cache: bool,
+    address: String,
+    namespace: Option<String>,
+}
+
+impl Vault {
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1/{path}", self.address)
+    }
+
+    /// Token to read secrets with: `VAULT_TOKEN`, an `AppRole` login or `~/.vault-token`
+    fn client_token(&self, approle_mount: &str) -> Result<String> {
+        if let Ok(token) = std::env::var("VAULT_TOKEN") {
+            return Ok(token);
+        }
+        if let (Ok(role_id), Ok(secret_id)) = (
+            std::env::var("VAULT_ROLE_ID"),
+            std::env::var("VAULT_SECRET_ID"),
+        ) {
+            return self.approle_token(approle_mount, &role_id, &secret_id);
+        }
+        BaseDirs::new()
+            .and_then(|dirs| std::fs::read_to_string(dirs.home_dir().join(".vault-token")).ok())
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| {
+                Error::ConfigError(
+                    "No Vault token (set VAULT_TOKEN, VAULT_ROLE_ID and VAULT_SECRET_ID, or run `vault login`)"
+                        .to_string(),
+                )
+            })
+    }
+
+    /// Log in with `AppRole`, reusing the token of an earlier login while it is valid if
+    /// caching is enabled
+    ///
+    /// Once less than half of its lease is left, a renewable token is renewed rather than
+    /// logging in again, which would use up a `secret_id` limited to a number of uses.
+    fn approle_token(&self, mount: &str, role_id: &str, secret_id: &str) -> Result<String> {
+        let key = format!("vault-login:{}:{mount}:{role_id}", self.address);
+        if self.cache
+            && let Some(cached) = read_cache(&key)
+        {
+            if cached.remaining() > cached.lease / 2 {
+                debug!("Using the cached Vault token");
+                return Ok(cached.value);
+            }
+            if cached.renewable {
+                let renewed = self
+                    .send(
+                        self.http.post(self.url("auth/token/renew-self")),
+                        Some(&cached.value),
+                    )
+                    .and_then(|response| login_entry(&response));
+                match renewed {
+                    Ok(entry) => {
+                        debug!("Renewed the Vault token for {}s", entry.lease);
+                        write_cache(&key, &entry);
+                        return Ok(entry.value);
+                    }
+                    Err(e) => debug!("Failed to renew the Vault token, logging in again: {e}"),
+                }
+            }
+        }
+
+        debug!("Logging in to Vault with AppRole");
+        let login = self.http.post(self.url(&format!("auth/{mount}/login")));
+        let response = self.send(
+            login.json(&json!({ "role_id": role_id, "secret_id": secret_id })),
+            None,
+        )?;
+        let entry = login_entry(&response)?;
+        if self.cache && entry.lease > 0 {
+            write_cache(&key, &entry);
+        }
+        Ok(entry.value)
+    }
+
+    fn send(&self, request: RequestBuilder, token: Option<&str>) -> Result<Value> {
+        let request = match &self.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        };
+        let request = match token {
+            Some(token) => request.header("X-Vault-Token", token),
+            None => request,
+        };
+
+        let response = request.send()?;
+        let status = response.status();
+        let body: Value = response.json().unwrap_or_default();
+        if !status.is_success() {
+            let errors: Vec<&str> = body["errors"]
+                .as_array()
+                .map(|errors| errors.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            return Err(Error::ConfigError(format!(
+                "Vault returned {status}{}",
+                if errors.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", errors.join("; "))
+                }
+            )));
+        }
+        Ok(body)
+    }
+}
+
+/// The token of a login or renewal response, ready to cache
+fn login_entry(response: &Value) -> Result<CacheEntry> {
+    let auth = &response["auth"];
+    let token = auth["client_token"]
+        .as_str()
+        .ok_or_else(|| Error::ConfigError("Vault returned no client token".to_string()))?;
+    let lease = auth["lease_duration"].as_u64().unwrap_or_default();
+    Ok(CacheEntry {
+        value: token.to_string(),
+        expires_at: unix_now() + lease,
+        lease,
+        renewable: auth["renewable"].as_bool().unwrap_or_default(),
+    })
+}
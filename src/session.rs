@@ -0,0 +1,179 @@
+//! Upload sessions for build machines without internet access
+//!
+//! `upload --prepare-only` records everything an upload needs (file sizes and hashes, and
+//! the upload requests with their metadata) in a session file signed with a shared key.
+//! `upload --from-session` on a connected machine checks the signature and the files
+//! against it, then uploads exactly what was prepared.
+
+use crate::api::client::UploadRequest;
+use crate::error::{Error, Result};
+use crate::util::{hex_decode, hex_encode};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Session file layout version, bumped on incompatible changes
+const SESSION_VERSION: u32 = 1;
+
+/// Everything prepared for an upload on an offline machine
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadSession {
+    pub version: u32,
+    /// When the session was prepared (RFC 3339 timestamp)
+    pub created_at: String,
+    /// Version of the CLI that prepared the session
+    pub cli_version: String,
+    /// Project the uploads were prepared for, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    pub files: Vec<SessionFile>,
+}
+
+/// One prepared upload
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionFile {
+    /// SHA-256 of the file as lowercase hex
+    pub sha256: String,
+    /// The request sent to start the upload, including the file's name and size
+    pub request: UploadRequest,
+}
+
+/// The session with an HMAC-SHA256 signature over its canonical JSON
+#[derive(Serialize, Deserialize)]
+struct SignedSession {
+    session: Value,
+    signature: String,
+}
+
+impl UploadSession {
+    #[must_use]
+    pub fn new(project_id: Option<String>, files: Vec<SessionFile>) -> Self {
+        Self {
+            version: SESSION_VERSION,
+            created_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            cli_version: env!("CARGO_PKG_VERSION").to_string(),
+            project_id,
+            files,
+        }
+    }
+
+    /// Sign the session with `key` and write it to `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written
+    pub fn save(&self, path: &Path, key: &str) -> Result<()> {
+        let session = serde_json::to_value(self)?;
+        let signed = SignedSession {
+            signature: sign(&session, key)?,
+            session,
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&signed)?)?;
+        Ok(())
+    }
+
+    /// Read a session from `path`, checking its signature with `key`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, was signed with another key or modified
+    /// after signing, or was prepared by an incompatible CLI version
+    pub fn load(path: &Path, key: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let signed: SignedSession = serde_json::from_str(&contents).map_err(|e| {
+            Error::SessionError(format!("{} is not a session file ({e})", path.display()))
+        })?;
+
+        let key = hmac_key(key)?;
+        let message = serde_json::to_vec(&signed.session)?;
+        let valid = hex_decode(&signed.signature)
+            .is_some_and(|signature| hmac::verify(&key, &message, &signature).is_ok());
+        if !valid {
+            return Err(Error::SessionError(format!(
+                "signature of {} does not match; it was modified or signed with a different key",
+                path.display()
+            )));
+        }
+
+        let session: Self = serde_json::from_value(signed.session)?;
+        if session.version != SESSION_VERSION {
+            return Err(Error::SessionError(format!(
+                "{} has version {}, but this CLI supports version {SESSION_VERSION}",
+                path.display(),
+                session.version
+            )));
+        }
+        Ok(session)
+    }
+
+    /// The prepared upload of a file with this size and hash
+    #[must_use]
+    pub fn find(&self, size: u64, sha256: &str) -> Option<&SessionFile> {
+        self.files
+            .iter()
+            .find(|f| f.request.file_size == size && f.sha256 == sha256)
+    }
+}
+
+/// HMAC-SHA256 of the session as lowercase hex
+///
+/// The signature covers the compact JSON of `session`, whose object keys `serde_json` keeps
+/// sorted, so it does not depend on how the file was formatted.
+fn sign(session: &Value, key: &str) -> Result<String> {
+    let tag = hmac::sign(&hmac_key(key)?, &serde_json::to_vec(session)?);
+    Ok(hex_encode(tag.as_ref()))
+}
+
+fn hmac_key(key: &str) -> Result<hmac::Key> {
+    if key.is_empty() {
+        return Err(Error::SessionError("the session key is empty".to_string()));
+    }
+    Ok(hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(file_name: &str, file_size: u64) -> UploadRequest {
+        serde_json::from_value(serde_json::json!({
+            "name": "Nightly",
+            "file_name": file_name,
+            "file_size": file_size,
+            "platform": "windows",
+            "multipart": false,
+            "tags": ["nightly"],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.session");
+        let session = UploadSession::new(
+            Some("p1".to_string()),
+            vec![SessionFile {
+                sha256: "ab".repeat(32),
+                request: request("Game.exe", 1024),
+            }],
+        );
+        session.save(&path, "secret").unwrap();
+
+        let loaded = UploadSession::load(&path, "secret").unwrap();
+        assert_eq!(loaded.project_id.as_deref(), Some("p1"));
+        let file = loaded.find(1024, &"ab".repeat(32)).unwrap();
+        assert_eq!(file.request.name, "Nightly");
+        assert!(loaded.find(1025, &"ab".repeat(32)).is_none());
+
+        assert!(UploadSession::load(&path, "other").is_err());
+
+        let tampered = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("Nightly", "Release");
+        std::fs::write(&path, tampered).unwrap();
+        assert!(UploadSession::load(&path, "secret").is_err());
+    }
+}
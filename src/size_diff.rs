@@ -0,0 +1,88 @@
+//! Size change of an uploaded build relative to the previous build on its branch and platform
+
+use crate::format::format_size;
+use serde::{Deserialize, Serialize};
+
+/// The build uploaded before another one on the same branch and platform
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct PreviousBuild {
+    pub build_id: String,
+    #[serde(default)]
+    pub build_number: Option<String>,
+    /// Stored size in bytes
+    pub size: u64,
+}
+
+/// Size of a build compared with the previous one, attached to its metadata
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SizeDiff {
+    pub previous_build_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_build_number: Option<String>,
+    pub previous_size: u64,
+    pub size: u64,
+    /// `size - previous_size` in bytes (negative if the build shrank)
+    pub delta: i64,
+}
+
+impl SizeDiff {
+    #[must_use]
+    pub fn new(previous: PreviousBuild, size: u64) -> Self {
+        let delta = i128::from(size) - i128::from(previous.size);
+        Self {
+            previous_build_id: previous.build_id,
+            previous_build_number: previous.build_number,
+            previous_size: previous.size,
+            size,
+            delta: i64::try_from(delta).unwrap_or(if delta < 0 { i64::MIN } else { i64::MAX }),
+        }
+    }
+
+    /// Summary such as `+34.0 MiB vs build 1523`, naming the build by number when known
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let previous = self
+            .previous_build_number
+            .as_deref()
+            .unwrap_or(&self.previous_build_id);
+        format!("{} vs build {previous}", format_size_delta(self.delta))
+    }
+}
+
+/// Format a signed byte count with binary units (`+34.0 MiB`, `-512 B`, `±0 B`)
+#[must_use]
+pub fn format_size_delta(delta: i64) -> String {
+    match delta.signum() {
+        0 => "±0 B".to_string(),
+        1 => format!("+{}", format_size(delta.unsigned_abs())),
+        _ => format!("-{}", format_size(delta.unsigned_abs())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_diff() {
+        let previous = PreviousBuild {
+            build_id: "b-1".to_string(),
+            build_number: Some("1523".to_string()),
+            size: 100 << 20,
+        };
+        let diff = SizeDiff::new(previous.clone(), 134 << 20);
+        assert_eq!(diff.delta, 34 << 20);
+        assert_eq!(diff.describe(), "+34.0 MiB vs build 1523");
+
+        let shrunk = SizeDiff::new(
+            PreviousBuild {
+                build_number: None,
+                ..previous
+            },
+            (100 << 20) - 512,
+        );
+        assert_eq!(shrunk.describe(), "-512 B vs build b-1");
+        assert_eq!(format_size_delta(0), "±0 B");
+    }
+}
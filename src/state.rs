@@ -0,0 +1,253 @@
+use crate::error::Result;
+use crate::util::{cache_dir, unix_now};
+use indicatif::ProgressBar;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How often a running upload refreshes its state file
+pub const STATE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A running upload whose state file hasn't been refreshed for this long is presumed dead
+pub const STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// Finished uploads' state files are kept this long so late watchers can see the result
+const KEEP_FINISHED: Duration = Duration::from_mins(10);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Snapshot of an upload in progress, shared with `nunu-cli uploads watch` through a state file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadState {
+    pub build_id: String,
+    pub name: String,
+    pub file_name: String,
+    pub pid: u32,
+    pub uploaded_bytes: u64,
+    pub total_bytes: u64,
+    pub status: UploadStatus,
+    /// Unix timestamp (seconds) of the upload start
+    pub started_at: u64,
+    /// Unix timestamp (seconds) of the last refresh
+    pub updated_at: u64,
+}
+
+impl UploadState {
+    /// Whether the upload is still marked running but its process stopped refreshing it
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        self.status == UploadStatus::Running
+            && unix_now().saturating_sub(self.updated_at) > STALE_AFTER.as_secs()
+    }
+}
+
+/// Directory holding the state files of uploads on this machine
+///
+/// Uses `NUNU_STATE_DIR` if set, otherwise the user cache directory.
+#[must_use]
+pub fn state_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("NUNU_STATE_DIR") {
+        return PathBuf::from(dir);
+    }
+    cache_dir().join("uploads")
+}
+
+/// Read the state of every upload on this machine, oldest first
+///
+/// State files of uploads that finished a while ago are removed along the way.
+///
+/// # Errors
+///
+/// Returns an error if the state directory exists but cannot be read
+pub fn list_uploads() -> Result<Vec<UploadState>> {
+    let dir = state_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let now = unix_now();
+    let mut uploads = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(state) = read_state(&path) else {
+            continue;
+        };
+
+        let expired = now.saturating_sub(state.updated_at) > KEEP_FINISHED.as_secs();
+        if expired && (state.status != UploadStatus::Running || state.is_stale()) {
+            debug!("Removing expired upload state {}", path.display());
+            let _ = std::fs::remove_file(&path);
+            continue;
+        }
+        uploads.push(state);
+    }
+
+    uploads.sort_by_key(|s| s.started_at);
+    Ok(uploads)
+}
+
+/// Read a single upload's state by build ID
+#[must_use]
+pub fn read_upload(build_id: &str) -> Option<UploadState> {
+    read_state(&state_path(build_id))
+}
+
+fn read_state(path: &Path) -> Option<UploadState> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents)
+        .inspect_err(|e| debug!("Ignoring unreadable state file {}: {e}", path.display()))
+        .ok()
+}
+
+fn state_path(build_id: &str) -> PathBuf {
+    let safe: String = build_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    state_dir().join(format!("{safe}.json"))
+}
+
+/// Background task that mirrors an upload's progress into its state file
+///
+/// Writing is best effort: failures are logged and never interrupt the upload.
+/// Dropping the writer without calling [`StateWriter::complete`] marks the upload failed.
+pub struct StateWriter {
+    handle: JoinHandle<()>,
+    path: PathBuf,
+    state: UploadState,
+    pb: ProgressBar,
+    finished: bool,
+}
+
+impl StateWriter {
+    /// Start publishing the progress tracked by `pb` for `build_id`
+    #[must_use]
+    pub fn spawn(build_id: &str, name: &str, file_name: &str, pb: ProgressBar) -> Self {
+        let now = unix_now();
+        let state = UploadState {
+            build_id: build_id.to_string(),
+            name: name.to_string(),
+            file_name: file_name.to_string(),
+            pid: std::process::id(),
+            uploaded_bytes: 0,
+            total_bytes: pb.length().unwrap_or(0),
+            status: UploadStatus::Running,
+            started_at: now,
+            updated_at: now,
+        };
+        let path = state_path(build_id);
+
+        let handle = {
+            let path = path.clone();
+            let mut state = state.clone();
+            let pb = pb.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(STATE_UPDATE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    refresh(&mut state, &pb);
+                    write_state(&path, &state);
+                }
+            })
+        };
+
+        Self {
+            handle,
+            path,
+            state,
+            pb,
+            finished: false,
+        }
+    }
+
+    /// Record that the upload completed successfully
+    pub fn complete(mut self) {
+        self.finish(UploadStatus::Completed);
+    }
+
+    fn finish(&mut self, status: UploadStatus) {
+        self.handle.abort();
+        self.finished = true;
+        refresh(&mut self.state, &self.pb);
+        self.state.status = status;
+        write_state(&self.path, &self.state);
+    }
+}
+
+impl Drop for StateWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish(UploadStatus::Failed);
+        }
+    }
+}
+
+fn refresh(state: &mut UploadState, pb: &ProgressBar) {
+    state.uploaded_bytes = pb.position();
+    state.total_bytes = pb.length().unwrap_or(state.total_bytes);
+    state.updated_at = unix_now();
+}
+
+/// Atomically replace the state file so readers never see a partial write
+fn write_state(path: &Path, state: &UploadState) {
+    let result = (|| -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        std::fs::write(&tmp, serde_json::to_vec(state)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        debug!("Failed to write upload state {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_state_staleness() {
+        let now = unix_now();
+        let mut state = UploadState {
+            build_id: "b1".to_string(),
+            name: "Nightly".to_string(),
+            file_name: "game.apk".to_string(),
+            pid: 1,
+            uploaded_bytes: 10,
+            total_bytes: 100,
+            status: UploadStatus::Running,
+            started_at: now - 120,
+            updated_at: now,
+        };
+        assert!(!state.is_stale());
+
+        state.updated_at = now - 60;
+        assert!(state.is_stale());
+
+        state.status = UploadStatus::Completed;
+        assert!(!state.is_stale());
+
+        assert!(state_path("../b/1").ends_with("___b_1.json"));
+    }
+}
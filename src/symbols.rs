@@ -0,0 +1,168 @@
+//! Discovery of debug symbol files uploaded for crash symbolication
+
+use crate::api::BuildPlatform;
+use crate::error::{Error, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File name of ProGuard/R8 mapping files written by the Android Gradle plugin
+const PROGUARD_MAPPING_FILE: &str = "mapping.txt";
+
+/// Kind of debug symbols
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolKind {
+    /// Apple `.dSYM` bundle (a directory, uploaded as a zip)
+    Dsym,
+    /// Windows program database
+    Pdb,
+    /// ProGuard/R8 `mapping.txt` for deobfuscating Android stack traces
+    Proguard,
+}
+
+impl SymbolKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dsym => "dsym",
+            Self::Pdb => "pdb",
+            Self::Proguard => "proguard",
+        }
+    }
+
+    /// Platform recorded for symbols of this kind when none is given
+    #[must_use]
+    pub fn default_platform(self) -> BuildPlatform {
+        match self {
+            Self::Dsym => BuildPlatform::IosNative,
+            Self::Pdb => BuildPlatform::Windows,
+            Self::Proguard => BuildPlatform::Android,
+        }
+    }
+
+    /// Classify `path` by its name: `*.dSYM` directories, `*.pdb` files and `mapping.txt`
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let has_extension = |ext: &str| {
+            path.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+        };
+        if path.is_dir() {
+            has_extension("dsym").then_some(Self::Dsym)
+        } else if has_extension("pdb") {
+            Some(Self::Pdb)
+        } else if path.file_name().is_some_and(|n| n == PROGUARD_MAPPING_FILE) {
+            Some(Self::Proguard)
+        } else {
+            None
+        }
+    }
+}
+
+/// Build the symbols belong to, sent with the upload request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SymbolsInfo {
+    pub kind: SymbolKind,
+    /// ID of the build the symbols were produced with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<String>,
+    /// Version of the builds the symbols apply to, when the build ID is not known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_version: Option<String>,
+}
+
+/// A symbol file or bundle found on disk
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SymbolFile {
+    pub path: PathBuf,
+    pub kind: SymbolKind,
+}
+
+/// Find debug symbols in `paths`
+///
+/// Directories are searched recursively; `.dSYM` bundles are returned whole rather than
+/// searched. Paths given explicitly must be symbol files themselves. Symlinked
+/// directories are not followed.
+///
+/// # Errors
+///
+/// Returns an error if a path does not exist, an explicit file is not a symbol file, or a
+/// directory cannot be read
+pub fn find_symbols(paths: &[PathBuf]) -> Result<Vec<SymbolFile>> {
+    let mut found = Vec::new();
+    for path in paths {
+        if !path.exists() {
+            return Err(Error::FileError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Symbol path not found: {}", path.display()),
+            )));
+        }
+        if let Some(kind) = SymbolKind::from_path(path) {
+            found.push(SymbolFile {
+                path: path.clone(),
+                kind,
+            });
+        } else if path.is_dir() {
+            search_dir(path, &mut found)?;
+        } else {
+            return Err(Error::FileError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is not a dSYM bundle, PDB or ProGuard mapping file",
+                    path.display()
+                ),
+            )));
+        }
+    }
+
+    found.sort();
+    found.dedup();
+    Ok(found)
+}
+
+fn search_dir(dir: &Path, found: &mut Vec<SymbolFile>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if let Some(kind) = SymbolKind::from_path(&path) {
+            debug!("Found {} symbols: {}", kind.as_str(), path.display());
+            found.push(SymbolFile { path, kind });
+        } else if entry.file_type()?.is_dir() {
+            search_dir(&path, found)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find_symbols() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let dwarf = root.join("ios/Game.app.dSYM/Contents/Resources/DWARF");
+        fs::create_dir_all(&dwarf).unwrap();
+        fs::write(dwarf.join("Game"), b"dwarf").unwrap();
+        fs::create_dir_all(root.join("win")).unwrap();
+        fs::write(root.join("win/Game.pdb"), b"pdb").unwrap();
+        fs::write(root.join("win/Game.exe"), b"exe").unwrap();
+        let mapping = root.join("android/outputs/mapping/release");
+        fs::create_dir_all(&mapping).unwrap();
+        fs::write(mapping.join("mapping.txt"), b"a -> b:").unwrap();
+
+        let found = find_symbols(&[root.to_path_buf(), root.join("win/Game.pdb")]).unwrap();
+        let kinds: Vec<_> = found.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            [SymbolKind::Proguard, SymbolKind::Dsym, SymbolKind::Pdb]
+        );
+        assert!(found[1].path.ends_with("Game.app.dSYM"));
+
+        assert!(find_symbols(&[root.join("win/Game.exe")]).is_err());
+    }
+}
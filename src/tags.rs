@@ -0,0 +1,169 @@
+use crate::api::client::BuildDetails;
+
+/// Maximum tag length accepted by the API, in bytes
+pub const MAX_TAG_LEN: usize = 50;
+
+/// Derive tags from collected build metadata (`--auto-tags`)
+///
+/// Produces tags such as `branch:main`, `sha:abcdef1`, `ci:github-actions`, `pr:1234`
+/// and `nightly` for runs started by a CI schedule, sanitized with [`sanitize_tag`].
+#[must_use]
+pub fn derive_auto_tags(details: &BuildDetails) -> Vec<String> {
+    let mut tags = Vec::new();
+
+    if let Some(ref vcs) = details.vcs {
+        if let Some(ref branch) = vcs.branch {
+            tags.push(format!("branch:{branch}"));
+        }
+        tags.push(format!("sha:{}", vcs.commit.short_hash));
+        if let Some(ref pr) = vcs.pr {
+            tags.push(format!("pr:{}", pr.number));
+        }
+    }
+
+    if let Some(ref ci) = details.ci {
+        tags.push(format!("ci:{}", ci.system));
+        if ci.is_scheduled() {
+            tags.push("nightly".to_string());
+        }
+    }
+
+    tags.iter().filter_map(|tag| sanitize_tag(tag)).collect()
+}
+
+/// Tag identifying the build's platform (`platform:android`)
+#[must_use]
+pub fn platform_tag(platform: &str) -> String {
+    format!("platform:{platform}")
+}
+
+/// Make a derived value usable as a tag
+///
+/// Whitespace and control characters become `-` and the result is truncated to
+/// [`MAX_TAG_LEN`] bytes on a character boundary. Returns `None` if nothing is left.
+#[must_use]
+pub fn sanitize_tag(tag: &str) -> Option<String> {
+    let mut sanitized = String::with_capacity(tag.len().min(MAX_TAG_LEN));
+    for c in tag.trim().chars() {
+        let c = if c.is_whitespace() || c.is_control() {
+            '-'
+        } else {
+            c
+        };
+        if sanitized.len() + c.len_utf8() > MAX_TAG_LEN {
+            break;
+        }
+        sanitized.push(c);
+    }
+
+    (!sanitized.is_empty()).then_some(sanitized)
+}
+
+/// Append `extra` tags to a tag list, skipping duplicates
+#[must_use]
+pub fn merge_tags(tags: Option<Vec<String>>, extra: &[String]) -> Option<Vec<String>> {
+    if extra.is_empty() {
+        return tags;
+    }
+
+    let mut merged = tags.unwrap_or_default();
+    for tag in extra {
+        if !merged.contains(tag) {
+            merged.push(tag.clone());
+        }
+    }
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ci_metadata::CiMetadata;
+    use crate::metadata::{CommitInfo, PullRequestInfo, VcsMetadata};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_derive_auto_tags() {
+        let details = BuildDetails {
+            vcs: Some(VcsMetadata {
+                vcs_type: "git".to_string(),
+                provider: None,
+                repository_url: None,
+                commit: CommitInfo {
+                    hash: "abcdef1234567890".to_string(),
+                    short_hash: "abcdef1".to_string(),
+                    message: None,
+                    author: None,
+                    timestamp: None,
+                },
+                branch: Some("main".to_string()),
+                tag: None,
+                pr: Some(PullRequestInfo {
+                    number: 1234,
+                    title: None,
+                    url: None,
+                    source_branch: None,
+                    target_branch: None,
+                }),
+            }),
+            ci: Some(CiMetadata {
+                system: "github-actions".to_string(),
+                build_number: None,
+                job_name: None,
+                run_id: None,
+                run_url: None,
+                triggered_by: None,
+                agent: None,
+                event: Some("schedule".to_string()),
+            }),
+            upload: None,
+            custom: BTreeMap::new(),
+            artifact: None,
+            size_budget: None,
+            collection_warnings: Vec::new(),
+        };
+
+        let tags = derive_auto_tags(&details);
+        assert_eq!(
+            tags,
+            [
+                "branch:main",
+                "sha:abcdef1",
+                "pr:1234",
+                "ci:github-actions",
+                "nightly"
+            ]
+        );
+
+        let merged = merge_tags(
+            Some(vec!["release".to_string(), "nightly".to_string()]),
+            &tags,
+        );
+        assert_eq!(
+            merged.unwrap(),
+            [
+                "release",
+                "nightly",
+                "branch:main",
+                "sha:abcdef1",
+                "pr:1234",
+                "ci:github-actions"
+            ]
+        );
+        assert!(merge_tags(None, &[]).is_none());
+    }
+
+    #[test]
+    fn test_sanitize_tag() {
+        assert_eq!(
+            sanitize_tag("branch:feature/new menu").as_deref(),
+            Some("branch:feature/new-menu")
+        );
+        assert_eq!(sanitize_tag("  "), None);
+
+        let long = format!("branch:{}", "ä".repeat(40));
+        let sanitized = sanitize_tag(&long).unwrap();
+        assert!(sanitized.len() <= MAX_TAG_LEN);
+        assert!(long.starts_with(&sanitized));
+    }
+}
@@ -0,0 +1,117 @@
+//! TeamCity service messages (`--teamcity`), which TeamCity picks up from the build log
+//! to show upload progress and results in its UI
+
+use indicatif::ProgressBar;
+use std::fmt::Write;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How often upload progress is written to the build log
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Build parameter set to the build ID of the first uploaded file
+pub const BUILD_ID_PARAMETER: &str = "nunu.build_id";
+
+/// Build parameter set to the comma-separated build IDs of all uploaded files
+pub const BUILD_IDS_PARAMETER: &str = "nunu.build_ids";
+
+/// Escape a value for use in a service message
+#[must_use]
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '|' => out.push_str("||"),
+            '\'' => out.push_str("|'"),
+            '\n' => out.push_str("|n"),
+            '\r' => out.push_str("|r"),
+            '[' => out.push_str("|["),
+            ']' => out.push_str("|]"),
+            '\u{0085}' => out.push_str("|x"),
+            '\u{2028}' => out.push_str("|l"),
+            '\u{2029}' => out.push_str("|p"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Format a service message with named attributes, e.g. `##teamcity[message text='hi']`
+#[must_use]
+pub fn service_message(name: &str, attributes: &[(&str, &str)]) -> String {
+    let mut message = format!("##teamcity[{name}");
+    for (key, value) in attributes {
+        let _ = write!(message, " {key}='{}'", escape(value));
+    }
+    message.push(']');
+    message
+}
+
+/// Write a service message to stdout, where TeamCity reads them
+pub fn emit(name: &str, attributes: &[(&str, &str)]) {
+    println!("{}", service_message(name, attributes));
+}
+
+/// Write a single-value service message, e.g. `##teamcity[progressStart 'Uploading']`
+pub fn emit_value(name: &str, value: &str) {
+    println!("##teamcity[{name} '{}']", escape(value));
+}
+
+/// A progress block in the build log, updated periodically from a progress bar
+///
+/// The block is closed when the progress is dropped.
+pub struct TeamCityProgress {
+    label: String,
+    handle: JoinHandle<()>,
+}
+
+impl TeamCityProgress {
+    /// Open a progress block named `label` and start reporting the progress of `pb`
+    #[must_use]
+    pub fn start(label: &str, pb: ProgressBar) -> Self {
+        emit_value("progressStart", label);
+        let message = label.to_string();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let total = pb.length().unwrap_or(0);
+                let percent = (pb.position().min(total) * 100)
+                    .checked_div(total)
+                    .unwrap_or(0);
+                emit_value("progressMessage", &format!("{message}: {percent}%"));
+            }
+        });
+        Self {
+            label: label.to_string(),
+            handle,
+        }
+    }
+}
+
+impl Drop for TeamCityProgress {
+    fn drop(&mut self) {
+        self.handle.abort();
+        emit_value("progressFinish", &self.label);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_message() {
+        assert_eq!(
+            service_message(
+                "message",
+                &[
+                    ("text", "Uploaded [Game]\n'ok' | done"),
+                    ("status", "NORMAL")
+                ]
+            ),
+            "##teamcity[message text='Uploaded |[Game|]|n|'ok|' || done' status='NORMAL']"
+        );
+    }
+}
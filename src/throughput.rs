@@ -0,0 +1,323 @@
+//! Upload throughput history per network, for upfront estimates and slow-upload warnings
+//!
+//! After each batch of uploads the CLI records its throughput in `throughput.json` in the
+//! user cache directory, keyed by a fingerprint of the network: the API host and the prefix
+//! of the local address used to reach it (`/24` for IPv4, `/64` for IPv6), so the office
+//! and a home connection keep separate histories. Once a network has a few samples, their
+//! median is the typical throughput used to estimate how long the next batch takes and to
+//! warn when an upload runs far slower.
+
+use crate::error::Result;
+use crate::format::format_rate;
+use crate::util::{cache_dir, hex_encode};
+use indicatif::ProgressBar;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// Samples kept per network, newest last
+const MAX_SAMPLES: usize = 20;
+
+/// Networks kept in the history; the least recently used are dropped
+const MAX_NETWORKS: usize = 32;
+
+/// Samples a network needs before its typical throughput is trusted
+const MIN_SAMPLES: usize = 3;
+
+/// Batches smaller or quicker than this are dominated by API round trips and not recorded
+const MIN_SAMPLE_BYTES: u64 = 8 * 1024 * 1024;
+const MIN_SAMPLE_DURATION: Duration = Duration::from_secs(2);
+
+/// Estimates shorter than this are not worth printing
+pub const MIN_ESTIMATE: Duration = Duration::from_secs(10);
+
+/// How long finding the local address of the network may take
+const FINGERPRINT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Throughput below the typical one divided by this is reported as slow
+const SLOW_FACTOR: u64 = 3;
+
+/// Window over which the current throughput is measured, and how often it is checked
+const SLOW_WINDOW: Duration = Duration::from_secs(30);
+const SLOW_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Throughput of one batch of uploads
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub bytes_per_second: u64,
+    /// Unix timestamp (seconds) of the end of the batch
+    pub recorded_at: u64,
+}
+
+/// Recorded throughput samples by network fingerprint
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    #[serde(default)]
+    networks: BTreeMap<String, Vec<Sample>>,
+}
+
+impl History {
+    /// Read the history file, or start an empty history if it is missing or unreadable
+    #[must_use]
+    pub fn load() -> Self {
+        let path = history_path();
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&contents)
+            .inspect_err(|e| debug!("Ignoring unreadable {}: {e}", path.display()))
+            .unwrap_or_default()
+    }
+
+    /// Write the history file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be created or the file written
+    pub fn save(&self) -> Result<()> {
+        let path = history_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string(self)?)?;
+        debug!("Stored throughput history {}", path.display());
+        Ok(())
+    }
+
+    /// Median throughput of `network` in bytes per second, once it has enough samples
+    #[must_use]
+    pub fn typical(&self, network: &str) -> Option<u64> {
+        let samples = self.networks.get(network)?;
+        if samples.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mut rates: Vec<u64> = samples.iter().map(|s| s.bytes_per_second).collect();
+        rates.sort_unstable();
+        Some(rates[rates.len() / 2])
+    }
+
+    /// Record a batch of `bytes` uploaded in `elapsed` on `network`, unless it is too small
+    /// to say much about the network
+    ///
+    /// Returns whether the sample was recorded.
+    pub fn record(&mut self, network: &str, bytes: u64, elapsed: Duration, now: u64) -> bool {
+        if bytes < MIN_SAMPLE_BYTES || elapsed < MIN_SAMPLE_DURATION {
+            return false;
+        }
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let bytes_per_second = (bytes as f64 / elapsed.as_secs_f64()) as u64;
+        let samples = self.networks.entry(network.to_string()).or_default();
+        samples.push(Sample {
+            bytes_per_second,
+            recorded_at: now,
+        });
+        if samples.len() > MAX_SAMPLES {
+            samples.drain(..samples.len() - MAX_SAMPLES);
+        }
+
+        while self.networks.len() > MAX_NETWORKS {
+            let oldest = self
+                .networks
+                .iter()
+                .min_by_key(|(_, samples)| samples.last().map_or(0, |s| s.recorded_at))
+                .map(|(network, _)| network.clone());
+            if let Some(oldest) = oldest {
+                self.networks.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// How long `bytes` take at `bytes_per_second`
+#[must_use]
+pub fn estimate(bytes: u64, bytes_per_second: u64) -> Duration {
+    if bytes_per_second == 0 {
+        return Duration::MAX;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    Duration::from_secs_f64(bytes as f64 / bytes_per_second as f64)
+}
+
+/// Fingerprint of the network used to reach `api_url`, or `None` if the host cannot be
+/// resolved
+///
+/// The local address is found by connecting a UDP socket, which sends nothing.
+pub async fn network_fingerprint(api_url: &str) -> Option<String> {
+    let url = Url::parse(api_url).ok()?;
+    let host = url.host_str()?.to_string();
+    let port = url.port_or_known_default()?;
+    let local = async {
+        let remote = tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .ok()?
+            .next()?;
+        let bind = if remote.is_ipv6() {
+            "[::]:0"
+        } else {
+            "0.0.0.0:0"
+        };
+        let socket = tokio::net::UdpSocket::bind(bind).await.ok()?;
+        socket.connect(remote).await.ok()?;
+        Some(socket.local_addr().ok()?.ip())
+    };
+    let local = tokio::time::timeout(FINGERPRINT_TIMEOUT, local)
+        .await
+        .ok()
+        .flatten()
+        .inspect(|ip| debug!("Reaching {host} from {ip}"))?;
+    Some(fingerprint(&host, local))
+}
+
+/// Fingerprint of the network reaching `host` from the `local` address
+fn fingerprint(host: &str, local: IpAddr) -> String {
+    let prefix = match local {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{a}.{b}.{c}.0/24")
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, d, ..] = ip.segments();
+            format!("{a:x}:{b:x}:{c:x}:{d:x}::/64")
+        }
+    };
+    let digest = ring::digest::digest(
+        &ring::digest::SHA256,
+        format!("{}\n{prefix}", host.to_ascii_lowercase()).as_bytes(),
+    );
+    hex_encode(&digest.as_ref()[..16])
+}
+
+/// Background task that warns once when the combined throughput of the tracked progress
+/// bars falls far below the typical throughput
+pub struct SlowUploadMonitor {
+    bars: Arc<Mutex<Vec<ProgressBar>>>,
+    handle: JoinHandle<()>,
+}
+
+impl SlowUploadMonitor {
+    /// Start comparing throughput with `typical` bytes per second
+    #[must_use]
+    pub fn start(typical: u64) -> Self {
+        let bars: Arc<Mutex<Vec<ProgressBar>>> = Arc::new(Mutex::new(Vec::new()));
+        let handle = tokio::spawn({
+            let bars = bars.clone();
+            async move {
+                let mut window: VecDeque<(Instant, u64)> = VecDeque::new();
+                let mut interval = tokio::time::interval(SLOW_CHECK_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let now = Instant::now();
+                    let position = bars
+                        .lock()
+                        .map_or(0, |bars| bars.iter().map(ProgressBar::position).sum());
+                    window.push_back((now, position));
+                    while window
+                        .front()
+                        .is_some_and(|(at, _)| now.duration_since(*at) > SLOW_WINDOW)
+                    {
+                        window.pop_front();
+                    }
+                    let Some((since, from)) = window.front().copied() else {
+                        continue;
+                    };
+                    let elapsed = now.duration_since(since);
+                    // Only while bytes are moving: hashing and stalls are not slow uploads
+                    let moved = position.saturating_sub(from);
+                    if elapsed + SLOW_CHECK_INTERVAL < SLOW_WINDOW || moved == 0 {
+                        continue;
+                    }
+                    if estimate(moved, typical / SLOW_FACTOR) > elapsed {
+                        warn!(
+                            "Uploading at {}, far below the typical {} on this network",
+                            format_rate(moved, elapsed),
+                            format_rate(typical, Duration::from_secs(1))
+                        );
+                        break;
+                    }
+                }
+            }
+        });
+        Self { bars, handle }
+    }
+
+    /// Include the progress of `pb` in the throughput
+    pub fn track(&self, pb: ProgressBar) {
+        if let Ok(mut bars) = self.bars.lock() {
+            bars.push(pb);
+        }
+    }
+}
+
+impl Drop for SlowUploadMonitor {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+fn history_path() -> PathBuf {
+    cache_dir().join("throughput.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_history() {
+        const MIB: u64 = 1024 * 1024;
+        let mut history = History::default();
+        assert!(!history.record("home", MIB, Duration::from_secs(10), 1));
+        assert!(!history.record("home", 100 * MIB, Duration::from_secs(1), 1));
+        for (i, secs) in [10, 5, 20].into_iter().enumerate() {
+            assert_eq!(history.typical("home"), None);
+            history.record("home", 100 * MIB, Duration::from_secs(secs), i as u64);
+        }
+        assert_eq!(history.typical("home"), Some(10 * MIB));
+        assert_eq!(history.typical("office"), None);
+
+        for i in 0..30 {
+            history.record("home", 100 * MIB, Duration::from_secs(50), 10 + i);
+        }
+        assert_eq!(history.networks["home"].len(), MAX_SAMPLES);
+        assert_eq!(history.typical("home"), Some(2 * MIB));
+
+        for i in 0..MAX_NETWORKS as u64 {
+            history.record(
+                &format!("net{i}"),
+                100 * MIB,
+                Duration::from_secs(5),
+                100 + i,
+            );
+        }
+        assert_eq!(history.networks.len(), MAX_NETWORKS);
+        assert!(!history.networks.contains_key("home"));
+
+        assert_eq!(estimate(120 * MIB, 2 * MIB), Duration::from_mins(1));
+
+        let office = fingerprint("API.nunu.ai", "10.1.2.3".parse().unwrap());
+        assert_eq!(
+            office,
+            fingerprint("api.nunu.ai", "10.1.2.200".parse().unwrap())
+        );
+        assert_ne!(
+            office,
+            fingerprint("api.nunu.ai", "10.1.3.3".parse().unwrap())
+        );
+        assert_eq!(
+            fingerprint("api.nunu.ai", "2001:db8:1:2::5".parse().unwrap()),
+            fingerprint("api.nunu.ai", "2001:db8:1:2:abcd::9".parse().unwrap())
+        );
+        assert_eq!(office.len(), 32);
+    }
+}
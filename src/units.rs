@@ -0,0 +1,204 @@
+use crate::error::{Error, Result};
+use crate::format::format_size;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Parse a human-friendly duration such as `90m`, `1h30m`, `45s` or `2w`
+///
+/// A bare number is interpreted in `bare_unit` (e.g. minutes for `--upload-timeout`).
+///
+/// # Errors
+///
+/// Returns an error if the value is neither a number nor a valid duration
+pub fn parse_duration(value: &str, bare_unit: Duration) -> Result<Duration> {
+    let value = value.trim();
+    if let Ok(count) = value.parse::<u32>() {
+        return Ok(bare_unit * count);
+    }
+    humantime::parse_duration(value).map_err(|e| {
+        Error::ConfigError(format!(
+            "Invalid duration '{value}': {e} (examples: 90s, 15m, 2h, 1h30m, 2w)"
+        ))
+    })
+}
+
+/// Parse a byte size such as `512`, `25MiB`, `1.5GB` or `100k`
+///
+/// Decimal units (`k`, `MB`, ...) are powers of 1000 and binary units (`KiB`, `MiB`, ...)
+/// powers of 1024. Units are case-insensitive; a bare number is bytes.
+///
+/// # Errors
+///
+/// Returns an error if the number or unit is invalid
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let invalid = || {
+        Error::ConfigError(format!(
+            "Invalid size '{value}' (examples: 512, 100k, 25MiB, 1.5GB)"
+        ))
+    };
+
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        "t" | "tb" => 1_000_000_000_000,
+        "tib" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let bytes = (number * multiplier as f64).round() as u64;
+    Ok(bytes)
+}
+
+/// Number of bytes, parsed from `25MiB`-style strings (CLI) or numbers/strings (config files)
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_size(s).map(Self)
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_size(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match NumberOrString::deserialize(deserializer)? {
+            NumberOrString::Number(bytes) => Ok(Self(bytes)),
+            NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Transfer rate in bytes per second, parsed from `25MiB/s`-style strings (the `/s` is optional)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteRate(pub u64);
+
+impl FromStr for ByteRate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let size = s
+            .strip_suffix("/s")
+            .or_else(|| s.strip_suffix("ps"))
+            .unwrap_or(s);
+        parse_size(size).map(Self)
+    }
+}
+
+impl fmt::Display for ByteRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/s", format_size(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteRate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match NumberOrString::deserialize(deserializer)? {
+            NumberOrString::Number(bytes) => Ok(Self(bytes)),
+            NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Duration parsed from `90m`/`2w`-style strings; bare numbers are seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl FromStr for HumanDuration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_duration(s, Duration::from_secs(1)).map(Self)
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", humantime::format_duration(self.0))
+    }
+}
+
+impl Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match NumberOrString::deserialize(deserializer)? {
+            NumberOrString::Number(secs) => Ok(Self(Duration::from_secs(secs))),
+            NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Config files may give units as plain numbers or as strings with a unit
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(u64),
+    String(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_units() {
+        let minute = Duration::from_mins(1);
+        assert_eq!(parse_duration("90", minute).unwrap(), minute * 90);
+        assert_eq!(parse_duration("90m", minute).unwrap(), minute * 90);
+        assert_eq!(parse_duration("1h30m", minute).unwrap(), minute * 90);
+        assert_eq!(
+            parse_duration("2w", minute).unwrap(),
+            Duration::from_hours(14 * 24)
+        );
+        assert!(parse_duration("soon", minute).is_err());
+
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("100k").unwrap(), 100_000);
+        assert_eq!(parse_size("25MiB").unwrap(), 25 * 1024 * 1024);
+        assert_eq!(parse_size("1.5GB").unwrap(), 1_500_000_000);
+        assert!(parse_size("12 parsecs").is_err());
+
+        assert_eq!(
+            "25MiB/s".parse::<ByteRate>().unwrap(),
+            ByteRate(25 * 1024 * 1024)
+        );
+        assert_eq!(ByteRate(25 * 1024 * 1024).to_string(), "25.0 MiB/s");
+
+        let size: ByteSize = serde_json::from_str("\"1KiB\"").unwrap();
+        assert_eq!(size, ByteSize(1024));
+        let timeout: HumanDuration = serde_json::from_str("30").unwrap();
+        assert_eq!(timeout, HumanDuration(Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::api::Client;
+use futures::future::join_all;
+use log::debug;
+use std::time::Duration;
+
+/// How long aborting in-flight uploads may take in total, so an unresponsive API cannot
+/// keep an interrupted process alive past CI grace periods
+pub const ABORT_DEADLINE: Duration = Duration::from_secs(10);
+
+/// An upload that has been initiated but not completed, as reported through
+/// `UploadOptions::on_upload_initiated`
+#[derive(Debug, Clone)]
+pub struct InFlightUpload {
+    pub build_id: String,
+    pub upload_id: Option<String>,
+    pub object_key: String,
+}
+
+/// Outcome of [`abort_uploads`], keyed by the labels passed in (e.g. file paths)
+#[derive(Debug, Default)]
+pub struct AbortReport {
+    pub confirmed: Vec<String>,
+    /// Uploads the server did not confirm aborting, with the reason
+    pub unconfirmed: Vec<(String, String)>,
+}
+
+/// Abort `uploads` concurrently, giving up on any abort still pending after `deadline`
+///
+/// Aborting is best effort: failures are collected in the report rather than returned.
+pub async fn abort_uploads(
+    client: &Client,
+    uploads: Vec<(String, InFlightUpload)>,
+    deadline: Duration,
+) -> AbortReport {
+    let deadline = tokio::time::Instant::now() + deadline;
+    let attempts = uploads.into_iter().map(|(label, upload)| async move {
+        debug!("Aborting upload for {label}: build_id={}", upload.build_id);
+        let result = tokio::time::timeout_at(
+            deadline,
+            client.abort_upload(
+                &upload.build_id,
+                upload.upload_id.as_deref(),
+                Some(&upload.object_key),
+            ),
+        )
+        .await;
+        (label, result)
+    });
+
+    let mut report = AbortReport::default();
+    for (label, result) in join_all(attempts).await {
+        match result {
+            Ok(Ok(())) => {
+                debug!("Successfully aborted upload for {label}");
+                report.confirmed.push(label);
+            }
+            Ok(Err(e)) => report.unconfirmed.push((label, e.to_string())),
+            Err(_) => report
+                .unconfirmed
+                .push((label, "no response before the abort deadline".to_string())),
+        }
+    }
+    report
+}
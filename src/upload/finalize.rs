@@ -0,0 +1,139 @@
+//! Progress for the finalize phase of an upload
+//!
+//! Completing an upload can take minutes server-side (assembling a 40 GB multipart object,
+//! scanning it), during which no bytes move. The progress bar turns into a spinner with
+//! the elapsed time and, on servers that report it, the processing progress.
+
+use crate::api::Client;
+use crate::api::client::UploadStatus;
+use crate::error::{Error, Result};
+use crate::format::format_duration;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::debug;
+use std::fmt::Write as _;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// How long completing an upload may take when `finalize_timeout` is not changed
+pub const DEFAULT_FINALIZE_TIMEOUT: Duration = Duration::from_mins(30);
+
+/// How often the server is asked for the processing progress
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Run `complete`, the request completing the upload of `build_id`, showing its progress
+/// on `pb`
+///
+/// # Errors
+///
+/// Returns the error of `complete`, or an error if it does not finish within `timeout`
+///
+/// # Panics
+///
+/// Panics if the progress bar template string is invalid (which should not happen with the hardcoded template).
+pub async fn finalize<T, F>(
+    client: &Client,
+    build_id: &str,
+    pb: &ProgressBar,
+    timeout: Duration,
+    complete: F,
+) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let started = Instant::now();
+    #[allow(clippy::expect_used)]
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .expect("Failed to set progress bar template"),
+    );
+    pb.set_message("Finalizing upload");
+    pb.enable_steady_tick(Duration::from_millis(100));
+
+    let result = tokio::select! {
+        result = tokio::time::timeout(timeout, complete) => result,
+        () = poll_status(client, build_id, pb, started) => unreachable!("status polling never ends"),
+    };
+    pb.disable_steady_tick();
+
+    let completed = result.map_err(|_| {
+        Error::UploadError(format!(
+            "Finalizing build {build_id} did not finish within {} (raise it with --finalize-timeout)",
+            format_duration(timeout)
+        ))
+    })??;
+    pb.finish_with_message(format!(
+        "Upload complete (finalized in {})",
+        format_duration(started.elapsed())
+    ));
+    Ok(completed)
+}
+
+/// Update the spinner message until cancelled, with the server's progress while it reports one
+async fn poll_status(client: &Client, build_id: &str, pb: &ProgressBar, started: Instant) {
+    let mut interval = tokio::time::interval(STATUS_POLL_INTERVAL);
+    let mut polling = true;
+    let mut status = None;
+
+    loop {
+        interval.tick().await;
+        // The first tick completes immediately; the server has barely started by then
+        if polling && started.elapsed() >= STATUS_POLL_INTERVAL {
+            match client.upload_status(build_id).await {
+                Ok(Some(current)) => status = Some(current),
+                Ok(None) => {
+                    debug!("The server does not report finalize progress");
+                    polling = false;
+                }
+                Err(e) => debug!("Failed to poll the finalize progress of build {build_id}: {e}"),
+            }
+        }
+        pb.set_message(finalize_message(started.elapsed(), status.as_ref()));
+    }
+}
+
+fn finalize_message(elapsed: Duration, status: Option<&UploadStatus>) -> String {
+    let mut message = format!("Finalizing upload ({})", format_duration(elapsed));
+    if let Some(status) = status {
+        message.push_str(": ");
+        message.push_str(status.message.as_deref().unwrap_or(&status.state));
+        if let Some(percent) = status.percent {
+            let _ = write!(message, " {percent}%");
+        }
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finalize_message() {
+        let elapsed = Duration::from_secs(65);
+        assert_eq!(
+            finalize_message(elapsed, None),
+            "Finalizing upload (1m 05s)"
+        );
+
+        let status = UploadStatus {
+            state: "assembling".to_string(),
+            percent: Some(40),
+            message: None,
+        };
+        assert_eq!(
+            finalize_message(elapsed, Some(&status)),
+            "Finalizing upload (1m 05s): assembling 40%"
+        );
+
+        let status = UploadStatus {
+            message: Some("Scanning for malware".to_string()),
+            percent: None,
+            ..status
+        };
+        assert_eq!(
+            finalize_message(elapsed, Some(&status)),
+            "Finalizing upload (1m 05s): Scanning for malware"
+        );
+    }
+}
@@ -1,19 +1,42 @@
+pub mod abort;
+pub mod finalize;
 pub mod multipart;
+mod part_cache;
+pub mod progress;
+mod scheduler;
+pub mod shard;
 pub mod single;
+pub mod stall;
+pub mod targets;
 
-use crate::api::client::BuildDetails;
+use crate::api::client::{Arch, BuildDetails, CompleteResponse, UploadRequest};
+use crate::archive::{StreamingZip, directory_archive_name};
+use crate::artifact::{ArtifactType, artifact_type};
 use crate::config::Config;
 use crate::error::Result;
+use crate::files::IgnoreRules;
+use crate::receipts::save_receipt;
+use crate::symbols::SymbolsInfo;
+use crate::upload::finalize::DEFAULT_FINALIZE_TIMEOUT;
+use crate::upload::shard::Shard;
+use crate::upload::stall::DEFAULT_STALL_TIMEOUT;
 use indicatif::ProgressBar;
+use log::{info, warn};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 const MAX_SINGLE_PART_SIZE: u64 = 3 * 1024 * 1024 * 1024; // 3GB
 
+/// Parts whose upload URLs are requested together when `url_batch_size` is not changed
+pub const DEFAULT_URL_BATCH_SIZE: usize = 100;
+
 /// Callback function type for upload initiation
 pub type OnUploadInitiated = Arc<dyn Fn(String, Option<String>, String) + Send + Sync>;
 
 /// Options for uploading a file
 #[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct UploadOptions {
     pub name: String,
     pub platform: String,
@@ -23,6 +46,9 @@ pub struct UploadOptions {
     pub deletion_policy: Option<String>,
     pub force_multipart: bool,
     pub parallel: usize,
+    /// Number of parts whose upload URLs are requested in one API call, independent of
+    /// `parallel`
+    pub url_batch_size: usize,
     /// Optional callback invoked when upload is initiated with `(build_id, upload_id, object_key)`
     pub on_upload_initiated: Option<OnUploadInitiated>,
     /// Optional progress bar for tracking upload progress
@@ -31,6 +57,76 @@ pub struct UploadOptions {
     pub details: Option<BuildDetails>,
     /// Optional tags for the build
     pub tags: Option<Vec<String>>,
+    /// Periodically report upload progress to the backend for dashboard visibility
+    pub report_progress: bool,
+    /// Preferred storage region (e.g. `eu-west`) for deployments with regional buckets
+    pub storage_region: Option<String>,
+    /// Semantic version of the build (e.g. `1.4.2`)
+    pub build_version: Option<String>,
+    /// Build number assigned by the build system or CI
+    pub build_number: Option<String>,
+    /// Changelog shown alongside the build
+    pub release_notes: Option<String>,
+    /// Upload debug symbols for existing builds instead of a new build
+    pub symbols: Option<SymbolsInfo>,
+    /// CPU architecture of the build
+    pub arch: Option<Arch>,
+    /// Upload parts one at a time in part order, without straggler duplicates or timed
+    /// progress reports, so requests and progress events are reproducible (for tests)
+    pub deterministic: bool,
+    /// Whether this is a build or an auxiliary artifact such as a shader cache
+    pub artifact_type: ArtifactType,
+    /// Log every part of multipart uploads at debug level instead of sampling them
+    pub log_every_part: bool,
+    /// Encrypt every part with a data key from the project's key management service
+    /// (always uses multipart upload)
+    pub encrypt: bool,
+    /// Upload only this machine's share of the parts, for uploads split across machines
+    /// (always uses multipart upload)
+    pub shard: Option<Shard>,
+    /// How long completing the upload may take server-side before giving up
+    pub finalize_timeout: Duration,
+    /// Let the server copy multipart parts whose content was uploaded before, when it
+    /// supports that (see [`part_cache`])
+    pub part_cache: bool,
+    /// How long an upload or part may send no data before it is aborted and retried
+    /// (`None` waits forever)
+    pub stall_timeout: Option<Duration>,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            platform: String::new(),
+            description: None,
+            upload_timeout: None,
+            auto_delete: false,
+            deletion_policy: None,
+            force_multipart: false,
+            parallel: 4,
+            url_batch_size: DEFAULT_URL_BATCH_SIZE,
+            on_upload_initiated: None,
+            progress_bar: None,
+            details: None,
+            tags: None,
+            report_progress: true,
+            storage_region: None,
+            build_version: None,
+            build_number: None,
+            release_notes: None,
+            symbols: None,
+            arch: None,
+            deterministic: false,
+            artifact_type: ArtifactType::Build,
+            log_every_part: false,
+            encrypt: false,
+            shard: None,
+            finalize_timeout: DEFAULT_FINALIZE_TIMEOUT,
+            part_cache: true,
+            stall_timeout: Some(DEFAULT_STALL_TIMEOUT),
+        }
+    }
 }
 
 impl std::fmt::Debug for UploadOptions {
@@ -44,32 +140,229 @@ impl std::fmt::Debug for UploadOptions {
             .field("deletion_policy", &self.deletion_policy)
             .field("force_multipart", &self.force_multipart)
             .field("parallel", &self.parallel)
+            .field("url_batch_size", &self.url_batch_size)
             .field("on_upload_initiated", &self.on_upload_initiated.is_some())
             .field("progress_bar", &self.progress_bar.is_some())
             .field("details", &self.details.is_some())
             .field("tags", &self.tags.is_some())
+            .field("report_progress", &self.report_progress)
+            .field("storage_region", &self.storage_region)
+            .field("build_version", &self.build_version)
+            .field("build_number", &self.build_number)
+            .field("release_notes", &self.release_notes.is_some())
+            .field("symbols", &self.symbols)
+            .field("arch", &self.arch)
+            .field("deterministic", &self.deterministic)
+            .field("artifact_type", &self.artifact_type)
+            .field("log_every_part", &self.log_every_part)
+            .field("encrypt", &self.encrypt)
+            .field("shard", &self.shard)
+            .field("finalize_timeout", &self.finalize_timeout)
+            .field("part_cache", &self.part_cache)
+            .field("stall_timeout", &self.stall_timeout)
             .finish()
     }
 }
 
+impl UploadOptions {
+    /// Returns these options with the build fields of a request prepared earlier, e.g. by
+    /// `upload --prepare-only` on another machine
+    ///
+    /// Callbacks, progress and transfer settings are kept from `self`.
+    #[must_use]
+    pub fn with_request(self, request: &UploadRequest) -> Self {
+        Self {
+            name: request.name.clone(),
+            platform: request.platform.clone(),
+            description: request.description.clone(),
+            upload_timeout: request.upload_timeout,
+            auto_delete: request.auto_delete.unwrap_or(false),
+            deletion_policy: request.deletion_policy.clone(),
+            force_multipart: self.force_multipart || request.multipart,
+            details: request.details.clone(),
+            tags: request.tags.clone(),
+            storage_region: request.storage_region.clone(),
+            build_version: request.build_version.clone(),
+            build_number: request.build_number.clone(),
+            release_notes: request.release_notes.clone(),
+            symbols: request.symbols.clone(),
+            arch: request.arch,
+            // Build hints such as `aab` are derived from the file name again
+            artifact_type: request
+                .artifact_type
+                .as_deref()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default(),
+            ..self
+        }
+    }
+}
+
+/// Returns whether a file of `file_size` bytes will be uploaded using multipart upload
+#[must_use]
+pub fn uses_multipart(file_size: u64, options: &UploadOptions) -> bool {
+    // Encryption and sharding work per part
+    options.force_multipart
+        || options.encrypt
+        || options.shard.is_some()
+        || file_size > MAX_SINGLE_PART_SIZE
+}
+
+/// Builds the request sent to the API to initiate an upload
+#[must_use]
+pub fn build_upload_request(
+    filename: &str,
+    file_size: u64,
+    options: &UploadOptions,
+    multipart: bool,
+) -> UploadRequest {
+    UploadRequest {
+        name: options.name.clone(),
+        description: options.description.clone(),
+        file_name: filename.to_string(),
+        file_size,
+        platform: options.platform.clone(),
+        multipart,
+        auto_delete: Some(options.auto_delete),
+        deletion_policy: options.deletion_policy.clone(),
+        upload_timeout: options.upload_timeout,
+        details: options.details.clone(),
+        tags: options.tags.clone(),
+        storage_region: options.storage_region.clone(),
+        build_version: options.build_version.clone(),
+        build_number: options.build_number.clone(),
+        release_notes: options.release_notes.clone(),
+        artifact_type: match options.artifact_type {
+            // Symbol uploads are always auxiliary, whatever the options say
+            _ if options.symbols.is_some() => Some(ArtifactType::Symbols.as_str().to_string()),
+            ArtifactType::Build => artifact_type(filename).map(str::to_string),
+            other => Some(other.as_str().to_string()),
+        },
+        symbols: options.symbols.clone(),
+        arch: options.arch,
+        // Filled in once the data key has been generated
+        encryption: None,
+        shard: options.shard.clone(),
+    }
+}
+
+/// Log which region and host the presigned URLs target so data residency can be verified
+///
+/// Warns if the server placed the upload in a different region than requested.
+pub(crate) fn log_storage_target(requested: Option<&str>, reported: Option<&str>, url: &str) {
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| "unknown host".to_string());
+
+    info!(
+        "Storage target: region {} ({host})",
+        reported.unwrap_or("unspecified")
+    );
+
+    match (requested, reported) {
+        (Some(requested), Some(reported)) if !requested.eq_ignore_ascii_case(reported) => {
+            warn!(
+                "Requested storage region '{requested}' but the server assigned '{reported}' ({host})"
+            );
+        }
+        (Some(requested), None) => {
+            warn!("Server did not confirm storage region '{requested}'; uploading to {host}");
+        }
+        _ => {}
+    }
+}
+
+/// Store the signed receipt of a completed upload, if the server returned one
+///
+/// Failing to store it only warns: the upload itself has succeeded.
+pub(crate) fn store_receipt(response: &CompleteResponse) {
+    let Some(receipt) = &response.receipt else {
+        return;
+    };
+    match save_receipt(receipt) {
+        Ok(path) => info!("Upload receipt stored at {}", path.display()),
+        Err(e) => warn!(
+            "Could not store the upload receipt of build {}: {e}",
+            receipt.build_id
+        ),
+    }
+}
+
 /// Upload a file to Nunu.ai
 ///
+/// Directories (such as `.app` bundles) are archived into a zip on the fly while
+/// uploading, preserving symlinks and permissions and skipping entries matched by a
+/// `.nunuignore` file in the current directory.
+///
 /// # Errors
 ///
 /// Returns an error if:
 /// - The file cannot be read or accessed
+/// - A directory cannot be archived
 /// - The upload operation fails
 pub async fn upload_file(
     config: &Config,
     file_path: &str,
     options: UploadOptions,
 ) -> Result<String> {
+    if Path::new(file_path).is_dir() {
+        if options.shard.is_some() {
+            return Err(crate::error::Error::ConfigError(format!(
+                "{file_path} is a directory; sharded uploads need a file every shard can read"
+            )));
+        }
+        return upload_directory(config, file_path, options).await;
+    }
+
     let file_metadata = tokio::fs::metadata(file_path).await?;
     let file_size = file_metadata.len();
 
-    if options.force_multipart || file_size > MAX_SINGLE_PART_SIZE {
+    let options = match options.shard {
+        Some(shard) => UploadOptions {
+            shard: Some(shard.with_default_key(Path::new(file_path))?),
+            ..options
+        },
+        None => options,
+    };
+
+    if uses_multipart(file_size, &options) {
         multipart::upload_multipart(config, file_path, file_size, options).await
     } else {
         single::upload_single_part(config, file_path, file_size, options).await
     }
 }
+
+/// Archive a directory on the fly and upload it with multipart upload
+async fn upload_directory(config: &Config, dir: &str, options: UploadOptions) -> Result<String> {
+    info!("Archiving directory {dir}");
+    if let Some(pb) = &options.progress_bar {
+        pb.set_message(format!("Scanning {dir}"));
+    }
+
+    let ignore = IgnoreRules::load_from_current_dir()?;
+    let path = Path::new(dir).to_path_buf();
+    let archive = tokio::task::spawn_blocking(move || StreamingZip::plan(&path, ignore.as_ref()))
+        .await
+        .map_err(|e| crate::error::Error::UploadError(format!("Archiving task failed: {e}")))??;
+
+    let archive_name = directory_archive_name(Path::new(dir));
+    let archive_size = archive.size();
+    info!(
+        "Streaming {} entries from {dir} as {archive_name} ({archive_size} bytes)",
+        archive.len()
+    );
+
+    // The archive is produced while it uploads, which needs multipart upload
+    multipart::upload_multipart_from(
+        config,
+        &archive_name,
+        archive_size,
+        UploadOptions {
+            force_multipart: true,
+            ..options
+        },
+        multipart::PartSource::Archive(archive),
+    )
+    .await
+}
@@ -1,11 +1,34 @@
-use crate::api::{Client, client::UploadedPart};
+use crate::api::Client;
+use crate::api::client::{MultipartUploadResponse, UploadedPart};
+use crate::archive::StreamingZip;
 use crate::config::Config;
-use crate::error::Result;
-use crate::upload::UploadOptions;
-use futures::stream::{self, StreamExt};
+use crate::encryption::{ENCRYPTION_ALGORITHM, PART_TAG_LEN, PartCipher};
+use crate::error::{Error, Result};
+use crate::format::format_size;
+use crate::plain::hide_in_plain_mode;
+use crate::state::StateWriter;
+use crate::upload::finalize::finalize;
+use crate::upload::part_cache::PartReuser;
+use crate::upload::progress::ProgressReporter;
+use crate::upload::scheduler::{PartLog, PartTimings, upload_batch};
+use crate::upload::shard::Shard;
+use crate::upload::{UploadOptions, build_upload_request, log_storage_target, store_receipt};
+use bytes::Bytes;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{debug, info};
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Where the bytes of a multipart upload come from
+#[derive(Debug)]
+pub enum PartSource {
+    /// A file on disk
+    File(PathBuf),
+    /// A directory archived on the fly while its parts are uploaded
+    Archive(StreamingZip),
+}
 
 /// Uploads a file using multipart upload.
 ///
@@ -32,29 +55,63 @@ pub async fn upload_multipart(
         .and_then(|n| n.to_str())
         .ok_or_else(|| crate::error::Error::ConfigError("Invalid filename".to_string()))?;
 
-    info!(
-        "Uploading {} ({:.2} MB) using multipart upload",
+    upload_multipart_from(
+        config,
         filename,
-        file_size / 1024 / 1024
+        file_size,
+        options,
+        PartSource::File(PathBuf::from(file_path)),
+    )
+    .await
+}
+
+/// Uploads `file_size` bytes produced by `source` as `filename` using multipart upload.
+///
+/// Parts are read (or archived) in order on a blocking thread and spooled through a
+/// bounded channel, so producing the data overlaps with uploading earlier parts and
+/// at most a few parts are held in memory at once.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Reading or archiving the source fails, or it does not produce exactly `file_size` bytes
+/// - Network requests fail (initiate, part URLs request, part upload, or completion request)
+/// - API calls return error responses
+///
+/// # Panics
+///
+/// Panics if the progress bar template string is invalid (which should not happen with the hardcoded template).
+#[allow(clippy::too_many_lines)]
+pub async fn upload_multipart_from(
+    config: &Config,
+    filename: &str,
+    file_size: u64,
+    options: UploadOptions,
+    source: PartSource,
+) -> Result<String> {
+    info!(
+        "Uploading {filename} ({}) using multipart upload",
+        format_size(file_size)
     );
 
-    let client = Client::new(config.clone());
+    let client = Client::try_new(config.clone())?;
+
+    let mut request = build_upload_request(filename, file_size, &options, true);
+    let cipher = if options.encrypt {
+        let data_key = client.create_data_key().await?;
+        let (cipher, encryption) = PartCipher::new(&data_key)?;
+        info!(
+            "Encrypting parts with {ENCRYPTION_ALGORITHM} (key {})",
+            encryption.key_id
+        );
+        request.encryption = Some(encryption);
+        Some(cipher)
+    } else {
+        None
+    };
 
     // Step 1: Initiate multipart upload
-    let initiate_response = client
-        .initiate_multipart_upload(
-            &options.name,
-            filename,
-            file_size,
-            &options.platform,
-            options.description.clone(),
-            options.upload_timeout,
-            options.auto_delete,
-            options.deletion_policy.clone(),
-            options.details.clone(),
-            options.tags.clone(),
-        )
-        .await?;
+    let initiate_response = client.initiate_multipart_upload_with(&request).await?;
 
     // Notify about upload initiation
     if let Some(callback) = &options.on_upload_initiated {
@@ -66,21 +123,52 @@ pub async fn upload_multipart(
     }
 
     info!(
-        "Multipart upload initiated - {} parts of {} MB each",
+        "Multipart upload initiated - {} parts of {} each",
         initiate_response.total_parts,
-        initiate_response.part_size / 1024 / 1024
+        format_size(initiate_response.part_size as u64)
     );
 
-    // Read the entire file
-    let file_data = tokio::fs::read(file_path).await?;
+    let part_size = initiate_response.part_size;
+    let total_parts = initiate_response.total_parts;
+
+    // A shard uploads only its range of parts; otherwise this machine uploads all of them
+    let part_range = options
+        .shard
+        .as_ref()
+        .map_or(1..total_parts + 1, |shard| shard.part_range(total_parts));
+    let offset = (part_range.start as u64 - 1) * part_size as u64;
+    let range_size = if part_range.is_empty() {
+        0
+    } else {
+        ((part_range.end as u64 - 1) * part_size as u64).min(file_size) - offset
+    };
+    if let Some(shard) = &options.shard {
+        if part_range.is_empty() {
+            info!("Shard {shard} has no parts to upload out of {total_parts}");
+        } else {
+            info!(
+                "Shard {shard} uploads parts {}-{} of {total_parts} ({})",
+                part_range.start,
+                part_range.end - 1,
+                format_size(range_size)
+            );
+        }
+    }
+
+    // Encrypted parts carry an authentication tag each
+    let transfer_size = if cipher.is_some() {
+        range_size + PART_TAG_LEN * part_range.len() as u64
+    } else {
+        range_size
+    };
 
     // Use provided progress bar or create a new one
     let pb = if let Some(pb) = options.progress_bar.clone() {
-        pb.set_length(file_size);
+        pb.set_length(transfer_size);
         pb.set_message(format!("Uploading {filename}"));
         pb
     } else {
-        let pb = ProgressBar::new(file_size);
+        let pb = ProgressBar::new(transfer_size);
         #[allow(clippy::expect_used)]
         pb.set_style(
             ProgressStyle::default_bar()
@@ -90,23 +178,69 @@ pub async fn upload_multipart(
                 .expect("Failed to set progress bar template")
                 .progress_chars("#>-"),
         );
+        hide_in_plain_mode(&pb);
         pb
     };
 
+    // The dashboard shows one progress per build, which a single shard cannot report
+    let reporter = (options.report_progress && !options.deterministic && options.shard.is_none())
+        .then(|| {
+            ProgressReporter::spawn(
+                client.clone(),
+                initiate_response.build_id.clone(),
+                pb.clone(),
+            )
+        });
+    let state = StateWriter::spawn(
+        &initiate_response.build_id,
+        &options.name,
+        filename,
+        pb.clone(),
+    );
+
     // Step 2: Upload parts
-    // Process parts in batches to avoid too many concurrent requests
-    // Use the parallel setting from options to control batch size
+    // URLs are requested `url_batch_size` parts at a time to keep API calls down, while
+    // uploads run in batches of `parallel` parts so only a few are held in memory
 
     let mut uploaded_parts: Vec<UploadedPart> = Vec::new();
-    let part_size = initiate_response.part_size;
-    let total_parts = initiate_response.total_parts;
-    let batch_size = options.parallel;
+    let url_batch_size = options.url_batch_size.max(1);
+
+    // A single slot makes parts finish, and progress advance, strictly in order
+    let part_parallel = if options.deterministic {
+        1
+    } else {
+        options.parallel
+    };
 
-    for batch_start in (1..=total_parts).step_by(batch_size) {
-        let batch_end = (batch_start + batch_size - 1).min(total_parts);
+    // Encrypted parts differ on every upload, and shards do not complete the upload
+    let mut reuser = (options.part_cache
+        && initiate_response.part_reuse
+        && cipher.is_none()
+        && options.shard.is_none()
+        && !options.deterministic)
+        .then(|| PartReuser::new(config, part_size));
+
+    let (mut parts, producer) = spawn_part_producer(
+        source,
+        PartRange {
+            first_part: part_range.start as u64,
+            offset,
+            len: range_size,
+        },
+        part_size,
+        options.parallel,
+        cipher,
+    );
+    let mut timings = PartTimings::default();
+    let mut part_log = PartLog::new(options.log_every_part, part_range.len());
+
+    for batch_start in part_range.clone().step_by(url_batch_size) {
+        let batch_end = (batch_start + url_batch_size - 1).min(part_range.end - 1);
         let part_numbers: Vec<u64> = (batch_start..=batch_end).map(|n| n as u64).collect();
 
-        debug!("Requesting URLs for parts {batch_start}-{batch_end} of {total_parts}");
+        if part_log.logs_range(batch_start, batch_end) {
+            debug!("Requesting URLs for parts {batch_start}-{batch_end} of {total_parts}");
+        }
 
         // Step 2a: Request upload URLs for this batch
         let urls_response = client
@@ -117,65 +251,256 @@ pub async fn upload_multipart(
             )
             .await?;
 
-        // Step 2b: Upload parts in this batch concurrently
-        let batch_results: Vec<UploadedPart> = stream::iter(urls_response.upload_urls)
-            .map(|upload_url_part| {
-                let part_number = upload_url_part.part_number;
-                let part_url = upload_url_part.url;
-                let client = client.clone();
-                let file_data = &file_data;
-                let pb = pb.clone();
-
-                async move {
-                    // Calculate part data boundaries
-                    #[allow(clippy::cast_possible_truncation)]
-                    let start = ((part_number - 1) as usize) * part_size;
-                    let end = (start + part_size).min(file_data.len());
-                    let part_data = file_data[start..end].to_vec();
-
-                    debug!("Uploading part {} ({} bytes)", part_number, part_data.len());
-
-                    // Upload the part
-                    let etag = client.upload_part(&part_url, part_data.clone()).await?;
-
-                    // Update progress
-                    pb.inc(part_data.len() as u64);
-
-                    debug!("Part {part_number} uploaded successfully");
-
-                    Ok::<UploadedPart, crate::error::Error>(UploadedPart { part_number, etag })
-                }
-            })
-            .buffer_unordered(options.parallel)
-            .collect::<Vec<Result<UploadedPart>>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<UploadedPart>>>()?;
-
-        uploaded_parts.extend(batch_results);
+        if batch_start == part_range.start
+            && let Some(first) = urls_response.upload_urls.first()
+        {
+            log_storage_target(
+                options.storage_region.as_deref(),
+                initiate_response.storage_region.as_deref(),
+                &first.url,
+            );
+        }
+
+        let mut upload_urls = urls_response.upload_urls;
+        upload_urls.sort_by_key(|p| p.part_number);
+        let mut upload_urls = upload_urls.into_iter().peekable();
+        while upload_urls.peek().is_some() {
+            // Step 2b: Take the next `parallel` parts' data from the spool, in part order
+            let mut batch = Vec::with_capacity(options.parallel);
+            for upload_url_part in upload_urls.by_ref().take(options.parallel.max(1)) {
+                let part_data = parts.recv().await.ok_or_else(|| {
+                    Error::UploadError(format!(
+                        "Upload source ended before part {}",
+                        upload_url_part.part_number
+                    ))
+                })??;
+                batch.push((upload_url_part, part_data));
+            }
+
+            // Step 2c: Have the server copy parts uploaded before with the same content
+            if let Some(reuser) = &mut reuser {
+                let copied = reuser
+                    .reuse(
+                        &client,
+                        &initiate_response.upload_id,
+                        &initiate_response.object_key,
+                        &pb,
+                        &mut batch,
+                    )
+                    .await;
+                uploaded_parts.extend(copied);
+            }
+
+            // Step 2d: Upload parts in this batch concurrently, duplicating stragglers
+            let batch_results = upload_batch(
+                &client,
+                &pb,
+                batch,
+                part_parallel,
+                options.stall_timeout,
+                &mut timings,
+                &mut part_log,
+            )
+            .await?;
+
+            uploaded_parts.extend(batch_results);
+        }
     }
 
-    pb.finish_with_message("All parts uploaded");
+    if parts.recv().await.is_some() {
+        return Err(Error::UploadError(format!(
+            "Upload source produced more than the announced {range_size} bytes"
+        )));
+    }
+    drop(parts);
+    producer
+        .await
+        .map_err(|e| Error::UploadError(format!("Part producer failed: {e}")))?;
+
+    drop(reporter);
 
     // Sort parts by part number (required by S3)
     uploaded_parts.sort_by_key(|p| p.part_number);
 
+    if let Some(shard) = &options.shard {
+        Box::pin(finalize(
+            &client,
+            &initiate_response.build_id,
+            &pb,
+            options.finalize_timeout,
+            complete_shard(&client, &initiate_response, shard, uploaded_parts),
+        ))
+        .await?;
+        state.complete();
+        info!("Build ID: {}", initiate_response.build_id);
+        return Ok(initiate_response.build_id);
+    }
+
     info!(
         "Completing multipart upload with {} parts",
         uploaded_parts.len()
     );
 
     // Step 3: Complete the multipart upload
-    client
-        .complete_multipart_upload(
+    let completed = Box::pin(finalize(
+        &client,
+        &initiate_response.build_id,
+        &pb,
+        options.finalize_timeout,
+        client.complete_multipart_upload(
             &initiate_response.build_id,
             &initiate_response.upload_id,
             &initiate_response.object_key,
             uploaded_parts,
-        )
-        .await?;
+        ),
+    ))
+    .await?;
+    state.complete();
+    store_receipt(&completed);
+    if let Some(reuser) = reuser {
+        reuser.finish(&initiate_response.object_key);
+    }
 
     info!("Build ID: {}", initiate_response.build_id);
 
     Ok(initiate_response.build_id)
 }
+
+/// Report this shard's parts, completing the upload if it was the last shard to finish
+async fn complete_shard(
+    client: &Client,
+    upload: &MultipartUploadResponse,
+    shard: &Shard,
+    parts: Vec<UploadedPart>,
+) -> Result<()> {
+    info!("Reporting {} parts of shard {shard}", parts.len());
+    let response = client
+        .complete_shard(
+            &upload.build_id,
+            &upload.upload_id,
+            &upload.object_key,
+            shard.index,
+            parts,
+        )
+        .await?;
+
+    if response.completed {
+        info!("All shards finished; upload completed");
+        store_receipt(&response.complete);
+    } else {
+        let pending: Vec<String> = response
+            .pending_shards
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        info!(
+            "Shard {shard} finished; the upload completes when shards {} of {} have finished",
+            pending.join(", "),
+            shard.count
+        );
+    }
+    Ok(())
+}
+
+/// Bytes of the source this machine uploads
+#[derive(Clone, Copy)]
+struct PartRange {
+    /// Number of the part starting at `offset`
+    first_part: u64,
+    offset: u64,
+    len: u64,
+}
+
+/// Start reading `range` of `source` into `part_size` chunks on a blocking thread
+///
+/// With a `cipher`, each chunk is encrypted there too. Errors are delivered through the
+/// channel; the producer stops early if the receiver is dropped (e.g. because an upload
+/// failed).
+fn spawn_part_producer(
+    source: PartSource,
+    range: PartRange,
+    part_size: usize,
+    capacity: usize,
+    cipher: Option<PartCipher>,
+) -> (mpsc::Receiver<Result<Bytes>>, JoinHandle<()>) {
+    let (tx, rx) = mpsc::channel(capacity.max(1));
+
+    let handle = tokio::task::spawn_blocking(move || {
+        let mut writer = PartWriter {
+            tx: tx.clone(),
+            part_size,
+            buffer: Vec::with_capacity(part_size),
+            cipher,
+            next_part: range.first_part,
+        };
+        let result = match &source {
+            PartSource::File(path) => std::fs::File::open(path)
+                .and_then(|mut file| {
+                    file.seek(SeekFrom::Start(range.offset))?;
+                    std::io::copy(&mut file.take(range.len), &mut writer)
+                })
+                .map(|_| ())
+                .map_err(Error::from),
+            // Archives are produced from the start; directories cannot be sharded
+            PartSource::Archive(_) if range.offset > 0 => Err(Error::UploadError(
+                "Archived directories can only be uploaded whole".to_string(),
+            )),
+            PartSource::Archive(archive) => archive.write_to(&mut writer),
+        }
+        .and_then(|()| writer.finish());
+
+        if let Err(e) = result {
+            // The receiver is gone if the upload already failed; nothing left to report to
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+
+    (rx, handle)
+}
+
+/// Writer that splits a byte stream into parts and sends them to the uploader
+struct PartWriter {
+    tx: mpsc::Sender<Result<Bytes>>,
+    part_size: usize,
+    buffer: Vec<u8>,
+    cipher: Option<PartCipher>,
+    next_part: u64,
+}
+
+impl PartWriter {
+    fn send_buffer(&mut self) -> std::io::Result<()> {
+        let part = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.part_size));
+        let part = match &self.cipher {
+            Some(cipher) => cipher
+                .seal(self.next_part, &part)
+                .map_err(std::io::Error::other)?,
+            None => Bytes::from(part),
+        };
+        self.next_part += 1;
+        self.tx
+            .blocking_send(Ok(part))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "upload cancelled"))
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            self.send_buffer()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for PartWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let take = buf.len().min(self.part_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..take]);
+        if self.buffer.len() == self.part_size {
+            self.send_buffer()?;
+        }
+        Ok(take)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
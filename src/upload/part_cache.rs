@@ -0,0 +1,289 @@
+//! Local cache of uploaded parts, so unchanged parts of re-uploaded files are not sent again
+//!
+//! After a multipart upload completes, the SHA-256 of each part is stored with the object and
+//! part number it ended up in (`parts.json` in the user cache directory). Concurrent uploads
+//! merge their parts into the file under a lock and replace it atomically. When a later upload
+//! to the same project has a part of the same size and content and the server supports part
+//! reuse, the server copies the stored part instead. Developers re-uploading nearly identical
+//! local builds many times a day skip the unchanged parts, typically the leading ones.
+
+use crate::api::Client;
+use crate::api::client::{PartReuse, UploadUrlPart, UploadedPart};
+use crate::config::Config;
+use crate::error::Result;
+use crate::format::format_size;
+use crate::util::{cache_dir, hex_encode, unix_now, write_atomic};
+use bytes::Bytes;
+use indicatif::ProgressBar;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Parts remembered at most; the oldest are dropped first
+const MAX_ENTRIES: usize = 50_000;
+
+/// Parts older than this are forgotten, as their builds may have been deleted
+const MAX_AGE: Duration = Duration::from_hours(14 * 24);
+
+/// Where a part with some content was stored
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct CachedPart {
+    object_key: String,
+    part_number: u64,
+    /// Unix timestamp (seconds) of the upload that stored it
+    recorded_at: u64,
+}
+
+/// Stored parts by [`part_key`]
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct PartCache {
+    #[serde(default)]
+    parts: HashMap<String, CachedPart>,
+}
+
+impl PartCache {
+    /// Read the cache at `path`, starting over if it is missing or unreadable
+    fn load(path: &Path) -> Self {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&contents)
+            .inspect_err(|e| debug!("Ignoring unreadable {}: {e}", path.display()))
+            .unwrap_or_default()
+    }
+
+    /// Apply `change` to the cache at `path` and store it
+    ///
+    /// The file is re-read and replaced under an exclusive lock on a `.lock` file next to
+    /// it, so parts recorded by concurrent uploads are merged rather than lost.
+    fn update(path: &Path, change: impl FnOnce(&mut Self)) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let lock = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.with_extension("lock"))?;
+        lock.lock()?;
+
+        let mut cache = Self::load(path);
+        change(&mut cache);
+        write_atomic(path, serde_json::to_string(&cache)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str, now: u64) -> Option<&CachedPart> {
+        self.parts
+            .get(key)
+            .filter(|part| now.saturating_sub(part.recorded_at) < MAX_AGE.as_secs())
+    }
+
+    /// Drop expired parts, then the oldest ones beyond `max_entries`
+    fn prune(&mut self, now: u64, max_entries: usize) {
+        self.parts
+            .retain(|_, part| now.saturating_sub(part.recorded_at) < MAX_AGE.as_secs());
+        if self.parts.len() > max_entries {
+            let mut ages: Vec<u64> = self.parts.values().map(|p| p.recorded_at).collect();
+            ages.sort_unstable();
+            let cutoff = ages[ages.len() - max_entries];
+            self.parts.retain(|_, part| part.recorded_at >= cutoff);
+        }
+    }
+}
+
+/// Cache key of a part: its SHA-256, scoped to the API, project and part size
+fn part_key(scope: &str, data: &[u8]) -> String {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    context.update(scope.as_bytes());
+    context.update(b"\n");
+    context.update(data);
+    hex_encode(context.finish().as_ref())
+}
+
+/// Part reuse for one multipart upload
+pub(crate) struct PartReuser {
+    cache: PartCache,
+    scope: String,
+    /// Cache key of every part uploaded or reused so far, by part number
+    keys: Vec<(u64, String)>,
+    reused_parts: usize,
+    reused_bytes: u64,
+}
+
+impl PartReuser {
+    pub(crate) fn new(config: &Config, part_size: usize) -> Self {
+        Self {
+            cache: PartCache::load(&cache_path()),
+            scope: format!("{}\n{}\n{part_size}", config.api_url, config.project_id),
+            keys: Vec::new(),
+            reused_parts: 0,
+            reused_bytes: 0,
+        }
+    }
+
+    /// Have the server copy the parts of `batch` stored by earlier uploads, removing them
+    /// from `batch` and returning them as uploaded
+    ///
+    /// Parts the server cannot copy stay in `batch` to be uploaded as usual.
+    pub(crate) async fn reuse(
+        &mut self,
+        client: &Client,
+        upload_id: &str,
+        object_key: &str,
+        pb: &ProgressBar,
+        batch: &mut Vec<(UploadUrlPart, Bytes)>,
+    ) -> Vec<UploadedPart> {
+        let scope = self.scope.clone();
+        let data: Vec<Bytes> = batch.iter().map(|(_, data)| data.clone()).collect();
+        let Ok(keys) = tokio::task::spawn_blocking(move || {
+            data.iter()
+                .map(|data| part_key(&scope, data))
+                .collect::<Vec<_>>()
+        })
+        .await
+        else {
+            return Vec::new();
+        };
+
+        let now = unix_now();
+        let mut candidates = Vec::new();
+        for ((part, _), key) in batch.iter().zip(keys) {
+            if let Some(cached) = self.cache.get(&key, now) {
+                candidates.push(PartReuse {
+                    part_number: part.part_number,
+                    source_object_key: cached.object_key.clone(),
+                    source_part_number: cached.part_number,
+                });
+            }
+            self.keys.push((part.part_number, key));
+        }
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+
+        let reused = match client.reuse_parts(upload_id, object_key, candidates).await {
+            Ok(reused) => reused,
+            Err(e) => {
+                debug!("Uploading parts instead of reusing them: {e}");
+                return Vec::new();
+            }
+        };
+        batch.retain(|(part, data)| {
+            let was_reused = reused.iter().any(|r| r.part_number == part.part_number);
+            if was_reused {
+                self.reused_parts += 1;
+                self.reused_bytes += data.len() as u64;
+                pb.inc(data.len() as u64);
+            }
+            !was_reused
+        });
+        reused
+    }
+
+    /// Remember the parts of the completed upload stored as `object_key`
+    pub(crate) fn finish(self, object_key: &str) {
+        if self.reused_parts > 0 {
+            info!(
+                "Reused {} part(s) ({}) stored by earlier uploads",
+                self.reused_parts,
+                format_size(self.reused_bytes)
+            );
+        }
+        let now = unix_now();
+        let stored = PartCache::update(&cache_path(), |cache| {
+            for (part_number, key) in self.keys {
+                cache.parts.insert(
+                    key,
+                    CachedPart {
+                        object_key: object_key.to_string(),
+                        part_number,
+                        recorded_at: now,
+                    },
+                );
+            }
+            cache.prune(now, MAX_ENTRIES);
+        });
+        if let Err(e) = stored {
+            debug!("Failed to store the part cache: {e}");
+        }
+    }
+}
+
+fn cache_path() -> PathBuf {
+    cache_dir().join("parts.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_cache() {
+        let scope = "https://nunu.ai/api\nproj\n1024";
+        let key = part_key(scope, b"part data");
+        assert_eq!(key, part_key(scope, b"part data"));
+        assert_ne!(key, part_key(scope, b"other data"));
+        assert_ne!(
+            key,
+            part_key("https://nunu.ai/api\nproj\n2048", b"part data")
+        );
+
+        let now = 10 * MAX_AGE.as_secs();
+        let part = |recorded_at| CachedPart {
+            object_key: "builds/b1".to_string(),
+            part_number: 1,
+            recorded_at,
+        };
+        let mut cache = PartCache::default();
+        cache.parts.insert(key.clone(), part(now - 60));
+        cache
+            .parts
+            .insert("expired".to_string(), part(now - MAX_AGE.as_secs()));
+        assert_eq!(cache.get(&key, now), Some(&part(now - 60)));
+        assert_eq!(cache.get("expired", now), None);
+
+        for i in 0..5 {
+            cache.parts.insert(format!("old{i}"), part(now - 3600 + i));
+        }
+        cache.prune(now, 3);
+        let mut kept: Vec<&str> = cache.parts.keys().map(String::as_str).collect();
+        kept.sort_unstable();
+        assert_eq!(kept, [key.as_str(), "old3", "old4"]);
+    }
+
+    #[test]
+    fn test_part_cache_concurrent_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("parts.json");
+        // A file truncated by a crash is started over
+        std::fs::write(&path, r#"{"parts": {"ab"#).unwrap();
+        assert!(PartCache::load(&path).parts.is_empty());
+
+        std::thread::scope(|s| {
+            for upload in 0..8 {
+                let path = &path;
+                s.spawn(move || {
+                    for part in 0..10 {
+                        PartCache::update(path, |cache| {
+                            cache.parts.insert(
+                                format!("{upload}-{part}"),
+                                CachedPart {
+                                    object_key: format!("builds/b{upload}"),
+                                    part_number: part,
+                                    recorded_at: unix_now(),
+                                },
+                            );
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+        let cache = PartCache::load(&path);
+        assert_eq!(cache.parts.len(), 80);
+        assert_eq!(cache.parts["7-9"].object_key, "builds/b7");
+    }
+}
@@ -0,0 +1,97 @@
+use crate::api::{Client, client::ProgressReport};
+use indicatif::ProgressBar;
+use log::debug;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How often upload progress is reported to the backend
+pub const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Background task that periodically reports upload progress to the backend
+///
+/// Reporting is best effort: failures are logged and never interrupt the upload.
+/// The task is stopped when the reporter is dropped.
+pub struct ProgressReporter {
+    handle: JoinHandle<()>,
+}
+
+impl ProgressReporter {
+    /// Start reporting the progress tracked by `pb` for `build_id`
+    #[must_use]
+    pub fn spawn(client: Client, build_id: String, pb: ProgressBar) -> Self {
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROGRESS_REPORT_INTERVAL);
+            // The first tick completes immediately; skip it so we don't report 0%
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let report = build_report(&build_id, &pb);
+                if let Err(e) = client.report_progress(&report).await {
+                    debug!("Failed to report progress for build {build_id}: {e}");
+                }
+            }
+        });
+
+        Self { handle }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+fn build_report(build_id: &str, pb: &ProgressBar) -> ProgressReport {
+    let uploaded_bytes = pb.position();
+    let total_bytes = pb.length().unwrap_or(0);
+
+    #[allow(clippy::cast_possible_truncation)]
+    let percent = (uploaded_bytes.min(total_bytes) * 100)
+        .checked_div(total_bytes)
+        .unwrap_or(0) as u8;
+
+    let eta_seconds = if uploaded_bytes > 0 && uploaded_bytes < total_bytes {
+        Some(pb.eta().as_secs())
+    } else {
+        None
+    };
+
+    ProgressReport {
+        build_id: build_id.to_string(),
+        uploaded_bytes,
+        total_bytes,
+        percent,
+        eta_seconds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_report() {
+        let pb = ProgressBar::hidden();
+        let report = build_report("b1", &pb);
+        assert_eq!(report.build_id, "b1");
+        assert_eq!((report.uploaded_bytes, report.total_bytes), (0, 0));
+        assert_eq!(report.percent, 0);
+        assert_eq!(report.eta_seconds, None);
+
+        pb.set_length(200);
+        pb.set_position(50);
+        let report = build_report("b1", &pb);
+        assert_eq!((report.uploaded_bytes, report.total_bytes), (50, 200));
+        assert_eq!(report.percent, 25);
+        assert!(report.eta_seconds.is_some());
+
+        // The percentage never exceeds 100, even past the expected length
+        pb.set_position(300);
+        let report = build_report("b1", &pb);
+        assert_eq!(report.percent, 100);
+        assert_eq!(report.eta_seconds, None);
+    }
+}
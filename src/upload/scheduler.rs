@@ -0,0 +1,357 @@
+use crate::api::Client;
+use crate::api::client::{UploadUrlPart, UploadedPart};
+use crate::error::{Error, Result};
+use crate::format::{format_rate, format_size};
+use crate::upload::stall::{StallWatch, retry_stalled};
+use bytes::Bytes;
+use futures::future::{AbortHandle, Abortable, BoxFuture};
+use futures::stream::{FuturesUnordered, StreamExt};
+use indicatif::ProgressBar;
+use log::{debug, info};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Never hedge a part that has been running for less than this
+const MIN_HEDGE_DELAY: Duration = Duration::from_secs(5);
+
+/// A part is a straggler once it has run this many times longer than the median part
+const HEDGE_FACTOR: u32 = 2;
+
+/// How often idle slots are checked for stragglers while waiting on uploads
+const HEDGE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Without `--log-every-part`, only the first part and every Nth part are logged
+const LOG_EVERY_NTH_PART: u64 = 50;
+
+/// How often a summary of the parts uploaded since the last one is logged
+const PART_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Per-upload record of completed part durations, used to spot stragglers
+#[derive(Debug, Default)]
+pub(crate) struct PartTimings {
+    durations: Vec<Duration>,
+}
+
+impl PartTimings {
+    fn record(&mut self, duration: Duration) {
+        self.durations.push(duration);
+    }
+
+    /// How long a part may run before a speculative duplicate is started
+    ///
+    /// Returns `None` until at least one part has completed.
+    fn hedge_delay(&self) -> Option<Duration> {
+        let mut sorted = self.durations.clone();
+        sorted.sort_unstable();
+        let median = *sorted.get(sorted.len() / 2)?;
+        Some((median * HEDGE_FACTOR).max(MIN_HEDGE_DELAY))
+    }
+}
+
+/// Sampled part-level debug logging, so uploads of thousands of parts stay readable
+///
+/// Only the first part and every [`LOG_EVERY_NTH_PART`]th part are logged individually;
+/// the rest are aggregated into periodic summaries. Failures are always logged.
+#[derive(Debug)]
+pub(crate) struct PartLog {
+    every_part: bool,
+    total_parts: usize,
+    completed: usize,
+    parts_since_summary: u64,
+    bytes_since_summary: u64,
+    last_summary: Instant,
+}
+
+impl PartLog {
+    pub(crate) fn new(every_part: bool, total_parts: usize) -> Self {
+        Self {
+            every_part,
+            total_parts,
+            completed: 0,
+            parts_since_summary: 0,
+            bytes_since_summary: 0,
+            last_summary: Instant::now(),
+        }
+    }
+
+    /// Whether events of this part are logged individually
+    fn logs_part(&self, part_number: u64) -> bool {
+        self.every_part || part_number == 1 || part_number.is_multiple_of(LOG_EVERY_NTH_PART)
+    }
+
+    /// Whether URL requests for the parts `start..=end` are logged
+    pub(crate) fn logs_range(&self, start: usize, end: usize) -> bool {
+        (start..=end).any(|n| self.logs_part(n as u64))
+    }
+
+    /// Record a completed part, returning the summary line when one is due
+    fn record(&mut self, bytes: u64, now: Instant) -> Option<String> {
+        self.completed += 1;
+        self.parts_since_summary += 1;
+        self.bytes_since_summary += bytes;
+
+        let elapsed = now.duration_since(self.last_summary);
+        let finished = self.completed == self.total_parts;
+        if self.every_part || (elapsed < PART_SUMMARY_INTERVAL && !finished) {
+            return None;
+        }
+
+        let summary = format!(
+            "Uploaded {}/{} parts ({} parts, {} in the last {:.0}s, {})",
+            self.completed,
+            self.total_parts,
+            self.parts_since_summary,
+            format_size(self.bytes_since_summary),
+            elapsed.as_secs_f64(),
+            format_rate(self.bytes_since_summary, elapsed)
+        );
+        self.parts_since_summary = 0;
+        self.bytes_since_summary = 0;
+        self.last_summary = now;
+        Some(summary)
+    }
+}
+
+/// State of a part with at least one attempt in flight
+struct RunningPart {
+    url: String,
+    data: Bytes,
+    started: Instant,
+    attempts: Vec<AbortHandle>,
+    /// Attempts that have not failed yet
+    active: usize,
+    /// Attempts that were aborted for sending no data
+    stalls: u32,
+}
+
+/// Part number, start and outcome of an attempt, which is `None` if the attempt stalled
+type AttemptResult = (u64, Instant, Option<Result<String>>);
+
+/// Upload a batch of parts with at most `parallel` requests in flight
+///
+/// Once every part of the batch has started, idle slots are used to re-upload
+/// straggler parts to the same presigned URL (S3 accepts repeated PUTs of a part);
+/// whichever attempt finishes first wins and the other is cancelled. With `parallel`
+/// of 1 parts are uploaded strictly in order and never duplicated. An attempt that sends
+/// no data for `stall_timeout` is aborted and the part retried.
+#[allow(clippy::too_many_lines)]
+pub(crate) async fn upload_batch(
+    client: &Client,
+    pb: &ProgressBar,
+    batch: Vec<(UploadUrlPart, Bytes)>,
+    parallel: usize,
+    stall_timeout: Option<Duration>,
+    timings: &mut PartTimings,
+    log: &mut PartLog,
+) -> Result<Vec<UploadedPart>> {
+    let mut pending: VecDeque<_> = batch.into();
+    let mut running: HashMap<u64, RunningPart> = HashMap::new();
+    let mut in_flight: FuturesUnordered<BoxFuture<'static, AttemptResult>> =
+        FuturesUnordered::new();
+    let mut uploaded = Vec::new();
+
+    loop {
+        while in_flight.len() < parallel.max(1)
+            && let Some((part, data)) = pending.pop_front()
+        {
+            if log.logs_part(part.part_number) {
+                debug!("Uploading part {} ({} bytes)", part.part_number, data.len());
+            }
+            let mut state = RunningPart {
+                url: part.url,
+                data,
+                started: Instant::now(),
+                attempts: Vec::new(),
+                active: 0,
+                stalls: 0,
+            };
+            in_flight.push(start_attempt(
+                client,
+                part.part_number,
+                &mut state,
+                stall_timeout,
+            ));
+            running.insert(part.part_number, state);
+        }
+
+        if pending.is_empty() && in_flight.len() < parallel {
+            start_hedges(
+                client,
+                &mut running,
+                &mut in_flight,
+                parallel,
+                stall_timeout,
+                timings,
+            );
+        }
+
+        let next = if running.values().any(|p| p.attempts.len() == 1) {
+            match tokio::time::timeout(HEDGE_CHECK_INTERVAL, in_flight.next()).await {
+                Ok(next) => next,
+                Err(_) => continue,
+            }
+        } else {
+            in_flight.next().await
+        };
+
+        let Some((part_number, attempt_started, result)) = next else {
+            break;
+        };
+
+        // The part already finished through another attempt
+        let Some(state) = running.get_mut(&part_number) else {
+            continue;
+        };
+
+        match result {
+            None => {
+                state.active -= 1;
+                state.stalls += 1;
+                if state.active > 0 {
+                    debug!("Attempt for part {part_number} stalled, waiting on duplicate");
+                    continue;
+                }
+                retry_stalled(
+                    &format!("Part {part_number}"),
+                    state.stalls,
+                    stall_timeout.unwrap_or_default(),
+                )?;
+                in_flight.push(start_attempt(client, part_number, state, stall_timeout));
+            }
+            Some(Ok(etag)) => {
+                let hedged = state.attempts.len() > 1;
+                for handle in &state.attempts {
+                    handle.abort();
+                }
+                timings.record(attempt_started.elapsed());
+                let size = state.data.len() as u64;
+                pb.inc(size);
+                running.remove(&part_number);
+
+                if log.logs_part(part_number) {
+                    if hedged {
+                        debug!("Part {part_number} uploaded successfully (hedged)");
+                    } else {
+                        debug!("Part {part_number} uploaded successfully");
+                    }
+                }
+                if let Some(summary) = log.record(size, Instant::now()) {
+                    debug!("{summary}");
+                }
+                uploaded.push(UploadedPart { part_number, etag });
+            }
+            Some(Err(e)) => {
+                // Another attempt for this part is still running; let it finish
+                state.active -= 1;
+                if state.active > 0 {
+                    debug!("Attempt for part {part_number} failed, waiting on duplicate: {e}");
+                    continue;
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(uploaded)
+}
+
+/// Start speculative duplicates of straggler parts on idle slots
+fn start_hedges(
+    client: &Client,
+    running: &mut HashMap<u64, RunningPart>,
+    in_flight: &mut FuturesUnordered<BoxFuture<'static, AttemptResult>>,
+    parallel: usize,
+    stall_timeout: Option<Duration>,
+    timings: &PartTimings,
+) {
+    let Some(delay) = timings.hedge_delay() else {
+        return;
+    };
+
+    let mut stragglers: Vec<_> = running
+        .iter_mut()
+        .filter(|(_, p)| p.attempts.len() == 1 && p.started.elapsed() > delay)
+        .collect();
+    // Slowest first
+    stragglers.sort_by_key(|(_, p)| p.started);
+
+    for (part_number, state) in stragglers {
+        if in_flight.len() >= parallel {
+            break;
+        }
+        info!(
+            "Part {part_number} is straggling ({:.1}s); starting a duplicate upload",
+            state.started.elapsed().as_secs_f64()
+        );
+        in_flight.push(start_attempt(client, *part_number, state, stall_timeout));
+    }
+}
+
+/// Start one upload attempt for a part, registering its abort handle
+fn start_attempt(
+    client: &Client,
+    part_number: u64,
+    state: &mut RunningPart,
+    stall_timeout: Option<Duration>,
+) -> BoxFuture<'static, AttemptResult> {
+    let (handle, registration) = AbortHandle::new_pair();
+    state.attempts.push(handle);
+    state.active += 1;
+
+    let client = client.clone();
+    let url = state.url.clone();
+    let data = state.data.clone();
+    let started = Instant::now();
+    Box::pin(async move {
+        let watch = StallWatch::new();
+        let progress_watch = watch.clone();
+        let upload = client.upload_part_with_progress(&url, data, move |_| progress_watch.touch());
+        let result = Abortable::new(watch.guard(stall_timeout, upload), registration)
+            .await
+            .unwrap_or_else(|_| {
+                Some(Err(Error::UploadError(format!(
+                    "Upload of part {part_number} was superseded"
+                ))))
+            });
+        (part_number, started, result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hedge_delay() {
+        let mut timings = PartTimings::default();
+        assert_eq!(timings.hedge_delay(), None);
+
+        timings.record(Duration::from_secs(1));
+        assert_eq!(timings.hedge_delay(), Some(MIN_HEDGE_DELAY));
+
+        timings.record(Duration::from_secs(10));
+        timings.record(Duration::from_secs(12));
+        timings.record(Duration::from_secs(40));
+        assert_eq!(timings.hedge_delay(), Some(Duration::from_secs(24)));
+    }
+
+    #[test]
+    fn test_part_log_sampling() {
+        let mut log = PartLog::new(false, 120);
+        assert!(log.logs_part(1));
+        assert!(!log.logs_part(2));
+        assert!(log.logs_part(100));
+        assert!(log.logs_range(41, 60));
+        assert!(!log.logs_range(51, 60));
+
+        let start = log.last_summary;
+        assert_eq!(log.record(1024, start + Duration::from_secs(1)), None);
+        let summary = log.record(1024, start + PART_SUMMARY_INTERVAL).unwrap();
+        assert!(summary.starts_with("Uploaded 2/120 parts (2 parts, 2.0 KiB"));
+        assert_eq!(log.parts_since_summary, 0);
+
+        let mut verbose = PartLog::new(true, 120);
+        assert!(verbose.logs_part(2));
+        assert_eq!(verbose.record(1024, start + PART_SUMMARY_INTERVAL), None);
+    }
+}
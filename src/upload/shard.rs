@@ -0,0 +1,122 @@
+//! Multipart uploads split across several machines (`--shard 1/2`)
+//!
+//! Every agent of a farm reads the same artifact from shared storage and initiates the
+//! upload with the same shard key, so the server hands them the same upload. Each agent
+//! uploads its contiguous range of parts and reports them; the server completes the
+//! upload once every shard has reported.
+
+use crate::error::{Error, Result};
+use crate::util::unix_time;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Most shards one upload can be split into
+pub const MAX_SHARDS: u32 = 64;
+
+/// This machine's share of an upload split across several machines
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct Shard {
+    /// 1-based index of this shard
+    pub index: u32,
+    pub count: u32,
+    /// Identifies the upload all shards join; together with the file name the server
+    /// uses it to return the same upload to every shard
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl Shard {
+    /// The part numbers this shard uploads out of `total_parts`, end exclusive
+    ///
+    /// Parts are split into contiguous ranges whose sizes differ by at most one part, so
+    /// 1000 parts over two shards are 1-500 and 501-1000. Shards beyond the number of
+    /// parts get an empty range.
+    #[must_use]
+    pub fn part_range(&self, total_parts: usize) -> Range<usize> {
+        let count = self.count as usize;
+        let index = self.index as usize - 1;
+        let start = total_parts * index / count;
+        let end = total_parts * (index + 1) / count;
+        start + 1..end + 1
+    }
+
+    /// Returns this shard with a key derived from `path` when none was given
+    ///
+    /// Agents see the same name, size and modification time for a file on shared storage,
+    /// so they derive the same key without further coordination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file's metadata cannot be read
+    pub fn with_default_key(self, path: &Path) -> Result<Self> {
+        if self.key.is_some() {
+            return Ok(self);
+        }
+        let metadata = std::fs::metadata(path)?;
+        let modified = unix_time(metadata.modified()?).as_millis();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        Ok(Self {
+            key: Some(format!("{name}:{}:{modified}", metadata.len())),
+            ..self
+        })
+    }
+}
+
+impl FromStr for Shard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::ConfigError(format!(
+                "Invalid shard '{s}' (expected INDEX/COUNT, e.g. 1/2, with at most {MAX_SHARDS} shards)"
+            ))
+        };
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let index: u32 = index.trim().parse().map_err(|_| invalid())?;
+        let count: u32 = count.trim().parse().map_err(|_| invalid())?;
+        if count == 0 || count > MAX_SHARDS || index == 0 || index > count {
+            return Err(invalid());
+        }
+        Ok(Self {
+            index,
+            count,
+            key: None,
+        })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard_part_ranges() {
+        let first: Shard = "1/2".parse().unwrap();
+        let second: Shard = "2/2".parse().unwrap();
+        assert_eq!(first.part_range(1000), 1..501);
+        assert_eq!(second.part_range(1000), 501..1001);
+        assert_eq!(second.part_range(7), 4..8);
+
+        let third: Shard = "3/3".parse().unwrap();
+        assert_eq!(third.part_range(10), 7..11);
+        assert!("2/3".parse::<Shard>().unwrap().part_range(1).is_empty());
+        assert_eq!("3/3".parse::<Shard>().unwrap().part_range(1), 1..2);
+
+        for invalid in ["0/2", "3/2", "1/0", "1", "a/b", "1/65"] {
+            assert!(invalid.parse::<Shard>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -1,7 +1,14 @@
 use crate::api::Client;
 use crate::config::Config;
 use crate::error::Result;
-use crate::upload::UploadOptions;
+use crate::format::format_size;
+use crate::plain::hide_in_plain_mode;
+use crate::state::StateWriter;
+use crate::upload::finalize::finalize;
+use crate::upload::progress::ProgressReporter;
+use crate::upload::stall::{StallWatch, retry_stalled};
+use crate::upload::{UploadOptions, build_upload_request, log_storage_target, store_receipt};
+use bytes::Bytes;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::info;
 use std::path::Path;
@@ -30,25 +37,20 @@ pub async fn upload_single_part(
         .and_then(|n| n.to_str())
         .ok_or_else(|| crate::error::Error::ConfigError("Invalid filename".to_string()))?;
 
-    info!("Uploading {} ({:.2} MB)", filename, file_size / 1024 / 1024);
+    info!("Uploading {filename} ({})", format_size(file_size));
 
-    let client = Client::new(config.clone());
+    let client = Client::try_new(config.clone())?;
 
     let upload_response = client
-        .request_upload_url(
-            &options.name,
-            filename,
-            file_size,
-            &options.platform,
-            options.description.clone(),
-            options.upload_timeout,
-            options.auto_delete,
-            options.deletion_policy.clone(),
-            options.details.clone(),
-            options.tags.clone(),
-        )
+        .request_upload_url_with(&build_upload_request(filename, file_size, &options, false))
         .await?;
 
+    log_storage_target(
+        options.storage_region.as_deref(),
+        upload_response.storage_region.as_deref(),
+        &upload_response.upload_url,
+    );
+
     // Notify about upload initiation
     if let Some(callback) = &options.on_upload_initiated {
         callback(
@@ -58,7 +60,7 @@ pub async fn upload_single_part(
         );
     }
 
-    let file_data = tokio::fs::read(file_path).await?;
+    let file_data = Bytes::from(tokio::fs::read(file_path).await?);
 
     // Use provided progress bar or create a new one
     let pb = if let Some(pb) = options.progress_bar.clone() {
@@ -76,20 +78,58 @@ pub async fn upload_single_part(
                 .expect("Failed to set progress bar template")
                 .progress_chars("#>-"),
         );
+        hide_in_plain_mode(&pb);
         pb
     };
 
-    // Upload with progress tracking
-    let pb_clone = pb.clone();
-    client
-        .upload_to_url_with_progress(&upload_response.upload_url, file_data, move |uploaded| {
-            pb_clone.set_position(uploaded);
-        })
-        .await?;
+    let reporter = (options.report_progress && !options.deterministic).then(|| {
+        ProgressReporter::spawn(client.clone(), upload_response.build_id.clone(), pb.clone())
+    });
+    let state = StateWriter::spawn(
+        &upload_response.build_id,
+        &options.name,
+        filename,
+        pb.clone(),
+    );
+
+    // Upload with progress tracking, starting over if the upload stalls
+    let watch = StallWatch::new();
+    for stalls in 1.. {
+        let pb_clone = pb.clone();
+        let progress_watch = watch.clone();
+        let upload = client.upload_to_url_with_progress(
+            &upload_response.upload_url,
+            file_data.clone(),
+            move |uploaded| {
+                progress_watch.touch();
+                pb_clone.set_position(uploaded);
+            },
+        );
+        match watch.guard(options.stall_timeout, upload).await {
+            Some(result) => {
+                result?;
+                break;
+            }
+            None => retry_stalled(
+                &format!("Upload of {filename}"),
+                stalls,
+                options.stall_timeout.unwrap_or_default(),
+            )?,
+        }
+    }
 
-    pb.finish_with_message("Upload complete");
+    drop(reporter);
 
-    client.complete_upload(&upload_response.build_id).await?;
+    let completed = finalize(
+        &client,
+        &upload_response.build_id,
+        &pb,
+        options.finalize_timeout,
+        client.complete_upload(&upload_response.build_id),
+    )
+    .await?;
+    state.complete();
+    store_receipt(&completed);
 
     info!("Build ID: {}", upload_response.build_id);
 
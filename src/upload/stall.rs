@@ -0,0 +1,117 @@
+//! Detection of uploads that stop sending data
+//!
+//! A connection can stall without failing, e.g. behind a proxy that stops forwarding data,
+//! and the upload then waits forever. Uploads record when they last sent bytes in a
+//! [`StallWatch`]; an upload that sends nothing for the stall timeout is aborted and
+//! retried, up to [`MAX_STALL_RETRIES`] times.
+
+use crate::error::{Error, Result};
+use log::warn;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long an upload may send no bytes before it is retried, unless `--stall-timeout` is given
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// Retries of a stalled upload before it fails
+pub const MAX_STALL_RETRIES: u32 = 3;
+
+/// How often a running upload is checked for a stall, at most
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When an upload last sent bytes, shared with its progress callback
+#[derive(Debug, Clone)]
+pub(crate) struct StallWatch {
+    started: Instant,
+    /// Milliseconds from `started` to the last bytes sent
+    last_sent: Arc<AtomicU64>,
+}
+
+impl StallWatch {
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_sent: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Record that bytes were sent
+    pub(crate) fn touch(&self) {
+        #[allow(clippy::cast_possible_truncation)]
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        self.last_sent.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Time since bytes were last sent
+    fn idle(&self) -> Duration {
+        self.started.elapsed().saturating_sub(Duration::from_millis(
+            self.last_sent.load(Ordering::Relaxed),
+        ))
+    }
+
+    /// Run `upload`, giving up once it has sent no bytes for `timeout` (never without one)
+    ///
+    /// Returns `None` if the upload stalled.
+    pub(crate) async fn guard<T>(
+        &self,
+        timeout: Option<Duration>,
+        upload: impl Future<Output = T>,
+    ) -> Option<T> {
+        let Some(timeout) = timeout else {
+            return Some(upload.await);
+        };
+        self.touch();
+        tokio::select! {
+            result = upload => Some(result),
+            () = self.stalled(timeout) => None,
+        }
+    }
+
+    async fn stalled(&self, timeout: Duration) {
+        while let Some(left) = timeout
+            .checked_sub(self.idle())
+            .filter(|left| !left.is_zero())
+        {
+            tokio::time::sleep(left.min(STALL_CHECK_INTERVAL)).await;
+        }
+    }
+}
+
+/// Warn that `what` stalled for the `stalls`th time and is retried, or fail once it has
+/// stalled more than [`MAX_STALL_RETRIES`] times
+///
+/// # Errors
+///
+/// Returns an upload error once the retries are used up
+pub(crate) fn retry_stalled(what: &str, stalls: u32, timeout: Duration) -> Result<()> {
+    let secs = timeout.as_secs_f64();
+    if stalls > MAX_STALL_RETRIES {
+        return Err(Error::UploadError(format!(
+            "{what} stalled {stalls} times (no data sent for {secs:.0}s); check the network or raise --stall-timeout"
+        )));
+    }
+    warn!("{what} stalled (no data sent for {secs:.0}s); retrying ({stalls}/{MAX_STALL_RETRIES})");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_watch() {
+        let watch = StallWatch::new();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(watch.idle() >= Duration::from_millis(20));
+        watch.clone().touch();
+        assert!(watch.idle() < Duration::from_millis(20));
+
+        let timeout = Duration::from_secs(30);
+        assert!(retry_stalled("Part 3", 1, timeout).is_ok());
+        assert!(retry_stalled("Part 3", MAX_STALL_RETRIES, timeout).is_ok());
+        let error = retry_stalled("Part 3", MAX_STALL_RETRIES + 1, timeout).unwrap_err();
+        assert!(error.to_string().contains("Part 3 stalled 4 times"));
+    }
+}
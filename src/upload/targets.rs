@@ -0,0 +1,886 @@
+//! Files selected for an upload and the requests they become
+//!
+//! The `upload` command expands its file arguments or manifest into [`UploadTarget`]s, each
+//! carrying its per-file overrides, and turns them into API requests for `--dry-run`,
+//! `--prepare-only` and the upload itself.
+
+use crate::api::client::{Arch, BuildPlatform, DeletionPolicy, ProjectSettings, UploadRequest};
+use crate::archive::{
+    StreamingZip, app_bundle_platform, directory_archive_name, is_app_bundle, is_webgl_build,
+};
+use crate::artifact::{ArtifactInfo, sniff_platform};
+use crate::budget::SizeBudgetCheck;
+use crate::checksums::sha256_file;
+use crate::error::{Error, Result};
+use crate::files::{
+    IgnoreRules, NUNUIGNORE_FILE, filter_excluded, filter_ignored, is_excluded, parse_file_spec,
+};
+use crate::manifest::Manifest;
+use crate::naming::generate_build_name;
+use crate::session::{SessionFile, UploadSession};
+use crate::tags::{MAX_TAG_LEN, merge_tags, platform_tag};
+use crate::upload::{UploadOptions, build_upload_request, uses_multipart};
+use glob::glob;
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Infer platform from file extension, falling back to the file's contents
+///
+/// # Errors
+///
+/// Returns an error if the platform cannot be inferred from the file extension or contents
+pub fn infer_platform(file_path: &str) -> Result<BuildPlatform> {
+    let path = Path::new(file_path);
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    if path.is_dir() && extension != "app" {
+        if is_webgl_build(path) {
+            return Ok(BuildPlatform::Webgl);
+        }
+        return Err(Error::ConfigError(format!(
+            "Cannot infer platform for directory '{file_path}'. Please specify --platform explicitly"
+        )));
+    }
+
+    match extension.as_str() {
+        "exe" | "msi" => Ok(BuildPlatform::Windows),
+        "dmg" | "pkg" => Ok(BuildPlatform::Macos),
+        "ipa" => Ok(BuildPlatform::IosNative),
+        "apk" | "aab" => Ok(BuildPlatform::Android),
+        "deb" | "rpm" | "appimage" => Ok(BuildPlatform::Linux),
+        "nsp" | "xci" => Ok(BuildPlatform::Switch),
+        "app" => app_bundle_platform(path).ok_or_else(|| {
+            Error::ConfigError("Cannot infer platform for .app bundle without an Info.plist. Please specify --platform explicitly (macos or ios-simulator)".to_string())
+        }),
+        _ => {
+            // Fall back to the file's contents (e.g. a zipped APK or an extensionless binary)
+            if let Some(platform) = sniff_platform(path)? {
+                debug!(
+                    "Inferred platform {} for {file_path} from its contents",
+                    platform.as_str()
+                );
+                return Ok(platform);
+            }
+            if ["zip", "tar", "gz", "7z", "tgz", "bz2"].contains(&extension.as_str()) {
+                Err(Error::ConfigError(format!(
+                    "Cannot infer platform for archive files (.{extension}). Please specify --platform explicitly"
+                )))
+            } else {
+                Err(Error::ConfigError(format!(
+                    "Cannot infer platform from file extension '.{extension}'. Please specify --platform explicitly"
+                )))
+            }
+        }
+    }
+}
+
+/// Expand glob patterns to file paths
+///
+/// This function handles both regular file paths and glob patterns.
+/// Glob patterns that match nothing are skipped with a warning; plain paths
+/// are kept as-is (a missing file fails later with a clear error).
+///
+/// # Errors
+///
+/// Returns an error if glob pattern parsing fails
+pub fn expand_globs(patterns: &[String]) -> Result<Vec<String>> {
+    let mut expanded_files = Vec::new();
+    let mut seen = HashSet::new();
+
+    for pattern in patterns {
+        // Check if pattern contains glob characters
+        if pattern.contains('*') || pattern.contains('?') || pattern.contains('[') {
+            debug!("Expanding glob pattern: {pattern}");
+
+            match glob(pattern) {
+                Ok(paths) => {
+                    let mut matched_any = false;
+                    for entry in paths {
+                        match entry {
+                            Ok(path) => {
+                                matched_any = true;
+                                let path_str = path.to_string_lossy().to_string();
+
+                                // Only add files and .app bundles (skip other directories and
+                                // anything inside a bundle) and avoid duplicates
+                                let inside_bundle = path.ancestors().skip(1).any(is_app_bundle);
+                                if (path.is_file() || is_app_bundle(&path))
+                                    && !inside_bundle
+                                    && seen.insert(path_str.clone())
+                                {
+                                    expanded_files.push(path_str);
+                                }
+                            }
+                            Err(e) => {
+                                warn!("Error reading glob entry: {e}");
+                            }
+                        }
+                    }
+
+                    if matched_any {
+                        debug!(
+                            "Pattern '{pattern}' matched {} file(s)",
+                            expanded_files.len() - (expanded_files.len() - seen.len())
+                        );
+                    } else {
+                        warn!("Pattern '{pattern}' did not match any files");
+                    }
+                }
+                Err(e) => {
+                    return Err(Error::ConfigError(format!(
+                        "Invalid glob pattern '{pattern}': {e}"
+                    )));
+                }
+            }
+        } else {
+            // Not a glob pattern, use as-is (deduplicate)
+            if seen.insert(pattern.clone()) {
+                expanded_files.push(pattern.clone());
+            }
+        }
+    }
+
+    Ok(expanded_files)
+}
+
+/// A file to upload together with its per-file build settings
+#[derive(Debug)]
+pub struct UploadTarget {
+    pub path: String,
+    /// Build name template, rendered once the platform is known
+    pub name_template: String,
+    /// Number of files sharing the template (the filename is appended if more than one)
+    pub file_count: usize,
+    /// Overrides `--platform` and inference from the file extension
+    pub platform: Option<BuildPlatform>,
+    /// Overrides `--description`
+    pub description: Option<String>,
+    /// Overrides `--tags`
+    pub tags: Option<Vec<String>>,
+    /// Merged over `--meta` and config file metadata
+    pub metadata: BTreeMap<String, String>,
+    /// Details read from the file itself, filled in by [`inspect_artifacts`]
+    pub artifact: Option<ArtifactInfo>,
+    /// `--arch`, or detected from the binary by [`inspect_artifacts`]
+    pub arch: Option<Arch>,
+    /// Final build name set by `--script`, used instead of rendering `name_template`
+    pub name: Option<String>,
+    /// Size measured against the platform's budget by [`check_size_budgets`]
+    pub size_budget: Option<SizeBudgetCheck>,
+    /// Request recorded by `--prepare-only`, set by [`attach_session`]
+    pub prepared: Option<UploadRequest>,
+}
+
+/// Expand patterns and drop files matched by `--exclude` or `.nunuignore`
+///
+/// # Errors
+///
+/// Returns an error if a pattern is invalid or every match was excluded
+pub fn select_files(
+    patterns: &[String],
+    excludes: &[glob::Pattern],
+    ignore: Option<&IgnoreRules>,
+) -> Result<Vec<String>> {
+    let files = expand_globs(patterns)?;
+    if files.is_empty() {
+        return Err(Error::ConfigError(
+            "No files matched the provided patterns".to_string(),
+        ));
+    }
+
+    let mut files = filter_excluded(files, excludes);
+    if let Some(rules) = ignore {
+        files = filter_ignored(files, rules);
+    }
+    if files.is_empty() {
+        return Err(Error::ConfigError(format!(
+            "All matched files were excluded by --exclude patterns or {NUNUIGNORE_FILE}"
+        )));
+    }
+    Ok(files)
+}
+
+/// Validate tags (each tag must be 1-50 characters)
+///
+/// # Errors
+///
+/// Returns an error if a tag is empty or too long
+pub fn validate_tags(tags: &[String]) -> Result<()> {
+    for tag in tags {
+        if tag.is_empty() {
+            return Err(Error::ConfigError("Tags cannot be empty".to_string()));
+        }
+        if tag.len() > MAX_TAG_LEN {
+            return Err(Error::ConfigError(format!(
+                "Tag '{}' exceeds maximum length of {MAX_TAG_LEN} characters (length: {})",
+                tag,
+                tag.len()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Apply the project's default auto-delete, deletion policy and tags to `options`, except
+/// those set explicitly
+pub fn apply_project_settings(
+    options: &mut UploadOptions,
+    settings: ProjectSettings,
+    explicit_auto_delete: bool,
+    explicit_policy: bool,
+    explicit_tags: bool,
+) {
+    if !explicit_auto_delete && let Some(auto_delete) = settings.auto_delete {
+        info!(
+            "Auto-delete {} by project default",
+            if auto_delete { "enabled" } else { "disabled" }
+        );
+        options.auto_delete = auto_delete;
+    }
+    if !explicit_policy && let Some(policy) = settings.deletion_policy {
+        match policy.parse::<DeletionPolicy>() {
+            Ok(policy) => {
+                debug!("Deletion policy {} by project default", policy.as_str());
+                options.deletion_policy = Some(policy.as_str().to_string());
+            }
+            Err(e) => warn!("Ignoring the project's default deletion policy: {e}"),
+        }
+    }
+    if !explicit_tags && !settings.default_tags.is_empty() {
+        info!(
+            "Adding project default tags: {}",
+            settings.default_tags.join(", ")
+        );
+        options.tags = merge_tags(options.tags.take(), &settings.default_tags);
+    }
+}
+
+/// Resolve a target's tags: its own or the shared ones, plus `platform:<p>` with `--auto-tags`
+#[must_use]
+pub fn target_tags(
+    tags: Option<Vec<String>>,
+    base: Option<&Vec<String>>,
+    platform: &BuildPlatform,
+    auto_tags: bool,
+) -> Option<Vec<String>> {
+    let tags = tags.or_else(|| base.cloned());
+    if auto_tags {
+        merge_tags(tags, &[platform_tag(platform.as_str())])
+    } else {
+        tags
+    }
+}
+
+/// Build upload targets from file arguments with optional per-file overrides
+///
+/// # Errors
+///
+/// Returns an error if an argument is malformed or no files remain after filtering
+pub fn file_spec_targets(
+    files: &[String],
+    name: &str,
+    excludes: &[glob::Pattern],
+    ignore: Option<&IgnoreRules>,
+) -> Result<Vec<UploadTarget>> {
+    let specs = files
+        .iter()
+        .map(|f| parse_file_spec(f))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Expand each argument separately so its overrides apply to its own matches
+    let mut seen = HashSet::new();
+    let mut selected = Vec::new();
+    for (index, spec) in specs.iter().enumerate() {
+        for path in expand_globs(std::slice::from_ref(&spec.pattern))? {
+            if seen.insert(path.clone()) {
+                selected.push((index, path));
+            }
+        }
+    }
+    if selected.is_empty() {
+        return Err(Error::ConfigError(
+            "No files matched the provided patterns".to_string(),
+        ));
+    }
+
+    selected.retain(|(_, path)| {
+        !is_excluded(path, excludes)
+            && !ignore
+                .is_some_and(|rules| rules.is_ignored(Path::new(path), Path::new(path).is_dir()))
+    });
+    if selected.is_empty() {
+        return Err(Error::ConfigError(format!(
+            "All matched files were excluded by --exclude patterns or {NUNUIGNORE_FILE}"
+        )));
+    }
+
+    // Files sharing a template get their filename appended if there are several
+    let count = |index: Option<usize>| {
+        selected
+            .iter()
+            .filter(|(i, _)| match index {
+                Some(index) => *i == index,
+                None => specs[*i].name.is_none(),
+            })
+            .count()
+    };
+
+    Ok(selected
+        .iter()
+        .map(|(index, path)| {
+            let spec = &specs[*index];
+            UploadTarget {
+                name_template: spec.name.clone().unwrap_or_else(|| name.to_string()),
+                file_count: count(spec.name.as_ref().map(|_| *index)),
+                path: path.clone(),
+                platform: spec.platform.clone(),
+                description: None,
+                tags: None,
+                metadata: BTreeMap::new(),
+                artifact: None,
+                arch: None,
+                name: None,
+                size_budget: None,
+                prepared: None,
+            }
+        })
+        .collect())
+}
+
+/// Build upload targets from a manifest, expanding each artifact's path
+///
+/// # Errors
+///
+/// Returns an error if an artifact is invalid or matches no files
+pub fn manifest_targets(
+    manifest: &Manifest,
+    excludes: &[glob::Pattern],
+    ignore: Option<&IgnoreRules>,
+) -> Result<Vec<UploadTarget>> {
+    let mut targets = Vec::new();
+
+    for artifact in &manifest.artifacts {
+        let platform = artifact.platform()?;
+        if let Some(ref tags) = artifact.tags {
+            validate_tags(tags)?;
+        }
+
+        let files = select_files(std::slice::from_ref(&artifact.path), excludes, ignore)?;
+        let file_count = files.len();
+        for path in files {
+            targets.push(UploadTarget {
+                name_template: artifact.name.clone(),
+                file_count,
+                path,
+                platform: platform.clone(),
+                description: artifact.description.clone(),
+                tags: artifact.tags.clone(),
+                metadata: artifact.metadata.clone(),
+                artifact: None,
+                arch: None,
+                name: None,
+                size_budget: None,
+                prepared: None,
+            });
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Run the `--script` hook for every target, dropping the files it skips
+///
+/// # Errors
+///
+/// Returns an error if the script fails, sets an invalid platform, or a platform cannot
+/// be inferred
+#[cfg(feature = "lua")]
+pub fn apply_upload_script(
+    script_path: &Path,
+    targets: Vec<UploadTarget>,
+    platform: Option<&BuildPlatform>,
+    base_options: &UploadOptions,
+) -> Result<Vec<UploadTarget>> {
+    use crate::scripting::{ScriptedUpload, UploadScript};
+
+    let script = UploadScript::load(script_path)?;
+    let mut kept = Vec::with_capacity(targets.len());
+    for mut target in targets {
+        let file_platform = match target.platform.as_ref().or(platform) {
+            Some(p) => p.clone(),
+            None => infer_platform(&target.path)?,
+        };
+        let mut upload = ScriptedUpload {
+            file: target.path.clone(),
+            name: generate_build_name(
+                &target.name_template,
+                &target.path,
+                target.file_count,
+                file_platform.as_str(),
+                base_options.details.as_ref(),
+            ),
+            platform: file_platform.as_str().to_string(),
+            description: target
+                .description
+                .clone()
+                .or_else(|| base_options.description.clone()),
+            tags: target
+                .tags
+                .clone()
+                .or_else(|| base_options.tags.clone())
+                .unwrap_or_default(),
+            metadata: target.metadata.clone(),
+        };
+
+        if !script.apply(&mut upload)? {
+            info!("Upload script skipped {}", target.path);
+            continue;
+        }
+        validate_tags(&upload.tags)?;
+        target.platform = Some(upload.platform.parse()?);
+        target.name = Some(upload.name);
+        target.description = upload.description;
+        target.tags = Some(upload.tags);
+        target.metadata = upload.metadata;
+        kept.push(target);
+    }
+    Ok(kept)
+}
+
+/// Fail `--script` in builds without the `lua` feature
+///
+/// # Errors
+///
+/// Always returns an error
+#[cfg(not(feature = "lua"))]
+pub fn apply_upload_script(
+    _script_path: &Path,
+    _targets: Vec<UploadTarget>,
+    _platform: Option<&BuildPlatform>,
+    _base_options: &UploadOptions,
+) -> Result<Vec<UploadTarget>> {
+    Err(Error::ConfigError(
+        "--script requires a build of nunu-cli with the `lua` feature".to_string(),
+    ))
+}
+
+/// What an upload target will be uploaded as, shown by `--dry-run` and recorded by
+/// `--prepare-only`
+pub struct PreparedRequest {
+    pub filename: String,
+    pub file_size: u64,
+    pub directory: bool,
+    pub multipart: bool,
+    pub options: UploadOptions,
+    pub request: UploadRequest,
+}
+
+/// Build the options and initial API request of an upload target without uploading it
+///
+/// # Errors
+///
+/// Returns an error if a platform cannot be inferred or a file cannot be read
+pub fn prepare_request(
+    target: &UploadTarget,
+    platform: Option<&BuildPlatform>,
+    base_options: &UploadOptions,
+    auto_tags: bool,
+) -> Result<PreparedRequest> {
+    let file_path = &target.path;
+    let file_platform = match target
+        .prepared
+        .as_ref()
+        .and_then(|r| r.platform.parse::<BuildPlatform>().ok())
+        .or_else(|| target.platform.clone())
+        .or_else(|| platform.cloned())
+    {
+        Some(p) => p,
+        None => infer_platform(file_path)?,
+    };
+    let path = Path::new(file_path);
+    let directory = path.is_dir();
+    // Directories are archived on the fly; the archive size is known up front
+    let file_size = if directory {
+        StreamingZip::plan(path, IgnoreRules::load_from_current_dir()?.as_ref())?.size()
+    } else {
+        std::fs::metadata(file_path)
+            .map_err(|e| {
+                Error::ConfigError(format!("Failed to read file metadata for {file_path}: {e}"))
+            })?
+            .len()
+    };
+    let filename = if directory {
+        directory_archive_name(path)
+    } else {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| Error::ConfigError(format!("Invalid filename: {file_path}")))?
+            .to_string()
+    };
+
+    let options = UploadOptions {
+        name: target.name.clone().unwrap_or_else(|| {
+            generate_build_name(
+                &target.name_template,
+                file_path,
+                target.file_count,
+                file_platform.as_str(),
+                base_options.details.as_ref(),
+            )
+        }),
+        platform: file_platform.as_str().to_string(),
+        description: target
+            .description
+            .clone()
+            .or_else(|| base_options.description.clone()),
+        tags: target_tags(
+            target.tags.clone(),
+            base_options.tags.as_ref(),
+            &file_platform,
+            auto_tags,
+        ),
+        details: base_options.details.as_ref().map(|d| {
+            d.with_custom(&target.metadata)
+                .with_artifact(target.artifact.clone())
+                .with_size_budget(target.size_budget)
+        }),
+        force_multipart: base_options.force_multipart || directory,
+        arch: target.arch,
+        ..base_options.clone()
+    };
+    let options = match &target.prepared {
+        Some(request) => options.with_request(request),
+        None => options,
+    };
+    let multipart = uses_multipart(file_size, &options);
+    let request = build_upload_request(&filename, file_size, &options, multipart);
+
+    Ok(PreparedRequest {
+        filename,
+        file_size,
+        directory,
+        multipart,
+        options,
+        request,
+    })
+}
+
+/// Record the hashes and upload requests of `targets` for `--prepare-only`
+///
+/// # Errors
+///
+/// Returns an error if a target is a directory or a file cannot be read or hashed
+pub fn prepare_session(
+    project_id: Option<String>,
+    targets: &[UploadTarget],
+    platform: Option<&BuildPlatform>,
+    base_options: &UploadOptions,
+    auto_tags: bool,
+) -> Result<UploadSession> {
+    let mut files = Vec::new();
+    for target in targets {
+        let prepared = prepare_request(target, platform, base_options, auto_tags)?;
+        // A streamed archive has no stable hash to check on the uploading machine
+        if prepared.directory {
+            return Err(Error::ConfigError(format!(
+                "{} is a directory; archive it before preparing an upload session",
+                target.path
+            )));
+        }
+        files.push(SessionFile {
+            sha256: sha256_file(Path::new(&target.path))?,
+            request: prepared.request,
+        });
+    }
+
+    Ok(UploadSession::new(project_id, files))
+}
+
+/// Match `targets` to the uploads prepared in `session` by size and SHA-256 (`--from-session`)
+///
+/// # Errors
+///
+/// Returns an error if a file is not part of the session, or the session prepared files
+/// that were not given
+pub fn attach_session(targets: &mut [UploadTarget], session: &UploadSession) -> Result<()> {
+    let mut matched = HashSet::new();
+    for target in targets.iter_mut() {
+        let path = Path::new(&target.path);
+        if path.is_dir() {
+            return Err(Error::SessionError(format!(
+                "{} is a directory; upload sessions only contain files",
+                target.path
+            )));
+        }
+        let size = std::fs::metadata(path)
+            .map_err(|e| {
+                Error::ConfigError(format!(
+                    "Failed to read file metadata for {}: {e}",
+                    target.path
+                ))
+            })?
+            .len();
+        let sha256 = sha256_file(path)?;
+        let file = session.find(size, &sha256).ok_or_else(|| {
+            Error::SessionError(format!(
+                "{} does not match any file prepared in the session (it may have changed since)",
+                target.path
+            ))
+        })?;
+
+        if path.file_name().and_then(|n| n.to_str()) != Some(file.request.file_name.as_str()) {
+            warn!(
+                "{} matches {} from the session by content but was renamed",
+                target.path, file.request.file_name
+            );
+        }
+        matched.insert(sha256);
+        target.prepared = Some(file.request.clone());
+    }
+
+    let missing: Vec<_> = session
+        .files
+        .iter()
+        .filter(|f| !matched.contains(&f.sha256))
+        .map(|f| f.request.file_name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::SessionError(format!(
+            "The session also prepared {}, which {} not given",
+            missing.join(", "),
+            if missing.len() == 1 { "was" } else { "were" }
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::compile_excludes;
+
+    fn write_files(dir: &Path, names: &[&str]) {
+        for name in names {
+            std::fs::write(dir.join(name), name.as_bytes()).unwrap();
+        }
+    }
+
+    fn path_in(dir: &Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_infer_platform() {
+        assert_eq!(infer_platform("Game.EXE").unwrap().as_str(), "windows");
+        assert_eq!(infer_platform("game.aab").unwrap().as_str(), "android");
+        assert_eq!(infer_platform("game.AppImage").unwrap().as_str(), "linux");
+        assert_eq!(infer_platform("game.nsp").unwrap().as_str(), "switch");
+        assert_eq!(infer_platform("Game.XCI").unwrap().as_str(), "switch");
+
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path(), &["build.zip", "notes.txt"]);
+        let err = infer_platform(&path_in(dir.path(), "build.zip")).unwrap_err();
+        assert!(err.to_string().contains("archive files (.zip)"));
+        let err = infer_platform(&path_in(dir.path(), "notes.txt")).unwrap_err();
+        assert!(err.to_string().contains("file extension '.txt'"));
+        let err = infer_platform(&dir.path().to_string_lossy()).unwrap_err();
+        assert!(err.to_string().contains("for directory"));
+
+        let web = dir.path().join("Web");
+        std::fs::create_dir_all(web.join("Build")).unwrap();
+        write_files(&web, &["index.html", "Build/Web.wasm"]);
+        assert_eq!(
+            infer_platform(&web.to_string_lossy()).unwrap().as_str(),
+            "webgl"
+        );
+    }
+
+    #[test]
+    fn test_expand_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path(), &["a.apk", "b.apk", "c.txt"]);
+        std::fs::create_dir(dir.path().join("d.apk")).unwrap();
+
+        let files = expand_globs(&[
+            path_in(dir.path(), "*.apk"),
+            path_in(dir.path(), "a.apk"),
+            path_in(dir.path(), "*.ipa"),
+            "missing.exe".to_string(),
+        ])
+        .unwrap();
+        // Directories are skipped, duplicates dropped and plain paths kept as-is
+        assert_eq!(
+            files,
+            [
+                path_in(dir.path(), "a.apk"),
+                path_in(dir.path(), "b.apk"),
+                "missing.exe".to_string()
+            ]
+        );
+
+        assert!(expand_globs(&[path_in(dir.path(), "[")]).is_err());
+    }
+
+    #[test]
+    fn test_file_spec_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path(), &["a.apk", "b.apk", "tools.bin"]);
+        let files = [
+            path_in(dir.path(), "*.apk"),
+            format!("{}=linux::name=Tools", path_in(dir.path(), "tools.bin")),
+        ];
+
+        let targets = file_spec_targets(&files, "Game", &[], None).unwrap();
+        let summary: Vec<_> = targets
+            .iter()
+            .map(|t| {
+                (
+                    t.path.clone(),
+                    t.name_template.as_str(),
+                    t.file_count,
+                    t.platform.as_ref().map(BuildPlatform::as_str),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (path_in(dir.path(), "a.apk"), "Game", 2, None),
+                (path_in(dir.path(), "b.apk"), "Game", 2, None),
+                (path_in(dir.path(), "tools.bin"), "Tools", 1, Some("linux")),
+            ]
+        );
+
+        let excludes = compile_excludes(&["b.apk".to_string()]).unwrap();
+        let targets = file_spec_targets(&files[..1], "Game", &excludes, None).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].file_count, 1);
+
+        let excludes = compile_excludes(&["*.apk".to_string()]).unwrap();
+        let err = file_spec_targets(&files[..1], "Game", &excludes, None).unwrap_err();
+        assert!(err.to_string().contains("were excluded"));
+        let err =
+            file_spec_targets(&[path_in(dir.path(), "*.ipa")], "Game", &[], None).unwrap_err();
+        assert!(err.to_string().contains("No files matched"));
+    }
+
+    #[test]
+    fn test_validate_tags() {
+        assert!(validate_tags(&["nightly".to_string(), "qa".to_string()]).is_ok());
+        assert!(validate_tags(&[String::new()]).is_err());
+        assert!(validate_tags(&["x".repeat(MAX_TAG_LEN)]).is_ok());
+        assert!(validate_tags(&["x".repeat(MAX_TAG_LEN + 1)]).is_err());
+    }
+
+    #[test]
+    fn test_target_tags() {
+        let base = vec!["nightly".to_string()];
+        let own = Some(vec!["qa".to_string()]);
+
+        assert_eq!(
+            target_tags(None, Some(&base), &BuildPlatform::Linux, false),
+            Some(base.clone())
+        );
+        assert_eq!(
+            target_tags(own.clone(), Some(&base), &BuildPlatform::Linux, false),
+            own
+        );
+        assert_eq!(
+            target_tags(own, Some(&base), &BuildPlatform::Android, true),
+            Some(vec!["qa".to_string(), "platform:android".to_string()])
+        );
+        assert_eq!(
+            target_tags(None, None, &BuildPlatform::Windows, true),
+            Some(vec!["platform:windows".to_string()])
+        );
+        assert_eq!(
+            target_tags(None, None, &BuildPlatform::Windows, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_project_settings() {
+        let settings = ProjectSettings {
+            auto_delete: Some(true),
+            deletion_policy: Some("oldest".to_string()),
+            default_tags: vec!["team".to_string()],
+        };
+
+        let mut options = UploadOptions {
+            tags: Some(vec!["qa".to_string()]),
+            ..UploadOptions::default()
+        };
+        apply_project_settings(&mut options, settings.clone(), false, false, false);
+        assert!(options.auto_delete);
+        assert_eq!(options.deletion_policy.as_deref(), Some("oldest"));
+        assert_eq!(
+            options.tags,
+            Some(vec!["qa".to_string(), "team".to_string()])
+        );
+
+        // Explicit options win over the project defaults
+        let mut options = UploadOptions {
+            deletion_policy: Some("least_recent".to_string()),
+            tags: Some(vec!["qa".to_string()]),
+            ..UploadOptions::default()
+        };
+        apply_project_settings(&mut options, settings, true, true, true);
+        assert!(!options.auto_delete);
+        assert_eq!(options.deletion_policy.as_deref(), Some("least_recent"));
+        assert_eq!(options.tags, Some(vec!["qa".to_string()]));
+
+        // An unknown policy is ignored
+        let mut options = UploadOptions::default();
+        let settings = ProjectSettings {
+            deletion_policy: Some("newest".to_string()),
+            ..ProjectSettings::default()
+        };
+        apply_project_settings(&mut options, settings, false, false, false);
+        assert_eq!(options.deletion_policy, None);
+    }
+
+    #[test]
+    fn test_prepare_and_attach_session() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path(), &["game.apk", "tools.exe"]);
+        let files = [path_in(dir.path(), "*.*")];
+        let base_options = UploadOptions::default();
+
+        let targets = file_spec_targets(&files, "Game", &[], None).unwrap();
+        let session =
+            prepare_session(Some("p".to_string()), &targets, None, &base_options, true).unwrap();
+        assert_eq!(session.project_id.as_deref(), Some("p"));
+        assert_eq!(session.files.len(), 2);
+
+        let mut targets = file_spec_targets(&files, "Game", &[], None).unwrap();
+        attach_session(&mut targets, &session).unwrap();
+        let prepared = targets[0].prepared.as_ref().unwrap();
+        assert_eq!(prepared.file_name, "game.apk");
+        assert_eq!(prepared.platform, "android");
+        assert_eq!(prepared.name, "Game - game.apk");
+        assert_eq!(prepared.tags, Some(vec!["platform:android".to_string()]));
+
+        // Every prepared file must be given, unchanged
+        let mut targets = file_spec_targets(&files[..], "Game", &[], None).unwrap();
+        targets.truncate(1);
+        let err = attach_session(&mut targets, &session).unwrap_err();
+        assert!(err.to_string().contains("also prepared tools.exe"));
+        std::fs::write(dir.path().join("game.apk"), b"changed").unwrap();
+        let mut targets = file_spec_targets(&files, "Game", &[], None).unwrap();
+        let err = attach_session(&mut targets, &session).unwrap_err();
+        assert!(err.to_string().contains("does not match any file"));
+
+        // Directories are archived on the fly and cannot be part of a session
+        let mut targets = file_spec_targets(&files, "Game", &[], None).unwrap();
+        targets[0].path = dir.path().to_string_lossy().to_string();
+        targets[0].platform = Some(BuildPlatform::Webgl);
+        assert!(prepare_session(None, &targets, None, &base_options, false).is_err());
+        assert!(attach_session(&mut targets, &session).is_err());
+    }
+}
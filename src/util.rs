@@ -0,0 +1,105 @@
+//! Small helpers shared across modules: hex encoding, Unix time and the user directories
+
+use directories::ProjectDirs;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Lowercase hex encoding of `bytes`
+#[must_use]
+pub fn hex_encode(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(hex, "{b:02x}");
+    }
+    hex
+}
+
+/// Bytes of a hex string (either case), or `None` if it is not valid hex
+#[must_use]
+pub fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// SHA-256 of `data` as lowercase hex
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex_encode(ring::digest::digest(&ring::digest::SHA256, data).as_ref())
+}
+
+/// Current Unix timestamp in seconds
+#[must_use]
+pub fn unix_now() -> u64 {
+    unix_time(SystemTime::now()).as_secs()
+}
+
+/// Time since the Unix epoch of `time`, or zero for earlier times
+#[must_use]
+pub fn unix_time(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Write `contents` to `path` through a temporary file in the same directory that is then
+/// renamed over it, so readers and crashes never leave a partly written file
+///
+/// # Errors
+///
+/// Returns an error if the temporary file cannot be written or renamed
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = PathBuf::from(temp);
+    std::fs::write(&temp, contents)
+        .and_then(|()| std::fs::rename(&temp, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+}
+
+/// User cache directory of nunu, or `nunu` in the temporary directory if there is none
+#[must_use]
+pub fn cache_dir() -> PathBuf {
+    ProjectDirs::from("", "", "nunu").map_or_else(
+        || std::env::temp_dir().join("nunu"),
+        |dirs| dirs.cache_dir().to_path_buf(),
+    )
+}
+
+/// User data directory of nunu, or `nunu` in the temporary directory if there is none
+#[must_use]
+pub fn data_dir() -> PathBuf {
+    ProjectDirs::from("", "", "nunu").map_or_else(
+        || std::env::temp_dir().join("nunu"),
+        |dirs| dirs.data_dir().to_path_buf(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex_encode(&[0x00, 0xab, 0x7f]), "00ab7f");
+        assert_eq!(hex_decode("00AB7f"), Some(vec![0x00, 0xab, 0x7f]));
+        assert_eq!(hex_decode(&hex_encode(b"nunu")), Some(b"nunu".to_vec()));
+        assert_eq!(hex_decode("abc"), None);
+        assert_eq!(hex_decode("zz"), None);
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Checking uploaded files against what the server stored (`--verify`)
+//!
+//! Verifying a file hashes it locally and asks the server for the size and SHA-256 of the
+//! stored object, which for hundreds of small patch files takes longer than the uploads.
+//! Large batches verify a sample instead: a share of the files plus every file above a
+//! size threshold. A mismatch in the sample verifies the rest of the batch.
+
+use crate::api::Client;
+use crate::checksums::sha256_file;
+use crate::error::{Error, Result};
+use futures::StreamExt;
+use log::{debug, warn};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Share of the files verified in large batches, in percent
+pub const DEFAULT_VERIFY_PERCENT: u8 = 10;
+
+/// Files at least this large are always verified
+pub const DEFAULT_VERIFY_THRESHOLD: u64 = 100 * 1024 * 1024;
+
+/// Batches of up to this many files verify every file
+pub const FULL_VERIFICATION_BATCH: usize = 20;
+
+/// How many files are verified at once
+const VERIFY_CONCURRENCY: usize = 4;
+
+/// Which files of a batch are verified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifySampling {
+    /// Share of the files verified in batches larger than [`FULL_VERIFICATION_BATCH`]
+    pub percent: u8,
+    /// Files at least this many bytes are verified regardless of the sample
+    pub threshold: u64,
+    /// Verify every file (`--verify-all`)
+    pub all: bool,
+}
+
+impl Default for VerifySampling {
+    fn default() -> Self {
+        Self {
+            percent: DEFAULT_VERIFY_PERCENT,
+            threshold: DEFAULT_VERIFY_THRESHOLD,
+            all: false,
+        }
+    }
+}
+
+impl VerifySampling {
+    /// Indices of the files to verify out of a batch of files with sizes `sizes`
+    ///
+    /// The sample is spread evenly over the batch, so it is the same on every run.
+    #[must_use]
+    pub fn select(&self, sizes: &[u64]) -> Vec<usize> {
+        if self.all || sizes.len() <= FULL_VERIFICATION_BATCH {
+            return (0..sizes.len()).collect();
+        }
+        let percent = usize::from(self.percent.min(100));
+        sizes
+            .iter()
+            .enumerate()
+            .filter(|(i, size)| **size >= self.threshold || i * percent % 100 < percent)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Outcome of verifying a batch
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub files: usize,
+    pub verified: usize,
+    /// Directories, which are archived on the fly and have no local file to compare
+    pub skipped: usize,
+    /// Files whose stored object differs, with the difference
+    pub mismatches: Vec<(String, String)>,
+    /// Whether a mismatch in the sample caused the whole batch to be verified
+    pub escalated: bool,
+    /// Whether the server does not report stored objects, so nothing could be verified
+    pub unsupported: bool,
+}
+
+enum FileCheck {
+    Match,
+    Mismatch(String),
+    Skipped,
+    Unsupported,
+}
+
+/// Verify the files of `uploads` (file and build ID) selected by `sampling`
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or the server cannot be asked for its stored
+/// object
+pub async fn verify_uploads(
+    client: &Client,
+    uploads: &[(String, String)],
+    sampling: VerifySampling,
+) -> Result<VerifyReport> {
+    let sizes: Vec<u64> = uploads
+        .iter()
+        .map(|(file, _)| std::fs::metadata(file).map_or(0, |m| m.len()))
+        .collect();
+    let sample = sampling.select(&sizes);
+    debug!(
+        "Verifying {} of {} uploaded file(s)",
+        sample.len(),
+        uploads.len()
+    );
+
+    let mut report = VerifyReport {
+        files: uploads.len(),
+        ..VerifyReport::default()
+    };
+    verify_files(client, uploads, &sample, &mut report).await?;
+
+    if !report.mismatches.is_empty() && !report.unsupported && sample.len() < uploads.len() {
+        warn!("Uploaded files differ from what the server stored; verifying the whole batch");
+        report.escalated = true;
+        let rest: Vec<usize> = (0..uploads.len()).filter(|i| !sample.contains(i)).collect();
+        verify_files(client, uploads, &rest, &mut report).await?;
+    }
+    report.mismatches.sort();
+    Ok(report)
+}
+
+async fn verify_files(
+    client: &Client,
+    uploads: &[(String, String)],
+    indices: &[usize],
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let mut checks = futures::stream::iter(indices.iter().map(|&i| {
+        let (file, build_id) = &uploads[i];
+        async move { (file, verify_file(client, file, build_id).await) }
+    }))
+    .buffer_unordered(VERIFY_CONCURRENCY);
+
+    while let Some((file, check)) = checks.next().await {
+        match check? {
+            FileCheck::Match => report.verified += 1,
+            FileCheck::Mismatch(difference) => {
+                report.verified += 1;
+                report.mismatches.push((file.clone(), difference));
+            }
+            FileCheck::Skipped => report.skipped += 1,
+            FileCheck::Unsupported => {
+                warn!("The server does not report stored objects; uploads were not verified");
+                report.unsupported = true;
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn verify_file(client: &Client, file: &str, build_id: &str) -> Result<FileCheck> {
+    let path = PathBuf::from(file);
+    if path.is_dir() {
+        return Ok(FileCheck::Skipped);
+    }
+    let Some(stored) = client.stored_object(build_id).await? else {
+        return Ok(FileCheck::Unsupported);
+    };
+
+    let size = std::fs::metadata(&path)?.len();
+    if stored.size != size {
+        return Ok(FileCheck::Mismatch(format!(
+            "stored {} bytes, the file has {size}",
+            stored.size
+        )));
+    }
+    let Some(stored_sha256) = stored.sha256 else {
+        return Ok(FileCheck::Match);
+    };
+    let sha256 = tokio::task::spawn_blocking(move || sha256_file(&path))
+        .await
+        .map_err(|e| Error::UploadError(format!("Hashing task failed: {e}")))??;
+    if stored_sha256.eq_ignore_ascii_case(&sha256) {
+        Ok(FileCheck::Match)
+    } else {
+        Ok(FileCheck::Mismatch(format!(
+            "stored SHA-256 {stored_sha256}, the file has {sha256}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_sampling() {
+        let sampling = VerifySampling {
+            threshold: 1000,
+            ..VerifySampling::default()
+        };
+        assert_eq!(sampling.select(&[10; 5]), [0, 1, 2, 3, 4]);
+
+        let mut sizes = vec![10; 100];
+        sizes[15] = 5000;
+        assert_eq!(
+            sampling.select(&sizes),
+            [0, 10, 15, 20, 30, 40, 50, 60, 70, 80, 90]
+        );
+
+        let none = VerifySampling {
+            percent: 0,
+            ..sampling
+        };
+        assert_eq!(none.select(&sizes), [15]);
+
+        let all = VerifySampling { all: true, ..none };
+        assert_eq!(all.select(&sizes).len(), 100);
+    }
+}
@@ -0,0 +1,231 @@
+//! End-to-end uploads through the CLI binary against the stub API
+//!
+//! Run with `cargo test --features e2e --test e2e`. Uploads are stored in the stub's memory
+//! unless `NUNU_E2E_S3_ENDPOINT` points to an S3-compatible server such as MinIO
+//! (`just e2e-minio`).
+
+#![allow(clippy::unwrap_used)]
+
+use nunu_cli::checksums::sha256_file;
+use nunu_cli::e2e::{E2E_PART_SIZE, E2E_PROJECT_ID, E2E_TOKEN, Storage, StoredBuild, StubApi};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Write `size` bytes of varying content to `name` in `dir`
+fn write_file(dir: &Path, name: &str, size: u64) -> PathBuf {
+    let mut state: u32 = 0x2545_f491;
+    let data: Vec<u8> = (0..size)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state.to_be_bytes()[0]
+        })
+        .collect();
+    let path = dir.join(name);
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+/// Run `nunu-cli` in `dir` with `args`, isolated from the user's config and environment
+async fn run_cli(dir: &Path, args: Vec<String>) -> Output {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_nunu-cli"));
+        for (name, _) in std::env::vars() {
+            if (name.starts_with("NUNU_") && !name.starts_with("NUNU_E2E_"))
+                || name.to_ascii_lowercase().ends_with("_proxy")
+            {
+                command.env_remove(name);
+            }
+        }
+        command
+            .args(args)
+            .current_dir(&dir)
+            .env("HOME", &dir)
+            .env("XDG_CONFIG_HOME", dir.join(".config"))
+            .env("XDG_CACHE_HOME", dir.join(".cache"))
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap()
+}
+
+/// Upload `file` to `stub` and return what the stub stored
+async fn upload(stub: &StubApi, file: &Path, extra: &[&str]) -> StoredBuild {
+    let mut args: Vec<String> = [
+        "upload",
+        &file.display().to_string(),
+        "--name",
+        "e2e",
+        "--platform",
+        "linux",
+        "--no-size-diff",
+        "--verify-all",
+        "--api-url",
+        &stub.api_url(),
+        "--token",
+        E2E_TOKEN,
+        "--project-id",
+        E2E_PROJECT_ID,
+    ]
+    .iter()
+    .map(ToString::to_string)
+    .collect();
+    args.extend(extra.iter().map(ToString::to_string));
+
+    let output = run_cli(file.parent().unwrap(), args).await;
+    assert!(
+        output.status.success(),
+        "upload failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let builds = stub.builds();
+    assert_eq!(builds.len(), 1, "expected one stored build: {builds:?}");
+    builds.into_iter().next().unwrap()
+}
+
+async fn start_stub() -> StubApi {
+    StubApi::start(Storage::from_env(|name| std::env::var(name).ok()))
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_single_part_upload() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "game.bin", 256 * 1024);
+    let stub = start_stub().await;
+
+    let build = upload(&stub, &file, &[]).await;
+    assert_eq!(build.file_name, "game.bin");
+    assert_eq!(build.parts, None);
+    assert_eq!(build.size, 256 * 1024);
+    assert_eq!(build.sha256, sha256_file(&file).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_multipart_upload() {
+    let dir = tempfile::tempdir().unwrap();
+    let size = 2 * E2E_PART_SIZE + 123_457;
+    let file = write_file(dir.path(), "game.bin", size);
+    let stub = start_stub().await;
+
+    let build = upload(&stub, &file, &["--force-multipart"]).await;
+    assert_eq!(build.parts, Some(3));
+    assert_eq!(build.size, size);
+    assert_eq!(build.sha256, sha256_file(&file).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_e2e_local_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "game.bin", 1024);
+
+    let output = run_cli(
+        dir.path(),
+        [
+            "--e2e-local",
+            "upload",
+            "game.bin",
+            "--name",
+            "e2e",
+            "--platform",
+            "linux",
+            "--no-size-diff",
+        ]
+        .map(String::from)
+        .to_vec(),
+    )
+    .await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "upload failed:\n{stderr}");
+    assert!(
+        stderr.contains(&sha256_file(&file).unwrap()),
+        "stored build not reported:\n{stderr}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dry_run_skips_token_command() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "game.bin", 1024);
+    let marker = dir.path().join("token-command-ran");
+    let config = dir.path().join("config.json");
+    let token_command = format!("touch '{}' && echo token", marker.display());
+    std::fs::write(
+        &config,
+        serde_json::json!({ "project_id": E2E_PROJECT_ID, "token_command": token_command })
+            .to_string(),
+    )
+    .unwrap();
+
+    let output = run_cli(
+        dir.path(),
+        [
+            "--config",
+            &config.display().to_string(),
+            "upload",
+            "game.bin",
+            "--name",
+            "e2e",
+            "--platform",
+            "linux",
+            "--dry-run",
+        ]
+        .map(String::from)
+        .to_vec(),
+    )
+    .await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "dry run failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("<not resolved>"), "token shown:\n{stdout}");
+    assert!(!marker.exists(), "the dry run ran the token command");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sharded_upload_logs_storage_target_of_every_shard() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "game.bin", 2 * E2E_PART_SIZE + 123_457);
+    let stub = start_stub().await;
+
+    // Shard 2/2 starts the upload with parts 2-3, so it never requests a URL for part 1
+    for shard in ["2/2", "1/2"] {
+        let args = [
+            "upload",
+            "game.bin",
+            "--name",
+            "e2e",
+            "--platform",
+            "linux",
+            "--no-size-diff",
+            "--api-url",
+            &stub.api_url(),
+            "--token",
+            E2E_TOKEN,
+            "--project-id",
+            E2E_PROJECT_ID,
+            "--shard",
+            shard,
+            "--storage-region",
+            "eu-west-1",
+            "--log",
+            "upload=info",
+        ];
+        let output = run_cli(dir.path(), args.map(String::from).to_vec()).await;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "shard {shard} failed:\n{stderr}");
+        assert!(
+            stderr.contains("Storage target: region eu-west-1"),
+            "shard {shard} did not log its storage target:\n{stderr}"
+        );
+    }
+
+    let builds = stub.builds();
+    assert_eq!(builds.len(), 1, "expected one stored build: {builds:?}");
+    assert_eq!(builds[0].parts, Some(3));
+    assert_eq!(builds[0].sha256, sha256_file(&file).unwrap());
+}